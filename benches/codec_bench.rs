@@ -10,8 +10,10 @@ fn bench_codec_request_roundtrip(c: &mut Criterion) {
         transfer_id: "bench".to_string(),
         chunk_index: 1,
         total_chunks: 10,
+        offset: 1024 * 64,
         data: vec![0x55; 1024 * 64],
         is_last: false,
+        proof: Vec::new(),
     };
 
     c.bench_function("codec_request_roundtrip_64KB", |b| {
@@ -11,6 +11,7 @@ fn bench_codec_response_roundtrip(c: &mut Criterion) {
         chunk_index: 1,
         success: true,
         error: None,
+        retryable: false,
     };
 
     c.bench_function("codec_response_roundtrip_small", |b| {
@@ -0,0 +1,80 @@
+use crate::core::domain::{PeerBan, PeerId};
+use crate::core::traits::{DomainResult, PeerRepository};
+use std::time::SystemTime;
+
+/// Check `peer` is a valid peer id and store a ban on it in `repo`
+pub async fn ban_peer(
+    repo: &dyn PeerRepository,
+    peer: &str,
+    reason: Option<String>,
+    until: Option<SystemTime>,
+) -> DomainResult<PeerBan> {
+    peer.parse::<libp2p::PeerId>()
+        .map_err(|e| format!("Invalid peer id {}: {}", peer, e))?;
+    let ban = PeerBan {
+        peer: PeerId::new(peer.to_string()),
+        reason,
+        until,
+    };
+    repo.save_ban(&ban).await?;
+    Ok(ban)
+}
+
+/// Bans in `repo` still in force at `now`. Expired bans are deleted along
+/// the way so the store doesn't grow with bans nobody will lift.
+pub async fn active_bans(repo: &dyn PeerRepository, now: SystemTime) -> DomainResult<Vec<PeerBan>> {
+    let mut active = Vec::new();
+    for ban in repo.list_bans().await? {
+        if ban.is_active_at(now) {
+            active.push(ban);
+        } else {
+            repo.remove_ban(&ban.peer).await?;
+        }
+    }
+    Ok(active)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::infrastructure::InMemoryPeerRepository;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_expired_bans_are_dropped_from_the_store() {
+        let repo = InMemoryPeerRepository::new();
+        let now = SystemTime::now();
+        let forever = libp2p::PeerId::random().to_string();
+        let later = libp2p::PeerId::random().to_string();
+        let lapsed = libp2p::PeerId::random().to_string();
+        ban_peer(&repo, &forever, Some("spam".to_string()), None)
+            .await
+            .unwrap();
+        ban_peer(&repo, &later, None, Some(now + Duration::from_secs(60)))
+            .await
+            .unwrap();
+        ban_peer(&repo, &lapsed, None, Some(now - Duration::from_secs(1)))
+            .await
+            .unwrap();
+
+        let mut active: Vec<_> = active_bans(&repo, now)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|ban| ban.peer.as_str().to_string())
+            .collect();
+        active.sort();
+        let mut expected = vec![forever, later];
+        expected.sort();
+        assert_eq!(active, expected);
+        assert_eq!(repo.list_bans().await.unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_ban_rejects_invalid_peer_id() {
+        let repo = InMemoryPeerRepository::new();
+        let err = ban_peer(&repo, "not-a-peer", None, None).await.unwrap_err();
+        assert!(err.to_string().contains("not-a-peer"), "{}", err);
+        assert!(repo.list_bans().await.unwrap().is_empty());
+    }
+}
@@ -0,0 +1,164 @@
+use crate::application::use_cases::{
+    CancelTransferUseCase, PauseTransferUseCase, ResumeTransferUseCase,
+};
+use crate::core::services::TransferDomainService;
+use crate::core::traits::DomainResult;
+use serde::{Deserialize, Serialize};
+use std::net::{Ipv4Addr, SocketAddr};
+use std::sync::Arc;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::task::JoinHandle;
+use tracing::{debug, warn};
+
+/// A command for a running node, sent to its control API as one JSON line
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "command", rename_all = "snake_case")]
+pub enum ControlRequest {
+    CancelTransfer { transfer_id: String },
+    PauseTransfer { transfer_id: String },
+    ResumeTransfer { transfer_id: String },
+}
+
+/// The node's answer to a [`ControlRequest`]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ControlResponse {
+    pub ok: bool,
+    /// Why the command was refused, when it was
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl ControlResponse {
+    fn from_result(result: DomainResult<()>) -> Self {
+        match result {
+            Ok(()) => Self {
+                ok: true,
+                error: None,
+            },
+            Err(e) => Self {
+                ok: false,
+                error: Some(e.to_string()),
+            },
+        }
+    }
+}
+
+/// Use cases the control API drives
+struct ControlCommands {
+    cancel_transfer: CancelTransferUseCase,
+    pause_transfer: PauseTransferUseCase,
+    resume_transfer: ResumeTransferUseCase,
+}
+
+impl ControlCommands {
+    async fn execute(&self, request: ControlRequest) -> ControlResponse {
+        let result = match &request {
+            ControlRequest::CancelTransfer { transfer_id } => {
+                self.cancel_transfer.execute(transfer_id).await
+            }
+            ControlRequest::PauseTransfer { transfer_id } => {
+                self.pause_transfer.execute(transfer_id).await
+            }
+            ControlRequest::ResumeTransfer { transfer_id } => {
+                self.resume_transfer.execute(transfer_id).await
+            }
+        };
+        if let Err(e) = &result {
+            debug!("Control request {:?} refused: {}", request, e);
+        }
+        ControlResponse::from_result(result)
+    }
+}
+
+/// Loopback-only server through which the CLI steers a running node's
+/// transfers
+pub struct ControlServer {
+    listener: TcpListener,
+    commands: Arc<ControlCommands>,
+}
+
+impl ControlServer {
+    /// Listen on `port` of the loopback interface; 0 picks a free port
+    pub async fn bind(
+        port: u16,
+        transfer_service: Arc<TransferDomainService>,
+    ) -> DomainResult<Self> {
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, port))
+            .await
+            .map_err(|e| format!("Failed to bind control port {}: {}", port, e))?;
+        Ok(Self {
+            listener,
+            commands: Arc::new(ControlCommands {
+                cancel_transfer: CancelTransferUseCase::new(transfer_service.clone()),
+                pause_transfer: PauseTransferUseCase::new(transfer_service.clone()),
+                resume_transfer: ResumeTransferUseCase::new(transfer_service),
+            }),
+        })
+    }
+
+    pub fn local_addr(&self) -> DomainResult<SocketAddr> {
+        Ok(self.listener.local_addr()?)
+    }
+
+    /// Answer requests in the background, each connection on its own task
+    pub fn spawn(self) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            loop {
+                let stream = match self.listener.accept().await {
+                    Ok((stream, _)) => stream,
+                    Err(e) => {
+                        warn!("Failed to accept control connection: {}", e);
+                        continue;
+                    }
+                };
+                let commands = self.commands.clone();
+                tokio::spawn(async move {
+                    if let Err(e) = serve_connection(stream, &commands).await {
+                        debug!("Control connection ended: {}", e);
+                    }
+                });
+            }
+        })
+    }
+}
+
+/// Answer each request line on `stream` until the client hangs up
+async fn serve_connection(stream: TcpStream, commands: &ControlCommands) -> std::io::Result<()> {
+    let (reader, mut writer) = stream.into_split();
+    let mut lines = BufReader::new(reader).lines();
+    while let Some(line) = lines.next_line().await? {
+        let response = match serde_json::from_str::<ControlRequest>(&line) {
+            Ok(request) => commands.execute(request).await,
+            Err(e) => ControlResponse {
+                ok: false,
+                error: Some(format!("Invalid control request: {}", e)),
+            },
+        };
+        let mut json = serde_json::to_vec(&response)?;
+        json.push(b'\n');
+        writer.write_all(&json).await?;
+    }
+    Ok(())
+}
+
+/// Send `request` to the node whose control API listens on `port`
+pub async fn send_control_request(
+    port: u16,
+    request: &ControlRequest,
+) -> DomainResult<ControlResponse> {
+    let stream = TcpStream::connect((Ipv4Addr::LOCALHOST, port))
+        .await
+        .map_err(|e| format!("No node is taking commands on port {}: {}", port, e))?;
+    let (reader, mut writer) = stream.into_split();
+    let mut json = serde_json::to_vec(request)?;
+    json.push(b'\n');
+    writer.write_all(&json).await?;
+
+    let line = BufReader::new(reader)
+        .lines()
+        .next_line()
+        .await?
+        .ok_or("Node closed the control connection without answering")?;
+    Ok(serde_json::from_str(&line)?)
+}
@@ -1,4 +1,11 @@
+use crate::core::domain::{File, Peer, Transfer, TransferStatus};
 use serde::{Deserialize, Serialize};
+use std::time::SystemTime;
+
+/// Format a timestamp as an RFC3339 UTC string
+fn rfc3339(time: SystemTime) -> String {
+    humantime::format_rfc3339_millis(time).to_string()
+}
 
 /// DTO for file information
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -8,6 +15,22 @@ pub struct FileDto {
     pub size: u64,
     pub hash: String,
     pub path: String,
+    pub created_at: String,
+    pub modified_at: Option<String>,
+}
+
+impl From<File> for FileDto {
+    fn from(file: File) -> Self {
+        Self {
+            id: file.id.0,
+            name: file.name,
+            size: file.size,
+            hash: file.hash,
+            path: file.path,
+            created_at: rfc3339(file.created_at),
+            modified_at: file.modified_at.map(rfc3339),
+        }
+    }
 }
 
 /// DTO for peer information
@@ -16,6 +39,21 @@ pub struct PeerDto {
     pub id: String,
     pub addresses: Vec<String>,
     pub is_connected: bool,
+    pub last_seen: String,
+    #[serde(default)]
+    pub protocols: Vec<String>,
+}
+
+impl From<Peer> for PeerDto {
+    fn from(peer: Peer) -> Self {
+        Self {
+            id: peer.id.id,
+            addresses: peer.addresses,
+            is_connected: peer.is_connected,
+            last_seen: rfc3339(peer.last_seen),
+            protocols: peer.protocols,
+        }
+    }
 }
 
 /// DTO for transfer information
@@ -26,9 +64,45 @@ pub struct TransferDto {
     pub file_size: u64,
     pub sender_id: String,
     pub receiver_id: String,
+    /// One of `pending`, `in_progress`, `paused`, `completed`, `failed`, `cancelled`
     pub status: String,
+    /// Set when `status` is `failed`
+    pub failure_reason: Option<String>,
     pub progress_percentage: f32,
     pub bytes_transferred: u64,
+    pub chunks_transferred: u64,
+    pub total_chunks: u64,
+    pub started_at: String,
+    pub completed_at: Option<String>,
+}
+
+impl From<Transfer> for TransferDto {
+    fn from(transfer: Transfer) -> Self {
+        let (status, failure_reason) = match transfer.status {
+            TransferStatus::Pending => ("pending", None),
+            TransferStatus::InProgress => ("in_progress", None),
+            TransferStatus::Verifying => ("verifying", None),
+            TransferStatus::Paused => ("paused", None),
+            TransferStatus::Completed => ("completed", None),
+            TransferStatus::Failed { reason } => ("failed", Some(reason)),
+            TransferStatus::Cancelled => ("cancelled", None),
+        };
+        Self {
+            id: transfer.id.0,
+            file_name: transfer.file.name,
+            file_size: transfer.file.size,
+            sender_id: transfer.sender.id,
+            receiver_id: transfer.receiver.id,
+            status: status.to_string(),
+            failure_reason,
+            progress_percentage: transfer.progress.percentage(),
+            bytes_transferred: transfer.progress.bytes_transferred,
+            chunks_transferred: transfer.progress.chunks_transferred,
+            total_chunks: transfer.progress.total_chunks,
+            started_at: rfc3339(transfer.started_at),
+            completed_at: transfer.completed_at.map(rfc3339),
+        }
+    }
 }
 
 /// DTO for sending file request
@@ -51,3 +125,90 @@ pub struct ErrorResponse {
     pub error: String,
     pub details: Option<String>,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::domain::{FileId, PeerId, TransferId, TransferProgress};
+    use std::time::Duration;
+
+    fn at(secs: u64) -> SystemTime {
+        SystemTime::UNIX_EPOCH + Duration::from_secs(secs)
+    }
+
+    fn file() -> File {
+        File {
+            id: FileId("file-1".to_string()),
+            name: "notes.txt".to_string(),
+            size: 2048,
+            hash: "abc123".to_string(),
+            path: "/tmp/notes.txt".to_string(),
+            created_at: at(1_700_000_000),
+            modified_at: None,
+        }
+    }
+
+    fn round_trip<T: Serialize + for<'de> Deserialize<'de>>(dto: &T) -> T {
+        serde_json::from_str(&serde_json::to_string(dto).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_file_dto_formats_timestamps() {
+        let dto = round_trip(&FileDto::from(file()));
+        assert_eq!(dto.id, "file-1");
+        assert_eq!(dto.size, 2048);
+        assert_eq!(dto.created_at, "2023-11-14T22:13:20.000Z");
+        assert_eq!(dto.modified_at, None);
+    }
+
+    #[test]
+    fn test_peer_dto_flattens_peer_id() {
+        let peer = Peer {
+            id: PeerId::new("peer-a".to_string()),
+            addresses: vec!["/ip4/127.0.0.1/tcp/4001".to_string()],
+            last_seen: at(0),
+            is_connected: true,
+            protocols: Vec::new(),
+        };
+
+        let dto = round_trip(&PeerDto::from(peer));
+        assert_eq!(dto.id, "peer-a");
+        assert_eq!(dto.addresses, vec!["/ip4/127.0.0.1/tcp/4001"]);
+        assert!(dto.is_connected);
+        assert_eq!(dto.last_seen, "1970-01-01T00:00:00.000Z");
+    }
+
+    #[test]
+    fn test_transfer_dto_flattens_status_and_progress() {
+        let mut progress = TransferProgress::new(2048, 2);
+        progress.update(1024, 1);
+        let transfer = Transfer {
+            id: TransferId("transfer-1".to_string()),
+            file: file(),
+            sender: PeerId::new("alice".to_string()),
+            receiver: PeerId::new("bob".to_string()),
+            status: TransferStatus::Failed {
+                reason: "peer went away".to_string(),
+            },
+            progress,
+            started_at: at(1_700_000_000),
+            completed_at: Some(at(1_700_000_005)),
+        };
+
+        let dto = round_trip(&TransferDto::from(transfer));
+        assert_eq!(dto.id, "transfer-1");
+        assert_eq!(dto.file_name, "notes.txt");
+        assert_eq!(dto.sender_id, "alice");
+        assert_eq!(dto.receiver_id, "bob");
+        assert_eq!(dto.status, "failed");
+        assert_eq!(dto.failure_reason.as_deref(), Some("peer went away"));
+        assert_eq!(dto.progress_percentage, 50.0);
+        assert_eq!((dto.bytes_transferred, dto.chunks_transferred), (1024, 1));
+        assert_eq!(dto.total_chunks, 2);
+        assert_eq!(dto.started_at, "2023-11-14T22:13:20.000Z");
+        assert_eq!(
+            dto.completed_at.as_deref(),
+            Some("2023-11-14T22:13:25.000Z")
+        );
+    }
+}
@@ -0,0 +1,71 @@
+use crate::core::traits::DomainResult;
+use crate::infrastructure::network::NetworkEvent;
+use libp2p::PeerId;
+use std::path::PathBuf;
+
+/// A gossip message accepted by a [`GossipSink`]
+#[derive(Debug, Clone)]
+pub struct GossipDelivery {
+    pub from: PeerId,
+    pub data: Vec<u8>,
+    /// Where the payload was written, if the sink has an output directory
+    pub saved_to: Option<PathBuf>,
+}
+
+/// Consumer for the messages of a single gossipsub topic, optionally
+/// writing each payload to a directory
+pub struct GossipSink {
+    topic: String,
+    output_dir: Option<PathBuf>,
+    received: u64,
+}
+
+impl GossipSink {
+    pub fn new(topic: impl Into<String>) -> Self {
+        Self {
+            topic: topic.into(),
+            output_dir: None,
+            received: 0,
+        }
+    }
+
+    /// Write each received payload to a new file in `output_dir`
+    pub fn with_output_dir(mut self, output_dir: impl Into<PathBuf>) -> Self {
+        self.output_dir = Some(output_dir.into());
+        self
+    }
+
+    /// Number of messages accepted so far
+    pub fn received(&self) -> u64 {
+        self.received
+    }
+
+    /// Accept `event` if it is a message on this sink's topic.
+    ///
+    /// Other events are ignored and yield `None`.
+    pub async fn accept(&mut self, event: NetworkEvent) -> DomainResult<Option<GossipDelivery>> {
+        let NetworkEvent::GossipMessage { from, topic, data } = event else {
+            return Ok(None);
+        };
+        if topic != self.topic {
+            return Ok(None);
+        }
+        self.received += 1;
+
+        let saved_to = match &self.output_dir {
+            Some(dir) => {
+                tokio::fs::create_dir_all(dir).await?;
+                let path = dir.join(format!("{:06}-{}.msg", self.received, from));
+                tokio::fs::write(&path, &data).await?;
+                Some(path)
+            }
+            None => None,
+        };
+
+        Ok(Some(GossipDelivery {
+            from,
+            data,
+            saved_to,
+        }))
+    }
+}
@@ -1,7 +1,17 @@
+pub mod bans;
+pub mod control;
 pub mod dto;
+pub mod gossip;
+pub mod node;
+pub mod peer_book;
 pub mod services;
 pub mod use_cases;
 
+pub use bans::{active_bans, ban_peer};
+pub use control::{ControlRequest, ControlResponse, ControlServer, send_control_request};
 pub use dto::*;
+pub use gossip::{GossipDelivery, GossipSink};
+pub use node::Node;
+pub use peer_book::{PeerBookEntry, export_peers, import_peers};
 pub use services::*;
 pub use use_cases::*;
@@ -0,0 +1,102 @@
+use crate::application::services::{ApplicationService, FileSystemService};
+use crate::core::services::{PeerDisconnectHandler, PeerIdentifyHandler, TransferDomainService};
+use crate::core::traits::{DomainResult, EventPublisher};
+use crate::file_transfer::{FileSender, FileTransferHandler};
+use crate::infrastructure::{InMemoryEventPublisher, LibP2pNetworkService};
+use std::sync::Arc;
+
+/// The services a running node is made of, wired together the way `start`
+/// runs them
+pub struct Node {
+    /// Bus every domain event of the node is published on
+    pub events: Arc<InMemoryEventPublisher>,
+    pub network: Arc<LibP2pNetworkService>,
+    pub transfer_service: Arc<TransferDomainService>,
+    /// Sends files to peers; pausing, resuming or cancelling a transfer
+    /// through `transfer_service` steers its send
+    pub sender: Arc<FileSender>,
+    /// Receives the files peers send to this node
+    pub file_handler: Arc<FileTransferHandler>,
+}
+
+impl Node {
+    /// Build the network and transfer services over `app`'s configuration
+    /// and repositories, and start taking file transfers. The node listens
+    /// once the caller starts the network service.
+    pub async fn new(app: &ApplicationService) -> DomainResult<Self> {
+        let config = app.config.clone();
+        let events = Arc::new(InMemoryEventPublisher::new());
+
+        let network = Arc::new(
+            LibP2pNetworkService::new(config.clone(), events.clone())
+                .await
+                .map_err(|e| format!("Failed to create network service: {}", e))?,
+        );
+
+        let sender = Arc::new(
+            FileSender::new(network.clone(), config.chunk_size)
+                .with_max_uploads(config.upload_limit())
+                .with_max_file_size(config.max_file_size()),
+        );
+        let transfer_service = Arc::new(
+            TransferDomainService::new(
+                app.file_repository.clone(),
+                app.transfer_repository.clone(),
+                app.peer_repository.clone(),
+                Arc::new(FileSystemService::new(config.clone())),
+                events.clone(),
+            )
+            .with_deterministic_ids(config.deterministic_transfer_ids)
+            .with_verify_after(config.verify_after_transfer)
+            .with_transfer_control(sender.clone()),
+        );
+
+        // Pause transfers whose peer drops off so they can resume later
+        events
+            .subscribe(Box::new(PeerDisconnectHandler::new(
+                transfer_service.clone(),
+            )))
+            .map_err(|e| format!("Failed to watch peer disconnects: {}", e))?;
+        // Keep stored peers up to date with what they advertise and whether
+        // they are still connected
+        events
+            .subscribe(Box::new(PeerIdentifyHandler::new(
+                app.peer_repository.clone(),
+            )))
+            .map_err(|e| format!("Failed to watch peer identify: {}", e))?;
+
+        // Inbound transfers report on the same bus as everything else
+        let file_handler = Arc::new(
+            FileTransferHandler::new(&config.download_directory, config.chunk_size)
+                .with_event_publisher(events.clone(), network.local_peer_id())
+                .with_min_chunk_size(config.min_chunk_size)
+                .with_transfer_repository(app.transfer_repository.clone())
+                .with_max_downloads(config.download_limit())
+                .with_max_file_size(config.max_file_size())
+                .with_allowed_extensions(&config.security.allowed_file_extensions)
+                .with_content_types(
+                    &config.security.allowed_content_types,
+                    &config.security.blocked_content_types,
+                )
+                .with_max_total_chunks(config.max_total_chunks())
+                .with_verify_after(config.verify_after_transfer)
+                .with_download_layout(config.download_layout)
+                .with_max_pending_requests(
+                    config.network.max_pending_requests,
+                    config.network.max_pending_requests_per_peer,
+                ),
+        );
+        network
+            .serve_file_transfers(file_handler.clone())
+            .await
+            .map_err(|e| format!("Failed to serve file transfers: {}", e))?;
+
+        Ok(Self {
+            events,
+            network,
+            transfer_service,
+            sender,
+            file_handler,
+        })
+    }
+}
@@ -0,0 +1,138 @@
+use crate::core::domain::{Peer, PeerId};
+use crate::core::traits::{DomainResult, PeerRepository};
+use libp2p::Multiaddr;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// One known peer in an exported address book
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PeerBookEntry {
+    pub id: String,
+    pub addresses: Vec<String>,
+    /// RFC3339 UTC timestamp
+    pub last_seen: String,
+}
+
+impl From<&Peer> for PeerBookEntry {
+    fn from(peer: &Peer) -> Self {
+        Self {
+            id: peer.id.id.clone(),
+            addresses: peer.addresses.clone(),
+            last_seen: humantime::format_rfc3339_millis(peer.last_seen).to_string(),
+        }
+    }
+}
+
+impl PeerBookEntry {
+    /// Check the id and addresses and turn the entry back into a peer
+    fn into_peer(self) -> DomainResult<Peer> {
+        self.id
+            .parse::<libp2p::PeerId>()
+            .map_err(|e| format!("Invalid peer id {}: {}", self.id, e))?;
+        for addr in &self.addresses {
+            addr.parse::<Multiaddr>()
+                .map_err(|e| format!("Invalid address {} for peer {}: {}", addr, self.id, e))?;
+        }
+        let last_seen = humantime::parse_rfc3339(&self.last_seen)
+            .map_err(|e| format!("Invalid last_seen for peer {}: {}", self.id, e))?;
+
+        Ok(Peer {
+            id: PeerId::new(self.id),
+            addresses: self.addresses,
+            last_seen,
+            is_connected: false,
+            protocols: Vec::new(),
+        })
+    }
+}
+
+/// Write every peer in `repo` to `path` as a JSON address book
+pub async fn export_peers(repo: &dyn PeerRepository, path: &Path) -> DomainResult<usize> {
+    let entries: Vec<PeerBookEntry> = repo
+        .list_all_peers()
+        .await?
+        .iter()
+        .map(PeerBookEntry::from)
+        .collect();
+    let json = serde_json::to_vec_pretty(&entries)?;
+    tokio::fs::write(path, json).await?;
+    Ok(entries.len())
+}
+
+/// Load an address book written by [`export_peers`] into `repo`. The whole
+/// book is validated before anything is saved, so a bad entry imports nothing.
+pub async fn import_peers(repo: &dyn PeerRepository, path: &Path) -> DomainResult<Vec<Peer>> {
+    let json = tokio::fs::read(path).await?;
+    let entries: Vec<PeerBookEntry> = serde_json::from_slice(&json)?;
+    let peers = entries
+        .into_iter()
+        .map(PeerBookEntry::into_peer)
+        .collect::<DomainResult<Vec<_>>>()?;
+    for peer in &peers {
+        repo.save_peer(peer).await?;
+    }
+    Ok(peers)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::infrastructure::InMemoryPeerRepository;
+    use std::time::{Duration, SystemTime};
+
+    fn peer(addresses: Vec<&str>) -> Peer {
+        Peer {
+            id: PeerId::from(libp2p::PeerId::random()),
+            addresses: addresses.into_iter().map(String::from).collect(),
+            last_seen: SystemTime::UNIX_EPOCH + Duration::from_millis(1_700_000_000_123),
+            is_connected: true,
+            protocols: Vec::new(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_peer_book_round_trips_through_export_and_import() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("peers.json");
+        let source = InMemoryPeerRepository::new();
+        let peers = [
+            peer(vec!["/ip4/192.168.1.5/tcp/8000", "/ip6/::1/tcp/8000"]),
+            peer(vec!["/dnsaddr/example.com"]),
+        ];
+        for p in &peers {
+            source.save_peer(p).await.unwrap();
+        }
+
+        assert_eq!(export_peers(&source, &path).await.unwrap(), 2);
+        let target = InMemoryPeerRepository::new();
+        assert_eq!(import_peers(&target, &path).await.unwrap().len(), 2);
+
+        for p in &peers {
+            let imported = target.find_peer_by_id(&p.id).await.unwrap().unwrap();
+            assert_eq!(imported.addresses, p.addresses);
+            assert_eq!(imported.last_seen, p.last_seen);
+            assert!(!imported.is_connected);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_import_rejects_invalid_multiaddr() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("peers.json");
+        let source = InMemoryPeerRepository::new();
+        source
+            .save_peer(&peer(vec!["/ip4/10.0.0.1/tcp/1"]))
+            .await
+            .unwrap();
+        source
+            .save_peer(&peer(vec!["not-an-address"]))
+            .await
+            .unwrap();
+        export_peers(&source, &path).await.unwrap();
+
+        let target = InMemoryPeerRepository::new();
+        let err = import_peers(&target, &path).await.unwrap_err();
+        assert!(err.to_string().contains("not-an-address"), "{}", err);
+        assert!(target.list_all_peers().await.unwrap().is_empty());
+    }
+}
@@ -1,3 +1,4 @@
+use crate::core::crypto;
 use crate::core::traits::*;
 use crate::infrastructure::{UtilityService, config::AppConfig, repositories::*};
 use std::sync::Arc;
@@ -60,18 +61,7 @@ impl FileSystemService {
 #[async_trait::async_trait]
 impl FileService for FileSystemService {
     async fn add_file(&self, path: &str) -> DomainResult<crate::core::domain::File> {
-        let (name, size) = self.get_file_metadata(path).await?;
-        let hash = self.calculate_file_hash(path).await?;
-
-        Ok(crate::core::domain::File {
-            id: crate::core::domain::FileId::new(),
-            name,
-            size,
-            hash,
-            path: path.to_string(),
-            created_at: std::time::SystemTime::now(),
-            modified_at: None,
-        })
+        crate::core::domain::File::from_path(path).await
     }
 
     async fn calculate_file_hash(&self, path: &str) -> DomainResult<String> {
@@ -80,9 +70,25 @@ impl FileService for FileSystemService {
             .map_err(|e| e.into())
     }
 
+    async fn compute_chunk_merkle(
+        &self,
+        path: &str,
+        chunk_size: usize,
+    ) -> DomainResult<(String, Vec<String>)> {
+        let tree = crypto::merkle::compute_chunk_merkle(path, chunk_size).await?;
+        Ok((tree.root(), tree.leaves()))
+    }
+
     async fn get_file_metadata(&self, path: &str) -> DomainResult<(String, u64)> {
         let path_buf = std::path::Path::new(path);
+        // Follows symlinks, so a link to a directory is refused as one
         let metadata = tokio::fs::metadata(path).await?;
+        if metadata.is_dir() {
+            return Err(format!("{} is a directory, not a file", path).into());
+        }
+        if !metadata.is_file() {
+            return Err(format!("{} is not a regular file (device, socket or pipe)", path).into());
+        }
 
         let name = path_buf
             .file_name()
@@ -123,3 +129,39 @@ impl FileService for FileSystemService {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_get_file_metadata_accepts_only_regular_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("notes.txt");
+        std::fs::write(&file, b"hello").unwrap();
+        let service = FileSystemService::new(Arc::new(AppConfig::default()));
+
+        let (name, size) = service
+            .get_file_metadata(file.to_str().unwrap())
+            .await
+            .unwrap();
+        assert_eq!((name.as_str(), size), ("notes.txt", 5));
+
+        let err = service
+            .get_file_metadata(dir.path().to_str().unwrap())
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("is a directory"), "{}", err);
+
+        #[cfg(unix)]
+        {
+            let link = dir.path().join("link");
+            std::os::unix::fs::symlink(dir.path(), &link).unwrap();
+            let err = service
+                .get_file_metadata(link.to_str().unwrap())
+                .await
+                .unwrap_err();
+            assert!(err.to_string().contains("is a directory"), "{}", err);
+        }
+    }
+}
@@ -1,4 +1,6 @@
+use super::dto::TransferDto;
 use crate::core::{domain::*, services::*, traits::*};
+use crate::file_transfer::FileTransferHandler;
 use std::sync::Arc;
 
 /// Use case for sending a file to another peer
@@ -83,14 +85,26 @@ impl AddFileUseCase {
         Self { file_service }
     }
 
-    /// Execute the add file use case
-    pub async fn execute(&self, file_path: &str) -> DomainResult<File> {
+    /// Execute the add file use case. With `expected_hash`, the file is
+    /// refused with [`ChecksumMismatch`] unless its SHA-256 matches.
+    pub async fn execute(
+        &self,
+        file_path: &str,
+        expected_hash: Option<&str>,
+    ) -> DomainResult<File> {
         // Validate file exists and is readable
         if !std::path::Path::new(file_path).exists() {
             return Err("File does not exist".into());
         }
 
-        self.file_service.add_file(file_path).await
+        match expected_hash {
+            Some(expected) => {
+                self.file_service
+                    .add_file_with_hash(file_path, expected)
+                    .await
+            }
+            None => self.file_service.add_file(file_path).await,
+        }
     }
 }
 
@@ -128,6 +142,94 @@ impl CancelTransferUseCase {
     }
 }
 
+/// Use case for pausing an in-progress transfer
+pub struct PauseTransferUseCase {
+    transfer_service: Arc<TransferDomainService>,
+}
+
+impl PauseTransferUseCase {
+    pub fn new(transfer_service: Arc<TransferDomainService>) -> Self {
+        Self { transfer_service }
+    }
+
+    /// Execute the pause transfer use case
+    pub async fn execute(&self, transfer_id: &str) -> DomainResult<()> {
+        let transfer_id = TransferId::from_string(transfer_id.to_string());
+        self.transfer_service.pause_transfer(&transfer_id).await
+    }
+}
+
+/// Use case for resuming a paused transfer
+pub struct ResumeTransferUseCase {
+    transfer_service: Arc<TransferDomainService>,
+}
+
+impl ResumeTransferUseCase {
+    pub fn new(transfer_service: Arc<TransferDomainService>) -> Self {
+        Self { transfer_service }
+    }
+
+    /// Execute the resume transfer use case
+    pub async fn execute(&self, transfer_id: &str) -> DomainResult<()> {
+        let transfer_id = TransferId::from_string(transfer_id.to_string());
+        self.transfer_service.resume_transfer(&transfer_id).await
+    }
+}
+
+/// Use case for listing transfers that are still in flight
+pub struct ListActiveTransfersUseCase {
+    transfer_service: Arc<TransferDomainService>,
+}
+
+impl ListActiveTransfersUseCase {
+    pub fn new(transfer_service: Arc<TransferDomainService>) -> Self {
+        Self { transfer_service }
+    }
+
+    /// Execute the list active transfers use case
+    pub async fn execute(&self) -> DomainResult<Vec<TransferDto>> {
+        let transfers = self.transfer_service.list_active().await?;
+        Ok(transfers.into_iter().map(TransferDto::from).collect())
+    }
+}
+
+/// Use case for looking up a single transfer with its latest progress
+pub struct GetTransferUseCase {
+    transfer_service: Arc<TransferDomainService>,
+    handler: Option<Arc<FileTransferHandler>>,
+}
+
+impl GetTransferUseCase {
+    pub fn new(transfer_service: Arc<TransferDomainService>) -> Self {
+        Self {
+            transfer_service,
+            handler: None,
+        }
+    }
+
+    /// Report live progress from `handler` for transfers it is receiving
+    pub fn with_handler(mut self, handler: Arc<FileTransferHandler>) -> Self {
+        self.handler = Some(handler);
+        self
+    }
+
+    /// Execute the get transfer use case
+    pub async fn execute(&self, transfer_id: &str) -> DomainResult<Option<TransferDto>> {
+        let id = TransferId::from_string(transfer_id.to_string());
+        let Some(mut transfer) = self.transfer_service.find_transfer(&id).await? else {
+            return Ok(None);
+        };
+
+        if transfer.is_active()
+            && let Some(handler) = &self.handler
+            && let Some(progress) = handler.progress(transfer_id).await
+        {
+            transfer.progress = progress;
+        }
+        Ok(Some(transfer.into()))
+    }
+}
+
 /// Container for all use cases
 pub struct UseCases {
     pub send_file: SendFileUseCase,
@@ -136,6 +238,10 @@ pub struct UseCases {
     pub add_file: AddFileUseCase,
     pub accept_transfer: AcceptTransferUseCase,
     pub cancel_transfer: CancelTransferUseCase,
+    pub pause_transfer: PauseTransferUseCase,
+    pub resume_transfer: ResumeTransferUseCase,
+    pub list_active_transfers: ListActiveTransfersUseCase,
+    pub get_transfer: GetTransferUseCase,
 }
 
 impl UseCases {
@@ -150,7 +256,17 @@ impl UseCases {
             list_peers: ListPeersUseCase::new(peer_service.clone()),
             add_file: AddFileUseCase::new(file_service.clone()),
             accept_transfer: AcceptTransferUseCase::new(transfer_service.clone()),
-            cancel_transfer: CancelTransferUseCase::new(transfer_service),
+            cancel_transfer: CancelTransferUseCase::new(transfer_service.clone()),
+            pause_transfer: PauseTransferUseCase::new(transfer_service.clone()),
+            resume_transfer: ResumeTransferUseCase::new(transfer_service.clone()),
+            list_active_transfers: ListActiveTransfersUseCase::new(transfer_service.clone()),
+            get_transfer: GetTransferUseCase::new(transfer_service),
         }
     }
+
+    /// Let use cases report live progress for transfers `handler` is receiving
+    pub fn with_transfer_handler(mut self, handler: Arc<FileTransferHandler>) -> Self {
+        self.get_transfer = self.get_transfer.with_handler(handler);
+        self
+    }
 }
@@ -6,9 +6,11 @@ use ring::{
 };
 use std::error::Error;
 use std::fmt;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
 use tokio::fs::File;
 use tokio::io::AsyncReadExt;
+use tracing::warn;
 
 /// Cryptographic error types
 #[derive(Debug)]
@@ -64,14 +66,18 @@ pub fn generate_key() -> CryptoResult<Vec<u8>> {
     Ok(key)
 }
 
-/// Encrypt data with AES-256-GCM
+/// Encrypt data with AES-256-GCM under a fresh random nonce
+///
+/// Random 96-bit nonces are safe for moderate volumes, but the chance of two
+/// messages sharing a nonce grows with the number encrypted under one key:
+/// NIST caps random nonces at 2^32 messages per key. Use an [`Encryptor`]
+/// with [`NonceStrategy::Counter`] for higher volumes.
 pub fn encrypt(data: &[u8], key: &[u8]) -> CryptoResult<Vec<u8>> {
-    // Generate a random nonce
-    let mut nonce_bytes = [0u8; 12];
-    let rng = SystemRandom::new();
-    rng.fill(&mut nonce_bytes)
-        .map_err(|_| CryptoError::Encryption)?;
+    seal(data, key, random_nonce()?)
+}
 
+/// Seal `data` and prepend the nonce, so [`decrypt`] needs only the key
+pub(crate) fn seal(data: &[u8], key: &[u8], nonce_bytes: [u8; NONCE_LEN]) -> CryptoResult<Vec<u8>> {
     let unbound_key = UnboundKey::new(&AES_256_GCM, key).map_err(|_| CryptoError::InvalidKey)?;
     let aead_key = LessSafeKey::new(unbound_key);
     let nonce = aead::Nonce::assume_unique_for_key(nonce_bytes);
@@ -88,6 +94,122 @@ pub fn encrypt(data: &[u8], key: &[u8]) -> CryptoResult<Vec<u8>> {
     Ok(result)
 }
 
+fn random_nonce() -> CryptoResult<[u8; NONCE_LEN]> {
+    let mut nonce_bytes = [0u8; NONCE_LEN];
+    SystemRandom::new()
+        .fill(&mut nonce_bytes)
+        .map_err(|_| CryptoError::Encryption)?;
+    Ok(nonce_bytes)
+}
+
+const NONCE_LEN: usize = 12;
+
+/// Messages one key may encrypt with random nonces before reuse becomes a
+/// real risk
+pub const RANDOM_NONCE_LIMIT: u64 = 1 << 32;
+
+/// Counter values reserved on disk at a time, so the counter file is written
+/// once per block rather than once per message
+const COUNTER_RESERVATION: u64 = 1024;
+
+/// How an [`Encryptor`] picks the nonce for each message
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NonceStrategy {
+    /// A fresh random nonce per message. Fine up to [`RANDOM_NONCE_LIMIT`]
+    /// messages per key, after which the encryptor logs a warning.
+    Random,
+    /// A per-key counter that never repeats, persisted at `path` (keep it
+    /// next to the key). The file must not be shared between processes or
+    /// restored from a backup, or counter values will be handed out twice.
+    /// Values reserved but unused before a restart are skipped.
+    Counter { path: PathBuf },
+}
+
+enum NonceSource {
+    Random {
+        sealed: u64,
+    },
+    Counter {
+        path: PathBuf,
+        next: u64,
+        reserved: u64,
+    },
+}
+
+/// AES-256-GCM encryptor that owns a key and its nonce strategy. Output is
+/// readable by [`decrypt`].
+pub struct Encryptor {
+    key: Vec<u8>,
+    nonces: Mutex<NonceSource>,
+}
+
+impl Encryptor {
+    pub fn new(key: Vec<u8>, strategy: NonceStrategy) -> CryptoResult<Self> {
+        UnboundKey::new(&AES_256_GCM, &key).map_err(|_| CryptoError::InvalidKey)?;
+        let nonces = match strategy {
+            NonceStrategy::Random => NonceSource::Random { sealed: 0 },
+            NonceStrategy::Counter { path } => {
+                // Resume after everything a previous run may have used
+                let next = match std::fs::read(&path) {
+                    Ok(bytes) => u64::from_be_bytes(bytes.try_into().map_err(|_| {
+                        CryptoError::Other(format!("Corrupt nonce counter {}", path.display()))
+                    })?),
+                    Err(e) if e.kind() == std::io::ErrorKind::NotFound => 0,
+                    Err(e) => return Err(e.into()),
+                };
+                NonceSource::Counter {
+                    path,
+                    next,
+                    reserved: next,
+                }
+            }
+        };
+        Ok(Self {
+            key,
+            nonces: Mutex::new(nonces),
+        })
+    }
+
+    /// Encrypt `data` under the next nonce
+    pub fn encrypt(&self, data: &[u8]) -> CryptoResult<Vec<u8>> {
+        seal(data, &self.key, self.next_nonce()?)
+    }
+
+    /// Pick the nonce for the next message
+    pub fn next_nonce(&self) -> CryptoResult<[u8; NONCE_LEN]> {
+        let mut nonces = self.nonces.lock().unwrap_or_else(|e| e.into_inner());
+        match &mut *nonces {
+            NonceSource::Random { sealed } => {
+                *sealed += 1;
+                if *sealed == RANDOM_NONCE_LIMIT {
+                    warn!(
+                        "Encrypted {} messages with random nonces under one key; switch to a counter nonce strategy or rotate the key",
+                        sealed
+                    );
+                }
+                random_nonce()
+            }
+            NonceSource::Counter {
+                path,
+                next,
+                reserved,
+            } => {
+                if *next == *reserved {
+                    let until = reserved
+                        .checked_add(COUNTER_RESERVATION)
+                        .ok_or_else(|| CryptoError::Other("Nonce counter exhausted".into()))?;
+                    std::fs::write(&*path, until.to_be_bytes())?;
+                    *reserved = until;
+                }
+                let mut nonce = [0u8; NONCE_LEN];
+                nonce[NONCE_LEN - 8..].copy_from_slice(&next.to_be_bytes());
+                *next += 1;
+                Ok(nonce)
+            }
+        }
+    }
+}
+
 /// Decrypt data encrypted with AES-256-GCM
 pub fn decrypt(encrypted: &[u8], key: &[u8]) -> CryptoResult<Vec<u8>> {
     // Expect nonce (12) + tag (16)
@@ -176,6 +298,174 @@ pub mod hash {
     }
 }
 
+/// Merkle trees over the fixed-size chunks of a file, so each chunk can be
+/// checked on its own against a single root hash
+pub mod merkle {
+    use super::*;
+
+    type Hash = [u8; 32];
+
+    // Leaves and inner nodes are hashed under different prefixes so an inner
+    // node can never be passed off as a chunk
+    const LEAF_PREFIX: u8 = 0;
+    const NODE_PREFIX: u8 = 1;
+
+    fn leaf_hash(data: &[u8]) -> Hash {
+        let mut ctx = digest::Context::new(&SHA256);
+        ctx.update(&[LEAF_PREFIX]);
+        ctx.update(data);
+        ctx.finish()
+            .as_ref()
+            .try_into()
+            .expect("SHA-256 is 32 bytes")
+    }
+
+    fn node_hash(left: &Hash, right: &Hash) -> Hash {
+        let mut ctx = digest::Context::new(&SHA256);
+        ctx.update(&[NODE_PREFIX]);
+        ctx.update(left);
+        ctx.update(right);
+        ctx.finish()
+            .as_ref()
+            .try_into()
+            .expect("SHA-256 is 32 bytes")
+    }
+
+    /// Merkle tree over chunk hashes. A node without a sibling is promoted
+    /// to the next level unchanged.
+    #[derive(Debug, Clone)]
+    pub struct MerkleTree {
+        /// Leaves first, root last
+        levels: Vec<Vec<Hash>>,
+    }
+
+    impl MerkleTree {
+        /// Build a tree over `chunks`; an empty input hashes as one empty chunk
+        pub fn from_chunks<'a>(chunks: impl IntoIterator<Item = &'a [u8]>) -> Self {
+            let mut leaves: Vec<Hash> = chunks.into_iter().map(leaf_hash).collect();
+            if leaves.is_empty() {
+                leaves.push(leaf_hash(&[]));
+            }
+            Self::from_leaf_hashes(leaves)
+        }
+
+        fn from_leaf_hashes(leaves: Vec<Hash>) -> Self {
+            let mut levels = vec![leaves];
+            while let Some(level) = levels.last().filter(|level| level.len() > 1) {
+                let next = level
+                    .chunks(2)
+                    .map(|pair| match pair {
+                        [left, right] => node_hash(left, right),
+                        [only] => *only,
+                        _ => unreachable!(),
+                    })
+                    .collect();
+                levels.push(next);
+            }
+            Self { levels }
+        }
+
+        /// Hex root hash
+        pub fn root(&self) -> String {
+            hex::encode(self.levels[self.levels.len() - 1][0])
+        }
+
+        /// Hex leaf hashes, one per chunk
+        pub fn leaves(&self) -> Vec<String> {
+            self.levels[0].iter().map(hex::encode).collect()
+        }
+
+        pub fn leaf_count(&self) -> u64 {
+            self.levels[0].len() as u64
+        }
+
+        /// Hex sibling hashes from the leaf at `index` up to the root
+        pub fn proof(&self, index: u64) -> Vec<String> {
+            let mut index = index as usize;
+            let mut proof = Vec::new();
+            for level in &self.levels[..self.levels.len() - 1] {
+                if let Some(sibling) = level.get(index ^ 1) {
+                    proof.push(hex::encode(sibling));
+                }
+                index /= 2;
+            }
+            proof
+        }
+    }
+
+    /// Check that `data` is chunk `index` of `leaf_count` under `root`
+    pub fn verify_chunk(
+        data: &[u8],
+        index: u64,
+        leaf_count: u64,
+        proof: &[String],
+        root: &str,
+    ) -> bool {
+        if index >= leaf_count {
+            return false;
+        }
+        let mut hash = leaf_hash(data);
+        let mut siblings = proof.iter();
+        let (mut index, mut width) = (index, leaf_count);
+        while width > 1 {
+            // The last node of an odd level was promoted without a sibling
+            if index ^ 1 < width {
+                let Some(sibling) = siblings.next().and_then(|s| {
+                    hex::decode(s)
+                        .ok()
+                        .and_then(|bytes| Hash::try_from(bytes).ok())
+                }) else {
+                    return false;
+                };
+                hash = if index % 2 == 0 {
+                    node_hash(&hash, &sibling)
+                } else {
+                    node_hash(&sibling, &hash)
+                };
+            }
+            index /= 2;
+            width = width.div_ceil(2);
+        }
+        siblings.next().is_none() && hex::encode(hash) == root
+    }
+
+    /// Build the Merkle tree of a file split into `chunk_size` chunks
+    pub async fn compute_chunk_merkle<P: AsRef<Path>>(
+        path: P,
+        chunk_size: usize,
+    ) -> CryptoResult<MerkleTree> {
+        if chunk_size == 0 {
+            return Err(CryptoError::Other(
+                "Chunk size must be greater than 0".into(),
+            ));
+        }
+        let mut file = File::open(path).await?;
+        let mut chunk = vec![0u8; chunk_size];
+        let mut leaves = Vec::new();
+        loop {
+            let mut filled = 0;
+            while filled < chunk_size {
+                let n = file.read(&mut chunk[filled..]).await?;
+                if n == 0 {
+                    break;
+                }
+                filled += n;
+            }
+            if filled == 0 {
+                break;
+            }
+            leaves.push(leaf_hash(&chunk[..filled]));
+            if filled < chunk_size {
+                break;
+            }
+        }
+        if leaves.is_empty() {
+            leaves.push(leaf_hash(&[]));
+        }
+        Ok(MerkleTree::from_leaf_hashes(leaves))
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -230,6 +520,63 @@ mod tests {
         assert!(!verified);
     }
 
+    #[test]
+    fn test_counter_nonces_strictly_increase_across_restarts() {
+        let dir = tempfile::tempdir().unwrap();
+        let strategy = NonceStrategy::Counter {
+            path: dir.path().join("transfer.key.nonce"),
+        };
+        let key = generate_key().unwrap();
+
+        let mut nonces = Vec::new();
+        let encryptor = Encryptor::new(key.clone(), strategy.clone()).unwrap();
+        for _ in 0..2000 {
+            nonces.push(encryptor.next_nonce().unwrap());
+        }
+        // A restart resumes past every value the first run reserved
+        let encryptor = Encryptor::new(key.clone(), strategy).unwrap();
+        for _ in 0..10 {
+            nonces.push(encryptor.next_nonce().unwrap());
+        }
+
+        assert!(nonces.windows(2).all(|w| w[0] < w[1]));
+
+        let encrypted = encryptor.encrypt(b"counted").unwrap();
+        assert!(encrypted[..NONCE_LEN] > nonces.last().unwrap()[..]);
+        assert_eq!(decrypt(&encrypted, &key).unwrap(), b"counted");
+    }
+
+    #[tokio::test]
+    async fn test_chunk_merkle_proofs_verify_each_chunk() {
+        let content: Vec<u8> = (0..10_000u32).map(|i| (i % 251) as u8).collect();
+        let mut temp_file = NamedTempFile::new().unwrap();
+        temp_file.write_all(&content).unwrap();
+
+        // 10 000 bytes in 1 KiB chunks gives 10 leaves, so odd levels occur
+        let tree = merkle::compute_chunk_merkle(temp_file.path(), 1024)
+            .await
+            .unwrap();
+        assert_eq!(tree.leaf_count(), 10);
+        let in_memory = merkle::MerkleTree::from_chunks(content.chunks(1024));
+        assert_eq!(tree.root(), in_memory.root());
+        assert_eq!(tree.leaves(), in_memory.leaves());
+
+        let root = tree.root();
+        for (index, chunk) in content.chunks(1024).enumerate() {
+            let index = index as u64;
+            let proof = tree.proof(index);
+            assert!(merkle::verify_chunk(chunk, index, 10, &proof, &root));
+            assert!(!merkle::verify_chunk(b"forged", index, 10, &proof, &root));
+            assert!(!merkle::verify_chunk(
+                chunk,
+                (index + 1) % 10,
+                10,
+                &proof,
+                &root
+            ));
+        }
+    }
+
     #[tokio::test]
     async fn test_file_hash() {
         let mut temp_file = NamedTempFile::new().unwrap();
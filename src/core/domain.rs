@@ -1,5 +1,8 @@
+use super::traits::DomainResult;
 use serde::{Deserialize, Serialize};
-use std::time::SystemTime;
+use std::collections::BTreeSet;
+use std::path::Path;
+use std::time::{Duration, SystemTime};
 use uuid::Uuid;
 
 /// Core domain entity representing a peer in the network
@@ -67,6 +70,40 @@ pub struct File {
     pub modified_at: Option<SystemTime>,
 }
 
+impl File {
+    /// Describe the file at `path`, hashing its contents.
+    ///
+    /// Timestamps come from the filesystem; `created_at` falls back to now
+    /// and `modified_at` is `None` where the platform doesn't record them.
+    pub async fn from_path(path: impl AsRef<Path>) -> DomainResult<Self> {
+        let path = path.as_ref();
+        let metadata = tokio::fs::metadata(path)
+            .await
+            .map_err(|e| format!("Failed to read metadata for {}: {}", path.display(), e))?;
+        if !metadata.is_file() {
+            return Err(format!("{} is not a file", path.display()).into());
+        }
+        let name = path
+            .file_name()
+            .ok_or("Invalid filename")?
+            .to_string_lossy()
+            .to_string();
+        let hash = super::crypto::compute_file_hash(path)
+            .await
+            .map_err(|e| format!("Failed to hash {}: {}", path.display(), e))?;
+
+        Ok(Self {
+            id: FileId::new(),
+            name,
+            size: metadata.len(),
+            hash,
+            path: path.display().to_string(),
+            created_at: metadata.created().unwrap_or_else(|_| SystemTime::now()),
+            modified_at: metadata.modified().ok(),
+        })
+    }
+}
+
 /// Strongly typed file identifier
 #[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct FileId(pub String);
@@ -104,6 +141,26 @@ pub struct Transfer {
     pub completed_at: Option<SystemTime>,
 }
 
+impl Transfer {
+    /// Time from start to completion, or `None` while the transfer is unfinished
+    pub fn duration(&self) -> Option<Duration> {
+        self.completed_at?.duration_since(self.started_at).ok()
+    }
+
+    /// Whether the transfer has reached a final state
+    pub fn is_terminal(&self) -> bool {
+        matches!(
+            self.status,
+            TransferStatus::Completed | TransferStatus::Failed { .. } | TransferStatus::Cancelled
+        )
+    }
+
+    /// Whether the transfer is still expected to make progress
+    pub fn is_active(&self) -> bool {
+        !self.is_terminal()
+    }
+}
+
 /// Strongly typed transfer identifier
 #[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct TransferId(pub String);
@@ -117,6 +174,13 @@ impl TransferId {
         Self(id)
     }
 
+    /// Id derived from the file contents and both peers, so that retrying a
+    /// send of the same file to the same peer yields the same id
+    pub fn derived(file_hash: &str, sender: &PeerId, receiver: &PeerId) -> Self {
+        let input = format!("{}\0{}\0{}", file_hash, sender.as_str(), receiver.as_str());
+        Self(super::crypto::hash::compute_data_hash(input.as_bytes()))
+    }
+
     pub fn as_str(&self) -> &str {
         &self.0
     }
@@ -133,8 +197,14 @@ impl Default for TransferId {
 pub enum TransferStatus {
     Pending,
     InProgress,
+    /// Every chunk has arrived and the file is being checked against the
+    /// sender's hash
+    Verifying,
+    Paused,
     Completed,
-    Failed { reason: String },
+    Failed {
+        reason: String,
+    },
     Cancelled,
 }
 
@@ -145,7 +215,6 @@ pub struct TransferProgress {
     pub total_bytes: u64,
     pub chunks_transferred: u64,
     pub total_chunks: u64,
-    pub percentage: f32,
 }
 
 impl TransferProgress {
@@ -155,18 +224,26 @@ impl TransferProgress {
             total_bytes,
             chunks_transferred: 0,
             total_chunks,
-            percentage: 0.0,
         }
     }
 
     pub fn update(&mut self, bytes_transferred: u64, chunks_transferred: u64) {
         self.bytes_transferred = bytes_transferred;
         self.chunks_transferred = chunks_transferred;
-        self.percentage = if self.total_bytes > 0 {
-            (self.bytes_transferred as f32 / self.total_bytes as f32) * 100.0
-        } else {
-            0.0
-        };
+    }
+
+    /// Percent done to two decimal places, rounded down so only a complete
+    /// transfer reads 100
+    pub fn percentage(&self) -> f32 {
+        if self.is_complete() {
+            return 100.0;
+        }
+        if self.total_bytes == 0 {
+            return 0.0;
+        }
+        let hundredths = u128::from(self.bytes_transferred.min(self.total_bytes)) * 10_000
+            / u128::from(self.total_bytes);
+        hundredths.min(9_999) as f32 / 100.0
     }
 
     pub fn is_complete(&self) -> bool {
@@ -174,6 +251,23 @@ impl TransferProgress {
     }
 }
 
+/// Receiver-side state persisted so an interrupted transfer can resume
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ResumeState {
+    /// File the received chunks have been written to
+    pub partial_path: String,
+    pub received_chunks: BTreeSet<u64>,
+}
+
+impl ResumeState {
+    /// Chunks of a `total_chunks` transfer that still have to be received
+    pub fn missing_chunks(&self, total_chunks: u64) -> Vec<u64> {
+        (0..total_chunks)
+            .filter(|index| !self.received_chunks.contains(index))
+            .collect()
+    }
+}
+
 /// Network peer information
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Peer {
@@ -181,6 +275,25 @@ pub struct Peer {
     pub addresses: Vec<String>, // Multiaddr as strings for serialization
     pub last_seen: SystemTime,
     pub is_connected: bool,
+    /// Protocols the peer advertised through identify
+    #[serde(default)]
+    pub protocols: Vec<String>,
+}
+
+/// A peer refused at connection time, stored so the ban outlives restarts
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PeerBan {
+    pub peer: PeerId,
+    pub reason: Option<String>,
+    /// When the ban lifts; `None` bans the peer until it is unbanned
+    pub until: Option<SystemTime>,
+}
+
+impl PeerBan {
+    /// Whether the ban still applies at `now`
+    pub fn is_active_at(&self, now: SystemTime) -> bool {
+        self.until.is_none_or(|until| now < until)
+    }
 }
 
 /// Chunk of file data
@@ -191,6 +304,16 @@ pub struct Chunk {
     pub is_last: bool,
 }
 
+/// A published [`DomainEvent`] stamped by its publisher. `seq` starts at 1
+/// and increases by one with each event a publisher emits, so consumers can
+/// order and deduplicate what they receive.
+#[derive(Debug, Clone)]
+pub struct EventEnvelope {
+    pub seq: u64,
+    pub at: SystemTime,
+    pub event: DomainEvent,
+}
+
 /// Domain events that can occur in the system
 #[derive(Debug, Clone)]
 pub enum DomainEvent {
@@ -203,6 +326,12 @@ pub enum DomainEvent {
     PeerDisconnected {
         peer_id: PeerId,
     },
+    /// A connected peer described itself through identify
+    PeerIdentified {
+        peer_id: PeerId,
+        addresses: Vec<String>,
+        protocols: Vec<String>,
+    },
     TransferStarted {
         transfer: Box<Transfer>,
     },
@@ -210,6 +339,17 @@ pub enum DomainEvent {
         transfer_id: TransferId,
         progress: TransferProgress,
     },
+    TransferPaused {
+        transfer_id: TransferId,
+        reason: String,
+    },
+    TransferResumed {
+        transfer_id: TransferId,
+    },
+    /// The last chunk arrived and the file's hash is being checked
+    TransferVerifying {
+        transfer_id: TransferId,
+    },
     TransferCompleted {
         transfer_id: TransferId,
     },
@@ -221,4 +361,106 @@ pub enum DomainEvent {
         transfer_id: TransferId,
         chunk: Chunk,
     },
+    Heartbeat {
+        uptime: Duration,
+        connected_peers: usize,
+        active_transfers: usize,
+    },
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn transfer(status: TransferStatus, completed_after: Option<Duration>) -> Transfer {
+        let started_at = SystemTime::now();
+        Transfer {
+            id: TransferId::new(),
+            file: File {
+                id: FileId::new(),
+                name: "notes.txt".to_string(),
+                size: 10,
+                hash: "abc".to_string(),
+                path: "/tmp/notes.txt".to_string(),
+                created_at: started_at,
+                modified_at: None,
+            },
+            sender: PeerId::new("alice".to_string()),
+            receiver: PeerId::new("bob".to_string()),
+            status,
+            progress: TransferProgress::new(10, 1),
+            started_at,
+            completed_at: completed_after.map(|elapsed| started_at + elapsed),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_file_from_path_reads_metadata_and_hash() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("fixture.txt");
+        std::fs::write(&path, b"hello world").unwrap();
+
+        let file = File::from_path(&path).await.unwrap();
+
+        assert_eq!(file.name, "fixture.txt");
+        assert_eq!(file.size, 11);
+        assert_eq!(
+            file.hash,
+            "b94d27b9934d3e08a52e52d7da7dabfac484efe37a5380ee9088f7ace2efcde9"
+        );
+        assert_eq!(file.path, path.display().to_string());
+        assert_eq!(
+            file.modified_at,
+            Some(std::fs::metadata(&path).unwrap().modified().unwrap())
+        );
+        assert!(File::from_path(dir.path()).await.is_err());
+    }
+
+    #[test]
+    fn test_in_progress_transfer_has_no_duration() {
+        let transfer = transfer(TransferStatus::InProgress, None);
+        assert_eq!(transfer.duration(), None);
+        assert!(transfer.is_active());
+        assert!(!transfer.is_terminal());
+    }
+
+    #[test]
+    fn test_completed_transfer_reports_duration() {
+        let transfer = transfer(TransferStatus::Completed, Some(Duration::from_secs(3)));
+        assert_eq!(transfer.duration(), Some(Duration::from_secs(3)));
+        assert!(transfer.is_terminal());
+        assert!(!transfer.is_active());
+    }
+
+    #[test]
+    fn test_percentage_reaches_100_only_when_complete() {
+        let mut progress = TransferProgress::new(100_001, 3);
+        for bytes in [0, 1, 33_334, 50_000, 99_999, 100_000] {
+            progress.update(bytes, 2);
+            assert!(progress.percentage() < 100.0, "{} bytes", bytes);
+        }
+        // All bytes in but a chunk still unacknowledged
+        progress.update(100_001, 2);
+        assert!(!progress.is_complete());
+        assert_eq!(progress.percentage(), 99.99);
+
+        progress.update(100_001, 3);
+        assert!(progress.is_complete());
+        assert_eq!(progress.percentage(), 100.0);
+    }
+
+    #[test]
+    fn test_percentage_serializes_to_two_decimals() {
+        let mut progress = TransferProgress::new(3, 3);
+        progress.update(1, 1);
+        assert_eq!(
+            serde_json::to_string(&progress.percentage()).unwrap(),
+            "33.33"
+        );
+        progress.update(2, 2);
+        assert_eq!(
+            serde_json::to_string(&progress.percentage()).unwrap(),
+            "66.66"
+        );
+    }
 }
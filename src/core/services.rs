@@ -1,7 +1,34 @@
 use super::domain::*;
 use super::traits::*;
-use std::sync::Arc;
-use std::time::SystemTime;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime};
+use tracing::{debug, warn};
+
+/// How often interrupted transfers re-check whether their peer is back
+const RECOVERY_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Default minimum time between persisted progress updates of one transfer
+pub const DEFAULT_PROGRESS_PERSIST_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Progress gain, in percent, that is persisted even within the interval
+const PROGRESS_PERSIST_STEP: f32 = 5.0;
+
+/// When a transfer's progress was last written to the repository
+struct PersistedProgress {
+    at: Instant,
+    percentage: f32,
+}
+
+/// Outcome of reconciling transfers interrupted by a restart
+#[derive(Debug, Clone, Default)]
+pub struct TransferRecovery {
+    /// Paused transfers whose peer is back, ready to be sent again
+    pub resumable: Vec<TransferId>,
+    pub failed: Vec<TransferId>,
+    /// Chunks already on disk for resumable transfers that recorded any
+    pub resume_states: HashMap<TransferId, ResumeState>,
+}
 
 /// Domain service for managing file transfers
 pub struct TransferDomainService {
@@ -10,6 +37,14 @@ pub struct TransferDomainService {
     peer_repo: Arc<dyn PeerRepository>,
     file_service: Arc<dyn FileService>,
     event_publisher: Arc<dyn EventPublisher>,
+    deterministic_ids: bool,
+    verify_after: bool,
+    progress_persist_interval: Duration,
+    persisted_progress: Mutex<HashMap<TransferId, PersistedProgress>>,
+    /// Newest progress of in-flight transfers, ahead of what was persisted
+    live_progress: Mutex<HashMap<TransferId, TransferProgress>>,
+    /// Sender whose live sends follow pauses, if any
+    transfer_control: Option<Arc<dyn TransferControl>>,
 }
 
 impl TransferDomainService {
@@ -26,9 +61,68 @@ impl TransferDomainService {
             peer_repo,
             file_service,
             event_publisher,
+            deterministic_ids: false,
+            verify_after: true,
+            progress_persist_interval: DEFAULT_PROGRESS_PERSIST_INTERVAL,
+            persisted_progress: Mutex::new(HashMap::new()),
+            live_progress: Mutex::new(HashMap::new()),
+            transfer_control: None,
         }
     }
 
+    /// Pause and resume sends in progress on `control` along with the
+    /// transfers they belong to, rather than only changing their status
+    pub fn with_transfer_control(mut self, control: Arc<dyn TransferControl>) -> Self {
+        self.transfer_control = Some(control);
+        self
+    }
+
+    /// Derive transfer ids from the file hash and both peers instead of
+    /// generating random ones, so a retried send continues the earlier
+    /// transfer rather than starting a new one
+    pub fn with_deterministic_ids(mut self, enabled: bool) -> Self {
+        self.deterministic_ids = enabled;
+        self
+    }
+
+    /// Whether a transfer whose last chunk arrived is checked against its
+    /// file hash before it counts as completed (the default)
+    pub fn with_verify_after(mut self, verify_after: bool) -> Self {
+        self.verify_after = verify_after;
+        self
+    }
+
+    /// Write a transfer's progress at most once per `interval`, unless it has
+    /// advanced by several percent since the last write. Completion is always
+    /// written immediately; `Duration::ZERO` persists every update.
+    pub fn with_progress_persist_interval(mut self, interval: Duration) -> Self {
+        self.progress_persist_interval = interval;
+        self
+    }
+
+    /// Whether an in-flight progress update is worth writing to the repository
+    fn should_persist_progress(&self, transfer_id: &TransferId, percentage: f32) -> bool {
+        let mut persisted = self
+            .persisted_progress
+            .lock()
+            .unwrap_or_else(|e| e.into_inner());
+        let now = Instant::now();
+        let due = persisted.get(transfer_id).is_none_or(|last| {
+            now.duration_since(last.at) >= self.progress_persist_interval
+                || percentage - last.percentage >= PROGRESS_PERSIST_STEP
+        });
+        if due {
+            persisted.insert(
+                transfer_id.clone(),
+                PersistedProgress {
+                    at: now,
+                    percentage,
+                },
+            );
+        }
+        due
+    }
+
     /// Initiate a new file transfer
     pub async fn initiate_transfer(
         &self,
@@ -47,20 +141,21 @@ impl TransferDomainService {
             return Err("Receiver peer is not connected".into());
         }
 
-        // Get file metadata
-        let (file_name, file_size) = self.file_service.get_file_metadata(file_path).await?;
-        let file_hash = self.file_service.calculate_file_hash(file_path).await?;
-
-        // Create file entity
-        let file = File {
-            id: FileId::new(),
-            name: file_name,
-            size: file_size,
-            hash: file_hash,
-            path: file_path.to_string(),
-            created_at: SystemTime::now(),
-            modified_at: None,
+        // Create file entity from its metadata and contents
+        let file = self.file_service.add_file(file_path).await?;
+        let file_size = file.size;
+
+        let id = if self.deterministic_ids {
+            TransferId::derived(&file.hash, &sender, &receiver)
+        } else {
+            TransferId::new()
         };
+        if let Some(existing) = self.transfer_repo.find_transfer_by_id(&id).await?
+            && existing.is_active()
+        {
+            debug!("Continuing existing transfer {}", id.as_str());
+            return Ok(existing);
+        }
 
         // Calculate chunks
         const CHUNK_SIZE: u64 = 1024 * 1024; // 1MB
@@ -68,7 +163,7 @@ impl TransferDomainService {
 
         // Create transfer entity
         let transfer: Transfer = Transfer {
-            id: TransferId::new(),
+            id,
             file: file.clone(),
             sender,
             receiver,
@@ -128,15 +223,46 @@ impl TransferDomainService {
             .update(bytes_transferred, chunks_transferred);
 
         if transfer.progress.is_complete() {
-            transfer.status = TransferStatus::Completed;
-            transfer.completed_at = Some(SystemTime::now());
+            self.persisted_progress
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .remove(transfer_id);
+            self.live_progress
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .remove(transfer_id);
+            // Done only once the hash checks out, when there is one to check
+            if !self.verify_after || transfer.file.hash.is_empty() {
+                return self.settle(transfer, Ok(())).await;
+            }
+            transfer.status = TransferStatus::Verifying;
+            self.transfer_repo.save_transfer(&transfer).await?;
 
             self.event_publisher
-                .publish(DomainEvent::TransferCompleted {
+                .publish(DomainEvent::TransferVerifying {
                     transfer_id: transfer_id.clone(),
                 })
                 .await?;
+            let verified = match self
+                .file_service
+                .calculate_file_hash(&transfer.file.path)
+                .await
+            {
+                Ok(hash) if hash.eq_ignore_ascii_case(&transfer.file.hash) => Ok(()),
+                Ok(_) => Err("File hash does not match sender's hash".to_string()),
+                Err(e) => Err(format!("Failed to hash received file: {}", e)),
+            };
+            self.settle(transfer, verified).await?;
         } else {
+            // Progress counters are absolute, so a skipped write loses nothing
+            // the next one won't restore
+            if self.should_persist_progress(transfer_id, transfer.progress.percentage()) {
+                self.transfer_repo.save_transfer(&transfer).await?;
+            }
+            self.live_progress
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .insert(transfer_id.clone(), transfer.progress.clone());
             self.event_publisher
                 .publish(DomainEvent::TransferProgress {
                     transfer_id: transfer_id.clone(),
@@ -145,12 +271,305 @@ impl TransferDomainService {
                 .await?;
         }
 
-        self.transfer_repo.save_transfer(&transfer).await?;
         Ok(())
     }
 
-    /// Cancel a transfer
+    /// Settle a verifying transfer: completed when its hash matched,
+    /// failed with the reason otherwise
+    pub async fn finish_verification(
+        &self,
+        transfer_id: &TransferId,
+        verified: Result<(), String>,
+    ) -> DomainResult<()> {
+        let transfer = self
+            .transfer_repo
+            .find_transfer_by_id(transfer_id)
+            .await?
+            .ok_or("Transfer not found")?;
+        if !matches!(transfer.status, TransferStatus::Verifying) {
+            return Err("Transfer is not being verified".into());
+        }
+        self.settle(transfer, verified).await
+    }
+
+    /// Mark a fully received transfer completed, or failed with the reason
+    /// its verification gave
+    async fn settle(
+        &self,
+        mut transfer: Transfer,
+        verified: Result<(), String>,
+    ) -> DomainResult<()> {
+        let transfer_id = transfer.id.clone();
+        let event = match verified {
+            Ok(()) => {
+                transfer.status = TransferStatus::Completed;
+                transfer.completed_at = Some(SystemTime::now());
+                self.transfer_repo.clear_resume_state(&transfer_id).await?;
+                DomainEvent::TransferCompleted { transfer_id }
+            }
+            Err(reason) => {
+                transfer.status = TransferStatus::Failed {
+                    reason: reason.clone(),
+                };
+                DomainEvent::TransferFailed {
+                    transfer_id,
+                    reason,
+                }
+            }
+        };
+        self.transfer_repo.save_transfer(&transfer).await?;
+        self.event_publisher.publish(event).await
+    }
+
+    /// Persist that `chunk_index` has been written to `partial_path`
+    pub async fn record_received_chunk(
+        &self,
+        transfer_id: &TransferId,
+        partial_path: &str,
+        chunk_index: u64,
+    ) -> DomainResult<()> {
+        self.transfer_repo
+            .record_received_chunk(transfer_id, partial_path, chunk_index)
+            .await
+    }
+
+    /// List transfers sent by a peer
+    pub async fn list_sent(&self, peer_id: &PeerId) -> DomainResult<Vec<Transfer>> {
+        self.transfer_repo.find_transfers_by_sender(peer_id).await
+    }
+
+    /// List transfers received by a peer
+    pub async fn list_received(&self, peer_id: &PeerId) -> DomainResult<Vec<Transfer>> {
+        self.transfer_repo.find_transfers_by_receiver(peer_id).await
+    }
+
+    /// List every transfer a peer took part in, as sender or receiver
+    pub async fn list_transfers_for_peer(&self, peer_id: &PeerId) -> DomainResult<Vec<Transfer>> {
+        let mut transfers = self.list_sent(peer_id).await?;
+        transfers.extend(
+            self.list_received(peer_id)
+                .await?
+                .into_iter()
+                .filter(|t| t.sender != *peer_id),
+        );
+        Ok(transfers)
+    }
+
+    /// Find transfer by ID
+    pub async fn find_transfer(&self, transfer_id: &TransferId) -> DomainResult<Option<Transfer>> {
+        self.transfer_repo.find_transfer_by_id(transfer_id).await
+    }
+
+    /// Latest progress of a transfer, including updates not yet written to
+    /// the repository; `None` if the transfer is unknown
+    pub async fn get_progress(
+        &self,
+        transfer_id: &TransferId,
+    ) -> DomainResult<Option<TransferProgress>> {
+        let Some(transfer) = self.transfer_repo.find_transfer_by_id(transfer_id).await? else {
+            return Ok(None);
+        };
+        let live = match transfer.status {
+            TransferStatus::InProgress => self
+                .live_progress
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .get(transfer_id)
+                .cloned(),
+            _ => None,
+        };
+        Ok(Some(live.unwrap_or(transfer.progress)))
+    }
+
+    /// List transfers that have not reached a terminal state
+    pub async fn list_active(&self) -> DomainResult<Vec<Transfer>> {
+        self.transfer_repo.list_active_transfers().await
+    }
+
+    /// Reconcile transfers left `InProgress` or `Paused` by a previous run.
+    ///
+    /// Nothing is sending them any more, so all of them end up `Paused`.
+    /// Those whose peer is connected, or reconnects within `grace_period`,
+    /// are reported as resumable along with their recorded [`ResumeState`];
+    /// starting the send again is up to the caller. Transfers whose partial
+    /// file is gone or whose recorded chunks don't fit the transfer, and
+    /// those whose peer stays away, are marked failed.
+    ///
+    /// Peers must not still be marked connected from an earlier run; see
+    /// [`PeerDomainService::reset_connections`].
+    pub async fn recover_interrupted_transfers(
+        &self,
+        grace_period: Duration,
+    ) -> DomainResult<TransferRecovery> {
+        let mut recovery = TransferRecovery::default();
+        let mut interrupted = Vec::new();
+        for mut transfer in self.transfer_repo.list_active_transfers().await? {
+            if !matches!(
+                transfer.status,
+                TransferStatus::InProgress | TransferStatus::Verifying | TransferStatus::Paused
+            ) {
+                continue;
+            }
+            let state = self.transfer_repo.find_resume_state(&transfer.id).await?;
+            if let Some(reason) = state
+                .as_ref()
+                .and_then(|s| unusable_resume_state(&transfer, s))
+            {
+                self.fail_interrupted(&mut transfer, reason).await?;
+                recovery.failed.push(transfer.id);
+                continue;
+            }
+            if !matches!(transfer.status, TransferStatus::Paused) {
+                transfer.status = TransferStatus::Paused;
+                self.transfer_repo.save_transfer(&transfer).await?;
+            }
+            interrupted.push((transfer, state));
+        }
+
+        let deadline = tokio::time::Instant::now() + grace_period;
+        loop {
+            let mut waiting = Vec::new();
+            for (transfer, state) in interrupted {
+                if self.is_counterparty_connected(&transfer).await? {
+                    if let Some(state) = state {
+                        recovery.resume_states.insert(transfer.id.clone(), state);
+                    }
+                    recovery.resumable.push(transfer.id);
+                } else {
+                    waiting.push((transfer, state));
+                }
+            }
+            interrupted = waiting;
+
+            let now = tokio::time::Instant::now();
+            if interrupted.is_empty() || now >= deadline {
+                break;
+            }
+            tokio::time::sleep(RECOVERY_POLL_INTERVAL.min(deadline - now)).await;
+        }
+
+        for (mut transfer, _) in interrupted {
+            self.fail_interrupted(
+                &mut transfer,
+                "Peer did not reconnect after restart".to_string(),
+            )
+            .await?;
+            recovery.failed.push(transfer.id);
+        }
+
+        Ok(recovery)
+    }
+
+    /// Mark a transfer interrupted by a restart as failed
+    async fn fail_interrupted(&self, transfer: &mut Transfer, reason: String) -> DomainResult<()> {
+        transfer.status = TransferStatus::Failed {
+            reason: reason.clone(),
+        };
+        self.transfer_repo.save_transfer(transfer).await?;
+        self.event_publisher
+            .publish(DomainEvent::TransferFailed {
+                transfer_id: transfer.id.clone(),
+                reason,
+            })
+            .await
+    }
+
+    /// Whether either side of the transfer is currently a connected peer
+    async fn is_counterparty_connected(&self, transfer: &Transfer) -> DomainResult<bool> {
+        for peer_id in [&transfer.sender, &transfer.receiver] {
+            if let Some(peer) = self.peer_repo.find_peer_by_id(peer_id).await?
+                && peer.is_connected
+            {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    /// Look up a transfer that has yet to finish, for cancelling, pausing or
+    /// resuming it
+    async fn find_unfinished(&self, transfer_id: &TransferId) -> DomainResult<Transfer> {
+        let transfer = self
+            .transfer_repo
+            .find_transfer_by_id(transfer_id)
+            .await?
+            .ok_or_else(|| TransferControlError::NotFound {
+                transfer_id: transfer_id.as_str().to_string(),
+            })?;
+        if transfer.is_terminal() {
+            return Err(TransferControlError::Finished {
+                transfer_id: transfer_id.as_str().to_string(),
+                status: transfer.status,
+            }
+            .into());
+        }
+        Ok(transfer)
+    }
+
+    /// Cancel a transfer that has yet to finish
     pub async fn cancel_transfer(&self, transfer_id: &TransferId) -> DomainResult<()> {
+        let mut transfer = self.find_unfinished(transfer_id).await?;
+        transfer.status = TransferStatus::Cancelled;
+        self.transfer_repo.save_transfer(&transfer).await?;
+        if let Some(control) = &self.transfer_control {
+            control.cancel(&transfer.id).await;
+        }
+        self.live_progress
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(transfer_id);
+        Ok(())
+    }
+
+    /// Pause an in-progress transfer until [`resume_transfer`](Self::resume_transfer)
+    pub async fn pause_transfer(&self, transfer_id: &TransferId) -> DomainResult<()> {
+        let mut transfer = self.find_unfinished(transfer_id).await?;
+        if !matches!(transfer.status, TransferStatus::InProgress) {
+            return Err(TransferControlError::NotApplicable {
+                transfer_id: transfer_id.as_str().to_string(),
+                action: "pause",
+                status: transfer.status,
+            }
+            .into());
+        }
+        transfer.status = TransferStatus::Paused;
+        self.transfer_repo.save_transfer(&transfer).await?;
+        if let Some(control) = &self.transfer_control {
+            control.pause(&transfer.id).await;
+        }
+        self.event_publisher
+            .publish(DomainEvent::TransferPaused {
+                transfer_id: transfer.id,
+                reason: "Paused by user".to_string(),
+            })
+            .await
+    }
+
+    /// Continue a paused transfer
+    pub async fn resume_transfer(&self, transfer_id: &TransferId) -> DomainResult<()> {
+        let mut transfer = self.find_unfinished(transfer_id).await?;
+        if !matches!(transfer.status, TransferStatus::Paused) {
+            return Err(TransferControlError::NotApplicable {
+                transfer_id: transfer_id.as_str().to_string(),
+                action: "resume",
+                status: transfer.status,
+            }
+            .into());
+        }
+        transfer.status = TransferStatus::InProgress;
+        self.transfer_repo.save_transfer(&transfer).await?;
+        if let Some(control) = &self.transfer_control {
+            control.resume(&transfer.id).await;
+        }
+        self.event_publisher
+            .publish(DomainEvent::TransferResumed {
+                transfer_id: transfer.id,
+            })
+            .await
+    }
+
+    /// Restart a failed transfer from the beginning under the same id
+    pub async fn retry_transfer(&self, transfer_id: &TransferId) -> DomainResult<Transfer> {
         let mut transfer = self
             .transfer_repo
             .find_transfer_by_id(transfer_id)
@@ -158,13 +577,199 @@ impl TransferDomainService {
             .ok_or("Transfer not found")?;
 
         match transfer.status {
-            TransferStatus::Completed => Err("Cannot cancel completed transfer".into()),
-            _ => {
-                transfer.status = TransferStatus::Cancelled;
-                self.transfer_repo.save_transfer(&transfer).await?;
-                Ok(())
+            TransferStatus::Failed { .. } => {}
+            TransferStatus::Completed => return Err("Cannot retry completed transfer".into()),
+            _ => return Err("Only failed transfers can be retried".into()),
+        }
+
+        transfer.status = TransferStatus::Pending;
+        transfer.progress = TransferProgress::new(
+            transfer.progress.total_bytes,
+            transfer.progress.total_chunks,
+        );
+        transfer.started_at = SystemTime::now();
+        transfer.completed_at = None;
+        self.transfer_repo.save_transfer(&transfer).await?;
+
+        self.event_publisher
+            .publish(DomainEvent::TransferStarted {
+                transfer: Box::new(transfer.clone()),
+            })
+            .await?;
+
+        Ok(transfer)
+    }
+
+    /// Pause every in-progress transfer with `peer_id` so it can be resumed
+    /// once the peer is back
+    pub async fn pause_transfers_with_peer(
+        &self,
+        peer_id: &PeerId,
+    ) -> DomainResult<Vec<TransferId>> {
+        let mut paused = Vec::new();
+        for mut transfer in self.list_transfers_for_peer(peer_id).await? {
+            if !matches!(transfer.status, TransferStatus::InProgress) {
+                continue;
             }
+            transfer.status = TransferStatus::Paused;
+            self.transfer_repo.save_transfer(&transfer).await?;
+            if let Some(control) = &self.transfer_control {
+                control.pause(&transfer.id).await;
+            }
+            self.event_publisher
+                .publish(DomainEvent::TransferPaused {
+                    transfer_id: transfer.id.clone(),
+                    reason: format!("Peer {} went offline", peer_id.as_str()),
+                })
+                .await?;
+            paused.push(transfer.id);
         }
+        Ok(paused)
+    }
+}
+
+/// Why the chunks recorded for an interrupted transfer can't be resumed
+fn unusable_resume_state(transfer: &Transfer, state: &ResumeState) -> Option<String> {
+    if !std::path::Path::new(&state.partial_path).is_file() {
+        return Some(format!("Partial file {} is missing", state.partial_path));
+    }
+    let total_chunks = transfer.progress.total_chunks;
+    if let Some(&index) = state.received_chunks.range(total_chunks..).next() {
+        return Some(format!(
+            "Recorded chunk {} is beyond the transfer's {} chunks",
+            index, total_chunks
+        ));
+    }
+    None
+}
+
+/// Pauses a peer's in-progress transfers as soon as it disconnects
+pub struct PeerDisconnectHandler {
+    transfers: Arc<TransferDomainService>,
+}
+
+impl PeerDisconnectHandler {
+    pub fn new(transfers: Arc<TransferDomainService>) -> Self {
+        Self { transfers }
+    }
+}
+
+#[async_trait::async_trait]
+impl EventHandler for PeerDisconnectHandler {
+    async fn handle_event(&self, event: DomainEvent) -> DomainResult<()> {
+        if let DomainEvent::PeerDisconnected { peer_id } = event {
+            let paused = self.transfers.pause_transfers_with_peer(&peer_id).await?;
+            if !paused.is_empty() {
+                warn!(
+                    "Paused {} transfers after {} disconnected",
+                    paused.len(),
+                    peer_id.as_str()
+                );
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Records what identify reports about a peer on its stored [`Peer`], and
+/// marks the peer disconnected once its last connection closes
+pub struct PeerIdentifyHandler {
+    peer_repo: Arc<dyn PeerRepository>,
+}
+
+impl PeerIdentifyHandler {
+    pub fn new(peer_repo: Arc<dyn PeerRepository>) -> Self {
+        Self { peer_repo }
+    }
+}
+
+#[async_trait::async_trait]
+impl EventHandler for PeerIdentifyHandler {
+    async fn handle_event(&self, event: DomainEvent) -> DomainResult<()> {
+        let (peer_id, addresses, protocols) = match event {
+            DomainEvent::PeerIdentified {
+                peer_id,
+                addresses,
+                protocols,
+            } => (peer_id, addresses, protocols),
+            DomainEvent::PeerDisconnected { peer_id } => {
+                return self
+                    .peer_repo
+                    .update_peer_connection_status(&peer_id, false)
+                    .await;
+            }
+            _ => return Ok(()),
+        };
+        let mut peer = self
+            .peer_repo
+            .find_peer_by_id(&peer_id)
+            .await?
+            .unwrap_or_else(|| Peer {
+                id: peer_id.clone(),
+                addresses: Vec::new(),
+                last_seen: SystemTime::now(),
+                is_connected: true,
+                protocols: Vec::new(),
+            });
+        for address in addresses {
+            if !peer.addresses.contains(&address) {
+                peer.addresses.push(address);
+            }
+        }
+        // Identify only arrives over a live connection
+        peer.is_connected = true;
+        peer.last_seen = SystemTime::now();
+        peer.protocols = protocols;
+        self.peer_repo.save_peer(&peer).await
+    }
+}
+
+/// Publishes [`DomainEvent::Heartbeat`] so monitors can see the node is alive
+pub struct HeartbeatService {
+    peer_repo: Arc<dyn PeerRepository>,
+    transfer_repo: Arc<dyn TransferRepository>,
+    event_publisher: Arc<dyn EventPublisher>,
+    started_at: Instant,
+}
+
+impl HeartbeatService {
+    pub fn new(
+        peer_repo: Arc<dyn PeerRepository>,
+        transfer_repo: Arc<dyn TransferRepository>,
+        event_publisher: Arc<dyn EventPublisher>,
+    ) -> Self {
+        Self {
+            peer_repo,
+            transfer_repo,
+            event_publisher,
+            started_at: Instant::now(),
+        }
+    }
+
+    /// Publish a single heartbeat with the current counts
+    pub async fn beat(&self) -> DomainResult<()> {
+        let connected_peers = self.peer_repo.list_connected_peers().await?.len();
+        let active_transfers = self.transfer_repo.list_active_transfers().await?.len();
+        self.event_publisher
+            .publish(DomainEvent::Heartbeat {
+                uptime: self.started_at.elapsed(),
+                connected_peers,
+                active_transfers,
+            })
+            .await
+    }
+
+    /// Publish a heartbeat every `interval` until the returned task is aborted
+    pub fn spawn(self, interval: Duration) -> tokio::task::JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            loop {
+                ticker.tick().await;
+                if let Err(e) = self.beat().await {
+                    warn!("Failed to publish heartbeat: {}", e);
+                }
+            }
+        })
     }
 }
 
@@ -196,6 +801,7 @@ impl PeerDomainService {
             addresses,
             last_seen: SystemTime::now(),
             is_connected: false,
+            protocols: Vec::new(),
         };
 
         self.peer_repo.save_peer(&peer).await?;
@@ -231,12 +837,78 @@ impl PeerDomainService {
         Ok(())
     }
 
+    /// Mark every stored peer disconnected, returning how many were marked.
+    ///
+    /// Meant for startup, when no connection from a previous run survives; no
+    /// events are published.
+    pub async fn reset_connections(&self) -> DomainResult<usize> {
+        let connected = self.peer_repo.list_connected_peers().await?;
+        for peer in &connected {
+            self.peer_repo
+                .update_peer_connection_status(&peer.id, false)
+                .await?;
+        }
+        Ok(connected.len())
+    }
+
+    /// Remove peers not seen within `max_age`, returning how many were removed.
+    ///
+    /// A `PeerDisconnected` event is published for each pruned peer that was
+    /// still marked connected.
+    pub async fn prune_stale_peers(&self, max_age: Duration) -> DomainResult<usize> {
+        let cutoff = SystemTime::now()
+            .checked_sub(max_age)
+            .unwrap_or(SystemTime::UNIX_EPOCH);
+        let stale = self.peer_repo.find_peers_seen_before(cutoff).await?;
+
+        for peer in &stale {
+            self.peer_repo.delete_peer(&peer.id).await?;
+            if peer.is_connected {
+                self.event_publisher
+                    .publish(DomainEvent::PeerDisconnected {
+                        peer_id: peer.id.clone(),
+                    })
+                    .await?;
+            }
+        }
+
+        Ok(stale.len())
+    }
+
     /// Get connected peers
     pub async fn get_connected_peers(&self) -> DomainResult<Vec<Peer>> {
         self.peer_repo.list_connected_peers().await
     }
 }
 
+/// Why a transfer could not be paused, resumed or cancelled
+#[derive(Debug, Clone, thiserror::Error)]
+pub enum TransferControlError {
+    #[error("Transfer {transfer_id} not found")]
+    NotFound { transfer_id: String },
+    /// The transfer completed, failed or was cancelled already
+    #[error("Transfer {transfer_id} has already finished ({status:?})")]
+    Finished {
+        transfer_id: String,
+        status: TransferStatus,
+    },
+    #[error("Cannot {action} transfer {transfer_id} while it is {status:?}")]
+    NotApplicable {
+        transfer_id: String,
+        action: &'static str,
+        status: TransferStatus,
+    },
+}
+
+/// A file's contents don't hash to what the caller expected
+#[derive(Debug, thiserror::Error)]
+#[error("Checksum mismatch for {path}: expected {expected}, computed {actual}")]
+pub struct ChecksumMismatch {
+    pub path: String,
+    pub expected: String,
+    pub actual: String,
+}
+
 /// Domain service for file operations
 pub struct FileDomainService {
     file_repo: Arc<dyn FileRepository>,
@@ -258,6 +930,63 @@ impl FileDomainService {
         Ok(file)
     }
 
+    /// Add many files, hashing up to `concurrency` of them at once.
+    ///
+    /// Results are in the order of `paths`; a file that fails doesn't stop
+    /// the others.
+    pub async fn add_files(&self, paths: &[String], concurrency: usize) -> Vec<DomainResult<File>> {
+        let permits = Arc::new(tokio::sync::Semaphore::new(concurrency.max(1)));
+        let mut hashing = tokio::task::JoinSet::new();
+        for (i, path) in paths.iter().enumerate() {
+            let permits = permits.clone();
+            let file_service = self.file_service.clone();
+            let path = path.clone();
+            hashing.spawn(async move {
+                let _permit = permits.acquire_owned().await;
+                (i, file_service.add_file(&path).await)
+            });
+        }
+
+        let mut results: Vec<Option<DomainResult<File>>> = paths.iter().map(|_| None).collect();
+        while let Some(joined) = hashing.join_next().await {
+            match joined {
+                Ok((i, result)) => results[i] = Some(result),
+                Err(e) => warn!("File hashing task failed: {}", e),
+            }
+        }
+
+        let mut added = Vec::with_capacity(paths.len());
+        for result in results {
+            let result = match result {
+                Some(Ok(file)) => self.file_repo.save_file(&file).await.map(|_| file),
+                Some(Err(e)) => Err(e),
+                None => Err("File hashing task failed".into()),
+            };
+            added.push(result);
+        }
+        added
+    }
+
+    /// Add a file only if its contents hash to `expected_hash`, so a
+    /// mismatched file is never recorded
+    pub async fn add_file_with_hash(
+        &self,
+        file_path: &str,
+        expected_hash: &str,
+    ) -> DomainResult<File> {
+        let file = self.file_service.add_file(file_path).await?;
+        if !file.hash.eq_ignore_ascii_case(expected_hash.trim()) {
+            return Err(ChecksumMismatch {
+                path: file_path.to_string(),
+                expected: expected_hash.to_string(),
+                actual: file.hash,
+            }
+            .into());
+        }
+        self.file_repo.save_file(&file).await?;
+        Ok(file)
+    }
+
     /// Get all available files
     pub async fn list_files(&self) -> DomainResult<Vec<File>> {
         self.file_repo.list_all_files().await
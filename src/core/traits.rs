@@ -1,6 +1,7 @@
 use super::domain::*;
 use async_trait::async_trait;
 use std::error::Error;
+use std::time::SystemTime;
 
 /// Result type for domain operations
 pub type DomainResult<T> = Result<T, Box<dyn Error + Send + Sync>>;
@@ -22,6 +23,7 @@ pub trait TransferRepository: Send + Sync {
     async fn find_transfer_by_id(&self, id: &TransferId) -> DomainResult<Option<Transfer>>;
     async fn find_transfers_by_sender(&self, sender: &PeerId) -> DomainResult<Vec<Transfer>>;
     async fn find_transfers_by_receiver(&self, receiver: &PeerId) -> DomainResult<Vec<Transfer>>;
+    async fn find_transfers_by_file(&self, file_id: &FileId) -> DomainResult<Vec<Transfer>>;
     async fn list_active_transfers(&self) -> DomainResult<Vec<Transfer>>;
     async fn update_transfer_status(
         &self,
@@ -33,6 +35,15 @@ pub trait TransferRepository: Send + Sync {
         id: &TransferId,
         progress: TransferProgress,
     ) -> DomainResult<()>;
+    /// Add `chunk_index` to the transfer's resume state, creating it if needed
+    async fn record_received_chunk(
+        &self,
+        id: &TransferId,
+        partial_path: &str,
+        chunk_index: u64,
+    ) -> DomainResult<()>;
+    async fn find_resume_state(&self, id: &TransferId) -> DomainResult<Option<ResumeState>>;
+    async fn clear_resume_state(&self, id: &TransferId) -> DomainResult<()>;
 }
 
 /// Repository trait for peer operations
@@ -44,6 +55,15 @@ pub trait PeerRepository: Send + Sync {
     async fn list_all_peers(&self) -> DomainResult<Vec<Peer>>;
     async fn update_peer_connection_status(&self, id: &PeerId, connected: bool)
     -> DomainResult<()>;
+    /// Peers whose `last_seen` is earlier than `cutoff`
+    async fn find_peers_seen_before(&self, cutoff: SystemTime) -> DomainResult<Vec<Peer>>;
+    async fn delete_peer(&self, id: &PeerId) -> DomainResult<()>;
+    /// Store a ban, replacing any earlier ban of the same peer
+    async fn save_ban(&self, ban: &PeerBan) -> DomainResult<()>;
+    /// Lift a ban, returning whether the peer was banned
+    async fn remove_ban(&self, id: &PeerId) -> DomainResult<bool>;
+    /// Every stored ban, including ones that have expired
+    async fn list_bans(&self) -> DomainResult<Vec<PeerBan>>;
 }
 
 /// Service trait for file operations
@@ -51,6 +71,12 @@ pub trait PeerRepository: Send + Sync {
 pub trait FileService: Send + Sync {
     async fn add_file(&self, path: &str) -> DomainResult<File>;
     async fn calculate_file_hash(&self, path: &str) -> DomainResult<String>;
+    /// Merkle root and leaf hashes of the file split into `chunk_size` chunks
+    async fn compute_chunk_merkle(
+        &self,
+        path: &str,
+        chunk_size: usize,
+    ) -> DomainResult<(String, Vec<String>)>;
     async fn get_file_metadata(&self, path: &str) -> DomainResult<(String, u64)>; // (name, size)
     async fn read_file_chunk(&self, path: &str, offset: u64, size: usize) -> DomainResult<Vec<u8>>;
     async fn write_file_chunk(&self, path: &str, offset: u64, data: &[u8]) -> DomainResult<()>;
@@ -66,6 +92,23 @@ pub trait TransferService: Send + Sync {
     async fn send_chunk(&self, transfer_id: &TransferId, chunk: Chunk) -> DomainResult<()>;
     async fn receive_chunk(&self, transfer_id: &TransferId, chunk: Chunk) -> DomainResult<()>;
     async fn complete_transfer(&self, transfer_id: &TransferId) -> DomainResult<()>;
+    /// Latest progress of a transfer, or `None` if it is unknown
+    async fn get_progress(
+        &self,
+        transfer_id: &TransferId,
+    ) -> DomainResult<Option<TransferProgress>>;
+}
+
+/// Steers outgoing transfers while they are being sent
+#[async_trait]
+pub trait TransferControl: Send + Sync {
+    /// Stop sending a transfer until it is resumed, returning whether it is
+    /// being sent
+    async fn pause(&self, transfer_id: &TransferId) -> bool;
+    /// Continue sending a paused transfer, returning whether it is being sent
+    async fn resume(&self, transfer_id: &TransferId) -> bool;
+    /// Stop sending a transfer for good, returning whether it was being sent
+    async fn cancel(&self, transfer_id: &TransferId) -> bool;
 }
 
 /// Service trait for peer discovery and management
@@ -81,14 +124,29 @@ pub trait PeerService: Send + Sync {
 #[async_trait]
 pub trait NetworkService: Send + Sync {
     async fn start_listening(&self, port: u16) -> DomainResult<()>;
+    /// Dial `addr` and wait until the connection is established
+    async fn connect(&self, addr: libp2p::Multiaddr) -> DomainResult<PeerId>;
     async fn send_message(&self, peer_id: &PeerId, message: Vec<u8>) -> DomainResult<()>;
     async fn broadcast_message(&self, message: Vec<u8>) -> DomainResult<()>;
+    /// Publish `data` on `topic`, returning roughly how many peers it was
+    /// forwarded to
+    async fn broadcast_to_topic(&self, topic: &str, data: Vec<u8>) -> DomainResult<usize>;
+    /// Stop the service and close its connections. Does nothing by default.
+    async fn shutdown(&self) -> DomainResult<()> {
+        Ok(())
+    }
 }
 
 /// Event handler trait for domain events
 #[async_trait]
 pub trait EventHandler: Send + Sync {
     async fn handle_event(&self, event: DomainEvent) -> DomainResult<()>;
+
+    /// Called by publishers for every event. Override to see the sequence
+    /// number and timestamp; by default the bare event goes to `handle_event`.
+    async fn handle_envelope(&self, envelope: EventEnvelope) -> DomainResult<()> {
+        self.handle_event(envelope.event).await
+    }
 }
 
 /// Event publisher trait for publishing domain events
@@ -104,5 +162,7 @@ pub trait Configuration: Send + Sync {
     fn get_download_directory(&self) -> &str;
     fn get_default_port(&self) -> u16;
     fn get_max_concurrent_transfers(&self) -> usize;
+    fn get_max_concurrent_uploads(&self) -> usize;
+    fn get_max_concurrent_downloads(&self) -> usize;
     fn get_chunk_size(&self) -> usize;
 }
@@ -0,0 +1,195 @@
+use crate::core::traits::DomainResult;
+use bincode::{Decode, Encode};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::io::{Read, Write};
+use std::sync::Arc;
+
+/// Compression algorithm applied to chunk payloads
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize, Encode, Decode,
+)]
+pub enum Compression {
+    #[default]
+    None,
+    Gzip,
+    Zstd,
+}
+
+impl fmt::Display for Compression {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::None => write!(f, "none"),
+            Self::Gzip => write!(f, "gzip"),
+            Self::Zstd => write!(f, "zstd"),
+        }
+    }
+}
+
+/// One compression algorithm for chunk payloads
+pub trait ChunkCompressor: Send + Sync {
+    fn compress(&self, data: &[u8]) -> DomainResult<Vec<u8>>;
+    fn decompress(&self, data: &[u8]) -> DomainResult<Vec<u8>>;
+}
+
+/// Passes payloads through unchanged
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Identity;
+
+impl ChunkCompressor for Identity {
+    fn compress(&self, data: &[u8]) -> DomainResult<Vec<u8>> {
+        Ok(data.to_vec())
+    }
+
+    fn decompress(&self, data: &[u8]) -> DomainResult<Vec<u8>> {
+        Ok(data.to_vec())
+    }
+}
+
+/// DEFLATE in a gzip wrapper
+#[derive(Debug, Clone, Copy)]
+pub struct Gzip {
+    pub level: u32,
+}
+
+impl Default for Gzip {
+    fn default() -> Self {
+        Self { level: 6 }
+    }
+}
+
+impl ChunkCompressor for Gzip {
+    fn compress(&self, data: &[u8]) -> DomainResult<Vec<u8>> {
+        let mut encoder =
+            flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::new(self.level));
+        encoder.write_all(data)?;
+        Ok(encoder.finish()?)
+    }
+
+    fn decompress(&self, data: &[u8]) -> DomainResult<Vec<u8>> {
+        let mut decoded = Vec::new();
+        flate2::read::GzDecoder::new(data).read_to_end(&mut decoded)?;
+        Ok(decoded)
+    }
+}
+
+/// Zstandard
+#[derive(Debug, Clone, Copy)]
+pub struct Zstd {
+    pub level: i32,
+}
+
+impl Default for Zstd {
+    fn default() -> Self {
+        Self { level: 3 }
+    }
+}
+
+impl ChunkCompressor for Zstd {
+    fn compress(&self, data: &[u8]) -> DomainResult<Vec<u8>> {
+        Ok(zstd::encode_all(data, self.level)?)
+    }
+
+    fn decompress(&self, data: &[u8]) -> DomainResult<Vec<u8>> {
+        Ok(zstd::decode_all(data)?)
+    }
+}
+
+/// Compressors by the algorithm a handshake names, so transfer code never
+/// depends on a particular one
+#[derive(Clone)]
+pub struct CompressorRegistry {
+    compressors: HashMap<Compression, Arc<dyn ChunkCompressor>>,
+}
+
+impl CompressorRegistry {
+    /// A registry holding only [`Compression::None`]
+    pub fn empty() -> Self {
+        let mut compressors: HashMap<Compression, Arc<dyn ChunkCompressor>> = HashMap::new();
+        compressors.insert(Compression::None, Arc::new(Identity));
+        Self { compressors }
+    }
+
+    /// Use `compressor` for `algorithm`, replacing any earlier one
+    pub fn register(&mut self, algorithm: Compression, compressor: Arc<dyn ChunkCompressor>) {
+        self.compressors.insert(algorithm, compressor);
+    }
+
+    /// The compressor for `algorithm`, if one is registered
+    pub fn get(&self, algorithm: Compression) -> Option<Arc<dyn ChunkCompressor>> {
+        self.compressors.get(&algorithm).cloned()
+    }
+
+    /// Registered algorithms, in a stable order
+    pub fn algorithms(&self) -> Vec<Compression> {
+        let mut algorithms: Vec<_> = self.compressors.keys().copied().collect();
+        algorithms.sort_by_key(|algorithm| algorithm.to_string());
+        algorithms
+    }
+}
+
+impl Default for CompressorRegistry {
+    /// Gzip and Zstd at their default levels, plus no compression
+    fn default() -> Self {
+        let mut registry = Self::empty();
+        registry.register(Compression::Gzip, Arc::new(Gzip::default()));
+        registry.register(Compression::Zstd, Arc::new(Zstd::default()));
+        registry
+    }
+}
+
+impl fmt::Debug for CompressorRegistry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CompressorRegistry")
+            .field("algorithms", &self.algorithms())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_every_registered_compressor_round_trips() {
+        let registry = CompressorRegistry::default();
+        let payload: Vec<u8> = b"cipherstream chunk ".repeat(200);
+        assert_eq!(
+            registry.algorithms(),
+            vec![Compression::Gzip, Compression::None, Compression::Zstd]
+        );
+
+        for algorithm in registry.algorithms() {
+            let compressor = registry.get(algorithm).unwrap();
+            let compressed = compressor.compress(&payload).unwrap();
+            if algorithm != Compression::None {
+                assert!(compressed.len() < payload.len(), "{}", algorithm);
+            }
+            assert_eq!(compressor.decompress(&compressed).unwrap(), payload);
+        }
+
+        // Garbage is an error, not a panic
+        let gzip = registry.get(Compression::Gzip).unwrap();
+        assert!(gzip.decompress(b"not gzip").is_err());
+    }
+
+    #[test]
+    fn test_registry_accepts_custom_compressors() {
+        struct Reverse;
+        impl ChunkCompressor for Reverse {
+            fn compress(&self, data: &[u8]) -> DomainResult<Vec<u8>> {
+                Ok(data.iter().rev().copied().collect())
+            }
+            fn decompress(&self, data: &[u8]) -> DomainResult<Vec<u8>> {
+                self.compress(data)
+            }
+        }
+
+        let mut registry = CompressorRegistry::empty();
+        assert!(registry.get(Compression::Zstd).is_none());
+        registry.register(Compression::Zstd, Arc::new(Reverse));
+        let reverse = registry.get(Compression::Zstd).unwrap();
+        assert_eq!(reverse.compress(b"abc").unwrap(), b"cba");
+    }
+}
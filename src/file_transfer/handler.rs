@@ -0,0 +1,1248 @@
+use super::compression::{Compression, CompressorRegistry};
+use super::layout::DownloadLayout;
+use super::sniff::sniff_content_type;
+use super::throughput::ThroughputHistory;
+use super::types::{ProtocolRequest, ProtocolResponse, RejectReason};
+use crate::core::crypto::{compute_file_hash, merkle};
+use crate::core::domain::{
+    DomainEvent, File, FileId, PeerId as DomainPeerId, Transfer, TransferId, TransferProgress,
+    TransferStatus,
+};
+use crate::core::traits::{DomainResult, EventPublisher, TransferRepository};
+use libp2p::PeerId;
+use std::collections::{BTreeSet, HashMap, HashSet, VecDeque};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant, SystemTime};
+use tokio::io::{AsyncSeekExt, AsyncWriteExt};
+use tokio::sync::{Mutex, mpsc};
+use tracing::{debug, info, warn};
+
+/// State kept for a transfer being received
+#[derive(Debug, Clone)]
+struct IncomingTransfer {
+    peer: PeerId,
+    path: PathBuf,
+    filesize: u64,
+    /// Chunk size agreed in the handshake
+    chunk_size: u64,
+    /// Chunks may be any size up to `chunk_size`
+    adaptive_chunks: bool,
+    /// Algorithm chunk data arrives compressed with
+    compression: Compression,
+    bytes_received: u64,
+    chunks_received: u64,
+    /// Indices of the chunks written so far
+    received_chunks: ReceivedChunks,
+    /// Latest estimate reported by the sender
+    total_chunks: u64,
+    /// Whole-file hash announced in the handshake
+    sha256: Option<String>,
+    /// Root every chunk's proof must lead to
+    merkle_root: Option<String>,
+}
+
+/// Set of chunk indices that stays small while chunks arrive in order,
+/// since it is cloned along with its transfer for every chunk
+#[derive(Debug, Clone, Default)]
+struct ReceivedChunks {
+    /// Every index below this has been received
+    contiguous: u64,
+    /// Indices received ahead of `contiguous`
+    ahead: BTreeSet<u64>,
+}
+
+impl ReceivedChunks {
+    /// Record `index`, returning whether it wasn't received before
+    fn insert(&mut self, index: u64) -> bool {
+        if index < self.contiguous || !self.ahead.insert(index) {
+            return false;
+        }
+        while self.ahead.remove(&self.contiguous) {
+            self.contiguous += 1;
+        }
+        true
+    }
+}
+
+impl IncomingTransfer {
+    /// Check a chunk lies where the handshake says it should: the final one
+    /// ends the file and, unless the sender adapts its chunk size, every
+    /// other one fills exactly one agreed-size slot
+    fn check_chunk_layout(
+        &self,
+        index: u64,
+        offset: u64,
+        len: u64,
+        is_last: bool,
+    ) -> Result<(), String> {
+        let end = offset
+            .checked_add(len)
+            .ok_or_else(|| format!("Chunk {} ends past the largest file offset", index))?;
+        if is_last && end != self.filesize {
+            return Err(format!(
+                "Final chunk ends at byte {} of {}",
+                end, self.filesize
+            ));
+        }
+        if !is_last && len == 0 {
+            return Err("Empty chunk before the end of the file".to_string());
+        }
+        if self.adaptive_chunks {
+            return Ok(());
+        }
+
+        let expected_offset = index.saturating_mul(self.chunk_size);
+        if offset != expected_offset {
+            return Err(format!(
+                "Chunk {} starts at byte {}; expected {}",
+                index, offset, expected_offset
+            ));
+        }
+        // Only the final chunk may be short, carrying what remains of the file
+        let expected_len = self.chunk_size.min(self.filesize.saturating_sub(offset));
+        if len != expected_len {
+            return Err(format!(
+                "Chunk {} is {} bytes; expected {}",
+                index, len, expected_len
+            ));
+        }
+        Ok(())
+    }
+
+    fn progress(&self) -> TransferProgress {
+        let mut progress = TransferProgress::new(self.filesize, self.total_chunks);
+        progress.update(self.bytes_received, self.chunks_received);
+        progress
+    }
+}
+
+/// Destination for domain events about handler-driven transfers
+struct EventSink {
+    publisher: Arc<dyn EventPublisher>,
+    local_peer: PeerId,
+}
+
+impl std::fmt::Debug for EventSink {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("EventSink")
+            .field("local_peer", &self.local_peer)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Repository that received chunks are recorded in, so a transfer
+/// interrupted by a restart knows which chunks it still needs
+struct ChunkLog(Arc<dyn TransferRepository>);
+
+impl std::fmt::Debug for ChunkLog {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ChunkLog").finish_non_exhaustive()
+    }
+}
+
+/// Receiver of `(sender, transfer id, progress)` updates
+type ProgressListener = mpsc::UnboundedSender<(PeerId, String, TransferProgress)>;
+
+/// Where a received chunk belongs within its transfer
+struct ChunkPosition {
+    index: u64,
+    total: u64,
+    offset: u64,
+    is_last: bool,
+    proof: Vec<String>,
+}
+
+/// Reduce a sender-supplied filename to its final component, trimmed of
+/// surrounding whitespace. Names containing control characters or bidi
+/// overrides are refused outright, since they could rewrite terminal output
+/// and logs when displayed.
+fn safe_filename(filename: &str) -> Option<String> {
+    let unsafe_char =
+        |c: char| c.is_control() || matches!(c, '\u{202A}'..='\u{202E}' | '\u{2066}'..='\u{2069}');
+    if filename.contains(unsafe_char) {
+        return None;
+    }
+    let name = Path::new(filename).file_name()?.to_string_lossy();
+    let name = name.trim();
+    (!name.is_empty()).then(|| name.to_string())
+}
+
+/// Everything a sender announced in its handshake, plus who sent it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HandshakeInfo {
+    pub peer: PeerId,
+    pub transfer_id: String,
+    /// Name as sent, before sanitizing
+    pub filename: String,
+    pub filesize: u64,
+    pub sha256: Option<String>,
+    pub merkle_root: Option<String>,
+    /// Chunk size the sender proposed
+    pub chunk_size: Option<u64>,
+    pub adaptive_chunks: bool,
+    /// Compression the sender proposed
+    pub compression: Compression,
+}
+
+impl HandshakeInfo {
+    /// The handshake `peer` sent in `request`, or `None` for any other request
+    pub fn from_request(peer: PeerId, request: ProtocolRequest) -> Option<Self> {
+        let ProtocolRequest::HandshakeRequest {
+            filename,
+            filesize,
+            transfer_id,
+            sha256,
+            merkle_root,
+            chunk_size,
+            adaptive_chunks,
+            compression,
+        } = request
+        else {
+            return None;
+        };
+        Some(Self {
+            peer,
+            transfer_id,
+            filename,
+            filesize,
+            sha256,
+            merkle_root,
+            chunk_size,
+            adaptive_chunks,
+            compression,
+        })
+    }
+}
+
+impl std::fmt::Display for HandshakeInfo {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "transfer {} of {:?} ({} bytes) from {}",
+            self.transfer_id, self.filename, self.filesize, self.peer
+        )
+    }
+}
+
+/// Outcome of running a handshake through the receiver's acceptance checks
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ApprovalDecision {
+    /// The transfer would be accepted
+    Accept {
+        /// Sanitized name the file is saved under
+        filename: String,
+        /// Chunk size the receiver would agree to
+        chunk_size: u64,
+        /// Compression the receiver would agree to
+        compression: Compression,
+    },
+    /// The transfer would be refused for this reason
+    Reject(RejectReason),
+}
+
+/// Default cap on declared chunks: a 1 GiB file in 1 KiB chunks
+const DEFAULT_MAX_TOTAL_CHUNKS: u64 = 1 << 20;
+
+/// Cancelled transfer ids remembered to drop chunks still on their way
+const CANCELLED_TRANSFERS_REMEMBERED: usize = 1024;
+
+/// The most recently cancelled transfer ids, oldest forgotten first
+#[derive(Debug, Default)]
+struct CancelledTransfers {
+    order: VecDeque<String>,
+    ids: HashSet<String>,
+}
+
+impl CancelledTransfers {
+    fn insert(&mut self, transfer_id: String) {
+        if !self.ids.insert(transfer_id.clone()) {
+            return;
+        }
+        if self.order.len() == CANCELLED_TRANSFERS_REMEMBERED
+            && let Some(oldest) = self.order.pop_front()
+        {
+            self.ids.remove(&oldest);
+        }
+        self.order.push_back(transfer_id);
+    }
+
+    fn contains(&self, transfer_id: &str) -> bool {
+        self.ids.contains(transfer_id)
+    }
+
+    fn remove(&mut self, transfer_id: &str) {
+        if self.ids.remove(transfer_id) {
+            self.order.retain(|id| id != transfer_id);
+        }
+    }
+}
+
+/// Requests being handled at once, in total and per peer
+#[derive(Debug, Default)]
+struct PendingCounts {
+    total: usize,
+    per_peer: HashMap<PeerId, usize>,
+}
+
+/// A slot held by an inbound request until it has been answered; see
+/// [`FileTransferHandler::try_reserve`]
+#[derive(Debug)]
+pub struct PendingRequest {
+    pending: Arc<std::sync::Mutex<PendingCounts>>,
+    peer: PeerId,
+}
+
+impl Drop for PendingRequest {
+    fn drop(&mut self) {
+        let mut pending = self.pending.lock().unwrap_or_else(|e| e.into_inner());
+        pending.total -= 1;
+        if let Some(count) = pending.per_peer.get_mut(&self.peer) {
+            *count -= 1;
+            if *count == 0 {
+                pending.per_peer.remove(&self.peer);
+            }
+        }
+    }
+}
+
+/// Receiver-side handler for file transfer protocol requests
+#[derive(Debug)]
+pub struct FileTransferHandler {
+    /// Where new transfers are written; in-flight ones keep their own path
+    download_dir: RwLock<PathBuf>,
+    /// Subdirectories files are sorted into under the download directory
+    layout: DownloadLayout,
+    /// Largest chunk accepted from a sender
+    chunk_size: usize,
+    /// Smallest chunk size a sender may propose
+    min_chunk_size: usize,
+    /// Largest `total_chunks` a sender may declare
+    max_total_chunks: u64,
+    max_downloads: usize,
+    /// Largest file accepted, in bytes
+    max_file_size: u64,
+    /// Lowercase extensions accepted; `None` accepts any
+    allowed_extensions: Option<HashSet<String>>,
+    /// Sniffed content types accepted; `None` accepts any not blocked
+    allowed_content_types: Option<HashSet<String>>,
+    /// Sniffed content types refused regardless of extension
+    blocked_content_types: HashSet<String>,
+    /// Bytes that in-flight transfers may claim in the download directory
+    disk_budget: u64,
+    /// At most this many accepted handshakes per peer within the window
+    handshake_limit: Option<(usize, Duration)>,
+    /// When each peer's recent handshakes were accepted
+    recent_handshakes: Mutex<HashMap<PeerId, VecDeque<Instant>>>,
+    /// Re-hash completed files against the sender's announced hash
+    verify_after: bool,
+    /// Algorithms chunk data may be compressed with
+    compressors: CompressorRegistry,
+    /// Requests that may be in flight at once, node-wide and per peer
+    max_pending_requests: usize,
+    max_pending_requests_per_peer: usize,
+    pending: Arc<std::sync::Mutex<PendingCounts>>,
+    transfers: Mutex<HashMap<String, IncomingTransfer>>,
+    cancelled: Mutex<CancelledTransfers>,
+    /// Bytes received per interval for each active transfer
+    throughput: Mutex<ThroughputHistory>,
+    progress_listeners: Mutex<Vec<ProgressListener>>,
+    events: Option<EventSink>,
+    chunk_log: Option<ChunkLog>,
+}
+
+impl FileTransferHandler {
+    pub fn new(download_dir: impl Into<PathBuf>, chunk_size: usize) -> Self {
+        Self {
+            download_dir: RwLock::new(download_dir.into()),
+            layout: DownloadLayout::default(),
+            chunk_size,
+            min_chunk_size: 1,
+            max_total_chunks: DEFAULT_MAX_TOTAL_CHUNKS,
+            max_downloads: usize::MAX,
+            max_file_size: u64::MAX,
+            allowed_extensions: None,
+            allowed_content_types: None,
+            blocked_content_types: HashSet::new(),
+            disk_budget: u64::MAX,
+            handshake_limit: None,
+            recent_handshakes: Mutex::new(HashMap::new()),
+            verify_after: true,
+            compressors: CompressorRegistry::default(),
+            max_pending_requests: usize::MAX,
+            max_pending_requests_per_peer: usize::MAX,
+            pending: Arc::default(),
+            transfers: Mutex::new(HashMap::new()),
+            cancelled: Mutex::new(CancelledTransfers::default()),
+            throughput: Mutex::new(ThroughputHistory::default()),
+            progress_listeners: Mutex::new(Vec::new()),
+            events: None,
+            chunk_log: None,
+        }
+    }
+
+    /// Publish transfer lifecycle events for transfers received by `local_peer`
+    pub fn with_event_publisher(
+        mut self,
+        publisher: Arc<dyn EventPublisher>,
+        local_peer: PeerId,
+    ) -> Self {
+        self.events = Some(EventSink {
+            publisher,
+            local_peer,
+        });
+        self
+    }
+
+    /// Record every chunk written in `repo`'s resume state, and clear it once
+    /// the transfer ends
+    pub fn with_transfer_repository(mut self, repo: Arc<dyn TransferRepository>) -> Self {
+        self.chunk_log = Some(ChunkLog(repo));
+        self
+    }
+
+    /// Sort received files into subdirectories of the download directory.
+    /// A file with the same name in the same subdirectory is overwritten, as
+    /// with the flat layout.
+    pub fn with_download_layout(mut self, layout: DownloadLayout) -> Self {
+        self.layout = layout;
+        self
+    }
+
+    /// Refuse handshakes proposing chunks smaller than `min_chunk_size`
+    /// bytes. A proposal of 0 is always refused.
+    pub fn with_min_chunk_size(mut self, min_chunk_size: usize) -> Self {
+        self.min_chunk_size = min_chunk_size.max(1);
+        self
+    }
+
+    /// Reject chunks of transfers declaring more than `max_total_chunks`
+    pub fn with_max_total_chunks(mut self, max_total_chunks: u64) -> Self {
+        self.max_total_chunks = max_total_chunks;
+        self
+    }
+
+    /// Limit how many transfers may be received at once
+    pub fn with_max_downloads(mut self, max_downloads: usize) -> Self {
+        self.max_downloads = max_downloads;
+        self
+    }
+
+    /// Refuse files larger than `max_file_size` bytes
+    pub fn with_max_file_size(mut self, max_file_size: u64) -> Self {
+        self.max_file_size = max_file_size;
+        self
+    }
+
+    /// Only accept files with one of `extensions`, compared case-insensitively
+    /// and with or without a leading dot. An empty list accepts any file.
+    pub fn with_allowed_extensions<I, S>(mut self, extensions: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let extensions: HashSet<String> = extensions
+            .into_iter()
+            .map(|ext| ext.as_ref().trim_start_matches('.').to_lowercase())
+            .collect();
+        self.allowed_extensions = (!extensions.is_empty()).then_some(extensions);
+        self
+    }
+
+    /// Check each file's content type, sniffed from its first chunk, against
+    /// `allowed` and `blocked`. An empty `allowed` accepts any type that
+    /// isn't blocked; unrecognized content counts as
+    /// `application/octet-stream`.
+    pub fn with_content_types<S: AsRef<str>>(mut self, allowed: &[S], blocked: &[S]) -> Self {
+        let normalize = |types: &[S]| -> HashSet<String> {
+            types
+                .iter()
+                .map(|t| t.as_ref().trim().to_lowercase())
+                .collect()
+        };
+        let allowed = normalize(allowed);
+        self.allowed_content_types = (!allowed.is_empty()).then_some(allowed);
+        self.blocked_content_types = normalize(blocked);
+        self
+    }
+
+    /// Refuse transfers once the files being received would together exceed
+    /// `disk_budget` bytes
+    pub fn with_disk_budget(mut self, disk_budget: u64) -> Self {
+        self.disk_budget = disk_budget;
+        self
+    }
+
+    /// Accept at most `max` transfers from any one peer within `window`
+    pub fn with_handshake_rate_limit(mut self, max: usize, window: Duration) -> Self {
+        self.handshake_limit = Some((max, window));
+        self
+    }
+
+    /// Allow at most `max` requests in flight at once, and `per_peer` from
+    /// any one peer, so stalled requests can't tie up the receiver
+    pub fn with_max_pending_requests(mut self, max: usize, per_peer: usize) -> Self {
+        self.max_pending_requests = max;
+        self.max_pending_requests_per_peer = per_peer;
+        self
+    }
+
+    /// Claim a slot for a request from `peer`, held until the returned guard
+    /// drops. `None` when the peer or the node already has as many requests
+    /// in flight as allowed; answer with [`ProtocolResponse::busy`] instead.
+    pub fn try_reserve(&self, peer: PeerId) -> Option<PendingRequest> {
+        let mut pending = self.pending.lock().unwrap_or_else(|e| e.into_inner());
+        let from_peer = pending.per_peer.get(&peer).copied().unwrap_or(0);
+        if pending.total >= self.max_pending_requests
+            || from_peer >= self.max_pending_requests_per_peer
+        {
+            return None;
+        }
+        pending.total += 1;
+        *pending.per_peer.entry(peer).or_default() += 1;
+        Some(PendingRequest {
+            pending: self.pending.clone(),
+            peer,
+        })
+    }
+
+    /// Whether to re-hash each completed file and fail the transfer when it
+    /// doesn't match the hash the sender announced. On by default.
+    pub fn with_verify_after(mut self, verify_after: bool) -> Self {
+        self.verify_after = verify_after;
+        self
+    }
+
+    /// Agree to compress chunk data with the algorithms in `compressors`.
+    /// Defaults to [`CompressorRegistry::default`]; senders proposing any
+    /// other algorithm send plain chunks.
+    pub fn with_compressors(mut self, compressors: CompressorRegistry) -> Self {
+        self.compressors = compressors;
+        self
+    }
+
+    /// Keep throughput samples `interval` wide, at most `max_samples` per
+    /// transfer. Defaults to one-second samples covering five minutes.
+    pub fn with_throughput_history(mut self, interval: Duration, max_samples: usize) -> Self {
+        self.throughput = Mutex::new(ThroughputHistory::new(interval, max_samples));
+        self
+    }
+
+    /// Directory new transfers are written to
+    pub fn download_dir(&self) -> PathBuf {
+        self.download_dir
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    /// Switch the directory new transfers are written to. The directory is
+    /// created if needed; transfers already in flight finish where they started.
+    pub async fn set_download_dir(&self, dir: impl Into<PathBuf>) -> DomainResult<()> {
+        let dir = dir.into();
+        tokio::fs::create_dir_all(&dir)
+            .await
+            .map_err(|e| format!("Cannot use {} as download directory: {}", dir.display(), e))?;
+        if !tokio::fs::metadata(&dir).await?.is_dir() {
+            return Err(format!("{} is not a directory", dir.display()).into());
+        }
+        info!("Receiving new transfers into {}", dir.display());
+        *self.download_dir.write().unwrap_or_else(|e| e.into_inner()) = dir;
+        Ok(())
+    }
+
+    /// Number of transfers currently being received
+    pub async fn active_transfers(&self) -> usize {
+        self.transfers.lock().await.len()
+    }
+
+    /// Whether a transfer has been cancelled by its sender
+    pub async fn is_cancelled(&self, transfer_id: &str) -> bool {
+        self.cancelled.lock().await.contains(transfer_id)
+    }
+
+    /// Live progress of a transfer being received, if it is still active
+    pub async fn progress(&self, transfer_id: &str) -> Option<TransferProgress> {
+        let transfers = self.transfers.lock().await;
+        transfers.get(transfer_id).map(IncomingTransfer::progress)
+    }
+
+    /// `(interval start, bytes received)` samples of an active transfer,
+    /// oldest first, for plotting its receive rate
+    pub async fn throughput_samples(&self, transfer_id: &str) -> Vec<(SystemTime, u64)> {
+        self.throughput.lock().await.samples(transfer_id)
+    }
+
+    /// Receive `(sender, transfer id, progress)` after every chunk written,
+    /// including the last one of each transfer
+    pub async fn subscribe_progress(
+        &self,
+    ) -> mpsc::UnboundedReceiver<(PeerId, String, TransferProgress)> {
+        let (tx, rx) = mpsc::unbounded_channel();
+        self.progress_listeners.lock().await.push(tx);
+        rx
+    }
+
+    /// Handle an inbound request from `peer` and produce the response to send back
+    pub async fn handle_request(&self, peer: PeerId, request: ProtocolRequest) -> ProtocolResponse {
+        match request {
+            request @ ProtocolRequest::HandshakeRequest { .. } => {
+                let handshake = HandshakeInfo::from_request(peer, request)
+                    .expect("matched a handshake request");
+                self.handle_handshake(handshake).await
+            }
+            ProtocolRequest::FileChunk {
+                transfer_id,
+                chunk_index,
+                total_chunks,
+                offset,
+                data,
+                is_last,
+                proof,
+            } => {
+                let position = ChunkPosition {
+                    index: chunk_index,
+                    total: total_chunks,
+                    offset,
+                    is_last,
+                    proof,
+                };
+                self.handle_chunk(peer, transfer_id, position, data).await
+            }
+            ProtocolRequest::CancelTransfer { transfer_id } => {
+                self.handle_cancel(peer, transfer_id).await
+            }
+            ProtocolRequest::Ping { nonce } => ProtocolResponse::Pong { nonce },
+        }
+    }
+
+    /// Run a handshake through every acceptance check without registering
+    /// anything, reporting what [`handle_request`](Self::handle_request)
+    /// would decide if it arrived now
+    pub async fn evaluate_handshake(
+        &self,
+        peer: PeerId,
+        request: &ProtocolRequest,
+    ) -> ApprovalDecision {
+        let Some(handshake) = HandshakeInfo::from_request(peer, request.clone()) else {
+            return ApprovalDecision::Reject(RejectReason::Other(
+                "Not a handshake request".to_string(),
+            ));
+        };
+        let transfers = self.transfers.lock().await;
+        let recent = self.recent_handshakes.lock().await;
+        self.evaluate(&handshake, &transfers, &recent)
+    }
+
+    /// Acceptance checks against a snapshot of the active transfers and
+    /// recent handshakes
+    fn evaluate(
+        &self,
+        handshake: &HandshakeInfo,
+        transfers: &HashMap<String, IncomingTransfer>,
+        recent: &HashMap<PeerId, VecDeque<Instant>>,
+    ) -> ApprovalDecision {
+        let Some(filename) = safe_filename(&handshake.filename) else {
+            return ApprovalDecision::Reject(RejectReason::InvalidFilename);
+        };
+        if handshake.filesize > self.max_file_size {
+            return ApprovalDecision::Reject(RejectReason::TooLarge);
+        }
+        if let Some(allowed) = &self.allowed_extensions {
+            let extension = Path::new(&filename)
+                .extension()
+                .map(|ext| ext.to_string_lossy().to_lowercase());
+            if !extension.is_some_and(|ext| allowed.contains(&ext)) {
+                return ApprovalDecision::Reject(RejectReason::DisallowedExtension);
+            }
+        }
+        if transfers.len() >= self.max_downloads {
+            return ApprovalDecision::Reject(RejectReason::RateLimited);
+        }
+        if let Some((max, window)) = self.handshake_limit {
+            let now = Instant::now();
+            let accepted = recent.get(&handshake.peer).map_or(0, |times| {
+                times
+                    .iter()
+                    .filter(|&&at| now.duration_since(at) < window)
+                    .count()
+            });
+            if accepted >= max {
+                return ApprovalDecision::Reject(RejectReason::RateLimited);
+            }
+        }
+        let reserved: u64 = transfers.values().map(|t| t.filesize).sum();
+        if reserved.saturating_add(handshake.filesize) > self.disk_budget {
+            return ApprovalDecision::Reject(RejectReason::InsufficientSpace);
+        }
+
+        // Never take chunks larger than our own limit
+        let (min, max) = (self.min_chunk_size as u64, self.chunk_size as u64);
+        let chunk_size = match handshake.chunk_size {
+            None => max,
+            Some(proposed) if proposed < min => {
+                return ApprovalDecision::Reject(RejectReason::ChunkSizeUnsupported { min, max });
+            }
+            // Proofs against the root follow the proposed chunk boundaries,
+            // so smaller chunks couldn't be checked against it
+            Some(proposed) if proposed > max && handshake.merkle_root.is_some() => {
+                return ApprovalDecision::Reject(RejectReason::ChunkSizeUnsupported { min, max });
+            }
+            Some(proposed) => proposed.min(max),
+        };
+        // Fall back to plain chunks rather than refuse an unknown algorithm
+        let compression = if self
+            .compressors
+            .algorithms()
+            .contains(&handshake.compression)
+        {
+            handshake.compression
+        } else {
+            Compression::None
+        };
+        ApprovalDecision::Accept {
+            filename,
+            chunk_size,
+            compression,
+        }
+    }
+
+    async fn handle_handshake(&self, handshake: HandshakeInfo) -> ProtocolResponse {
+        let peer = handshake.peer;
+        let transfer_id = handshake.transfer_id.clone();
+        let filesize = handshake.filesize;
+        let reject = |reason: RejectReason| ProtocolResponse::HandshakeResponse {
+            accepted: false,
+            reason: Some(reason),
+            transfer_id: Some(transfer_id.clone()),
+            accepted_chunk_size: None,
+            compression: Compression::None,
+        };
+
+        // Hold the locks until the transfer is registered so concurrent
+        // handshakes can't both claim the last download slot
+        let mut transfers = self.transfers.lock().await;
+
+        // A retransmitted handshake gets the original answer, leaving the
+        // transfer and the chunks it already wrote alone
+        if let Some(existing) = transfers.get(&transfer_id) {
+            if existing.peer == peer
+                && existing.filesize == filesize
+                && existing.sha256 == handshake.sha256
+            {
+                debug!("Repeated handshake for transfer {}", transfer_id);
+                return ProtocolResponse::HandshakeResponse {
+                    accepted: true,
+                    reason: None,
+                    transfer_id: Some(transfer_id.clone()),
+                    accepted_chunk_size: Some(existing.chunk_size),
+                    compression: existing.compression,
+                };
+            }
+            warn!("Rejecting {}: transfer id already in use", handshake);
+            return reject(RejectReason::Other(
+                "Transfer id already in use".to_string(),
+            ));
+        }
+
+        let mut recent = self.recent_handshakes.lock().await;
+        let (filename, chunk_size, compression) =
+            match self.evaluate(&handshake, &transfers, &recent) {
+                ApprovalDecision::Accept {
+                    filename,
+                    chunk_size,
+                    compression,
+                } => (filename, chunk_size, compression),
+                ApprovalDecision::Reject(reason) => {
+                    warn!("Rejecting {}: {}", handshake, reason);
+                    return reject(reason);
+                }
+            };
+        let download_dir = self
+            .layout
+            .directory(&self.download_dir(), peer, SystemTime::now());
+        let path = download_dir.join(&filename);
+
+        if let Err(e) = tokio::fs::create_dir_all(&download_dir).await {
+            return reject(RejectReason::Other(format!(
+                "Failed to prepare download directory: {}",
+                e
+            )));
+        }
+        if let Err(e) = tokio::fs::File::create(&path).await {
+            return reject(RejectReason::Other(format!(
+                "Failed to create {}: {}",
+                path.display(),
+                e
+            )));
+        }
+        if let Some((_, window)) = self.handshake_limit {
+            let now = Instant::now();
+            let times = recent.entry(peer).or_default();
+            times.retain(|&at| now.duration_since(at) < window);
+            times.push_back(now);
+        }
+        drop(recent);
+
+        info!("Accepted {} as {}", handshake, filename);
+        let incoming = IncomingTransfer {
+            peer,
+            path,
+            filesize,
+            chunk_size,
+            adaptive_chunks: handshake.adaptive_chunks,
+            compression,
+            bytes_received: 0,
+            chunks_received: 0,
+            received_chunks: ReceivedChunks::default(),
+            total_chunks: filesize.div_ceil(chunk_size).max(1),
+            sha256: handshake.sha256,
+            merkle_root: handshake.merkle_root,
+        };
+        transfers.insert(transfer_id.clone(), incoming.clone());
+        // A new handshake starts the id afresh, even if it was cancelled before
+        self.cancelled.lock().await.remove(&transfer_id);
+        drop(transfers);
+
+        if let Some(events) = &self.events {
+            let now = SystemTime::now();
+            let transfer = Transfer {
+                id: TransferId::from_string(transfer_id.clone()),
+                file: File {
+                    id: FileId::new(),
+                    name: filename,
+                    size: filesize,
+                    hash: String::new(),
+                    path: incoming.path.display().to_string(),
+                    created_at: now,
+                    modified_at: None,
+                },
+                sender: peer.into(),
+                receiver: DomainPeerId::from(events.local_peer),
+                status: TransferStatus::InProgress,
+                progress: incoming.progress(),
+                started_at: now,
+                completed_at: None,
+            };
+            self.publish(DomainEvent::TransferStarted {
+                transfer: Box::new(transfer),
+            })
+            .await;
+        }
+
+        ProtocolResponse::HandshakeResponse {
+            accepted: true,
+            reason: None,
+            transfer_id: Some(transfer_id),
+            accepted_chunk_size: Some(chunk_size),
+            compression,
+        }
+    }
+
+    async fn handle_chunk(
+        &self,
+        peer: PeerId,
+        transfer_id: String,
+        position: ChunkPosition,
+        data: Vec<u8>,
+    ) -> ProtocolResponse {
+        let ChunkPosition {
+            index: chunk_index,
+            offset,
+            is_last,
+            ..
+        } = position;
+        let refuse_chunk = |error: &str, retryable: bool| ProtocolResponse::ChunkResponse {
+            transfer_id: transfer_id.clone(),
+            chunk_index,
+            success: false,
+            error: Some(error.to_string()),
+            retryable,
+        };
+        let chunk_error = |error: &str| refuse_chunk(error, false);
+
+        if self.is_cancelled(&transfer_id).await {
+            debug!(
+                "Dropping chunk {} for cancelled transfer {}",
+                chunk_index, transfer_id
+            );
+            return chunk_error("Transfer cancelled");
+        }
+
+        let Some(transfer) = self.transfers.lock().await.get(&transfer_id).cloned() else {
+            return chunk_error("Unknown transfer");
+        };
+        if transfer.peer != peer {
+            warn!(
+                "Peer {} sent a chunk for transfer {} it does not own",
+                peer, transfer_id
+            );
+            return chunk_error("Unknown transfer");
+        }
+        // Every check below is on the chunk as it will be written
+        let data = match self.decompress(transfer.compression, data) {
+            Ok(data) => data,
+            Err(e) => {
+                warn!(
+                    "Chunk {} of transfer {} from {} failed to decompress: {}",
+                    chunk_index, transfer_id, peer, e
+                );
+                return refuse_chunk(&format!("Failed to decompress chunk: {}", e), true);
+            }
+        };
+
+        if position.total > self.max_total_chunks || chunk_index >= position.total {
+            warn!(
+                "Peer {} declared {} chunks for transfer {} (limit {})",
+                peer, position.total, transfer_id, self.max_total_chunks
+            );
+            return chunk_error(&format!(
+                "Chunk count {} is invalid or exceeds {}",
+                position.total, self.max_total_chunks
+            ));
+        }
+        if data.len() > self.chunk_size {
+            return chunk_error(&format!("Chunk exceeds {} bytes", self.chunk_size));
+        }
+        if offset
+            .checked_add(data.len() as u64)
+            .is_none_or(|end| end > transfer.filesize)
+        {
+            return chunk_error("Chunk extends past the end of the file");
+        }
+        if let Err(error) =
+            transfer.check_chunk_layout(chunk_index, offset, data.len() as u64, is_last)
+        {
+            warn!(
+                "Rejecting chunk {} of transfer {} from {}: {}",
+                chunk_index, transfer_id, peer, error
+            );
+            return chunk_error(&error);
+        }
+        if let Some(root) = &transfer.merkle_root
+            && !merkle::verify_chunk(&data, chunk_index, position.total, &position.proof, root)
+        {
+            warn!(
+                "Chunk {} of transfer {} from {} failed Merkle verification",
+                chunk_index, transfer_id, peer
+            );
+            return refuse_chunk("Chunk does not match the announced Merkle root", true);
+        }
+        // The start of the file tells what it really is, whatever its name
+        if offset == 0
+            && let Some(content_type) = self.disallowed_content_type(&data)
+        {
+            warn!(
+                "Aborting transfer {} from {}: content is {}",
+                transfer_id, peer, content_type
+            );
+            let reason = RejectReason::DisallowedContentType(content_type);
+            return self.abort(transfer_id, &transfer.path, reason).await;
+        }
+        if let Err(e) = write_at(&transfer.path, offset, &data).await {
+            return refuse_chunk(&format!("Failed to write chunk: {}", e), true);
+        }
+        self.record_chunk(&transfer_id, &transfer.path, chunk_index)
+            .await;
+
+        let mut transfers = self.transfers.lock().await;
+        // The transfer may have been cancelled while the chunk was being written
+        let Some(entry) = transfers.get_mut(&transfer_id) else {
+            return chunk_error("Transfer cancelled");
+        };
+        // A chunk sent again, after a pause or a lost acknowledgement, is
+        // only counted once
+        if entry.received_chunks.insert(chunk_index) {
+            entry.bytes_received += data.len() as u64;
+            entry.chunks_received += 1;
+        }
+        self.throughput
+            .lock()
+            .await
+            .record(&transfer_id, data.len() as u64, SystemTime::now());
+        entry.total_chunks = position.total;
+        let progress = entry.progress();
+
+        if !is_last {
+            drop(transfers);
+            self.report_progress(peer, &transfer_id, progress).await;
+            return ProtocolResponse::ChunkResponse {
+                transfer_id,
+                chunk_index,
+                success: true,
+                error: None,
+                retryable: false,
+            };
+        }
+
+        let entry = transfers
+            .remove(&transfer_id)
+            .expect("transfer present while lock is held");
+        drop(transfers);
+        self.throughput.lock().await.remove(&transfer_id);
+        self.forget_chunks(&transfer_id).await;
+        self.report_progress(peer, &transfer_id, progress).await;
+
+        let id = TransferId::from_string(transfer_id.clone());
+        let failure = if entry.bytes_received != entry.filesize {
+            warn!(
+                "Transfer {} ended with {} of {} bytes",
+                transfer_id, entry.bytes_received, entry.filesize
+            );
+            Some("Received size does not match handshake".to_string())
+        } else if self.verify_after && entry.sha256.is_some() {
+            self.publish(DomainEvent::TransferVerifying {
+                transfer_id: id.clone(),
+            })
+            .await;
+            self.verify(&transfer_id, &entry).await.err()
+        } else {
+            None
+        };
+        let success = failure.is_none();
+        let error = if let Some(reason) = failure {
+            self.publish(DomainEvent::TransferFailed {
+                transfer_id: id,
+                reason: reason.clone(),
+            })
+            .await;
+            Some(RejectReason::Other(reason))
+        } else {
+            info!(
+                "Transfer {} complete: {} chunks written to {}",
+                transfer_id,
+                entry.chunks_received,
+                entry.path.display()
+            );
+            self.publish(DomainEvent::TransferCompleted { transfer_id: id })
+                .await;
+            None
+        };
+        ProtocolResponse::TransferComplete {
+            transfer_id,
+            success,
+            error,
+        }
+    }
+
+    async fn handle_cancel(&self, peer: PeerId, transfer_id: String) -> ProtocolResponse {
+        let removed = {
+            let mut transfers = self.transfers.lock().await;
+            match transfers.get(&transfer_id) {
+                Some(t) if t.peer == peer => transfers.remove(&transfer_id),
+                _ => None,
+            }
+        };
+
+        if let Some(transfer) = removed {
+            self.cancelled.lock().await.insert(transfer_id.clone());
+            self.throughput.lock().await.remove(&transfer_id);
+            self.forget_chunks(&transfer_id).await;
+            let _ = tokio::fs::remove_file(&transfer.path).await;
+            info!("Transfer {} cancelled by {}", transfer_id, peer);
+            self.publish(DomainEvent::TransferFailed {
+                transfer_id: TransferId::from_string(transfer_id.clone()),
+                reason: "Cancelled by sender".to_string(),
+            })
+            .await;
+        }
+
+        ProtocolResponse::TransferComplete {
+            transfer_id,
+            success: false,
+            error: Some(RejectReason::Other("Transfer cancelled".to_string())),
+        }
+    }
+
+    /// `data` as sent, decompressed with `compression`
+    fn decompress(&self, compression: Compression, data: Vec<u8>) -> DomainResult<Vec<u8>> {
+        if compression == Compression::None {
+            return Ok(data);
+        }
+        let compressor = self
+            .compressors
+            .get(compression)
+            .ok_or_else(|| format!("No {} compressor", compression))?;
+        compressor.decompress(&data)
+    }
+
+    /// The sniffed type of a file starting with `head`, if policy refuses it
+    fn disallowed_content_type(&self, head: &[u8]) -> Option<String> {
+        let content_type = sniff_content_type(head);
+        let allowed = !self.blocked_content_types.contains(content_type)
+            && self
+                .allowed_content_types
+                .as_ref()
+                .is_none_or(|allowed| allowed.contains(content_type));
+        (!allowed).then(|| content_type.to_string())
+    }
+
+    /// Drop an active transfer and its partial file, telling the sender why
+    async fn abort(
+        &self,
+        transfer_id: String,
+        path: &Path,
+        reason: RejectReason,
+    ) -> ProtocolResponse {
+        self.transfers.lock().await.remove(&transfer_id);
+        self.throughput.lock().await.remove(&transfer_id);
+        self.forget_chunks(&transfer_id).await;
+        let _ = tokio::fs::remove_file(path).await;
+        self.publish(DomainEvent::TransferFailed {
+            transfer_id: TransferId::from_string(transfer_id.clone()),
+            reason: reason.to_string(),
+        })
+        .await;
+        ProtocolResponse::TransferComplete {
+            transfer_id,
+            success: false,
+            error: Some(reason),
+        }
+    }
+
+    /// Check a fully received file against the sender's announced hash
+    async fn verify(&self, transfer_id: &str, entry: &IncomingTransfer) -> Result<(), String> {
+        let Some(expected) = entry.sha256.as_deref().filter(|_| self.verify_after) else {
+            return Ok(());
+        };
+        let actual = compute_file_hash(&entry.path)
+            .await
+            .map_err(|e| format!("Failed to hash received file: {}", e))?;
+        if !actual.eq_ignore_ascii_case(expected) {
+            warn!(
+                "Transfer {} failed verification: expected {}, got {}",
+                transfer_id, expected, actual
+            );
+            return Err("File hash does not match sender's hash".to_string());
+        }
+        debug!("Transfer {} verified against sender's hash", transfer_id);
+        Ok(())
+    }
+
+    /// Publish a chunk's progress as a domain event and to progress subscribers
+    async fn report_progress(&self, peer: PeerId, transfer_id: &str, progress: TransferProgress) {
+        self.progress_listeners.lock().await.retain(|listener| {
+            listener
+                .send((peer, transfer_id.to_string(), progress.clone()))
+                .is_ok()
+        });
+        self.publish(DomainEvent::TransferProgress {
+            transfer_id: TransferId::from_string(transfer_id.to_string()),
+            progress,
+        })
+        .await;
+    }
+
+    /// Note that chunk `index` of `transfer_id` is on disk at `path`
+    async fn record_chunk(&self, transfer_id: &str, path: &Path, index: u64) {
+        let Some(ChunkLog(repo)) = &self.chunk_log else {
+            return;
+        };
+        let id = TransferId::from_string(transfer_id.to_string());
+        if let Err(e) = repo
+            .record_received_chunk(&id, &path.to_string_lossy(), index)
+            .await
+        {
+            warn!(
+                "Failed to record chunk {} of transfer {}: {}",
+                index, transfer_id, e
+            );
+        }
+    }
+
+    /// Drop the recorded chunks of a transfer that has ended
+    async fn forget_chunks(&self, transfer_id: &str) {
+        let Some(ChunkLog(repo)) = &self.chunk_log else {
+            return;
+        };
+        let id = TransferId::from_string(transfer_id.to_string());
+        if let Err(e) = repo.clear_resume_state(&id).await {
+            warn!(
+                "Failed to clear recorded chunks of transfer {}: {}",
+                transfer_id, e
+            );
+        }
+    }
+
+    async fn publish(&self, event: DomainEvent) {
+        if let Some(events) = &self.events
+            && let Err(e) = events.publisher.publish(event).await
+        {
+            warn!("Failed to publish transfer event: {}", e);
+        }
+    }
+}
+
+/// Write `data` at `offset` in an existing file
+async fn write_at(path: &Path, offset: u64, data: &[u8]) -> std::io::Result<()> {
+    let mut file = tokio::fs::OpenOptions::new().write(true).open(path).await?;
+    file.seek(std::io::SeekFrom::Start(offset)).await?;
+    file.write_all(data).await?;
+    file.flush().await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn incoming(filesize: u64, adaptive_chunks: bool) -> IncomingTransfer {
+        IncomingTransfer {
+            peer: PeerId::random(),
+            path: PathBuf::from("layout.bin"),
+            filesize,
+            chunk_size: 4,
+            adaptive_chunks,
+            compression: Compression::None,
+            bytes_received: 0,
+            chunks_received: 0,
+            received_chunks: ReceivedChunks::default(),
+            total_chunks: filesize.div_ceil(4),
+            sha256: None,
+            merkle_root: None,
+        }
+    }
+
+    #[test]
+    fn test_chunk_layout_rejects_end_past_largest_offset() {
+        for adaptive in [false, true] {
+            let transfer = incoming(8, adaptive);
+            for is_last in [false, true] {
+                assert!(
+                    transfer
+                        .check_chunk_layout(0, u64::MAX - 1, 4, is_last)
+                        .is_err()
+                );
+            }
+        }
+        assert!(incoming(8, false).check_chunk_layout(1, 4, 4, true).is_ok());
+    }
+
+    #[test]
+    fn test_cancelled_transfers_forget_the_oldest_beyond_capacity() {
+        let mut cancelled = CancelledTransfers::default();
+        for i in 0..=CANCELLED_TRANSFERS_REMEMBERED {
+            cancelled.insert(format!("t{}", i));
+        }
+        assert!(!cancelled.contains("t0"));
+        assert!(cancelled.contains("t1"));
+        assert_eq!(cancelled.ids.len(), CANCELLED_TRANSFERS_REMEMBERED);
+
+        cancelled.remove("t1");
+        assert!(!cancelled.contains("t1"));
+        assert_eq!(cancelled.order.len(), CANCELLED_TRANSFERS_REMEMBERED - 1);
+    }
+
+    #[test]
+    fn test_received_chunks_are_new_only_once() {
+        let mut received = ReceivedChunks::default();
+        assert!(received.insert(0));
+        assert!(received.insert(2));
+        assert!(!received.insert(0));
+        assert!(!received.insert(2));
+        assert!(received.insert(1));
+        // Indices caught up with are folded into the contiguous run
+        assert_eq!(received.contiguous, 3);
+        assert!(received.ahead.is_empty());
+        assert!(!received.insert(1));
+    }
+}
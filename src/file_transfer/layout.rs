@@ -0,0 +1,85 @@
+use libp2p::PeerId;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::SystemTime;
+
+/// How received files are arranged under the download directory
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DownloadLayout {
+    /// Every file directly in the download directory
+    #[default]
+    Flat,
+    /// One subdirectory per sending peer, named by its peer id
+    Peer,
+    /// One subdirectory per UTC day, named `YYYY-MM-DD`
+    Date,
+}
+
+impl DownloadLayout {
+    /// Directory a file from `peer` arriving at `now` is written to
+    pub fn directory(&self, download_dir: &Path, peer: PeerId, now: SystemTime) -> PathBuf {
+        match self {
+            Self::Flat => download_dir.to_path_buf(),
+            Self::Peer => download_dir.join(peer.to_string()),
+            Self::Date => {
+                let timestamp = humantime::format_rfc3339_seconds(now).to_string();
+                download_dir.join(&timestamp[..10])
+            }
+        }
+    }
+}
+
+impl FromStr for DownloadLayout {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "flat" => Ok(Self::Flat),
+            "peer" => Ok(Self::Peer),
+            "date" => Ok(Self::Date),
+            other => Err(format!(
+                "Unknown download layout {:?}; expected flat, peer or date",
+                other
+            )),
+        }
+    }
+}
+
+impl fmt::Display for DownloadLayout {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Flat => write!(f, "flat"),
+            Self::Peer => write!(f, "peer"),
+            Self::Date => write!(f, "date"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_layout_directories() {
+        let root = Path::new("/downloads");
+        let peer = PeerId::random();
+        // 2023-11-14T22:13:20Z
+        let now = SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+
+        assert_eq!(DownloadLayout::Flat.directory(root, peer, now), root);
+        assert_eq!(
+            DownloadLayout::Peer.directory(root, peer, now),
+            root.join(peer.to_string())
+        );
+        assert_eq!(
+            DownloadLayout::Date.directory(root, peer, now),
+            root.join("2023-11-14")
+        );
+        assert_eq!("Peer".parse(), Ok(DownloadLayout::Peer));
+        assert!("by-size".parse::<DownloadLayout>().is_err());
+    }
+}
@@ -1,8 +1,22 @@
+pub mod compression;
+pub mod handler;
+pub mod layout;
 pub mod request_handler;
+pub mod sender;
+pub mod sniff;
+pub mod throughput;
 pub mod types;
+pub mod walk;
 
 // Re-exports for easier access from crate::file_transfer::{...}
-pub use request_handler::{FileTransferCodec, FileTransferProtocol};
-pub use types::{FileMetadata, ProtocolRequest, ProtocolResponse};
+pub use compression::{ChunkCompressor, Compression, CompressorRegistry};
+pub use handler::{ApprovalDecision, FileTransferHandler, HandshakeInfo, PendingRequest};
+pub use layout::DownloadLayout;
+pub use request_handler::{FileTransferCodec, FileTransferProtocol, WIRE_VERSION, WireFormatError};
+pub use sender::{AdaptiveChunking, FileSender, SendOutcome, TransferTransport};
+pub use sniff::sniff_content_type;
+pub use throughput::ThroughputHistory;
+pub use types::{FileMetadata, ProtocolRequest, ProtocolResponse, RejectReason};
+pub use walk::{SymlinkPolicy, WalkEntry, walk_directory};
 
 // Avoid wildcard re-exports to keep the public API explicit and lints clean
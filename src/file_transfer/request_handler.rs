@@ -43,6 +43,51 @@ const MAX_FRAME_SIZE: usize = 2 * 1024 * 1024; // 2 MiB
 // Per-message-type budgets (handshake is small, chunk is larger)
 const MAX_HANDSHAKE_SIZE: usize = 64 * 1024; // 64 KiB
 
+/// Bytes opening every frame, ahead of the wire format version
+pub const WIRE_MAGIC: [u8; 2] = *b"CS";
+/// Version of the bincode layout of [`ProtocolRequest`] and
+/// [`ProtocolResponse`]; bump it whenever either enum changes shape
+pub const WIRE_VERSION: u8 = 2;
+
+/// A frame header this build cannot decode
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum WireFormatError {
+    #[error("Not a cipherstream frame (magic {found:02x?})")]
+    BadMagic { found: [u8; 2] },
+    #[error("Unsupported wire format version {found}; this build speaks {expected}")]
+    UnsupportedVersion { found: u8, expected: u8 },
+}
+
+/// Check the magic and version opening a frame
+async fn read_header<T>(io: &mut T) -> io::Result<()>
+where
+    T: AsyncRead + Unpin + Send,
+{
+    let mut header = [0u8; 3];
+    io.read_exact(&mut header).await?;
+    let error = if header[..2] != WIRE_MAGIC {
+        WireFormatError::BadMagic {
+            found: [header[0], header[1]],
+        }
+    } else if header[2] != WIRE_VERSION {
+        WireFormatError::UnsupportedVersion {
+            found: header[2],
+            expected: WIRE_VERSION,
+        }
+    } else {
+        return Ok(());
+    };
+    Err(io::Error::new(io::ErrorKind::InvalidData, error))
+}
+
+async fn write_header<T>(io: &mut T) -> io::Result<()>
+where
+    T: AsyncWrite + Unpin + Send,
+{
+    io.write_all(&WIRE_MAGIC).await?;
+    io.write_all(&[WIRE_VERSION]).await
+}
+
 #[async_trait]
 impl Codec for FileTransferCodec {
     type Protocol = FileTransferProtocol;
@@ -57,6 +102,8 @@ impl Codec for FileTransferCodec {
     where
         T: AsyncRead + Unpin + Send,
     {
+        read_header(io).await?;
+
         // Read length prefix (4 bytes)
         let mut len_bytes = [0u8; 4];
         io.read_exact(&mut len_bytes).await?;
@@ -97,7 +144,7 @@ impl Codec for FileTransferCodec {
                     ));
                 }
             }
-            ProtocolRequest::CancelTransfer { .. } => {}
+            ProtocolRequest::CancelTransfer { .. } | ProtocolRequest::Ping { .. } => {}
         }
 
         Ok(request)
@@ -111,6 +158,8 @@ impl Codec for FileTransferCodec {
     where
         T: AsyncRead + Unpin + Send,
     {
+        read_header(io).await?;
+
         // Read length prefix (4 bytes)
         let mut len_bytes = [0u8; 4];
         io.read_exact(&mut len_bytes).await?;
@@ -146,7 +195,8 @@ impl Codec for FileTransferCodec {
         let data = bincode::encode_to_vec(req, config::standard())
             .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
 
-        // Write length prefix
+        // Write header and length prefix
+        write_header(io).await?;
         let len = data.len() as u32;
         io.write_all(&len.to_be_bytes()).await?;
 
@@ -170,7 +220,8 @@ impl Codec for FileTransferCodec {
         let data = bincode::encode_to_vec(res, config::standard())
             .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
 
-        // Write length prefix
+        // Write header and length prefix
+        write_header(io).await?;
         let len = data.len() as u32;
         io.write_all(&len.to_be_bytes()).await?;
 
@@ -0,0 +1,778 @@
+use super::compression::{ChunkCompressor, Compression, CompressorRegistry};
+use super::types::{ProtocolRequest, ProtocolResponse, RejectReason};
+use crate::core::crypto::merkle::{self, MerkleTree};
+use crate::core::domain::TransferId;
+use crate::core::traits::{DomainResult, TransferControl};
+use async_trait::async_trait;
+use libp2p::PeerId;
+use std::collections::hash_map::Entry;
+use std::collections::{BTreeSet, HashMap};
+use std::path::Path;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncReadExt, AsyncSeekExt};
+use tokio::sync::{Mutex, Semaphore, watch};
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, info, warn};
+
+/// Request/response transport used to drive an outgoing transfer
+#[async_trait]
+pub trait TransferTransport: Send + Sync {
+    async fn send_request(
+        &self,
+        peer: PeerId,
+        request: ProtocolRequest,
+    ) -> DomainResult<ProtocolResponse>;
+}
+
+/// How an outgoing transfer ended
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SendOutcome {
+    Completed { chunks_sent: u64 },
+    Cancelled { chunks_sent: u64 },
+}
+
+/// Bounds for adapting the chunk size to acknowledgement latency.
+///
+/// Like TCP congestion control, the chunk size grows additively by
+/// `min_chunk_size` while acks arrive within `target_latency` and is halved
+/// when they don't.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AdaptiveChunking {
+    pub min_chunk_size: usize,
+    pub max_chunk_size: usize,
+    pub target_latency: Duration,
+}
+
+impl AdaptiveChunking {
+    /// Chunk size to use after a chunk was acknowledged in `latency`
+    pub fn next_chunk_size(&self, current: usize, latency: Duration) -> usize {
+        let next = if latency <= self.target_latency {
+            current.saturating_add(self.min_chunk_size)
+        } else {
+            current / 2
+        };
+        next.clamp(self.min_chunk_size, self.max_chunk_size)
+    }
+}
+
+/// Where a resumed send picks up an interrupted transfer
+#[derive(Debug, Clone, Copy)]
+struct ResumePoint<'a> {
+    /// Chunk size the interrupted send agreed with the receiver
+    chunk_size: usize,
+    start_chunk: u64,
+    /// Chunks the receiver already holds
+    skip: &'a BTreeSet<u64>,
+}
+
+/// Handles for steering one send in progress
+struct SendControl {
+    /// Receiver of the transfer
+    peer: PeerId,
+    /// Cancelled to stop the send for good
+    cancel: CancellationToken,
+    /// `true` while the send is paused
+    paused: watch::Sender<bool>,
+}
+
+/// Default size of the buffer chunks are read from disk through
+const DEFAULT_READ_BUFFER_SIZE: usize = 64 * 1024;
+
+/// Default number of transfers sent to one peer at a time
+pub const DEFAULT_SENDS_PER_PEER: usize = 1;
+
+/// Default number of times a chunk rejected as retryable is resent
+pub const DEFAULT_CHUNK_RETRIES: u32 = 3;
+
+/// Sender-side driver that streams a file to a peer chunk by chunk
+pub struct FileSender {
+    transport: Arc<dyn TransferTransport>,
+    chunk_size: usize,
+    read_buffer_size: usize,
+    adaptive: Option<AdaptiveChunking>,
+    /// Largest file this sender will offer, in bytes
+    max_file_size: u64,
+    uploads: Arc<Semaphore>,
+    sends_per_peer: usize,
+    /// Queue of transfers waiting for each peer, dropped once it is idle
+    peer_queues: std::sync::Mutex<HashMap<PeerId, Arc<Semaphore>>>,
+    handshake_retries: u32,
+    retry_backoff: Duration,
+    /// Times a chunk the receiver rejects as retryable is resent before
+    /// giving up
+    chunk_retries: u32,
+    /// Compression proposed in handshakes
+    compression: Compression,
+    compressors: CompressorRegistry,
+    /// Sends in progress, by transfer id
+    controls: Mutex<HashMap<String, Arc<SendControl>>>,
+}
+
+impl FileSender {
+    pub fn new(transport: Arc<dyn TransferTransport>, chunk_size: usize) -> Self {
+        Self {
+            transport,
+            chunk_size,
+            read_buffer_size: DEFAULT_READ_BUFFER_SIZE,
+            adaptive: None,
+            max_file_size: u64::MAX,
+            uploads: Arc::new(Semaphore::new(Semaphore::MAX_PERMITS)),
+            sends_per_peer: DEFAULT_SENDS_PER_PEER,
+            peer_queues: std::sync::Mutex::new(HashMap::new()),
+            handshake_retries: 3,
+            retry_backoff: Duration::from_secs(1),
+            chunk_retries: DEFAULT_CHUNK_RETRIES,
+            compression: Compression::None,
+            compressors: CompressorRegistry::default(),
+            controls: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Read files through a buffer of `size` bytes. Chunks are assembled
+    /// from these reads and sized to the data actually left in the file, so
+    /// a large wire chunk size doesn't cost memory on small files.
+    pub fn with_read_buffer_size(mut self, size: usize) -> Self {
+        assert!(size > 0, "Read buffer size must be greater than 0");
+        self.read_buffer_size = size;
+        self
+    }
+
+    /// Refuse to send files larger than `max_file_size` bytes. The check
+    /// happens before hashing or contacting the peer.
+    pub fn with_max_file_size(mut self, max_file_size: u64) -> Self {
+        self.max_file_size = max_file_size;
+        self
+    }
+
+    /// Limit how many transfers may be sent at once; further ones wait for
+    /// a slot
+    pub fn with_max_uploads(mut self, max_uploads: usize) -> Self {
+        self.uploads = Arc::new(Semaphore::new(max_uploads));
+        self
+    }
+
+    /// Let up to `sends` transfers to the same peer run at once; further
+    /// ones wait their turn. Transfers to different peers don't wait on
+    /// each other.
+    pub fn with_sends_per_peer(mut self, sends: usize) -> Self {
+        assert!(sends > 0, "Sends per peer must be greater than 0");
+        self.sends_per_peer = sends;
+        self
+    }
+
+    /// Retry a handshake rejected as [`RejectReason::RateLimited`] up to
+    /// `retries` times, waiting `backoff` between attempts
+    pub fn with_handshake_retries(mut self, retries: u32, backoff: Duration) -> Self {
+        self.handshake_retries = retries;
+        self.retry_backoff = backoff;
+        self
+    }
+
+    /// Resend a chunk the receiver rejects as retryable up to `retries` times
+    /// before failing the transfer. Other rejections fail it at once.
+    pub fn with_chunk_retries(mut self, retries: u32) -> Self {
+        self.chunk_retries = retries;
+        self
+    }
+
+    /// Propose compressing chunk data with `compression`. The receiver may
+    /// settle on [`Compression::None`] instead if it lacks the algorithm.
+    pub fn with_compression(mut self, compression: Compression) -> Self {
+        self.compression = compression;
+        self
+    }
+
+    /// Compress with the algorithms in `compressors` rather than
+    /// [`CompressorRegistry::default`]
+    pub fn with_compressors(mut self, compressors: CompressorRegistry) -> Self {
+        self.compressors = compressors;
+        self
+    }
+
+    /// Start at the configured chunk size and adapt it to ack latency within
+    /// `adaptive`'s bounds. The receiver must accept chunks up to
+    /// `max_chunk_size`.
+    pub fn with_adaptive_chunking(mut self, adaptive: AdaptiveChunking) -> Self {
+        assert!(
+            0 < adaptive.min_chunk_size && adaptive.min_chunk_size <= adaptive.max_chunk_size,
+            "Adaptive chunk bounds must satisfy 0 < min <= max"
+        );
+        self.adaptive = Some(adaptive);
+        self
+    }
+
+    /// Send the file at `path` to `peer` under `transfer_id`.
+    ///
+    /// Cancellation via [`FileSender::cancel_transfer`] is honored between
+    /// chunks and while waiting for a chunk acknowledgement. Fails if
+    /// `transfer_id` is already being sent.
+    pub async fn send_file(
+        &self,
+        peer: PeerId,
+        path: &Path,
+        transfer_id: &str,
+    ) -> DomainResult<SendOutcome> {
+        self.send(peer, path, transfer_id, None).await
+    }
+
+    /// Continue an interrupted send of `path` under `transfer_id`, starting
+    /// at `start_chunk` and leaving out the chunks in `skip` that the
+    /// receiver already holds.
+    ///
+    /// No new handshake is made, so this only works while the receiver still
+    /// holds the transfer in memory; once it restarts or drops the transfer,
+    /// send the file again from the start. Chunks are cut at `chunk_size`,
+    /// which must be the size the interrupted send agreed with the receiver
+    /// rather than the one this sender proposes, and compressed with the
+    /// algorithm this sender proposes, which the receiver must have agreed to. The final chunk is always
+    /// sent so the receiver can complete.
+    pub async fn send_file_from(
+        &self,
+        peer: PeerId,
+        path: &Path,
+        transfer_id: &str,
+        chunk_size: usize,
+        start_chunk: u64,
+        skip: &BTreeSet<u64>,
+    ) -> DomainResult<SendOutcome> {
+        if chunk_size == 0 {
+            return Err("Chunk size must be greater than 0".into());
+        }
+        let resume = ResumePoint {
+            chunk_size,
+            start_chunk,
+            skip,
+        };
+        self.send(peer, path, transfer_id, Some(resume)).await
+    }
+
+    async fn send(
+        &self,
+        peer: PeerId,
+        path: &Path,
+        transfer_id: &str,
+        resume: Option<ResumePoint<'_>>,
+    ) -> DomainResult<SendOutcome> {
+        let control = Arc::new(SendControl {
+            peer,
+            cancel: CancellationToken::new(),
+            paused: watch::Sender::new(false),
+        });
+        // Pause, resume and cancel find a send by id alone, so an id stays
+        // with one send until it ends
+        match self.controls.lock().await.entry(transfer_id.to_string()) {
+            Entry::Occupied(_) => {
+                return Err(format!("Transfer {} is already being sent", transfer_id).into());
+            }
+            Entry::Vacant(entry) => {
+                entry.insert(control.clone());
+            }
+        }
+
+        // Wait behind earlier transfers to the same peer, then for an upload
+        // slot, still cancellable. Taking the slot only once it's our turn
+        // keeps a queue for one busy peer from starving the others.
+        let queue = self.peer_queue(peer);
+        let permits = async {
+            let turn = queue.clone().acquire_owned().await;
+            let upload = self.uploads.clone().acquire_owned().await;
+            (upload, turn)
+        };
+        let result = tokio::select! {
+            (upload, turn) = permits => {
+                let _upload = upload.expect("the upload limit is never closed");
+                let _turn = turn.expect("peer queues are never closed");
+                self.run_transfer(peer, path, transfer_id, &control, resume)
+                    .await
+            }
+            _ = control.cancel.cancelled() => {
+                info!("Transfer {} cancelled while queued", transfer_id);
+                Ok(SendOutcome::Cancelled { chunks_sent: 0 })
+            }
+        };
+        drop(queue);
+        self.release_peer_queue(peer);
+        self.controls.lock().await.remove(transfer_id);
+        result
+    }
+
+    fn peer_queue(&self, peer: PeerId) -> Arc<Semaphore> {
+        self.peer_queues
+            .lock()
+            .expect("peer queue lock poisoned")
+            .entry(peer)
+            .or_insert_with(|| Arc::new(Semaphore::new(self.sends_per_peer)))
+            .clone()
+    }
+
+    /// Forget `peer`'s queue once no transfer holds or waits on it
+    fn release_peer_queue(&self, peer: PeerId) {
+        let mut queues = self.peer_queues.lock().expect("peer queue lock poisoned");
+        if queues
+            .get(&peer)
+            .is_some_and(|queue| Arc::strong_count(queue) == 1)
+        {
+            queues.remove(&peer);
+        }
+    }
+
+    /// Check that `peer`'s transfer handler answers within `timeout`,
+    /// returning the round-trip time
+    pub async fn ping(&self, peer: PeerId, timeout: Duration) -> DomainResult<Duration> {
+        let nonce = rand::random::<u64>();
+        let started = Instant::now();
+        let response = tokio::time::timeout(
+            timeout,
+            self.transport
+                .send_request(peer, ProtocolRequest::Ping { nonce }),
+        )
+        .await
+        .map_err(|_| format!("Peer {} did not answer ping within {:?}", peer, timeout))??;
+        match response {
+            ProtocolResponse::Pong { nonce: echoed } if echoed == nonce => Ok(started.elapsed()),
+            other => Err(format!("Unexpected ping response: {:?}", other).into()),
+        }
+    }
+
+    /// Cancel an outgoing transfer and tell the receiver to discard it
+    pub async fn cancel_transfer(&self, peer: PeerId, transfer_id: &str) -> DomainResult<()> {
+        if let Some(control) = self.controls.lock().await.get(transfer_id) {
+            control.cancel.cancel();
+        }
+        self.transport
+            .send_request(
+                peer,
+                ProtocolRequest::CancelTransfer {
+                    transfer_id: transfer_id.to_string(),
+                },
+            )
+            .await?;
+        Ok(())
+    }
+
+    /// Stop sending chunks of `transfer_id` until it is resumed, returning
+    /// whether it is being sent. A chunk awaiting its acknowledgement is
+    /// sent again on resume; the receiver keeps the transfer meanwhile.
+    pub async fn pause_transfer(&self, transfer_id: &str) -> bool {
+        self.set_paused(transfer_id, true).await
+    }
+
+    /// Continue a paused send, returning whether `transfer_id` is being sent
+    pub async fn resume_transfer(&self, transfer_id: &str) -> bool {
+        self.set_paused(transfer_id, false).await
+    }
+
+    async fn set_paused(&self, transfer_id: &str, paused: bool) -> bool {
+        let Some(control) = self.controls.lock().await.get(transfer_id).cloned() else {
+            return false;
+        };
+        if control.paused.send_replace(paused) != paused {
+            info!(
+                "Transfer {} {}",
+                transfer_id,
+                if paused { "paused" } else { "resumed" }
+            );
+        }
+        true
+    }
+
+    async fn run_transfer(
+        &self,
+        peer: PeerId,
+        path: &Path,
+        transfer_id: &str,
+        control: &SendControl,
+        resume: Option<ResumePoint<'_>>,
+    ) -> DomainResult<SendOutcome> {
+        let token = &control.cancel;
+        let mut paused = control.paused.subscribe();
+        let filename = path
+            .file_name()
+            .ok_or("Invalid filename")?
+            .to_string_lossy()
+            .to_string();
+        let filesize = tokio::fs::metadata(path).await?.len();
+        if filesize > self.max_file_size {
+            return Err(format!(
+                "{} is {} bytes, over the {} byte limit for sending",
+                path.display(),
+                filesize,
+                self.max_file_size
+            )
+            .into());
+        }
+        let (merkle, adaptive, mut chunk_size, start_chunk, compression) = match resume {
+            Some(resume) => {
+                let start = resume.start_chunk.saturating_mul(resume.chunk_size as u64);
+                if start > 0 && start >= filesize {
+                    return Err(format!(
+                        "Chunk {} is past the end of {}",
+                        resume.start_chunk,
+                        path.display()
+                    )
+                    .into());
+                }
+                let merkle = merkle::compute_chunk_merkle(path, resume.chunk_size)
+                    .await
+                    .map_err(|e| format!("Failed to hash {}: {}", path.display(), e))?;
+                info!(
+                    "Resuming transfer {} at chunk {}, skipping {} received chunks",
+                    transfer_id,
+                    resume.start_chunk,
+                    resume.skip.len()
+                );
+                (
+                    Some(merkle),
+                    None,
+                    resume.chunk_size,
+                    resume.start_chunk,
+                    self.compression,
+                )
+            }
+            None => {
+                let (merkle, adaptive, chunk_size, compression) = self
+                    .negotiate(peer, path, &filename, filesize, transfer_id, token)
+                    .await?;
+                (merkle, adaptive, chunk_size, 0, compression)
+            }
+        };
+        let compressor = self.compressor(compression)?;
+        let skip = resume.map(|resume| resume.skip);
+        let mut file = tokio::fs::File::open(path).await?;
+        let mut buffer = vec![0u8; self.read_buffer_size];
+        let mut chunk_index = start_chunk;
+        let mut offset = start_chunk * chunk_size as u64;
+        let mut seek_to = Some(offset).filter(|&offset| offset > 0);
+        let mut chunks_sent = 0;
+        // Times the current chunk has been rejected
+        let mut rejections = 0;
+
+        loop {
+            if *paused.borrow_and_update() {
+                tokio::select! {
+                    biased;
+                    _ = token.cancelled() => {}
+                    _ = paused.wait_for(|paused| !paused) => {}
+                }
+            }
+            if token.is_cancelled() {
+                info!(
+                    "Transfer {} cancelled after {} chunks",
+                    transfer_id, chunks_sent
+                );
+                return Ok(SendOutcome::Cancelled { chunks_sent });
+            }
+
+            let want = (chunk_size as u64).min(filesize.saturating_sub(offset)) as usize;
+            let final_chunk = offset + want as u64 >= filesize;
+            if !final_chunk && skip.is_some_and(|skip| skip.contains(&chunk_index)) {
+                chunk_index += 1;
+                offset += want as u64;
+                seek_to = Some(offset);
+                continue;
+            }
+            if let Some(position) = seek_to.take() {
+                file.seek(std::io::SeekFrom::Start(position)).await?;
+            }
+            let mut data = Vec::with_capacity(want);
+            while data.len() < want {
+                let n = (want - data.len()).min(buffer.len());
+                let read = file.read(&mut buffer[..n]).await?;
+                if read == 0 {
+                    break;
+                }
+                data.extend_from_slice(&buffer[..read]);
+            }
+            let len = data.len() as u64;
+            let remaining = filesize.saturating_sub(offset + len);
+            let is_last = remaining == 0;
+            if len == 0 && !is_last {
+                return Err(format!("{} shrank during transfer", path.display()).into());
+            }
+            let total_chunks = chunk_index + 1 + remaining.div_ceil(chunk_size as u64);
+            // Offsets and proofs stay those of the uncompressed chunk
+            let data = match &compressor {
+                Some(compressor) => compressor.compress(&data)?,
+                None => data,
+            };
+
+            let request = ProtocolRequest::FileChunk {
+                transfer_id: transfer_id.to_string(),
+                chunk_index,
+                total_chunks,
+                offset,
+                data,
+                is_last,
+                proof: merkle
+                    .as_ref()
+                    .map(|tree| tree.proof(chunk_index))
+                    .unwrap_or_default(),
+            };
+            let started = Instant::now();
+            let response = tokio::select! {
+                biased;
+                _ = token.cancelled() => {
+                    info!("Transfer {} cancelled after {} chunks", transfer_id, chunks_sent);
+                    return Ok(SendOutcome::Cancelled { chunks_sent });
+                }
+                _ = paused.wait_for(|paused| *paused) => {
+                    // The chunk may not have arrived; send it again on resume
+                    seek_to = Some(offset);
+                    continue;
+                }
+                response = self.transport.send_request(peer, request) => response?,
+            };
+            let latency = started.elapsed();
+
+            match response {
+                ProtocolResponse::ChunkResponse { success: true, .. } => {
+                    debug!(
+                        "Chunk {}/{} ({} bytes) acknowledged in {:?}",
+                        chunk_index + 1,
+                        total_chunks,
+                        len,
+                        latency
+                    );
+                }
+                ProtocolResponse::ChunkResponse {
+                    chunk_index: rejected,
+                    error,
+                    retryable,
+                    ..
+                } => {
+                    let error = error.unwrap_or_else(|| "no reason given".to_string());
+                    if rejected != chunk_index || !retryable || rejections >= self.chunk_retries {
+                        return Err(format!("Chunk {} rejected: {}", rejected, error).into());
+                    }
+                    rejections += 1;
+                    warn!(
+                        "Chunk {} of transfer {} rejected ({}), resending ({}/{})",
+                        chunk_index, transfer_id, error, rejections, self.chunk_retries
+                    );
+                    // Read it from disk again, in case it was corrupted on the way
+                    seek_to = Some(offset);
+                    continue;
+                }
+                ProtocolResponse::TransferComplete { success: true, .. } if is_last => {}
+                ProtocolResponse::TransferComplete { error, .. } => {
+                    let error = describe(error);
+                    warn!("Receiver ended transfer {}: {}", transfer_id, error);
+                    return Err(format!("Transfer failed: {}", error).into());
+                }
+                other => return Err(format!("Unexpected chunk response: {:?}", other).into()),
+            }
+            chunks_sent += 1;
+            chunk_index += 1;
+            offset += len;
+            rejections = 0;
+
+            if is_last {
+                break;
+            }
+            if let Some(adaptive) = adaptive {
+                chunk_size = adaptive.next_chunk_size(chunk_size, latency);
+            }
+        }
+
+        info!("Transfer {} sent in {} chunks", transfer_id, chunks_sent);
+        Ok(SendOutcome::Completed { chunks_sent })
+    }
+
+    /// Hash the file, handshake with `peer` and settle on the chunk size,
+    /// returning the Merkle tree to prove chunks against, if any, along with
+    /// the adaptive bounds, starting chunk size and agreed compression
+    async fn negotiate(
+        &self,
+        peer: PeerId,
+        path: &Path,
+        filename: &str,
+        filesize: u64,
+        transfer_id: &str,
+        token: &CancellationToken,
+    ) -> DomainResult<(
+        Option<MerkleTree>,
+        Option<AdaptiveChunking>,
+        usize,
+        Compression,
+    )> {
+        let sha256 = crate::core::crypto::compute_file_hash(path)
+            .await
+            .map_err(|e| format!("Failed to hash {}: {}", path.display(), e))?;
+        // Adaptive transfers may grow chunks up to their maximum
+        let mut proposed = match self.adaptive {
+            Some(adaptive) => adaptive.max_chunk_size,
+            None => self.chunk_size,
+        };
+        // Chunk boundaries must be fixed up front for per-chunk proofs, so
+        // adaptive transfers rely on the whole-file hash alone
+        let mut merkle = match self.adaptive {
+            Some(_) => None,
+            None => Some(chunk_merkle(path, proposed).await?),
+        };
+
+        let (accepted, compression) = loop {
+            let request = ProtocolRequest::HandshakeRequest {
+                filename: filename.to_string(),
+                filesize,
+                transfer_id: transfer_id.to_string(),
+                sha256: Some(sha256.clone()),
+                merkle_root: merkle.as_ref().map(MerkleTree::root),
+                chunk_size: Some(proposed as u64),
+                adaptive_chunks: self.adaptive.is_some(),
+                compression: self.compression,
+            };
+            match self.handshake(peer, request, transfer_id, token).await? {
+                Ok(accepted) => break accepted,
+                // Offer the transfer again in chunks the receiver takes,
+                // with proofs for the new boundaries
+                Err(Some(RejectReason::ChunkSizeUnsupported { max, .. }))
+                    if 0 < max && max < proposed as u64 =>
+                {
+                    debug!(
+                        "Receiver takes chunks of at most {} bytes; offering transfer {} again",
+                        max, transfer_id
+                    );
+                    proposed = max as usize;
+                    if merkle.is_some() {
+                        merkle = Some(chunk_merkle(path, proposed).await?);
+                    }
+                }
+                Err(reason) => {
+                    return Err(format!("Transfer rejected: {}", describe(reason)).into());
+                }
+            }
+        };
+
+        if compression != self.compression && compression != Compression::None {
+            return Err(format!(
+                "Receiver chose {} compression, which was not offered",
+                compression
+            )
+            .into());
+        }
+
+        // Stay within the receiver's limit, keeping our proposal if it gave none
+        let limit = match accepted {
+            Some(0) => return Err("Receiver accepted a chunk size of 0".into()),
+            Some(accepted) => usize::try_from(accepted)
+                .unwrap_or(usize::MAX)
+                .min(proposed),
+            None => proposed,
+        };
+        if limit < proposed {
+            debug!(
+                "Receiver limited transfer {} to {} byte chunks",
+                transfer_id, limit
+            );
+            // Proofs were built for the proposed chunk boundaries
+            merkle = None;
+        }
+        let adaptive = self.adaptive.map(|adaptive| AdaptiveChunking {
+            min_chunk_size: adaptive.min_chunk_size.min(limit),
+            max_chunk_size: limit,
+            ..adaptive
+        });
+        let chunk_size = match adaptive {
+            Some(adaptive) => self
+                .chunk_size
+                .clamp(adaptive.min_chunk_size, adaptive.max_chunk_size),
+            None => limit,
+        };
+        Ok((merkle, adaptive, chunk_size, compression))
+    }
+
+    /// Negotiate the transfer, backing off and retrying while the receiver
+    /// reports it is rate limited. Yields the accepted chunk size, if the
+    /// receiver gave one, and compression, or why the transfer was rejected.
+    async fn handshake(
+        &self,
+        peer: PeerId,
+        request: ProtocolRequest,
+        transfer_id: &str,
+        token: &CancellationToken,
+    ) -> DomainResult<Result<(Option<u64>, Compression), Option<RejectReason>>> {
+        let mut attempt = 0;
+        loop {
+            let reason = match self.transport.send_request(peer, request.clone()).await? {
+                ProtocolResponse::HandshakeResponse {
+                    accepted: true,
+                    accepted_chunk_size,
+                    compression,
+                    ..
+                } => return Ok(Ok((accepted_chunk_size, compression))),
+                ProtocolResponse::HandshakeResponse { reason, .. } => reason,
+                other => {
+                    return Err(format!("Unexpected handshake response: {:?}", other).into());
+                }
+            };
+
+            let retryable = reason.as_ref().is_some_and(RejectReason::is_retryable);
+            if !retryable || attempt >= self.handshake_retries || token.is_cancelled() {
+                return Ok(Err(reason));
+            }
+            attempt += 1;
+            debug!(
+                "Transfer {} rate limited, retrying ({}/{})",
+                transfer_id, attempt, self.handshake_retries
+            );
+            tokio::time::sleep(self.retry_backoff).await;
+        }
+    }
+
+    /// The compressor for `compression`, or `None` when chunks go as they are
+    fn compressor(
+        &self,
+        compression: Compression,
+    ) -> DomainResult<Option<Arc<dyn ChunkCompressor>>> {
+        if compression == Compression::None {
+            return Ok(None);
+        }
+        self.compressors
+            .get(compression)
+            .map(Some)
+            .ok_or_else(|| format!("No {} compressor", compression).into())
+    }
+}
+
+#[async_trait]
+impl TransferControl for FileSender {
+    async fn pause(&self, transfer_id: &TransferId) -> bool {
+        self.pause_transfer(transfer_id.as_str()).await
+    }
+
+    async fn resume(&self, transfer_id: &TransferId) -> bool {
+        self.resume_transfer(transfer_id.as_str()).await
+    }
+
+    async fn cancel(&self, transfer_id: &TransferId) -> bool {
+        let id = transfer_id.as_str();
+        let Some(peer) = self
+            .controls
+            .lock()
+            .await
+            .get(id)
+            .map(|control| control.peer)
+        else {
+            return false;
+        };
+        if let Err(e) = self.cancel_transfer(peer, id).await {
+            warn!(
+                "Failed to tell {} transfer {} is cancelled: {}",
+                peer, id, e
+            );
+        }
+        true
+    }
+}
+
+/// Merkle tree over `path` cut into `chunk_size` byte chunks
+async fn chunk_merkle(path: &Path, chunk_size: usize) -> DomainResult<MerkleTree> {
+    merkle::compute_chunk_merkle(path, chunk_size)
+        .await
+        .map_err(|e| format!("Failed to hash {}: {}", path.display(), e).into())
+}
+
+fn describe(reason: Option<RejectReason>) -> String {
+    reason.map_or_else(|| "no reason given".to_string(), |r| r.to_string())
+}
@@ -0,0 +1,52 @@
+/// Type reported for content that matches no known signature
+pub const UNKNOWN_CONTENT_TYPE: &str = "application/octet-stream";
+
+/// Leading bytes of each recognized type, checked in order
+const SIGNATURES: &[(&[u8], &str)] = &[
+    (b"\x7fELF", "application/x-executable"),
+    (b"MZ", "application/x-msdownload"),
+    (b"\xfe\xed\xfa\xce", "application/x-mach-binary"),
+    (b"\xfe\xed\xfa\xcf", "application/x-mach-binary"),
+    (b"\xce\xfa\xed\xfe", "application/x-mach-binary"),
+    (b"\xcf\xfa\xed\xfe", "application/x-mach-binary"),
+    (b"#!", "text/x-shellscript"),
+    (b"%PDF-", "application/pdf"),
+    (b"\x89PNG\r\n\x1a\n", "image/png"),
+    (b"\xff\xd8\xff", "image/jpeg"),
+    (b"GIF87a", "image/gif"),
+    (b"GIF89a", "image/gif"),
+    (b"PK\x03\x04", "application/zip"),
+    (b"\x1f\x8b", "application/gzip"),
+];
+
+/// Content type of a file judged by its first bytes rather than its name,
+/// or [`UNKNOWN_CONTENT_TYPE`] when no signature matches
+pub fn sniff_content_type(head: &[u8]) -> &'static str {
+    SIGNATURES
+        .iter()
+        .find(|(magic, _)| head.starts_with(magic))
+        .map_or(UNKNOWN_CONTENT_TYPE, |&(_, content_type)| content_type)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sniff_recognizes_signatures_regardless_of_length() {
+        assert_eq!(
+            sniff_content_type(b"\x7fELF\x02\x01\x01\x00"),
+            "application/x-executable"
+        );
+        assert_eq!(
+            sniff_content_type(b"MZ\x90\x00"),
+            "application/x-msdownload"
+        );
+        assert_eq!(sniff_content_type(b"%PDF-1.7\n"), "application/pdf");
+        assert_eq!(sniff_content_type(b"\x89PNG\r\n\x1a\n...."), "image/png");
+        assert_eq!(sniff_content_type(b"plain text"), UNKNOWN_CONTENT_TYPE);
+        // Too short to match anything
+        assert_eq!(sniff_content_type(b"\x7fEL"), UNKNOWN_CONTENT_TYPE);
+        assert_eq!(sniff_content_type(b""), UNKNOWN_CONTENT_TYPE);
+    }
+}
@@ -0,0 +1,136 @@
+use std::collections::{HashMap, VecDeque};
+use std::time::{Duration, SystemTime};
+
+/// Default width of one throughput sample
+pub const DEFAULT_SAMPLE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Default number of samples kept per transfer: five minutes at one per second
+pub const DEFAULT_MAX_SAMPLES: usize = 300;
+
+/// Bytes moved per fixed interval for each active transfer, for rate graphs.
+///
+/// Samples are `(interval start, bytes in interval)`. Intervals in which
+/// nothing arrived are recorded as zero so the series stays evenly spaced,
+/// and only the newest `max_samples` are kept.
+#[derive(Debug, Clone)]
+pub struct ThroughputHistory {
+    interval: Duration,
+    max_samples: usize,
+    transfers: HashMap<String, VecDeque<(SystemTime, u64)>>,
+}
+
+impl ThroughputHistory {
+    pub fn new(interval: Duration, max_samples: usize) -> Self {
+        assert!(!interval.is_zero(), "Sample interval must be non-zero");
+        assert!(max_samples > 0, "Sample count must be greater than 0");
+        Self {
+            interval,
+            max_samples,
+            transfers: HashMap::new(),
+        }
+    }
+
+    /// Add `bytes` received for `transfer_id` at `now`
+    pub fn record(&mut self, transfer_id: &str, bytes: u64, now: SystemTime) {
+        let samples = self.transfers.entry(transfer_id.to_string()).or_default();
+        let Some(&(start, _)) = samples.back() else {
+            samples.push_back((now, bytes));
+            return;
+        };
+
+        // A clock step backwards counts towards the current interval
+        let elapsed = now.duration_since(start).unwrap_or_default();
+        let skipped = (elapsed.as_nanos() / self.interval.as_nanos()) as u64;
+        if skipped == 0 {
+            samples.back_mut().expect("checked above").1 += bytes;
+            return;
+        }
+
+        // Zero-fill idle intervals, but never more than the buffer holds
+        let interval = self.interval;
+        let interval_start = |k: u64| start + interval * u32::try_from(k).unwrap_or(u32::MAX);
+        let first_gap = skipped.saturating_sub(self.max_samples as u64).max(1);
+        for k in first_gap..skipped {
+            samples.push_back((interval_start(k), 0));
+        }
+        samples.push_back((interval_start(skipped), bytes));
+        while samples.len() > self.max_samples {
+            samples.pop_front();
+        }
+    }
+
+    /// Samples for `transfer_id`, oldest first
+    pub fn samples(&self, transfer_id: &str) -> Vec<(SystemTime, u64)> {
+        self.transfers
+            .get(transfer_id)
+            .map(|samples| samples.iter().copied().collect())
+            .unwrap_or_default()
+    }
+
+    /// Forget a transfer that is no longer active
+    pub fn remove(&mut self, transfer_id: &str) {
+        self.transfers.remove(transfer_id);
+    }
+}
+
+impl Default for ThroughputHistory {
+    fn default() -> Self {
+        Self::new(DEFAULT_SAMPLE_INTERVAL, DEFAULT_MAX_SAMPLES)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_samples_bucket_bytes_per_interval_and_cap_length() {
+        let t0 = SystemTime::UNIX_EPOCH + Duration::from_secs(1_000);
+        let at = |ms: u64| t0 + Duration::from_millis(ms);
+        let mut history = ThroughputHistory::new(Duration::from_secs(1), 4);
+
+        history.record("t", 100, at(0));
+        history.record("t", 50, at(900));
+        history.record("t", 70, at(1_200));
+        // Nothing during the third second
+        history.record("t", 30, at(3_500));
+        history.record("other", 1, at(0));
+
+        assert_eq!(
+            history.samples("t"),
+            vec![
+                (at(0), 150),
+                (at(1_000), 70),
+                (at(2_000), 0),
+                (at(3_000), 30)
+            ]
+        );
+
+        history.record("t", 5, at(4_000));
+        assert_eq!(
+            history.samples("t"),
+            vec![
+                (at(1_000), 70),
+                (at(2_000), 0),
+                (at(3_000), 30),
+                (at(4_000), 5)
+            ]
+        );
+
+        // A long stall keeps only the newest intervals
+        history.record("t", 9, at(60_000));
+        assert_eq!(
+            history.samples("t"),
+            vec![
+                (at(57_000), 0),
+                (at(58_000), 0),
+                (at(59_000), 0),
+                (at(60_000), 9)
+            ]
+        );
+
+        history.remove("t");
+        assert!(history.samples("t").is_empty());
+        assert_eq!(history.samples("other"), vec![(at(0), 1)]);
+    }
+}
@@ -1,5 +1,7 @@
+use super::compression::Compression;
 use bincode::{Decode, Encode};
 use serde::{Deserialize, Serialize};
+use std::fmt;
 
 /// Protocol request types for file transfer operations
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Encode, Decode)]
@@ -9,17 +11,38 @@ pub enum ProtocolRequest {
         filename: String,
         filesize: u64,
         transfer_id: String,
+        /// Hex SHA-256 of the whole file, checked by receivers that verify
+        /// transfers once the last chunk arrives
+        sha256: Option<String>,
+        /// Merkle root over the fixed-size chunks that follow, letting the
+        /// receiver check each chunk as it arrives
+        merkle_root: Option<String>,
+        /// Chunk size the sender proposes to use
+        chunk_size: Option<u64>,
+        /// Whether the sender varies its chunk size mid-transfer; otherwise
+        /// every chunk but the last is exactly the agreed size
+        adaptive_chunks: bool,
+        /// Algorithm the sender would like to compress chunk data with
+        compression: Compression,
     },
     /// File chunk data
     FileChunk {
         transfer_id: String,
         chunk_index: u64,
+        /// Estimated from the current chunk size, which may change mid-transfer
         total_chunks: u64,
+        /// Byte position of `data` within the file, once decompressed
+        offset: u64,
+        /// Chunk bytes, compressed with the algorithm agreed in the handshake
         data: Vec<u8>,
         is_last: bool,
+        /// Hex sibling hashes linking `data` to the handshake's Merkle root
+        proof: Vec<String>,
     },
     /// Cancel an ongoing transfer
     CancelTransfer { transfer_id: String },
+    /// Check that the peer's transfer handler is responsive
+    Ping { nonce: u64 },
 }
 
 /// Protocol response types for file transfer operations
@@ -28,8 +51,14 @@ pub enum ProtocolResponse {
     /// Response to handshake request
     HandshakeResponse {
         accepted: bool,
-        reason: Option<String>,
+        reason: Option<RejectReason>,
         transfer_id: Option<String>,
+        /// Largest chunk the receiver will take, at most the proposed size.
+        /// Senders keep their proposal when this is absent.
+        accepted_chunk_size: Option<u64>,
+        /// Algorithm chunk data is compressed with: the sender's proposal if
+        /// the receiver supports it, otherwise [`Compression::None`]
+        compression: Compression,
     },
     /// Response to file chunk
     ChunkResponse {
@@ -37,13 +66,102 @@ pub enum ProtocolResponse {
         chunk_index: u64,
         success: bool,
         error: Option<String>,
+        /// Whether sending the same chunk again may succeed, as when it was
+        /// damaged on the way or the receiver was briefly unable to take it
+        retryable: bool,
     },
     /// Transfer completion notification
     TransferComplete {
         transfer_id: String,
         success: bool,
-        error: Option<String>,
+        error: Option<RejectReason>,
     },
+    /// Answer to a ping, echoing its nonce
+    Pong { nonce: u64 },
+}
+
+impl ProtocolResponse {
+    /// The answer to `request` from a receiver too busy to handle it
+    pub fn busy(request: &ProtocolRequest) -> Self {
+        let reason = RejectReason::RateLimited;
+        match request {
+            ProtocolRequest::HandshakeRequest { transfer_id, .. } => Self::HandshakeResponse {
+                accepted: false,
+                reason: Some(reason),
+                transfer_id: Some(transfer_id.clone()),
+                accepted_chunk_size: None,
+                compression: Compression::None,
+            },
+            ProtocolRequest::FileChunk {
+                transfer_id,
+                chunk_index,
+                ..
+            } => Self::ChunkResponse {
+                transfer_id: transfer_id.clone(),
+                chunk_index: *chunk_index,
+                success: false,
+                error: Some(reason.to_string()),
+                retryable: reason.is_retryable(),
+            },
+            ProtocolRequest::CancelTransfer { transfer_id } => Self::TransferComplete {
+                transfer_id: transfer_id.clone(),
+                success: false,
+                error: Some(reason),
+            },
+            // Answering a ping costs nothing
+            ProtocolRequest::Ping { nonce } => Self::Pong { nonce: *nonce },
+        }
+    }
+}
+
+/// Why a receiver refused or aborted a transfer
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Encode, Decode)]
+pub enum RejectReason {
+    /// The file exceeds the receiver's size limit
+    TooLarge,
+    /// The file extension is not on the receiver's allowlist
+    DisallowedExtension,
+    /// The receiver is busy; retrying later may succeed
+    RateLimited,
+    /// The receiver lacks disk space for the file
+    InsufficientSpace,
+    /// The filename is unsafe or malformed
+    InvalidFilename,
+    /// Any other reason, described in free form
+    Other(String),
+    /// The file's content, sniffed from its first bytes, is of a type the
+    /// receiver doesn't accept
+    DisallowedContentType(String),
+    /// The proposed chunk size is outside what the receiver takes. A sender
+    /// proving chunks against a Merkle root must rebuild it for at most
+    /// `max` byte chunks.
+    ChunkSizeUnsupported { min: u64, max: u64 },
+}
+
+impl RejectReason {
+    /// Whether the sender may reasonably retry after this rejection
+    pub fn is_retryable(&self) -> bool {
+        matches!(self, RejectReason::RateLimited)
+    }
+}
+
+impl fmt::Display for RejectReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RejectReason::TooLarge => write!(f, "File too large"),
+            RejectReason::DisallowedExtension => write!(f, "File extension not allowed"),
+            RejectReason::RateLimited => write!(f, "Receiver is busy, try again later"),
+            RejectReason::InsufficientSpace => write!(f, "Insufficient disk space"),
+            RejectReason::InvalidFilename => write!(f, "Invalid filename"),
+            RejectReason::Other(reason) => write!(f, "{}", reason),
+            RejectReason::DisallowedContentType(content_type) => {
+                write!(f, "Content type {} not allowed", content_type)
+            }
+            RejectReason::ChunkSizeUnsupported { min, max } => {
+                write!(f, "Chunk size must be between {} and {} bytes", min, max)
+            }
+        }
+    }
 }
 
 /// File metadata used in protocol messages
@@ -0,0 +1,174 @@
+use std::collections::HashSet;
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use tracing::{debug, warn};
+
+/// What to do with symlinks met while walking a directory to send
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SymlinkPolicy {
+    /// Leave symlinks out of the transfer
+    #[default]
+    Skip,
+    /// Send what symlinks point to, descending into linked directories once
+    Follow,
+    /// Send symlinks as links, without reading their targets
+    Preserve,
+}
+
+impl FromStr for SymlinkPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "skip" => Ok(Self::Skip),
+            "follow" => Ok(Self::Follow),
+            "preserve" => Ok(Self::Preserve),
+            other => Err(format!(
+                "Unknown symlink policy {:?}; expected skip, follow or preserve",
+                other
+            )),
+        }
+    }
+}
+
+impl fmt::Display for SymlinkPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Skip => write!(f, "skip"),
+            Self::Follow => write!(f, "follow"),
+            Self::Preserve => write!(f, "preserve"),
+        }
+    }
+}
+
+/// One item to send from a walked directory, relative to its root
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WalkEntry {
+    /// A regular file, or the target of a followed symlink
+    File { path: PathBuf, relative: PathBuf },
+    /// A symlink kept as a link under [`SymlinkPolicy::Preserve`]
+    Symlink { relative: PathBuf, target: PathBuf },
+}
+
+impl WalkEntry {
+    /// Path of the entry relative to the walked root
+    pub fn relative(&self) -> &Path {
+        match self {
+            Self::File { relative, .. } | Self::Symlink { relative, .. } => relative,
+        }
+    }
+}
+
+/// List everything under `root` that a directory transfer would send,
+/// sorted by relative path.
+///
+/// Each directory is entered at most once, keyed by its canonical path, so
+/// following symlinks can't loop forever or send a tree twice.
+pub async fn walk_directory(root: &Path, policy: SymlinkPolicy) -> io::Result<Vec<WalkEntry>> {
+    let mut entries = Vec::new();
+    let mut visited = HashSet::from([tokio::fs::canonicalize(root).await?]);
+    let mut pending = vec![(root.to_path_buf(), PathBuf::new())];
+
+    while let Some((dir, relative_dir)) = pending.pop() {
+        let mut listing = tokio::fs::read_dir(&dir).await?;
+        while let Some(entry) = listing.next_entry().await? {
+            let path = entry.path();
+            let relative = relative_dir.join(entry.file_name());
+            let file_type = entry.file_type().await?;
+
+            let is_dir = if file_type.is_symlink() {
+                match policy {
+                    SymlinkPolicy::Skip => {
+                        debug!("Skipping symlink {}", path.display());
+                        continue;
+                    }
+                    SymlinkPolicy::Preserve => {
+                        let target = tokio::fs::read_link(&path).await?;
+                        entries.push(WalkEntry::Symlink { relative, target });
+                        continue;
+                    }
+                    SymlinkPolicy::Follow => match tokio::fs::metadata(&path).await {
+                        Ok(metadata) => metadata.is_dir(),
+                        Err(e) => {
+                            warn!("Skipping broken symlink {}: {}", path.display(), e);
+                            continue;
+                        }
+                    },
+                }
+            } else {
+                file_type.is_dir()
+            };
+
+            if is_dir {
+                if visited.insert(tokio::fs::canonicalize(&path).await?) {
+                    pending.push((path, relative));
+                } else {
+                    debug!("Not revisiting {}", path.display());
+                }
+            } else {
+                entries.push(WalkEntry::File { path, relative });
+            }
+        }
+    }
+
+    entries.sort_by(|a, b| a.relative().cmp(b.relative()));
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::symlink;
+
+    #[tokio::test]
+    async fn test_walk_terminates_on_symlink_loop_under_each_policy() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        std::fs::create_dir_all(root.join("docs/nested")).unwrap();
+        std::fs::write(root.join("top.txt"), b"top").unwrap();
+        std::fs::write(root.join("docs/nested/deep.txt"), b"deep").unwrap();
+        // A link back to the root and a link to a file
+        symlink(root, root.join("docs/nested/loop")).unwrap();
+        symlink(root.join("top.txt"), root.join("docs/alias.txt")).unwrap();
+
+        let relative = |entries: Vec<WalkEntry>| -> Vec<String> {
+            entries
+                .iter()
+                .map(|e| e.relative().display().to_string())
+                .collect()
+        };
+
+        let skipped = walk_directory(root, SymlinkPolicy::Skip).await.unwrap();
+        assert_eq!(relative(skipped), ["docs/nested/deep.txt", "top.txt"]);
+
+        let followed = walk_directory(root, SymlinkPolicy::Follow).await.unwrap();
+        assert_eq!(
+            relative(followed),
+            ["docs/alias.txt", "docs/nested/deep.txt", "top.txt"]
+        );
+
+        let preserved = walk_directory(root, SymlinkPolicy::Preserve).await.unwrap();
+        assert!(preserved.contains(&WalkEntry::Symlink {
+            relative: PathBuf::from("docs/nested/loop"),
+            target: root.to_path_buf(),
+        }));
+        assert_eq!(
+            relative(preserved),
+            [
+                "docs/alias.txt",
+                "docs/nested/deep.txt",
+                "docs/nested/loop",
+                "top.txt"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_symlink_policy_parses_case_insensitively() {
+        assert_eq!("Follow".parse(), Ok(SymlinkPolicy::Follow));
+        assert_eq!(SymlinkPolicy::default(), SymlinkPolicy::Skip);
+        assert!("sometimes".parse::<SymlinkPolicy>().is_err());
+    }
+}
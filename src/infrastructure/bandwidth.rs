@@ -0,0 +1,88 @@
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Token bucket shared by every transfer moving data in one direction.
+///
+/// The bucket holds up to one second of traffic. Callers that take more
+/// than is available go into debt and wait for it to be repaid, so
+/// concurrent transfers are served in the order they asked.
+#[derive(Debug)]
+pub struct BandwidthLimiter {
+    bytes_per_sec: f64,
+    bucket: Mutex<Bucket>,
+}
+
+#[derive(Debug)]
+struct Bucket {
+    tokens: f64,
+    refilled_at: Instant,
+}
+
+impl BandwidthLimiter {
+    pub fn new(bytes_per_sec: u64) -> Self {
+        assert!(bytes_per_sec > 0, "Bandwidth limit must be positive");
+        Self {
+            bytes_per_sec: bytes_per_sec as f64,
+            bucket: Mutex::new(Bucket {
+                tokens: bytes_per_sec as f64,
+                refilled_at: Instant::now(),
+            }),
+        }
+    }
+
+    /// Configured rate in bytes per second
+    pub fn bytes_per_sec(&self) -> u64 {
+        self.bytes_per_sec as u64
+    }
+
+    /// Wait until `bytes` may be sent or received
+    pub async fn acquire(&self, bytes: usize) {
+        let wait = self.reserve(bytes, Instant::now());
+        if !wait.is_zero() {
+            tokio::time::sleep(wait).await;
+        }
+    }
+
+    /// Take `bytes` from the bucket and return how long the caller must wait
+    fn reserve(&self, bytes: usize, now: Instant) -> Duration {
+        let mut bucket = self.bucket.lock().expect("bandwidth bucket poisoned");
+        let refill = now
+            .saturating_duration_since(bucket.refilled_at)
+            .as_secs_f64();
+        bucket.tokens = (bucket.tokens + refill * self.bytes_per_sec).min(self.bytes_per_sec);
+        bucket.refilled_at = now;
+        bucket.tokens -= bytes as f64;
+
+        if bucket.tokens >= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(-bucket.tokens / self.bytes_per_sec)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_burst_is_free_then_callers_queue() {
+        let limiter = BandwidthLimiter::new(1000);
+        let now = Instant::now();
+
+        assert_eq!(limiter.reserve(1000, now), Duration::ZERO);
+        assert_eq!(limiter.reserve(500, now), Duration::from_millis(500));
+        assert_eq!(limiter.reserve(500, now), Duration::from_secs(1));
+    }
+
+    #[test]
+    fn test_bucket_refills_up_to_one_second() {
+        let limiter = BandwidthLimiter::new(1000);
+        let now = Instant::now();
+        limiter.reserve(1000, now);
+
+        let later = now + Duration::from_secs(10);
+        assert_eq!(limiter.reserve(1000, later), Duration::ZERO);
+        assert_eq!(limiter.reserve(100, later), Duration::from_millis(100));
+    }
+}
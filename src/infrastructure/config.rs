@@ -1,36 +1,119 @@
 use crate::core::traits::Configuration;
+use crate::file_transfer::DownloadLayout;
 use serde::{Deserialize, Serialize};
 use std::path::PathBuf;
+use std::time::Duration;
+
+/// Port the control API listens on unless configured otherwise
+pub const DEFAULT_CONTROL_PORT: u16 = 7700;
 
 /// Application configuration structure
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct AppConfig {
     pub data_directory: String,
     pub download_directory: String,
+    /// Subdirectories received files are sorted into: flat, peer or date
+    pub download_layout: DownloadLayout,
     pub default_port: u16,
+    /// Loopback port a running node takes transfer commands on; 0 disables
+    pub control_port: u16,
     pub max_concurrent_transfers: usize,
+    /// Cap on outgoing transfers; falls back to `max_concurrent_transfers`
+    pub max_concurrent_uploads: Option<usize>,
+    /// Cap on incoming transfers; falls back to `max_concurrent_transfers`
+    pub max_concurrent_downloads: Option<usize>,
     pub chunk_size: usize,
+    /// Smallest chunk a sender is expected to use; bounds how many chunks a
+    /// transfer may declare
+    pub min_chunk_size: usize,
+    /// Derive transfer ids from the file and peers so retried sends resume
+    /// the earlier transfer; random ids otherwise
+    pub deterministic_transfer_ids: bool,
+    /// Re-hash received files against the sender's hash before accepting them
+    pub verify_after_transfer: bool,
+    /// How long interrupted transfers wait for their peer after a restart
+    /// before being marked failed
+    pub resume_grace_period_seconds: u64,
+    /// How often a running node publishes a heartbeat event; 0 disables
+    pub heartbeat_interval_seconds: u64,
+    /// Gossip payloads larger than this are dropped on receipt
+    pub max_gossip_message_bytes: usize,
+    /// Topics gossip is accepted on; `None` accepts every topic
+    pub allowed_topics: Option<Vec<String>>,
     pub network: NetworkConfig,
     pub security: SecurityConfig,
 }
 
 /// Network-specific configuration
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct NetworkConfig {
     pub listen_addresses: Vec<String>,
+    /// Multiaddrs with a `/p2p/` peer id to seed the DHT with; `/ip4/`,
+    /// `/ip6/` and `/dnsaddr/` entries are all accepted
     pub bootstrap_peers: Vec<String>,
+    /// Multiaddrs with a `/p2p/` peer id that the node dials at start and
+    /// redials whenever the connection drops
+    pub persistent_peers: Vec<String>,
+    /// Delay before redialing a persistent peer; doubles on each failed
+    /// dial, up to five minutes
+    pub persistent_peer_retry_seconds: u64,
+    /// Bootstrap from the public IPFS nodes and serve DHT queries from
+    /// anyone. Off by default so private swarms stay private.
+    pub join_public_dht: bool,
     pub connection_timeout_seconds: u64,
     pub keep_alive_interval_seconds: u64,
     pub max_connections: usize,
+    /// Established connections allowed to any single peer
+    pub max_connections_per_peer: usize,
+    /// Connections allowed to be mid-handshake in each direction
+    pub max_pending_connections: usize,
+    /// Queue length for connections the OS has accepted but the node hasn't
+    /// picked up yet
+    pub listen_backlog: u32,
+    /// How long a request-response round trip (handshake, chunk, direct
+    /// message) may take before it fails
+    pub request_timeout_seconds: u64,
+    /// Inbound plus outbound request-response streams open at once, per
+    /// protocol
+    pub max_concurrent_streams: usize,
+    /// Inbound file transfer requests handled at once; further ones are
+    /// answered as rate limited
+    pub max_pending_requests: usize,
+    /// Inbound file transfer requests handled at once for any one peer
+    pub max_pending_requests_per_peer: usize,
+    /// Delay before the first Kademlia bootstrap retry; doubles on each failure
+    pub bootstrap_retry_backoff_seconds: u64,
+    pub bootstrap_max_attempts: u32,
+    /// How often to re-bootstrap and refresh the DHT routing table; 0 disables
+    pub dht_refresh_interval_seconds: u64,
+    /// Number of recent gossip message ids remembered to drop duplicates
+    pub gossip_dedup_cache_size: usize,
+    /// Time for a peer quality observation to lose half its weight
+    pub peer_score_half_life_seconds: u64,
+    /// Protocol version advertised through identify
+    pub identify_protocol_version: String,
+    /// Operator label appended to the identify agent version, e.g. a region
+    pub agent_label: Option<String>,
+    /// Node-wide cap on file chunk bytes sent per second; `None` is unlimited
+    pub max_upload_bytes_per_sec: Option<u64>,
+    /// Node-wide cap on file chunk bytes received per second; `None` is unlimited
+    pub max_download_bytes_per_sec: Option<u64>,
 }
 
 /// Security-specific configuration
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct SecurityConfig {
     pub enable_encryption: bool,
     pub key_rotation_interval_hours: u64,
     pub max_file_size_mb: u64,
     pub allowed_file_extensions: Vec<String>,
+    /// Sniffed content types accepted; empty accepts any not blocked
+    pub allowed_content_types: Vec<String>,
+    /// Sniffed content types refused whatever the file is named
+    pub blocked_content_types: Vec<String>,
 }
 
 impl Default for AppConfig {
@@ -41,33 +124,93 @@ impl Default for AppConfig {
         Self {
             data_directory: data_dir.clone(),
             download_directory: format!("{}/downloads", data_dir),
+            download_layout: DownloadLayout::default(),
             default_port: 8000,
+            control_port: DEFAULT_CONTROL_PORT,
             max_concurrent_transfers: 10,
+            max_concurrent_uploads: None,
+            max_concurrent_downloads: None,
             chunk_size: 1024 * 1024, // 1MB
-            network: NetworkConfig {
-                listen_addresses: vec![
-                    "/ip4/0.0.0.0/tcp/0".to_string(),
-                    "/ip6/::/tcp/0".to_string(),
-                ],
-                bootstrap_peers: vec![],
-                connection_timeout_seconds: 30,
-                keep_alive_interval_seconds: 60,
-                max_connections: 100,
-            },
-            security: SecurityConfig {
-                enable_encryption: true,
-                key_rotation_interval_hours: 24,
-                max_file_size_mb: 1024, // 1GB
-                allowed_file_extensions: vec![
-                    "txt".to_string(),
-                    "pdf".to_string(),
-                    "jpg".to_string(),
-                    "png".to_string(),
-                    "doc".to_string(),
-                    "docx".to_string(),
-                    "zip".to_string(),
-                ],
-            },
+            min_chunk_size: 1024,
+            deterministic_transfer_ids: false,
+            verify_after_transfer: true,
+            resume_grace_period_seconds: 60,
+            heartbeat_interval_seconds: 60,
+            max_gossip_message_bytes: 64 * 1024,
+            allowed_topics: None,
+            network: NetworkConfig::default(),
+            security: SecurityConfig::default(),
+        }
+    }
+}
+
+impl Default for NetworkConfig {
+    fn default() -> Self {
+        Self {
+            listen_addresses: vec![
+                "/ip4/0.0.0.0/tcp/0".to_string(),
+                "/ip6/::/tcp/0".to_string(),
+            ],
+            bootstrap_peers: vec![],
+            persistent_peers: vec![],
+            persistent_peer_retry_seconds: 5,
+            join_public_dht: false,
+            connection_timeout_seconds: 30,
+            keep_alive_interval_seconds: 60,
+            max_connections: 100,
+            max_connections_per_peer: 2,
+            max_pending_connections: 32,
+            listen_backlog: 1024,
+            request_timeout_seconds: 10,
+            max_concurrent_streams: 100,
+            max_pending_requests: 256,
+            max_pending_requests_per_peer: 32,
+            bootstrap_retry_backoff_seconds: 5,
+            bootstrap_max_attempts: 6,
+            dht_refresh_interval_seconds: 300,
+            gossip_dedup_cache_size: 1024,
+            peer_score_half_life_seconds: 600,
+            identify_protocol_version: "/cipherstream/1.0.0".to_string(),
+            agent_label: None,
+            max_upload_bytes_per_sec: None,
+            max_download_bytes_per_sec: None,
+        }
+    }
+}
+
+impl Default for SecurityConfig {
+    fn default() -> Self {
+        Self {
+            enable_encryption: true,
+            key_rotation_interval_hours: 24,
+            max_file_size_mb: 1024, // 1GB
+            allowed_file_extensions: vec![
+                "txt".to_string(),
+                "pdf".to_string(),
+                "jpg".to_string(),
+                "png".to_string(),
+                "doc".to_string(),
+                "docx".to_string(),
+                "zip".to_string(),
+            ],
+            allowed_content_types: Vec::new(),
+            blocked_content_types: vec![
+                "application/x-executable".to_string(),
+                "application/x-msdownload".to_string(),
+                "application/x-mach-binary".to_string(),
+                "text/x-shellscript".to_string(),
+            ],
+        }
+    }
+}
+
+impl NetworkConfig {
+    /// Identify agent version: the crate version plus the operator label, if any
+    pub fn agent_version(&self) -> String {
+        let version = format!("cipherstream/{}", env!("CARGO_PKG_VERSION"));
+        match &self.agent_label {
+            Some(label) => format!("{} ({})", version, label),
+            None => version,
         }
     }
 }
@@ -101,6 +244,41 @@ impl AppConfig {
         PathBuf::from(&self.download_directory)
     }
 
+    /// Effective limit on concurrent outgoing transfers
+    pub fn upload_limit(&self) -> usize {
+        self.max_concurrent_uploads
+            .unwrap_or(self.max_concurrent_transfers)
+    }
+
+    /// Effective limit on concurrent incoming transfers
+    pub fn download_limit(&self) -> usize {
+        self.max_concurrent_downloads
+            .unwrap_or(self.max_concurrent_transfers)
+    }
+
+    /// Largest file that may be sent or received, in bytes
+    pub fn max_file_size(&self) -> u64 {
+        self.security.max_file_size_mb.saturating_mul(1024 * 1024)
+    }
+
+    /// Most chunks a transfer may declare: the largest allowed file split
+    /// into the smallest expected chunks
+    pub fn max_total_chunks(&self) -> u64 {
+        self.max_file_size()
+            .div_ceil(self.min_chunk_size.max(1) as u64)
+    }
+
+    /// Grace period granted to interrupted transfers on startup
+    pub fn resume_grace_period(&self) -> Duration {
+        Duration::from_secs(self.resume_grace_period_seconds)
+    }
+
+    /// Interval between heartbeat events, if heartbeats are enabled
+    pub fn heartbeat_interval(&self) -> Option<Duration> {
+        (self.heartbeat_interval_seconds > 0)
+            .then(|| Duration::from_secs(self.heartbeat_interval_seconds))
+    }
+
     /// Ensure all directories exist
     pub fn ensure_directories(&self) -> Result<(), std::io::Error> {
         std::fs::create_dir_all(&self.data_directory)?;
@@ -114,10 +292,18 @@ impl AppConfig {
             return Err("Chunk size must be greater than 0".into());
         }
 
+        if self.min_chunk_size == 0 || self.min_chunk_size > self.chunk_size {
+            return Err("Min chunk size must be between 1 and the chunk size".into());
+        }
+
         if self.max_concurrent_transfers == 0 {
             return Err("Max concurrent transfers must be greater than 0".into());
         }
 
+        if self.upload_limit() == 0 || self.download_limit() == 0 {
+            return Err("Upload and download limits must be greater than 0".into());
+        }
+
         if self.default_port == 0 {
             return Err("Default port must be greater than 0".into());
         }
@@ -127,6 +313,33 @@ impl AppConfig {
             return Err("Max connections must be greater than 0".into());
         }
 
+        if self.network.max_connections_per_peer == 0 || self.network.max_pending_connections == 0 {
+            return Err("Per-peer and pending connection limits must be greater than 0".into());
+        }
+
+        if self.network.listen_backlog == 0 {
+            return Err("Listen backlog must be greater than 0".into());
+        }
+
+        if self.network.persistent_peer_retry_seconds == 0 {
+            return Err("Persistent peer retry delay must be greater than 0".into());
+        }
+
+        if self.network.request_timeout_seconds == 0 || self.network.max_concurrent_streams == 0 {
+            return Err("Request timeout and concurrent streams must be greater than 0".into());
+        }
+
+        if self.network.max_pending_requests == 0 || self.network.max_pending_requests_per_peer == 0
+        {
+            return Err("Pending request limits must be greater than 0".into());
+        }
+
+        if self.network.max_upload_bytes_per_sec == Some(0)
+            || self.network.max_download_bytes_per_sec == Some(0)
+        {
+            return Err("Bandwidth limits must be greater than 0".into());
+        }
+
         Ok(())
     }
 }
@@ -148,6 +361,14 @@ impl Configuration for AppConfig {
         self.max_concurrent_transfers
     }
 
+    fn get_max_concurrent_uploads(&self) -> usize {
+        self.upload_limit()
+    }
+
+    fn get_max_concurrent_downloads(&self) -> usize {
+        self.download_limit()
+    }
+
     fn get_chunk_size(&self) -> usize {
         self.chunk_size
     }
@@ -172,4 +393,44 @@ mod tests {
         let json = serde_json::to_string(&config).expect("Should serialize");
         let _deserialized: AppConfig = serde_json::from_str(&json).expect("Should deserialize");
     }
+
+    #[test]
+    fn test_partial_config_uses_defaults() {
+        let config: AppConfig =
+            serde_json::from_str(r#"{"default_port": 9000}"#).expect("Should deserialize");
+        assert_eq!(config.default_port, 9000);
+        assert_eq!(
+            config.resume_grace_period_seconds,
+            AppConfig::default().resume_grace_period_seconds
+        );
+        config.validate().expect("Partial config should be valid");
+    }
+
+    #[test]
+    fn test_transfer_limits_fall_back_to_combined_limit() {
+        let mut config = AppConfig {
+            max_concurrent_transfers: 4,
+            ..AppConfig::default()
+        };
+        assert_eq!(config.upload_limit(), 4);
+        assert_eq!(config.download_limit(), 4);
+
+        config.max_concurrent_uploads = Some(1);
+        assert_eq!(config.upload_limit(), 1);
+        assert_eq!(config.download_limit(), 4);
+
+        config.max_concurrent_downloads = Some(0);
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_max_total_chunks_splits_largest_file_into_smallest_chunks() {
+        let mut config = AppConfig::default();
+        config.security.max_file_size_mb = 10;
+        config.min_chunk_size = 4096;
+        assert_eq!(config.max_total_chunks(), 2560);
+
+        config.min_chunk_size = config.chunk_size + 1;
+        assert!(config.validate().is_err());
+    }
 }
@@ -1,36 +1,130 @@
 use crate::core::{
-    domain::DomainEvent,
+    domain::{DomainEvent, EventEnvelope},
     traits::{DomainResult, EventHandler, EventPublisher},
 };
+use crate::infrastructure::services::UtilityService;
 use async_trait::async_trait;
 use futures::future::join_all;
-use std::sync::Arc;
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
 use tokio::sync::{RwLock, mpsc};
-use tracing::{error, info};
+use tracing::{error, info, warn};
+
+/// Hands an event to each handler concurrently, logging handler errors.
+/// Takes a snapshot of the handlers so no lock is held across the awaits.
+async fn dispatch(handlers: Vec<Arc<dyn EventHandler>>, envelope: &EventEnvelope) {
+    let futures = handlers.into_iter().map(|h| {
+        let envelope = envelope.clone();
+        async move { h.handle_envelope(envelope).await }
+    });
+    for res in join_all(futures).await {
+        if let Err(e) = res {
+            error!("Error in event handler: {}", e);
+        }
+    }
+}
+
+/// Events kept in memory by default before the oldest are evicted
+pub const DEFAULT_EVENT_LOG_CAPACITY: usize = 10_000;
 
 /// In-memory event publisher for testing and development
 pub struct InMemoryEventPublisher {
     handlers: Arc<RwLock<Vec<Arc<dyn EventHandler>>>>,
-    event_log: Arc<RwLock<Vec<DomainEvent>>>,
+    event_log: Arc<RwLock<VecDeque<EventEnvelope>>>,
+    log_capacity: usize,
+    next_seq: AtomicU64,
 }
 
 impl InMemoryEventPublisher {
     pub fn new() -> Self {
         Self {
             handlers: Arc::new(RwLock::new(Vec::new())),
-            event_log: Arc::new(RwLock::new(Vec::new())),
+            event_log: Arc::new(RwLock::new(VecDeque::new())),
+            log_capacity: DEFAULT_EVENT_LOG_CAPACITY,
+            next_seq: AtomicU64::new(1),
         }
     }
 
+    /// Keep at most `capacity` events in the log, evicting the oldest
+    pub fn with_log_capacity(mut self, capacity: usize) -> Self {
+        self.log_capacity = capacity;
+        self
+    }
+
+    /// Number of events currently held in the log
+    pub async fn log_len(&self) -> usize {
+        self.event_log.read().await.len()
+    }
+
     /// Get all events that have been published (for testing)
     pub async fn get_events(&self) -> Vec<DomainEvent> {
-        self.event_log.read().await.clone()
+        let log = self.event_log.read().await;
+        log.iter().map(|envelope| envelope.event.clone()).collect()
+    }
+
+    /// Get all published events with their sequence numbers and timestamps
+    pub async fn get_envelopes(&self) -> Vec<EventEnvelope> {
+        self.event_log.read().await.iter().cloned().collect()
     }
 
-    /// Clear the event log
+    /// Clear the event log. Sequence numbers keep counting.
     pub async fn clear_events(&self) {
         self.event_log.write().await.clear();
     }
+
+    /// Subscribe `handler` after replaying every logged event with a
+    /// sequence number above `seq`. Sequence numbers start at 1, so 0
+    /// replays the whole log; events already evicted from it are not
+    /// replayed. Live events wait until the replay is done.
+    pub async fn subscribe_from(
+        &self,
+        seq: u64,
+        handler: Box<dyn EventHandler>,
+    ) -> DomainResult<()> {
+        let handler = Arc::new(CatchUpHandler {
+            inner: handler,
+            replaying: tokio::sync::Mutex::new(()),
+        });
+        let replaying = handler.replaying.lock().await;
+
+        let backlog: Vec<EventEnvelope> = {
+            let log = self.event_log.read().await;
+            self.handlers.write().await.push(handler.clone());
+            log.iter()
+                .filter(|envelope| envelope.seq > seq)
+                .cloned()
+                .collect()
+        };
+        for envelope in backlog {
+            if let Err(e) = handler.inner.handle_envelope(envelope).await {
+                error!("Error in event handler during replay: {}", e);
+            }
+        }
+
+        drop(replaying);
+        Ok(())
+    }
+}
+
+/// Holds back live events from a handler until its replay has finished
+struct CatchUpHandler {
+    inner: Box<dyn EventHandler>,
+    replaying: tokio::sync::Mutex<()>,
+}
+
+#[async_trait]
+impl EventHandler for CatchUpHandler {
+    async fn handle_event(&self, event: DomainEvent) -> DomainResult<()> {
+        let _replayed = self.replaying.lock().await;
+        self.inner.handle_event(event).await
+    }
+
+    async fn handle_envelope(&self, envelope: EventEnvelope) -> DomainResult<()> {
+        let _replayed = self.replaying.lock().await;
+        self.inner.handle_envelope(envelope).await
+    }
 }
 
 impl Default for InMemoryEventPublisher {
@@ -42,25 +136,24 @@ impl Default for InMemoryEventPublisher {
 #[async_trait]
 impl EventPublisher for InMemoryEventPublisher {
     async fn publish(&self, event: DomainEvent) -> DomainResult<()> {
-        // Log the event
-        self.event_log.write().await.push(event.clone());
-
-        // Notify all handlers. Clone Arcs first to avoid holding the lock across await.
-        let handlers_snapshot = {
-            let handlers = self.handlers.read().await;
-            handlers.clone()
-        };
-        let futures = handlers_snapshot.into_iter().map(|h| {
-            let ev = event.clone();
-            async move { h.handle_event(ev).await }
-        });
-        let results = join_all(futures).await;
-        for res in results {
-            if let Err(e) = res {
-                error!("Error in event handler: {}", e);
+        // Number the event and pick its handlers while holding the log, so
+        // seq matches log order and `subscribe_from` sees each event exactly
+        // once, either in the log or live
+        let (envelope, handlers) = {
+            let mut log = self.event_log.write().await;
+            let envelope = EventEnvelope {
+                seq: self.next_seq.fetch_add(1, Ordering::Relaxed),
+                at: SystemTime::now(),
+                event,
+            };
+            log.push_back(envelope.clone());
+            if log.len() > self.log_capacity {
+                log.pop_front();
             }
-        }
+            (envelope, self.handlers.read().await.clone())
+        };
 
+        dispatch(handlers, &envelope).await;
         Ok(())
     }
 
@@ -78,16 +171,18 @@ impl EventPublisher for InMemoryEventPublisher {
 
 /// Async event publisher using channels for better performance
 pub struct ChannelEventPublisher {
-    event_tx: mpsc::UnboundedSender<DomainEvent>,
+    /// Next sequence number, locked across the send so the channel
+    /// delivers events in sequence order
+    event_tx: Mutex<(u64, mpsc::UnboundedSender<EventEnvelope>)>,
     handlers: Arc<RwLock<Vec<Arc<dyn EventHandler>>>>,
 }
 
 impl ChannelEventPublisher {
-    pub fn new() -> (Self, mpsc::UnboundedReceiver<DomainEvent>) {
+    pub fn new() -> (Self, mpsc::UnboundedReceiver<EventEnvelope>) {
         let (event_tx, event_rx) = mpsc::unbounded_channel();
 
         let publisher = Self {
-            event_tx,
+            event_tx: Mutex::new((1, event_tx)),
             handlers: Arc::new(RwLock::new(Vec::new())),
         };
 
@@ -96,24 +191,12 @@ impl ChannelEventPublisher {
 
     /// Start the event processing loop
     pub async fn start_processing(
-        mut event_rx: mpsc::UnboundedReceiver<DomainEvent>,
+        mut event_rx: mpsc::UnboundedReceiver<EventEnvelope>,
         handlers: Arc<RwLock<Vec<Arc<dyn EventHandler>>>>,
     ) {
-        while let Some(event) = event_rx.recv().await {
-            let snapshot = {
-                let handlers_guard = handlers.read().await;
-                handlers_guard.clone()
-            };
-            let futures = snapshot.into_iter().map(|h| {
-                let ev = event.clone();
-                async move { h.handle_event(ev).await }
-            });
-            let results = join_all(futures).await;
-            for res in results {
-                if let Err(e) = res {
-                    error!("Error in event handler: {}", e);
-                }
-            }
+        while let Some(envelope) = event_rx.recv().await {
+            let snapshot = handlers.read().await.clone();
+            dispatch(snapshot, &envelope).await;
         }
     }
 }
@@ -121,9 +204,17 @@ impl ChannelEventPublisher {
 #[async_trait]
 impl EventPublisher for ChannelEventPublisher {
     async fn publish(&self, event: DomainEvent) -> DomainResult<()> {
-        self.event_tx
-            .send(event)
-            .map_err(|e| format!("Failed to publish event: {}", e).into())
+        let mut guard = self.event_tx.lock().unwrap_or_else(|e| e.into_inner());
+        let (next_seq, event_tx) = &mut *guard;
+        event_tx
+            .send(EventEnvelope {
+                seq: *next_seq,
+                at: SystemTime::now(),
+                event,
+            })
+            .map_err(|e| format!("Failed to publish event: {}", e))?;
+        *next_seq += 1;
+        Ok(())
     }
 
     fn subscribe(&self, handler: Box<dyn EventHandler>) -> DomainResult<()> {
@@ -157,6 +248,15 @@ impl EventHandler for LoggingEventHandler {
             DomainEvent::PeerDisconnected { peer_id } => {
                 info!("Peer disconnected: {}", peer_id.as_str());
             }
+            DomainEvent::PeerIdentified {
+                peer_id, protocols, ..
+            } => {
+                info!(
+                    "Peer identified: {} supporting {} protocols",
+                    peer_id.as_str(),
+                    protocols.len()
+                );
+            }
             DomainEvent::TransferStarted { transfer } => {
                 info!(
                     "Transfer started: {} -> {}",
@@ -171,9 +271,21 @@ impl EventHandler for LoggingEventHandler {
                 info!(
                     "Transfer progress {}: {:.2}%",
                     transfer_id.as_str(),
-                    progress.percentage
+                    progress.percentage()
                 );
             }
+            DomainEvent::TransferPaused {
+                transfer_id,
+                reason,
+            } => {
+                warn!("Transfer paused {}: {}", transfer_id.as_str(), reason);
+            }
+            DomainEvent::TransferResumed { transfer_id } => {
+                info!("Transfer resumed: {}", transfer_id.as_str());
+            }
+            DomainEvent::TransferVerifying { transfer_id } => {
+                info!("Transfer verifying: {}", transfer_id.as_str());
+            }
             DomainEvent::TransferCompleted { transfer_id } => {
                 info!("Transfer completed: {}", transfer_id.as_str());
             }
@@ -190,6 +302,18 @@ impl EventHandler for LoggingEventHandler {
                     transfer_id.as_str()
                 );
             }
+            DomainEvent::Heartbeat {
+                uptime,
+                connected_peers,
+                active_transfers,
+            } => {
+                info!(
+                    "Heartbeat: up {}, {} peers connected, {} active transfers",
+                    UtilityService::format_duration(*uptime),
+                    connected_peers,
+                    active_transfers
+                );
+            }
         }
         Ok(())
     }
@@ -220,6 +344,107 @@ mod tests {
         }
     }
 
+    struct SeqRecorder(Arc<Mutex<Vec<u64>>>);
+
+    #[async_trait]
+    impl EventHandler for SeqRecorder {
+        async fn handle_event(&self, _event: DomainEvent) -> DomainResult<()> {
+            Ok(())
+        }
+
+        async fn handle_envelope(&self, envelope: EventEnvelope) -> DomainResult<()> {
+            self.0.lock().unwrap().push(envelope.seq);
+            Ok(())
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_event_sequence_numbers_increase_across_publishes() {
+        let publisher = Arc::new(InMemoryEventPublisher::new());
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        publisher
+            .subscribe(Box::new(SeqRecorder(seen.clone())))
+            .unwrap();
+
+        let publishes = (0..20).map(|i| {
+            let publisher = publisher.clone();
+            tokio::spawn(async move {
+                let peer_id = PeerId::new(format!("peer-{}", i));
+                publisher
+                    .publish(DomainEvent::PeerConnected { peer_id })
+                    .await
+            })
+        });
+        for publish in publishes {
+            publish.await.unwrap().unwrap();
+        }
+        let envelopes = publisher.get_envelopes().await;
+        assert!(envelopes.windows(2).all(|w| w[1].seq == w[0].seq + 1));
+        assert!(envelopes.windows(2).all(|w| w[0].at <= w[1].at));
+        publisher.clear_events().await;
+        let peer_id = PeerId::new("late".to_string());
+        publisher
+            .publish(DomainEvent::PeerConnected { peer_id })
+            .await
+            .unwrap();
+
+        let envelopes = publisher.get_envelopes().await;
+        assert_eq!(envelopes.len(), 1);
+        assert_eq!(envelopes[0].seq, 21, "clearing the log keeps counting");
+
+        let mut seen = seen.lock().unwrap().clone();
+        assert_eq!(seen.len(), 21);
+        seen.sort_unstable();
+        assert_eq!(seen, (1..=21).collect::<Vec<_>>());
+    }
+
+    #[tokio::test]
+    async fn test_subscribe_from_replays_logged_events_then_goes_live() {
+        let publisher = InMemoryEventPublisher::new();
+        let connect = |name: &str| DomainEvent::PeerConnected {
+            peer_id: PeerId::new(name.to_string()),
+        };
+        for name in ["a", "b", "c"] {
+            publisher.publish(connect(name)).await.unwrap();
+        }
+
+        let everything = Arc::new(Mutex::new(Vec::new()));
+        publisher
+            .subscribe_from(0, Box::new(SeqRecorder(everything.clone())))
+            .await
+            .unwrap();
+        let recent = Arc::new(Mutex::new(Vec::new()));
+        publisher
+            .subscribe_from(2, Box::new(SeqRecorder(recent.clone())))
+            .await
+            .unwrap();
+        publisher.publish(connect("d")).await.unwrap();
+
+        assert_eq!(*everything.lock().unwrap(), vec![1, 2, 3, 4]);
+        assert_eq!(*recent.lock().unwrap(), vec![3, 4]);
+    }
+
+    #[tokio::test]
+    async fn test_event_log_evicts_oldest_beyond_capacity() {
+        let publisher = InMemoryEventPublisher::new().with_log_capacity(5);
+        for i in 0..12 {
+            let peer_id = PeerId::new(format!("peer-{}", i));
+            publisher
+                .publish(DomainEvent::PeerConnected { peer_id })
+                .await
+                .unwrap();
+        }
+
+        assert_eq!(publisher.log_len().await, 5);
+        let seqs: Vec<u64> = publisher
+            .get_envelopes()
+            .await
+            .iter()
+            .map(|envelope| envelope.seq)
+            .collect();
+        assert_eq!(seqs, vec![8, 9, 10, 11, 12]);
+    }
+
     #[tokio::test]
     async fn test_channel_event_publisher() {
         let (publisher, mut event_rx) = ChannelEventPublisher::new();
@@ -229,8 +454,9 @@ mod tests {
 
         publisher.publish(event.clone()).await.unwrap();
 
-        let received_event = event_rx.recv().await.unwrap();
-        match received_event {
+        let received = event_rx.recv().await.unwrap();
+        assert_eq!(received.seq, 1);
+        match received.event {
             DomainEvent::PeerConnected { peer_id } => {
                 assert_eq!(peer_id.as_str(), "test-peer-id");
             }
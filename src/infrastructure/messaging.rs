@@ -0,0 +1,110 @@
+use async_trait::async_trait;
+use futures::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use libp2p::StreamProtocol;
+use libp2p::request_response::Codec;
+use std::io;
+
+/// Protocol for point-to-point messages between peers
+pub const DIRECT_MESSAGE_PROTOCOL: StreamProtocol =
+    StreamProtocol::new("/cipherstream/direct-message/1.0.0");
+
+/// Largest direct message accepted from a peer
+pub const MAX_DIRECT_MESSAGE_SIZE: usize = 1024 * 1024; // 1 MiB
+
+/// Byte sent back once a direct message has been received
+const ACK: u8 = 1;
+
+/// Codec for direct messages: a length-prefixed payload answered by a
+/// single acknowledgement byte
+#[derive(Default, Debug, Clone)]
+pub struct DirectMessageCodec;
+
+#[async_trait]
+impl Codec for DirectMessageCodec {
+    type Protocol = StreamProtocol;
+    type Request = Vec<u8>;
+    type Response = ();
+
+    async fn read_request<T>(
+        &mut self,
+        _protocol: &Self::Protocol,
+        io: &mut T,
+    ) -> io::Result<Self::Request>
+    where
+        T: AsyncRead + Unpin + Send,
+    {
+        let mut len_bytes = [0u8; 4];
+        io.read_exact(&mut len_bytes).await?;
+        let len = u32::from_be_bytes(len_bytes) as usize;
+        if len > MAX_DIRECT_MESSAGE_SIZE {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "direct message too large: {} > {}",
+                    len, MAX_DIRECT_MESSAGE_SIZE
+                ),
+            ));
+        }
+
+        let mut data = vec![0u8; len];
+        io.read_exact(&mut data).await?;
+        Ok(data)
+    }
+
+    async fn read_response<T>(
+        &mut self,
+        _protocol: &Self::Protocol,
+        io: &mut T,
+    ) -> io::Result<Self::Response>
+    where
+        T: AsyncRead + Unpin + Send,
+    {
+        let mut ack = [0u8; 1];
+        io.read_exact(&mut ack).await?;
+        if ack[0] != ACK {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("unexpected direct message acknowledgement: {}", ack[0]),
+            ));
+        }
+        Ok(())
+    }
+
+    async fn write_request<T>(
+        &mut self,
+        _protocol: &Self::Protocol,
+        io: &mut T,
+        data: Self::Request,
+    ) -> io::Result<()>
+    where
+        T: AsyncWrite + Unpin + Send,
+    {
+        if data.len() > MAX_DIRECT_MESSAGE_SIZE {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "direct message too large: {} > {}",
+                    data.len(),
+                    MAX_DIRECT_MESSAGE_SIZE
+                ),
+            ));
+        }
+
+        io.write_all(&(data.len() as u32).to_be_bytes()).await?;
+        io.write_all(&data).await?;
+        io.flush().await
+    }
+
+    async fn write_response<T>(
+        &mut self,
+        _protocol: &Self::Protocol,
+        io: &mut T,
+        _response: Self::Response,
+    ) -> io::Result<()>
+    where
+        T: AsyncWrite + Unpin + Send,
+    {
+        io.write_all(&[ACK]).await?;
+        io.flush().await
+    }
+}
@@ -1,11 +1,19 @@
+pub mod bandwidth;
 pub mod config;
 pub mod events;
+pub mod messaging;
 pub mod network;
 pub mod repositories;
+pub mod scoring;
 pub mod services;
 
+pub use bandwidth::BandwidthLimiter;
 pub use config::*;
 pub use events::*;
-pub use network::{LibP2pNetworkService, SimpleNetworkService};
+pub use network::{
+    LibP2pNetworkService, NetworkError, NetworkStatus, Reachability, SimpleNetworkService,
+    TopicRegistry,
+};
 pub use repositories::*;
+pub use scoring::{PeerScore, PeerScores};
 pub use services::*;
@@ -1,40 +1,165 @@
 use crate::core::{
-    domain::{DomainEvent, PeerId as DomainPeerId},
+    domain::{DomainEvent, PeerId as DomainPeerId, TransferProgress},
     traits::{DomainResult, EventPublisher, NetworkService},
 };
 use crate::file_transfer::{
-    FileTransferCodec, FileTransferProtocol, ProtocolRequest, ProtocolResponse,
+    FileTransferCodec, FileTransferHandler, FileTransferProtocol, ProtocolRequest,
+    ProtocolResponse, TransferTransport,
 };
+use crate::infrastructure::bandwidth::BandwidthLimiter;
 use crate::infrastructure::config::AppConfig;
+use crate::infrastructure::messaging::{DIRECT_MESSAGE_PROTOCOL, DirectMessageCodec};
+use crate::infrastructure::scoring::PeerScores;
 use async_trait::async_trait;
 use futures::stream::StreamExt;
 use libp2p::{
-    Multiaddr, PeerId, Swarm, SwarmBuilder, gossipsub, identify, identity, kad, mdns, noise,
-    request_response,
-    swarm::{NetworkBehaviour, SwarmEvent},
+    Multiaddr, PeerId, Swarm, SwarmBuilder, allow_block_list, connection_limits, gossipsub,
+    identify, identity, noise, ping,
+    request_response::{self, OutboundRequestId, ResponseChannel},
+    swarm::{ConnectionId, NetworkBehaviour, SwarmEvent, dial_opts::DialOpts},
     tcp, yamux,
 };
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::ops::RangeInclusive;
 use std::sync::Arc;
-use std::time::Duration;
-use tokio::sync::{RwLock, mpsc};
+use std::time::{Duration, SystemTime};
+use tokio::sync::{RwLock, mpsc, oneshot};
+use tokio::time::Instant;
 use tracing::{debug, error, info, warn};
 
+#[cfg(feature = "dht")]
+use libp2p::kad;
+#[cfg(not(all(feature = "dht", feature = "mdns")))]
+use libp2p::swarm::dummy;
+#[cfg(feature = "mdns")]
+use libp2p::{
+    core::transport::ListenerId,
+    mdns,
+    swarm::{FromSwarm, NewListenAddr, behaviour::toggle::Toggle},
+};
+
+/// Kademlia DHT, or a no-op stand-in when built without the `dht` feature
+#[cfg(feature = "dht")]
+pub type KademliaBehaviour = kad::Behaviour<kad::store::MemoryStore>;
+#[cfg(not(feature = "dht"))]
+pub type KademliaBehaviour = dummy::Behaviour;
+
+/// mDNS discovery, which can be switched off at runtime, or a no-op
+/// stand-in when built without the `mdns` feature
+#[cfg(feature = "mdns")]
+pub type MdnsBehaviour = Toggle<mdns::tokio::Behaviour>;
+#[cfg(not(feature = "mdns"))]
+pub type MdnsBehaviour = dummy::Behaviour;
+
 /// Network behavior combining all libp2p protocols including advanced features
 #[derive(NetworkBehaviour)]
 pub struct CipherStreamBehaviour {
     pub gossipsub: gossipsub::Behaviour,
     pub identify: identify::Behaviour,
     pub request_response: request_response::Behaviour<FileTransferCodec>,
-    pub mdns: mdns::tokio::Behaviour,
-    pub kademlia: kad::Behaviour<kad::store::MemoryStore>,
+    pub direct_message: request_response::Behaviour<DirectMessageCodec>,
+    pub mdns: MdnsBehaviour,
+    pub kademlia: KademliaBehaviour,
+    pub ping: ping::Behaviour,
+    pub limits: connection_limits::Behaviour,
+    /// Banned peers, refused on every connection attempt
+    pub blocked: allow_block_list::Behaviour<allow_block_list::BlockedPeers>,
+}
+
+/// Network failures callers may want to tell apart from generic errors
+#[derive(Debug, thiserror::Error)]
+pub enum NetworkError {
+    /// No connected peer is subscribed to the topic yet. Benign while the
+    /// gossip mesh is still forming; retrying later may succeed.
+    #[error("No peers are subscribed to topic {topic}")]
+    NoGossipPeers { topic: String },
+}
+
+/// Whether the node is known to be reachable from outside its network
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Reachability {
+    /// At least one external address has been confirmed
+    Public,
+    /// No external address has been confirmed yet
+    Unknown,
+}
+
+/// Snapshot of how the node is attached to the network
+#[derive(Debug, Clone)]
+pub struct NetworkStatus {
+    pub local_peer_id: PeerId,
+    pub listen_addresses: Vec<Multiaddr>,
+    /// Transports carrying the listeners, e.g. `tcp`, sorted
+    pub transports: Vec<String>,
+    /// Listen addresses reserved through a circuit relay
+    pub relay_reservations: Vec<Multiaddr>,
+    /// Addresses other peers have confirmed they can reach us on
+    pub external_addresses: Vec<Multiaddr>,
+    pub reachability: Reachability,
+    pub connected_peers: usize,
+    /// Whether the node is discovering and advertising itself over mDNS
+    pub mdns_enabled: bool,
+}
+
+impl NetworkStatus {
+    fn new(swarm: &Swarm<CipherStreamBehaviour>, connected_peers: usize) -> Self {
+        use libp2p::multiaddr::Protocol;
+
+        let listen_addresses: Vec<Multiaddr> = swarm.listeners().cloned().collect();
+        let mut transports: Vec<String> = listen_addresses
+            .iter()
+            .flat_map(|addr| addr.iter())
+            .filter_map(|protocol| match protocol {
+                Protocol::Tcp(_) => Some("tcp"),
+                Protocol::Udp(_) => Some("udp"),
+                Protocol::QuicV1 => Some("quic-v1"),
+                Protocol::Ws(_) => Some("ws"),
+                Protocol::WebRTCDirect => Some("webrtc-direct"),
+                _ => None,
+            })
+            .map(String::from)
+            .collect();
+        transports.sort();
+        transports.dedup();
+        let relay_reservations = listen_addresses
+            .iter()
+            .filter(|addr| addr.iter().any(|p| matches!(p, Protocol::P2pCircuit)))
+            .cloned()
+            .collect();
+        let external_addresses: Vec<Multiaddr> = swarm.external_addresses().cloned().collect();
+        let reachability = if external_addresses.is_empty() {
+            Reachability::Unknown
+        } else {
+            Reachability::Public
+        };
+
+        Self {
+            local_peer_id: *swarm.local_peer_id(),
+            listen_addresses,
+            transports,
+            relay_reservations,
+            external_addresses,
+            reachability,
+            connected_peers,
+            mdns_enabled: mdns_enabled(swarm),
+        }
+    }
 }
 
 /// Network events internal to the service
 #[derive(Debug)]
 pub enum NetworkEvent {
+    /// First connection to the peer opened
     PeerConnected(PeerId),
+    /// Last connection to the peer closed
     PeerDisconnected(PeerId),
+    /// mDNS or the DHT learned of the peer; it may not be connected
+    PeerDiscovered(PeerId),
+    PeerIdentified {
+        peer: PeerId,
+        agent_version: String,
+        protocol_version: String,
+    },
     FileTransferRequest {
         from: PeerId,
         request: ProtocolRequest,
@@ -48,21 +173,173 @@ pub enum NetworkEvent {
         topic: String,
         data: Vec<u8>,
     },
+    DirectMessage {
+        from: PeerId,
+        data: Vec<u8>,
+    },
+    /// A chunk of a transfer from `from` was written by the file handler
+    TransferProgress {
+        from: PeerId,
+        transfer_id: String,
+        progress: TransferProgress,
+    },
+}
+
+/// Serializable projection of a [`NetworkEvent`] for API consumers and
+/// `--json` output, with field names that stay stable as the event enum
+/// evolves
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct NetworkEventDto {
+    #[serde(rename = "type")]
+    pub kind: String,
+    /// The peer the event concerns, as a base58 peer id
+    pub peer: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub topic: Option<String>,
+    /// Payload size for events that carry data
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bytes: Option<usize>,
+}
+
+impl NetworkEventDto {
+    fn new(kind: &str, peer: &PeerId) -> Self {
+        Self {
+            kind: kind.to_string(),
+            peer: peer.to_string(),
+            topic: None,
+            bytes: None,
+        }
+    }
+}
+
+impl From<&NetworkEvent> for NetworkEventDto {
+    fn from(event: &NetworkEvent) -> Self {
+        match event {
+            NetworkEvent::PeerConnected(peer) => Self::new("peer_connected", peer),
+            NetworkEvent::PeerDisconnected(peer) => Self::new("peer_disconnected", peer),
+            NetworkEvent::PeerDiscovered(peer) => Self::new("peer_discovered", peer),
+            NetworkEvent::PeerIdentified { peer, .. } => Self::new("peer_identified", peer),
+            NetworkEvent::FileTransferRequest { from, request } => Self {
+                bytes: match request {
+                    ProtocolRequest::FileChunk { data, .. } => Some(data.len()),
+                    _ => None,
+                },
+                ..Self::new("file_transfer_request", from)
+            },
+            NetworkEvent::FileTransferResponse { from, .. } => {
+                Self::new("file_transfer_response", from)
+            }
+            NetworkEvent::GossipMessage { from, topic, data } => Self {
+                topic: Some(topic.clone()),
+                bytes: Some(data.len()),
+                ..Self::new("gossip_message", from)
+            },
+            NetworkEvent::DirectMessage { from, data } => Self {
+                bytes: Some(data.len()),
+                ..Self::new("direct_message", from)
+            },
+            NetworkEvent::TransferProgress { from, progress, .. } => Self {
+                bytes: usize::try_from(progress.bytes_transferred).ok(),
+                ..Self::new("transfer_progress", from)
+            },
+        }
+    }
+}
+
+impl std::fmt::Display for NetworkEvent {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            NetworkEvent::PeerConnected(peer) => write!(f, "Peer connected: {}", peer),
+            NetworkEvent::PeerDisconnected(peer) => write!(f, "Peer disconnected: {}", peer),
+            NetworkEvent::PeerDiscovered(peer) => write!(f, "Peer discovered: {}", peer),
+            NetworkEvent::PeerIdentified {
+                peer,
+                agent_version,
+                ..
+            } => write!(f, "Peer identified: {} ({})", peer, agent_version),
+            NetworkEvent::FileTransferRequest { from, .. } => {
+                write!(f, "File transfer request from {}", from)
+            }
+            NetworkEvent::FileTransferResponse { from, .. } => {
+                write!(f, "File transfer response from {}", from)
+            }
+            NetworkEvent::GossipMessage { from, topic, data } => write!(
+                f,
+                "Gossip message from {} on topic {}: {}",
+                from,
+                topic,
+                String::from_utf8_lossy(data)
+            ),
+            NetworkEvent::DirectMessage { from, data } => write!(
+                f,
+                "Direct message from {}: {}",
+                from,
+                String::from_utf8_lossy(data)
+            ),
+            NetworkEvent::TransferProgress {
+                from,
+                transfer_id,
+                progress,
+            } => write!(
+                f,
+                "Transfer {} from {}: {:.1}%",
+                transfer_id,
+                from,
+                progress.percentage()
+            ),
+        }
+    }
 }
 
 /// Commands that can be sent to the network service
 #[derive(Debug)]
 pub enum NetworkCommand {
     StartListening(u16),
+    /// Listen on the first free port of the range, replying with it
+    ListenInRange {
+        ports: RangeInclusive<u16>,
+        reply: oneshot::Sender<DomainResult<u16>>,
+    },
     ConnectToPeer(Multiaddr),
+    Dial {
+        addr: Multiaddr,
+        reply: oneshot::Sender<DomainResult<PeerId>>,
+    },
+    GetListenAddresses(oneshot::Sender<Vec<Multiaddr>>),
+    GetRoutingTablePeers(oneshot::Sender<Vec<PeerId>>),
+    /// Number of open connections to each connected peer
+    GetConnectionCounts(oneshot::Sender<HashMap<PeerId, usize>>),
+    /// Close every connection to a peer
+    DisconnectPeer(PeerId),
+    GetStatus(oneshot::Sender<NetworkStatus>),
+    /// Stop the swarm task, replying once the swarm has been dropped
+    Shutdown(oneshot::Sender<()>),
     SendFileRequest {
         peer_id: PeerId,
         request: ProtocolRequest,
     },
+    Request {
+        peer_id: PeerId,
+        request: ProtocolRequest,
+        reply: oneshot::Sender<DomainResult<ProtocolResponse>>,
+    },
+    SendDirectMessage {
+        peer_id: PeerId,
+        data: Vec<u8>,
+        reply: oneshot::Sender<DomainResult<()>>,
+    },
+    SetFileHandler(Arc<FileTransferHandler>),
+    /// Refuse connections to a peer until the given time, or indefinitely
+    BanPeer {
+        peer_id: PeerId,
+        until: Option<Instant>,
+    },
+    UnbanPeer(PeerId),
     SubscribeTopic(String),
-    PublishMessage {
+    Publish {
         topic: String,
         data: Vec<u8>,
+        reply: oneshot::Sender<Result<usize, gossipsub::PublishError>>,
     },
     // Advanced peer discovery commands
     StartMdnsDiscovery,
@@ -75,18 +352,521 @@ pub enum NetworkCommand {
     },
 }
 
+/// Replies owed to callers waiting on swarm activity
+#[derive(Default)]
+struct PendingReplies {
+    dials: HashMap<ConnectionId, oneshot::Sender<DomainResult<PeerId>>>,
+    requests: HashMap<OutboundRequestId, oneshot::Sender<DomainResult<ProtocolResponse>>>,
+    messages: HashMap<OutboundRequestId, oneshot::Sender<DomainResult<()>>>,
+}
+
+/// A response produced off the swarm task, waiting to be sent back
+type InboundResponse = (ResponseChannel<ProtocolResponse>, ProtocolResponse);
+
+/// Swarm-task state for answering inbound file transfer requests
+struct InboundRequests {
+    handler: Option<Arc<FileTransferHandler>>,
+    response_tx: mpsc::UnboundedSender<InboundResponse>,
+    /// Direct messages waiting to be acknowledged
+    ack_tx: mpsc::UnboundedSender<ResponseChannel<()>>,
+    download_limit: Option<Arc<BandwidthLimiter>>,
+    /// Where handler progress is forwarded as [`NetworkEvent::TransferProgress`]
+    event_tx: mpsc::UnboundedSender<NetworkEvent>,
+}
+
+/// Retry schedule for the Kademlia bootstrap.
+///
+/// A failed bootstrap is retried with exponential backoff until it succeeds
+/// or `max_attempts` is reached. Connectivity changes reset the count so a
+/// node that gave up while offline tries again once it has a network.
+#[derive(Debug)]
+struct BootstrapRetry {
+    base_backoff: Duration,
+    max_attempts: u32,
+    attempts: u32,
+    in_flight: bool,
+    succeeded: bool,
+    next_attempt: Option<Instant>,
+}
+
+impl BootstrapRetry {
+    fn new(base_backoff: Duration, max_attempts: u32) -> Self {
+        Self {
+            base_backoff,
+            max_attempts,
+            attempts: 0,
+            in_flight: false,
+            succeeded: false,
+            next_attempt: None,
+        }
+    }
+
+    /// A schedule that never bootstraps, for builds without a DHT
+    fn finished() -> Self {
+        Self {
+            succeeded: true,
+            ..Self::new(Duration::ZERO, 0)
+        }
+    }
+
+    /// When the next bootstrap should be attempted, if one is due at all
+    fn next_attempt(&self) -> Option<Instant> {
+        self.next_attempt
+    }
+
+    /// Record the outcome of calling `bootstrap()` on the DHT
+    fn record_attempt(&mut self, started: bool, now: Instant) {
+        self.attempts += 1;
+        self.next_attempt = None;
+        self.in_flight = started;
+        if !started {
+            self.schedule_retry(now);
+        }
+    }
+
+    /// The bootstrap query reached every peer it was going to
+    #[cfg_attr(not(feature = "dht"), allow(dead_code))]
+    fn record_success(&mut self) {
+        self.in_flight = false;
+        self.succeeded = true;
+        self.next_attempt = None;
+    }
+
+    /// The bootstrap query failed after being started
+    #[cfg_attr(not(feature = "dht"), allow(dead_code))]
+    fn record_failure(&mut self, now: Instant) {
+        self.in_flight = false;
+        self.schedule_retry(now);
+    }
+
+    /// A listen address or connection appeared; start over if still unbootstrapped
+    fn connectivity_changed(&mut self, now: Instant) {
+        if self.succeeded || self.in_flight {
+            return;
+        }
+        self.attempts = 0;
+        self.next_attempt = Some(now);
+    }
+
+    fn schedule_retry(&mut self, now: Instant) {
+        if self.attempts >= self.max_attempts {
+            warn!(
+                "Giving up on Kademlia bootstrap after {} attempts",
+                self.attempts
+            );
+            self.next_attempt = None;
+            return;
+        }
+        let backoff = self.base_backoff * 2u32.saturating_pow(self.attempts - 1);
+        debug!("Retrying Kademlia bootstrap in {:?}", backoff);
+        self.next_attempt = Some(now + backoff);
+    }
+}
+
+/// Longest wait between redials of a persistent peer
+const MAX_PERSISTENT_PEER_BACKOFF: Duration = Duration::from_secs(300);
+
+/// A peer the node keeps a connection to
+#[derive(Debug)]
+struct PersistentPeer {
+    addr: Multiaddr,
+    failed_dials: u32,
+    next_dial: Option<Instant>,
+}
+
+/// Redial schedule for the configured persistent peers.
+///
+/// Every peer is dialed at start. While a peer is unreachable it is redialed
+/// with exponential backoff from `retry`; once it disconnects it is redialed
+/// after `retry`.
+#[derive(Debug)]
+struct PersistentPeers {
+    retry: Duration,
+    peers: HashMap<PeerId, PersistentPeer>,
+}
+
+impl PersistentPeers {
+    fn new(config: &AppConfig, now: Instant) -> Self {
+        let mut peers = HashMap::new();
+        for addr_str in &config.network.persistent_peers {
+            let addr = match addr_str.parse::<Multiaddr>() {
+                Ok(addr) => addr,
+                Err(e) => {
+                    warn!("Ignoring persistent peer {}: {}", addr_str, e);
+                    continue;
+                }
+            };
+            let Some(peer_id) = peer_id_from_addr(&addr) else {
+                warn!("Ignoring persistent peer {}: no /p2p/ peer id", addr_str);
+                continue;
+            };
+            peers.insert(
+                peer_id,
+                PersistentPeer {
+                    addr,
+                    failed_dials: 0,
+                    next_dial: Some(now),
+                },
+            );
+        }
+        Self {
+            retry: Duration::from_secs(config.network.persistent_peer_retry_seconds),
+            peers,
+        }
+    }
+
+    /// When the next redial is due, if any peer needs one
+    fn next_dial(&self) -> Option<Instant> {
+        self.peers.values().filter_map(|peer| peer.next_dial).min()
+    }
+
+    /// Addresses to dial now. Each is scheduled to be dialed again after its
+    /// backoff in case this dial fails.
+    fn due(&mut self, now: Instant) -> Vec<Multiaddr> {
+        let mut due = Vec::new();
+        for peer in self.peers.values_mut() {
+            if peer.next_dial.is_some_and(|at| at <= now) {
+                let backoff = self.retry * 2u32.saturating_pow(peer.failed_dials);
+                peer.failed_dials = peer.failed_dials.saturating_add(1);
+                peer.next_dial = Some(now + backoff.min(MAX_PERSISTENT_PEER_BACKOFF));
+                due.push(peer.addr.clone());
+            }
+        }
+        due
+    }
+
+    fn connected(&mut self, peer_id: &PeerId) {
+        if let Some(peer) = self.peers.get_mut(peer_id) {
+            peer.failed_dials = 0;
+            peer.next_dial = None;
+        }
+    }
+
+    /// The last connection to `peer_id` closed
+    fn disconnected(&mut self, peer_id: &PeerId, now: Instant) {
+        if let Some(peer) = self.peers.get_mut(peer_id) {
+            info!(
+                "Persistent peer {} disconnected, redialing in {:?}",
+                peer_id, self.retry
+            );
+            peer.next_dial = Some(now + self.retry);
+        }
+    }
+}
+
+/// Banned peers and when each ban lifts.
+///
+/// The swarm itself refuses connections through the `blocked` behaviour;
+/// this only remembers which bans expire so they can be lifted on time.
+#[derive(Debug, Default)]
+struct PeerBans {
+    bans: HashMap<PeerId, Option<Instant>>,
+}
+
+impl PeerBans {
+    /// Ban `peer_id` until `until`, or indefinitely, replacing any earlier ban
+    fn ban(&mut self, peer_id: PeerId, until: Option<Instant>) {
+        self.bans.insert(peer_id, until);
+    }
+
+    /// Returns whether the peer was banned
+    fn unban(&mut self, peer_id: &PeerId) -> bool {
+        self.bans.remove(peer_id).is_some()
+    }
+
+    /// When the next ban lifts, if any expires
+    fn next_expiry(&self) -> Option<Instant> {
+        self.bans.values().flatten().min().copied()
+    }
+
+    /// Forget bans that have lifted by `now`, returning their peers
+    fn expire(&mut self, now: Instant) -> Vec<PeerId> {
+        let expired: Vec<PeerId> = self
+            .bans
+            .iter()
+            .filter(|(_, until)| until.is_some_and(|until| until <= now))
+            .map(|(peer_id, _)| *peer_id)
+            .collect();
+        for peer_id in &expired {
+            self.bans.remove(peer_id);
+        }
+        expired
+    }
+}
+
+/// Schedule for periodic DHT maintenance.
+///
+/// Routing tables decay as peers churn, so the DHT is re-bootstrapped and
+/// probed with a random-key lookup every `interval` while the node has peers.
+#[derive(Debug)]
+struct DhtRefresh {
+    interval: Option<Duration>,
+    next_refresh: Option<Instant>,
+}
+
+impl DhtRefresh {
+    /// A zero `interval` disables periodic refreshes
+    fn new(interval: Duration, now: Instant) -> Self {
+        let interval = (!interval.is_zero()).then_some(interval);
+        Self {
+            interval,
+            next_refresh: interval.map(|interval| now + interval),
+        }
+    }
+
+    fn next_refresh(&self) -> Option<Instant> {
+        self.next_refresh
+    }
+
+    /// Schedule the next refresh and report whether one should run now
+    fn tick(&mut self, now: Instant, connected: bool) -> bool {
+        self.next_refresh = self.interval.map(|interval| now + interval);
+        connected
+    }
+}
+
+/// Bounded cache of recently seen gossip message ids.
+///
+/// Gossipsub may deliver the same message over several mesh paths; the
+/// oldest ids are evicted once `capacity` is reached.
+#[derive(Debug)]
+struct SeenMessages {
+    capacity: usize,
+    order: VecDeque<gossipsub::MessageId>,
+    ids: HashSet<gossipsub::MessageId>,
+}
+
+impl SeenMessages {
+    fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            order: VecDeque::with_capacity(capacity),
+            ids: HashSet::with_capacity(capacity),
+        }
+    }
+
+    /// Remember `id`, returning false if it was already seen
+    fn insert(&mut self, id: gossipsub::MessageId) -> bool {
+        if self.capacity == 0 {
+            return true;
+        }
+        if self.ids.contains(&id) {
+            return false;
+        }
+        if self.order.len() == self.capacity
+            && let Some(oldest) = self.order.pop_front()
+        {
+            self.ids.remove(&oldest);
+        }
+        self.order.push_back(id.clone());
+        self.ids.insert(id);
+        true
+    }
+}
+
+/// Why an inbound gossip message was not delivered
+#[derive(Debug)]
+enum GossipDrop {
+    Duplicate,
+    Rejected(String),
+}
+
+/// Checks applied to inbound gossip before it reaches subscribers
+#[derive(Debug)]
+struct GossipFilter {
+    max_message_bytes: usize,
+    allowed_topics: Option<HashSet<String>>,
+    seen: SeenMessages,
+}
+
+impl GossipFilter {
+    fn new(config: &AppConfig) -> Self {
+        Self {
+            max_message_bytes: config.max_gossip_message_bytes,
+            allowed_topics: config
+                .allowed_topics
+                .as_ref()
+                .map(|topics| topics.iter().cloned().collect()),
+            seen: SeenMessages::new(config.network.gossip_dedup_cache_size),
+        }
+    }
+
+    /// Decide whether a message should be delivered, or why it was dropped
+    fn admit(
+        &mut self,
+        message_id: gossipsub::MessageId,
+        message: &gossipsub::Message,
+    ) -> Result<(), GossipDrop> {
+        let topic = message.topic.as_str();
+        if let Some(allowed) = &self.allowed_topics
+            && !allowed.contains(topic)
+        {
+            return Err(GossipDrop::Rejected(format!(
+                "topic {} is not allowed",
+                topic
+            )));
+        }
+        if message.data.len() > self.max_message_bytes {
+            return Err(GossipDrop::Rejected(format!(
+                "{} bytes exceeds the {} byte limit",
+                message.data.len(),
+                self.max_message_bytes
+            )));
+        }
+        if !self.seen.insert(message_id) {
+            return Err(GossipDrop::Duplicate);
+        }
+        Ok(())
+    }
+}
+
+/// Bookkeeping the swarm task keeps alongside the swarm itself
+struct SwarmMaintenance {
+    bootstrap: BootstrapRetry,
+    refresh: DhtRefresh,
+    persistent: PersistentPeers,
+    gossip_filter: GossipFilter,
+    scores: Arc<RwLock<PeerScores>>,
+    download_limit: Option<Arc<BandwidthLimiter>>,
+}
+
+/// Well-known IPFS bootstrap nodes, used when joining the public DHT
+#[cfg(feature = "dht")]
+const PUBLIC_BOOTSTRAP_PEERS: &[&str] = &[
+    "/dnsaddr/bootstrap.libp2p.io/p2p/QmNnooDu7bfjPFoTZYxMNLWUQJyrVwtbZg5gBMjTezGAJN",
+    "/dnsaddr/bootstrap.libp2p.io/p2p/QmQCU2EcMqAqQPR2i9bChDtGNJchTbq5TbXJJ16u19uLTa",
+    "/dnsaddr/bootstrap.libp2p.io/p2p/QmbLHAnMoJPWSCR5Zp7y9BDkkFBhYZyEjhY5bGHxpmmk9N",
+    "/ip4/104.131.131.82/tcp/4001/p2p/QmaCpDMGvV2BGHeYERUEnRQAwe3N8SzbUtfsmvsqQLuvuJ",
+];
+
+/// Connection caps enforced by the swarm; connections past them are refused
+fn swarm_connection_limits(config: &AppConfig) -> connection_limits::ConnectionLimits {
+    let limit = |value: usize| Some(u32::try_from(value).unwrap_or(u32::MAX));
+    let network = &config.network;
+    connection_limits::ConnectionLimits::default()
+        .with_max_established(limit(network.max_connections))
+        .with_max_established_per_peer(limit(network.max_connections_per_peer))
+        .with_max_pending_incoming(limit(network.max_pending_connections))
+        .with_max_pending_outgoing(limit(network.max_pending_connections))
+}
+
+/// TCP transport settings. libp2p-tcp always sets `SO_REUSEADDR`, so a
+/// restarted node can rebind its port while old connections are still in
+/// `TIME_WAIT`; it sets `SO_REUSEPORT` on Unix only for sockets created with
+/// `PortUse::Reuse`.
+fn tcp_transport_config(config: &AppConfig) -> tcp::Config {
+    tcp::Config::default().listen_backlog(config.network.listen_backlog)
+}
+
+/// Timeout and stream limit shared by the request-response protocols
+fn request_response_config(config: &AppConfig) -> request_response::Config {
+    request_response::Config::default()
+        .with_request_timeout(Duration::from_secs(config.network.request_timeout_seconds))
+        .with_max_concurrent_streams(config.network.max_concurrent_streams)
+}
+
+/// mDNS for local peer discovery, starting enabled
+#[cfg(feature = "mdns")]
+fn mdns_behaviour(local_peer_id: PeerId) -> DomainResult<MdnsBehaviour> {
+    Ok(Toggle::from(Some(new_mdns(local_peer_id)?)))
+}
+
+#[cfg(feature = "mdns")]
+fn new_mdns(local_peer_id: PeerId) -> DomainResult<mdns::tokio::Behaviour> {
+    mdns::tokio::Behaviour::new(mdns::Config::default(), local_peer_id)
+        .map_err(|e| format!("Failed to create mDNS: {}", e).into())
+}
+
+#[cfg(not(feature = "mdns"))]
+fn mdns_behaviour(_local_peer_id: PeerId) -> DomainResult<MdnsBehaviour> {
+    Ok(dummy::Behaviour)
+}
+
+#[cfg(feature = "mdns")]
+fn mdns_enabled(swarm: &Swarm<CipherStreamBehaviour>) -> bool {
+    swarm.behaviour().mdns.is_enabled()
+}
+
+#[cfg(not(feature = "mdns"))]
+fn mdns_enabled(_swarm: &Swarm<CipherStreamBehaviour>) -> bool {
+    false
+}
+
+/// Kademlia DHT for global peer routing
+#[cfg(feature = "dht")]
+fn kademlia_behaviour(config: &AppConfig, local_peer_id: PeerId) -> KademliaBehaviour {
+    let mut kademlia =
+        kad::Behaviour::new(local_peer_id, kad::store::MemoryStore::new(local_peer_id));
+
+    // Answer DHT queries from anyone only when joining the public DHT;
+    // private swarms let libp2p pick the mode from confirmed addresses
+    if config.network.join_public_dht {
+        kademlia.set_mode(Some(kad::Mode::Server));
+    }
+
+    // Seed the routing table with the operator's bootstrap peers, plus the
+    // public IPFS ones when joining the public DHT. Every address of a
+    // peer is kept, so it can be reached over whichever stack works.
+    let public_peers = if config.network.join_public_dht {
+        PUBLIC_BOOTSTRAP_PEERS
+    } else {
+        &[]
+    };
+    let bootstrap_peers = config
+        .network
+        .bootstrap_peers
+        .iter()
+        .map(String::as_str)
+        .chain(public_peers.iter().copied());
+    for addr_str in bootstrap_peers {
+        let addr = match addr_str.parse::<Multiaddr>() {
+            Ok(addr) => addr,
+            Err(e) => {
+                warn!("Ignoring bootstrap peer {}: {}", addr_str, e);
+                continue;
+            }
+        };
+        let Some(peer_id) = peer_id_from_addr(&addr) else {
+            warn!("Ignoring bootstrap peer {}: no /p2p/ peer id", addr_str);
+            continue;
+        };
+        kademlia.add_address(&peer_id, addr);
+        info!("Added Kademlia bootstrap peer: {}", peer_id);
+    }
+    kademlia
+}
+
+#[cfg(not(feature = "dht"))]
+fn kademlia_behaviour(_config: &AppConfig, _local_peer_id: PeerId) -> KademliaBehaviour {
+    dummy::Behaviour
+}
+
+/// Sleep until `deadline`, or forever if there is none
+async fn sleep_until_or_forever(deadline: Option<Instant>) {
+    match deadline {
+        Some(deadline) => tokio::time::sleep_until(deadline).await,
+        None => std::future::pending().await,
+    }
+}
+
+/// How often to retry a publish while no peer is subscribed to the topic
+const PUBLISH_RETRY_INTERVAL: Duration = Duration::from_millis(200);
+
 /// Network service implementation using libp2p 0.55
 pub struct LibP2pNetworkService {
     command_tx: mpsc::UnboundedSender<NetworkCommand>,
-    #[allow(dead_code)] // Part of future API for receiving network events
     event_rx: Arc<tokio::sync::Mutex<mpsc::UnboundedReceiver<NetworkEvent>>>,
     local_peer_id: PeerId,
+    connection_timeout: Duration,
+    scores: Arc<RwLock<PeerScores>>,
+    /// Shared by every outgoing chunk so total egress stays bounded
+    upload_limit: Option<Arc<BandwidthLimiter>>,
 }
 
 impl LibP2pNetworkService {
     /// Create a new libp2p network service
     pub async fn new(
-        _config: Arc<AppConfig>,
+        config: Arc<AppConfig>,
         event_publisher: Arc<dyn EventPublisher>,
     ) -> DomainResult<Self> {
         // Generate or load keypair
@@ -110,10 +890,13 @@ impl LibP2pNetworkService {
         .map_err(|e| format!("Failed to create gossipsub: {}", e))?;
 
         // Configure identify
-        let identify = identify::Behaviour::new(identify::Config::new(
-            "/cipherstream/1.0.0".to_string(),
-            local_key.public(),
-        ));
+        let identify = identify::Behaviour::new(
+            identify::Config::new(
+                config.network.identify_protocol_version.clone(),
+                local_key.public(),
+            )
+            .with_agent_version(config.network.agent_version()),
+        );
 
         // Configure request-response for file transfers
         let protocols = [(
@@ -123,60 +906,38 @@ impl LibP2pNetworkService {
         let request_response = request_response::Behaviour::with_codec(
             FileTransferCodec,
             protocols,
-            request_response::Config::default(),
+            request_response_config(&config),
+        );
+        let direct_message = request_response::Behaviour::with_codec(
+            DirectMessageCodec,
+            [(
+                DIRECT_MESSAGE_PROTOCOL,
+                request_response::ProtocolSupport::Full,
+            )],
+            request_response_config(&config),
         );
 
-        // Configure mDNS for local peer discovery
-        let mdns = mdns::tokio::Behaviour::new(mdns::Config::default(), local_peer_id)
-            .map_err(|e| format!("Failed to create mDNS: {}", e))?;
-
-        // Configure Kademlia DHT for global peer routing
-        let mut kademlia =
-            kad::Behaviour::new(local_peer_id, kad::store::MemoryStore::new(local_peer_id));
-
-        // Set Kademlia to server mode to respond to DHT queries
-        kademlia.set_mode(Some(kad::Mode::Server));
-
-        // Add well-known IPFS bootstrap peers for global DHT connectivity
-        let bootstrap_peers = vec![
-            // IPFS bootstrap nodes
-            "/dnsaddr/bootstrap.libp2p.io/p2p/QmNnooDu7bfjPFoTZYxMNLWUQJyrVwtbZg5gBMjTezGAJN",
-            "/dnsaddr/bootstrap.libp2p.io/p2p/QmQCU2EcMqAqQPR2i9bChDtGNJchTbq5TbXJJ16u19uLTa",
-            "/dnsaddr/bootstrap.libp2p.io/p2p/QmbLHAnMoJPWSCR5Zp7y9BDkkFBhYZyEjhY5bGHxpmmk9N",
-            "/ip4/104.131.131.82/tcp/4001/p2p/QmaCpDMGvV2BGHeYERUEnRQAwe3N8SzbUtfsmvsqQLuvuJ",
-        ];
-
-        for addr_str in &bootstrap_peers {
-            let Ok(addr) = addr_str.parse::<Multiaddr>() else {
-                continue;
-            };
-            let Some(peer_id) = addr.iter().find_map(|p| {
-                if let libp2p::multiaddr::Protocol::P2p(peer_id) = p {
-                    Some(peer_id)
-                } else {
-                    None
-                }
-            }) else {
-                continue;
-            };
-            kademlia.add_address(&peer_id, addr);
-            info!("Added Kademlia bootstrap peer: {}", peer_id);
-        }
+        let mdns = mdns_behaviour(local_peer_id)?;
+        let kademlia = kademlia_behaviour(&config, local_peer_id);
 
         // Create behaviour
         let behaviour = CipherStreamBehaviour {
             gossipsub,
             identify,
             request_response,
+            direct_message,
             mdns,
             kademlia,
+            ping: ping::Behaviour::new(ping::Config::new()),
+            limits: connection_limits::Behaviour::new(swarm_connection_limits(&config)),
+            blocked: allow_block_list::Behaviour::default(),
         };
 
         // Build swarm using the new libp2p 0.55 API
         let swarm = SwarmBuilder::with_existing_identity(local_key)
             .with_tokio()
             .with_tcp(
-                tcp::Config::default(),
+                tcp_transport_config(&config),
                 noise::Config::new,
                 yamux::Config::default,
             )
@@ -189,18 +950,54 @@ impl LibP2pNetworkService {
         let (command_tx, command_rx) = mpsc::unbounded_channel();
         let (event_tx, event_rx) = mpsc::unbounded_channel();
 
+        let scores = Arc::new(RwLock::new(PeerScores::new(Duration::from_secs(
+            config.network.peer_score_half_life_seconds,
+        ))));
+        let maintenance = SwarmMaintenance {
+            bootstrap: if cfg!(feature = "dht") {
+                BootstrapRetry::new(
+                    Duration::from_secs(config.network.bootstrap_retry_backoff_seconds),
+                    config.network.bootstrap_max_attempts,
+                )
+            } else {
+                BootstrapRetry::finished()
+            },
+            refresh: DhtRefresh::new(
+                Duration::from_secs(if cfg!(feature = "dht") {
+                    config.network.dht_refresh_interval_seconds
+                } else {
+                    0
+                }),
+                Instant::now(),
+            ),
+            persistent: PersistentPeers::new(&config, Instant::now()),
+            gossip_filter: GossipFilter::new(&config),
+            scores: scores.clone(),
+            download_limit: config
+                .network
+                .max_download_bytes_per_sec
+                .map(|rate| Arc::new(BandwidthLimiter::new(rate))),
+        };
+
         // Spawn the swarm task
         tokio::spawn(Self::run_swarm_task(
             swarm,
             command_rx,
             event_tx,
             event_publisher,
+            maintenance,
         ));
 
         Ok(Self {
             command_tx,
             event_rx: Arc::new(tokio::sync::Mutex::new(event_rx)),
             local_peer_id,
+            connection_timeout: Duration::from_secs(config.network.connection_timeout_seconds),
+            scores,
+            upload_limit: config
+                .network
+                .max_upload_bytes_per_sec
+                .map(|rate| Arc::new(BandwidthLimiter::new(rate))),
         })
     }
 
@@ -212,42 +1009,197 @@ impl LibP2pNetworkService {
         Ok(())
     }
 
-    /// Main swarm task that handles all swarm operations
-    async fn run_swarm_task(
-        mut swarm: Swarm<CipherStreamBehaviour>,
-        mut command_rx: mpsc::UnboundedReceiver<NetworkCommand>,
-        event_tx: mpsc::UnboundedSender<NetworkEvent>,
-        event_publisher: Arc<dyn EventPublisher>,
-    ) {
-        let mut connected_peers: HashMap<PeerId, Vec<Multiaddr>> = HashMap::new();
-        let mut bootstrap_attempted = false;
+    /// Listen on every IPv4 interface at `port`, and on IPv6 when available
+    fn listen_on_port(swarm: &mut Swarm<CipherStreamBehaviour>, port: u16) -> DomainResult<()> {
+        let listen_addr: Multiaddr = format!("/ip4/0.0.0.0/tcp/{}", port)
+            .parse()
+            .map_err(|e| format!("Invalid listen address: {}", e))?;
+
+        swarm
+            .listen_on(listen_addr.clone())
+            .map_err(|e| format!("Failed to start listening: {}", e))?;
+        info!("Network service started on {}", listen_addr);
+
+        // IPv6 is best effort; hosts without it still serve over IPv4
+        let listen_addr_v6: Multiaddr = format!("/ip6/::/tcp/{}", port)
+            .parse()
+            .map_err(|e| format!("Invalid listen address: {}", e))?;
+        match swarm.listen_on(listen_addr_v6.clone()) {
+            Ok(_) => info!("Network service started on {}", listen_addr_v6),
+            Err(e) => warn!("Not listening on {}: {}", listen_addr_v6, e),
+        }
+        Ok(())
+    }
+
+    /// Start listening on the first free port in `ports`, returning it.
+    ///
+    /// A range of just port 0 binds an ephemeral port, whose number is read
+    /// back once the OS has assigned it.
+    pub async fn start_in_range(&self, ports: RangeInclusive<u16>) -> DomainResult<u16> {
+        let (reply, response) = oneshot::channel();
+        self.command_tx
+            .send(NetworkCommand::ListenInRange { ports, reply })
+            .map_err(|e| format!("Failed to send start command: {}", e))?;
+        let port = response
+            .await
+            .map_err(|_| "Network service stopped before replying")??;
+        if port != 0 {
+            return Ok(port);
+        }
+
+        for _ in 0..250 {
+            let assigned = self.listen_addresses().await?.iter().find_map(|addr| {
+                addr.iter().find_map(|p| match p {
+                    libp2p::multiaddr::Protocol::Tcp(port) if port != 0 => Some(port),
+                    _ => None,
+                })
+            });
+            if let Some(port) = assigned {
+                return Ok(port);
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        Err("Listening on an ephemeral port, but none was assigned".into())
+    }
+
+    /// Main swarm task that handles all swarm operations
+    async fn run_swarm_task(
+        mut swarm: Swarm<CipherStreamBehaviour>,
+        mut command_rx: mpsc::UnboundedReceiver<NetworkCommand>,
+        event_tx: mpsc::UnboundedSender<NetworkEvent>,
+        event_publisher: Arc<dyn EventPublisher>,
+        maintenance: SwarmMaintenance,
+    ) {
+        let SwarmMaintenance {
+            mut bootstrap,
+            mut refresh,
+            mut persistent,
+            mut gossip_filter,
+            scores,
+            download_limit,
+        } = maintenance;
+        // Open connections per peer, as libp2p counts them
+        let mut connected_peers: HashMap<PeerId, usize> = HashMap::new();
+        let mut pending = PendingReplies::default();
+        let mut bans = PeerBans::default();
+        let (response_tx, mut response_rx) = mpsc::unbounded_channel();
+        let (ack_tx, mut ack_rx) = mpsc::unbounded_channel();
+        let mut inbound = InboundRequests {
+            handler: None,
+            response_tx,
+            ack_tx,
+            download_limit,
+            event_tx: event_tx.clone(),
+        };
+
+        loop {
+            tokio::select! {
+                // Handle commands from the service
+                Some(command) = command_rx.recv() => {
+                    if let NetworkCommand::Shutdown(reply) = command {
+                        info!("Shutting down network service");
+                        drop(swarm);
+                        let _ = reply.send(());
+                        return;
+                    }
+                    if let NetworkCommand::GetConnectionCounts(reply) = command {
+                        let _ = reply.send(connected_peers.clone());
+                        continue;
+                    }
+                    if let NetworkCommand::GetStatus(reply) = command {
+                        let _ = reply.send(NetworkStatus::new(&swarm, connected_peers.len()));
+                        continue;
+                    }
+                    if let NetworkCommand::BanPeer { peer_id, until } = command {
+                        // Blocking also closes any open connection to the peer
+                        swarm.behaviour_mut().blocked.block_peer(peer_id);
+                        bans.ban(peer_id, until);
+                        info!("Banned peer {}", peer_id);
+                        continue;
+                    }
+                    if let NetworkCommand::UnbanPeer(peer_id) = command {
+                        swarm.behaviour_mut().blocked.unblock_peer(peer_id);
+                        if bans.unban(&peer_id) {
+                            info!("Unbanned peer {}", peer_id);
+                        }
+                        continue;
+                    }
+                    if let Err(e) =
+                        Self::handle_command(&mut swarm, command, &mut pending, &mut inbound).await
+                    {
+                        error!("Error handling command: {}", e);
+                    }
+                }
+
+                // Send responses produced by the file transfer handler
+                Some((channel, response)) = response_rx.recv() => {
+                    if swarm
+                        .behaviour_mut()
+                        .request_response
+                        .send_response(channel, response)
+                        .is_err()
+                    {
+                        warn!("Peer went away before the file transfer response was sent");
+                    }
+                }
+
+                // Acknowledge received direct messages
+                Some(channel) = ack_rx.recv() => {
+                    if swarm
+                        .behaviour_mut()
+                        .direct_message
+                        .send_response(channel, ())
+                        .is_err()
+                    {
+                        warn!("Peer went away before the direct message was acknowledged");
+                    }
+                }
+
+                // Bootstrap the DHT once listening, retrying on failure
+                _ = sleep_until_or_forever(bootstrap.next_attempt()) => {
+                    let started = Self::bootstrap_dht(&mut swarm);
+                    bootstrap.record_attempt(started, Instant::now());
+                }
+
+                // Keep the routing table healthy while we have peers
+                _ = sleep_until_or_forever(refresh.next_refresh()) => {
+                    if refresh.tick(Instant::now(), !connected_peers.is_empty()) {
+                        Self::refresh_dht(&mut swarm);
+                    }
+                }
 
-        loop {
-            tokio::select! {
-                // Handle commands from the service
-                Some(command) = command_rx.recv() => {
-                    if let Err(e) = Self::handle_command(&mut swarm, command).await {
-                        error!("Error handling command: {}", e);
+                // Lift bans that have expired
+                _ = sleep_until_or_forever(bans.next_expiry()) => {
+                    for peer_id in bans.expire(Instant::now()) {
+                        swarm.behaviour_mut().blocked.unblock_peer(peer_id);
+                        info!("Ban on peer {} expired", peer_id);
                     }
                 }
 
-                // Handle swarm events
-                event = swarm.select_next_some() => {
-                    // Trigger Kademlia bootstrap once we start listening
-                    if !bootstrap_attempted && matches!(event, SwarmEvent::NewListenAddr { .. }) {
-                        if let Err(e) = swarm.behaviour_mut().kademlia.bootstrap() {
-                            warn!("Kademlia bootstrap failed: {:?}", e);
-                        } else {
-                            info!("Kademlia bootstrap initiated successfully");
-                            bootstrap_attempted = true;
+                // Keep persistent peers connected
+                _ = sleep_until_or_forever(persistent.next_dial()) => {
+                    for addr in persistent.due(Instant::now()) {
+                        debug!("Dialing persistent peer at {}", addr);
+                        if let Err(e) = swarm.dial(addr.clone()) {
+                            warn!("Failed to dial persistent peer {}: {}", addr, e);
                         }
                     }
+                }
+
+                // Handle swarm events
+                event = swarm.select_next_some() => {
+                    Self::track_bootstrap(&event, &mut bootstrap);
+                    Self::track_persistent_peers(&event, &mut persistent);
+                    Self::track_scores(&event, &scores).await;
+                    Self::resolve_pending_replies(&event, &mut pending);
 
                     if let Err(e) = Self::handle_swarm_event(
                         event,
                         &event_tx,
                         &event_publisher,
                         &mut connected_peers,
+                        &inbound,
+                        &mut gossip_filter,
                     ).await {
                         error!("Error handling swarm event: {}", e);
                     }
@@ -260,24 +1212,86 @@ impl LibP2pNetworkService {
     async fn handle_command(
         swarm: &mut Swarm<CipherStreamBehaviour>,
         command: NetworkCommand,
+        pending: &mut PendingReplies,
+        inbound: &mut InboundRequests,
     ) -> DomainResult<()> {
         match command {
             NetworkCommand::StartListening(port) => {
-                let listen_addr: Multiaddr = format!("/ip4/0.0.0.0/tcp/{}", port)
-                    .parse()
-                    .map_err(|e| format!("Invalid listen address: {}", e))?;
-
-                swarm
-                    .listen_on(listen_addr.clone())
-                    .map_err(|e| format!("Failed to start listening: {}", e))?;
-
-                info!("Network service started on {}", listen_addr);
+                Self::listen_on_port(swarm, port)?;
+            }
+            NetworkCommand::ListenInRange { ports, reply } => {
+                let (start, end) = (*ports.start(), *ports.end());
+                let bound = ports
+                    .into_iter()
+                    .find(|&port| {
+                        // Probe with a plain socket, without the reuse
+                        // options libp2p may set, so a port some other
+                        // socket holds counts as taken
+                        if port != 0 && std::net::TcpListener::bind(("0.0.0.0", port)).is_err() {
+                            debug!("Port {} is in use", port);
+                            return false;
+                        }
+                        match Self::listen_on_port(swarm, port) {
+                            Ok(()) => true,
+                            Err(e) => {
+                                debug!("Port {} unavailable: {}", port, e);
+                                false
+                            }
+                        }
+                    })
+                    .ok_or_else(|| format!("No free port in {}-{}", start, end).into());
+                let _ = reply.send(bound);
             }
             NetworkCommand::ConnectToPeer(addr) => {
                 swarm
                     .dial(addr.clone())
                     .map_err(|e| format!("Failed to dial {}: {}", addr, e))?;
             }
+            NetworkCommand::Dial { addr, reply } => {
+                let opts = DialOpts::from(addr.clone());
+                let connection_id = opts.connection_id();
+                match swarm.dial(opts) {
+                    Ok(()) => {
+                        pending.dials.insert(connection_id, reply);
+                    }
+                    Err(e) => {
+                        let _ = reply.send(Err(format!("Failed to dial {}: {}", addr, e).into()));
+                    }
+                }
+            }
+            NetworkCommand::GetListenAddresses(reply) => {
+                let _ = reply.send(swarm.listeners().cloned().collect());
+            }
+            NetworkCommand::Shutdown(_) => unreachable!("shutdown is handled by the swarm task"),
+            NetworkCommand::GetConnectionCounts(_) => {
+                unreachable!("connection counts are answered by the swarm task")
+            }
+            NetworkCommand::GetStatus(_) => {
+                unreachable!("status is answered by the swarm task")
+            }
+            NetworkCommand::BanPeer { .. } | NetworkCommand::UnbanPeer(_) => {
+                unreachable!("bans are tracked by the swarm task")
+            }
+            #[cfg(feature = "dht")]
+            NetworkCommand::GetRoutingTablePeers(reply) => {
+                let peers = swarm
+                    .behaviour_mut()
+                    .kademlia
+                    .kbuckets()
+                    .flat_map(|bucket| {
+                        bucket
+                            .iter()
+                            .map(|entry| *entry.node.key.preimage())
+                            .collect::<Vec<_>>()
+                    })
+                    .collect();
+                let _ = reply.send(peers);
+            }
+            NetworkCommand::DisconnectPeer(peer_id) => {
+                if swarm.disconnect_peer_id(peer_id).is_ok() {
+                    info!("Disconnecting from {}", peer_id);
+                }
+            }
             NetworkCommand::SendFileRequest { peer_id, request } => {
                 let _request_id = swarm
                     .behaviour_mut()
@@ -285,6 +1299,49 @@ impl LibP2pNetworkService {
                     .send_request(&peer_id, request);
                 info!("Sent file transfer request to {}", peer_id);
             }
+            NetworkCommand::Request {
+                peer_id,
+                request,
+                reply,
+            } => {
+                let request_id = swarm
+                    .behaviour_mut()
+                    .request_response
+                    .send_request(&peer_id, request);
+                pending.requests.insert(request_id, reply);
+            }
+            NetworkCommand::SendDirectMessage {
+                peer_id,
+                data,
+                reply,
+            } => {
+                let request_id = swarm
+                    .behaviour_mut()
+                    .direct_message
+                    .send_request(&peer_id, data);
+                pending.messages.insert(request_id, reply);
+            }
+            NetworkCommand::SetFileHandler(handler) => {
+                info!(
+                    "Serving file transfers into {}",
+                    handler.download_dir().display()
+                );
+                let mut progress = handler.subscribe_progress().await;
+                let event_tx = inbound.event_tx.clone();
+                tokio::spawn(async move {
+                    while let Some((from, transfer_id, progress)) = progress.recv().await {
+                        let event = NetworkEvent::TransferProgress {
+                            from,
+                            transfer_id,
+                            progress,
+                        };
+                        if event_tx.send(event).is_err() {
+                            break;
+                        }
+                    }
+                });
+                inbound.handler = Some(handler);
+            }
             NetworkCommand::SubscribeTopic(topic) => {
                 let topic = gossipsub::IdentTopic::new(topic);
                 swarm
@@ -294,21 +1351,27 @@ impl LibP2pNetworkService {
                     .map_err(|e| format!("Failed to subscribe to topic: {}", e))?;
                 info!("Subscribed to topic: {}", topic);
             }
-            NetworkCommand::PublishMessage { topic, data } => {
+            NetworkCommand::Publish { topic, data, reply } => {
                 let topic = gossipsub::IdentTopic::new(topic);
-                let _message_id = swarm
-                    .behaviour_mut()
-                    .gossipsub
-                    .publish(topic.clone(), data)
-                    .map_err(|e| format!("Failed to publish message: {}", e))?;
-                info!("Published message to topic: {}", topic);
-            }
-            NetworkCommand::StartMdnsDiscovery => {
-                info!("mDNS discovery is automatically enabled");
+                let gossipsub = &mut swarm.behaviour_mut().gossipsub;
+                // Flood publishing sends to every peer known to be subscribed
+                let result = gossipsub.publish(topic.clone(), data).map(|_| {
+                    gossipsub
+                        .all_peers()
+                        .filter(|(_, topics)| topics.contains(&&topic.hash()))
+                        .count()
+                });
+                let _ = reply.send(result);
             }
-            NetworkCommand::StopMdnsDiscovery => {
-                info!("mDNS discovery is automatically managed");
+            #[cfg(feature = "mdns")]
+            NetworkCommand::StartMdnsDiscovery => Self::set_mdns_enabled(swarm, true),
+            #[cfg(feature = "mdns")]
+            NetworkCommand::StopMdnsDiscovery => Self::set_mdns_enabled(swarm, false),
+            #[cfg(not(feature = "mdns"))]
+            NetworkCommand::StartMdnsDiscovery | NetworkCommand::StopMdnsDiscovery => {
+                warn!("Ignoring mDNS command: built without the `mdns` feature");
             }
+            #[cfg(feature = "dht")]
             NetworkCommand::BootstrapKademlia(peers) => {
                 if let Err(e) = swarm.behaviour_mut().kademlia.bootstrap() {
                     warn!("Kademlia bootstrap failed: {:?}", e);
@@ -318,13 +1381,7 @@ impl LibP2pNetworkService {
 
                 // Add bootstrap peers to routing table
                 for addr in peers {
-                    if let Some(peer_id) = addr.iter().find_map(|p| {
-                        if let libp2p::multiaddr::Protocol::P2p(peer_id) = p {
-                            Some(peer_id)
-                        } else {
-                            None
-                        }
-                    }) {
+                    if let Some(peer_id) = peer_id_from_addr(&addr) {
                         swarm
                             .behaviour_mut()
                             .kademlia
@@ -332,41 +1389,272 @@ impl LibP2pNetworkService {
                     }
                 }
             }
+            #[cfg(feature = "dht")]
             NetworkCommand::FindClosestPeers(peer_id) => {
                 let _ = swarm.behaviour_mut().kademlia.get_closest_peers(peer_id);
                 debug!("Kademlia finding closest peers to {}", peer_id);
             }
+            #[cfg(feature = "dht")]
             NetworkCommand::AddKademliaAddress { peer_id, addr } => {
                 let addr_clone = addr.clone();
                 swarm.behaviour_mut().kademlia.add_address(&peer_id, addr);
                 info!("Kademlia added address {} to peer {}", addr_clone, peer_id);
             }
+            #[cfg(not(feature = "dht"))]
+            NetworkCommand::GetRoutingTablePeers(reply) => {
+                let _ = reply.send(Vec::new());
+            }
+            #[cfg(not(feature = "dht"))]
+            NetworkCommand::BootstrapKademlia(_)
+            | NetworkCommand::FindClosestPeers(_)
+            | NetworkCommand::AddKademliaAddress { .. } => {
+                warn!("Ignoring DHT command: built without the `dht` feature");
+            }
         }
         Ok(())
     }
 
+    /// Switch mDNS discovery on or off. Stopping drops the behaviour, so the
+    /// node neither queries nor answers on the LAN; starting builds a fresh
+    /// one and tells it the current listen addresses to advertise.
+    #[cfg(feature = "mdns")]
+    fn set_mdns_enabled(swarm: &mut Swarm<CipherStreamBehaviour>, enabled: bool) {
+        if swarm.behaviour().mdns.is_enabled() == enabled {
+            debug!(
+                "mDNS discovery already {}",
+                if enabled { "on" } else { "off" }
+            );
+            return;
+        }
+        if !enabled {
+            swarm.behaviour_mut().mdns = Toggle::from(None);
+            info!("mDNS discovery stopped");
+            return;
+        }
+
+        let mut mdns = match new_mdns(*swarm.local_peer_id()) {
+            Ok(mdns) => mdns,
+            Err(e) => {
+                warn!("Failed to restart mDNS discovery: {}", e);
+                return;
+            }
+        };
+        let listeners: Vec<Multiaddr> = swarm.listeners().cloned().collect();
+        for addr in &listeners {
+            mdns.on_swarm_event(FromSwarm::NewListenAddr(NewListenAddr {
+                listener_id: ListenerId::next(),
+                addr,
+            }));
+        }
+        swarm.behaviour_mut().mdns = Toggle::from(Some(mdns));
+        info!("mDNS discovery started");
+    }
+
+    /// Start bootstrapping the DHT, reporting whether a query is under way
+    #[cfg(feature = "dht")]
+    fn bootstrap_dht(swarm: &mut Swarm<CipherStreamBehaviour>) -> bool {
+        match swarm.behaviour_mut().kademlia.bootstrap() {
+            Ok(_) => {
+                info!("Kademlia bootstrap initiated successfully");
+                true
+            }
+            Err(e) => {
+                warn!("Kademlia bootstrap failed: {:?}", e);
+                false
+            }
+        }
+    }
+
+    #[cfg(not(feature = "dht"))]
+    fn bootstrap_dht(_swarm: &mut Swarm<CipherStreamBehaviour>) -> bool {
+        false
+    }
+
+    /// Re-bootstrap the DHT and look up a random key to refresh distant buckets
+    #[cfg(feature = "dht")]
+    fn refresh_dht(swarm: &mut Swarm<CipherStreamBehaviour>) {
+        let kademlia = &mut swarm.behaviour_mut().kademlia;
+        if let Err(e) = kademlia.bootstrap() {
+            debug!("Periodic Kademlia bootstrap skipped: {:?}", e);
+        }
+        kademlia.get_closest_peers(PeerId::random());
+        debug!("Refreshing Kademlia routing table");
+    }
+
+    #[cfg(not(feature = "dht"))]
+    fn refresh_dht(_swarm: &mut Swarm<CipherStreamBehaviour>) {}
+
+    /// Feed connectivity changes and bootstrap results into the retry schedule
+    fn track_persistent_peers(
+        event: &SwarmEvent<CipherStreamBehaviourEvent>,
+        persistent: &mut PersistentPeers,
+    ) {
+        match event {
+            SwarmEvent::ConnectionEstablished { peer_id, .. } => persistent.connected(peer_id),
+            SwarmEvent::ConnectionClosed {
+                peer_id,
+                num_established: 0,
+                ..
+            } => persistent.disconnected(peer_id, Instant::now()),
+            _ => {}
+        }
+    }
+
+    fn track_bootstrap(
+        event: &SwarmEvent<CipherStreamBehaviourEvent>,
+        bootstrap: &mut BootstrapRetry,
+    ) {
+        match event {
+            SwarmEvent::NewListenAddr { .. } | SwarmEvent::ConnectionEstablished { .. } => {
+                bootstrap.connectivity_changed(Instant::now());
+            }
+            #[cfg(feature = "dht")]
+            SwarmEvent::Behaviour(CipherStreamBehaviourEvent::Kademlia(
+                kad::Event::OutboundQueryProgressed {
+                    result: kad::QueryResult::Bootstrap(result),
+                    step,
+                    ..
+                },
+            )) => match result {
+                Ok(kad::BootstrapOk {
+                    num_remaining: 0, ..
+                }) => bootstrap.record_success(),
+                Ok(_) => {}
+                Err(_) if step.last => bootstrap.record_failure(Instant::now()),
+                Err(_) => {}
+            },
+            _ => {}
+        }
+    }
+
+    /// Feed ping round trips and request outcomes into the peer scores
+    async fn track_scores(
+        event: &SwarmEvent<CipherStreamBehaviourEvent>,
+        scores: &RwLock<PeerScores>,
+    ) {
+        let now = std::time::Instant::now();
+        match event {
+            SwarmEvent::Behaviour(CipherStreamBehaviourEvent::Ping(ping::Event {
+                peer,
+                result: Ok(rtt),
+                ..
+            })) => scores.write().await.record_rtt(*peer, *rtt, now),
+            SwarmEvent::Behaviour(CipherStreamBehaviourEvent::RequestResponse(
+                request_response::Event::Message {
+                    peer,
+                    message: request_response::Message::Response { .. },
+                    ..
+                },
+            )) => scores.write().await.record_success(*peer, now),
+            SwarmEvent::Behaviour(CipherStreamBehaviourEvent::RequestResponse(
+                request_response::Event::OutboundFailure { peer, .. },
+            )) => scores.write().await.record_failure(*peer, now),
+            _ => {}
+        }
+    }
+
+    /// Answer callers waiting on the outcome of a dial or request
+    fn resolve_pending_replies(
+        event: &SwarmEvent<CipherStreamBehaviourEvent>,
+        pending: &mut PendingReplies,
+    ) {
+        match event {
+            SwarmEvent::ConnectionEstablished {
+                peer_id,
+                connection_id,
+                ..
+            } => {
+                if let Some(reply) = pending.dials.remove(connection_id) {
+                    let _ = reply.send(Ok(*peer_id));
+                }
+            }
+            SwarmEvent::OutgoingConnectionError {
+                connection_id,
+                error,
+                ..
+            } => {
+                if let Some(reply) = pending.dials.remove(connection_id) {
+                    let _ = reply.send(Err(format!("Connection failed: {}", error).into()));
+                }
+            }
+            SwarmEvent::Behaviour(CipherStreamBehaviourEvent::RequestResponse(
+                request_response::Event::Message {
+                    message:
+                        request_response::Message::Response {
+                            request_id,
+                            response,
+                        },
+                    ..
+                },
+            )) => {
+                if let Some(reply) = pending.requests.remove(request_id) {
+                    let _ = reply.send(Ok(response.clone()));
+                }
+            }
+            SwarmEvent::Behaviour(CipherStreamBehaviourEvent::RequestResponse(
+                request_response::Event::OutboundFailure {
+                    request_id, error, ..
+                },
+            )) => {
+                if let Some(reply) = pending.requests.remove(request_id) {
+                    let _ = reply.send(Err(format!("Request failed: {}", error).into()));
+                }
+            }
+            SwarmEvent::Behaviour(CipherStreamBehaviourEvent::DirectMessage(
+                request_response::Event::Message {
+                    message: request_response::Message::Response { request_id, .. },
+                    ..
+                },
+            )) => {
+                if let Some(reply) = pending.messages.remove(request_id) {
+                    let _ = reply.send(Ok(()));
+                }
+            }
+            SwarmEvent::Behaviour(CipherStreamBehaviourEvent::DirectMessage(
+                request_response::Event::OutboundFailure {
+                    peer,
+                    request_id,
+                    error,
+                    ..
+                },
+            )) => {
+                if let Some(reply) = pending.messages.remove(request_id) {
+                    let _ =
+                        reply.send(Err(
+                            format!("Direct message to {} failed: {}", peer, error).into()
+                        ));
+                }
+            }
+            _ => {}
+        }
+    }
+
     /// Handle individual swarm events
     async fn handle_swarm_event(
         event: SwarmEvent<CipherStreamBehaviourEvent>,
         event_tx: &mpsc::UnboundedSender<NetworkEvent>,
         event_publisher: &Arc<dyn EventPublisher>,
-        connected_peers: &mut HashMap<PeerId, Vec<Multiaddr>>,
+        connected_peers: &mut HashMap<PeerId, usize>,
+        inbound: &InboundRequests,
+        gossip_filter: &mut GossipFilter,
     ) -> DomainResult<()> {
         match event {
             SwarmEvent::NewListenAddr { address, .. } => {
                 info!("Listening on {}", address);
             }
             SwarmEvent::ConnectionEstablished {
-                peer_id, endpoint, ..
+                peer_id,
+                num_established,
+                ..
             } => {
+                let open = num_established.get() as usize;
+                connected_peers.insert(peer_id, open);
+                if open > 1 {
+                    debug!("Additional connection to {} ({} open)", peer_id, open);
+                    return Ok(());
+                }
                 info!("Connected to peer: {}", peer_id);
 
-                // Store peer address
-                connected_peers
-                    .entry(peer_id)
-                    .or_default()
-                    .push(endpoint.get_remote_address().clone());
-
                 // Send internal event
                 let _ = event_tx.send(NetworkEvent::PeerConnected(peer_id));
 
@@ -377,12 +1665,25 @@ impl LibP2pNetworkService {
                 };
                 let _ = event_publisher.publish(domain_event).await;
             }
-            SwarmEvent::ConnectionClosed { peer_id, .. } => {
+            SwarmEvent::ConnectionClosed {
+                peer_id,
+                num_established,
+                ..
+            } => {
+                if num_established > 0 {
+                    connected_peers.insert(peer_id, num_established as usize);
+                    debug!(
+                        "Closed one connection to {} ({} still open)",
+                        peer_id, num_established
+                    );
+                    return Ok(());
+                }
+                // Only peers reported as connected are reported as gone
+                if connected_peers.remove(&peer_id).is_none() {
+                    return Ok(());
+                }
                 info!("Disconnected from peer: {}", peer_id);
 
-                // Remove peer
-                connected_peers.remove(&peer_id);
-
                 // Send internal event
                 let _ = event_tx.send(NetworkEvent::PeerDisconnected(peer_id));
 
@@ -393,20 +1694,35 @@ impl LibP2pNetworkService {
                 };
                 let _ = event_publisher.publish(domain_event).await;
             }
+            SwarmEvent::IncomingConnectionError {
+                send_back_addr,
+                error,
+                ..
+            } => {
+                warn!(
+                    "Refused incoming connection from {}: {}",
+                    send_back_addr, error
+                );
+            }
             SwarmEvent::Behaviour(CipherStreamBehaviourEvent::RequestResponse(event)) => {
-                Self::handle_request_response_event(event, event_tx).await?;
+                Self::handle_request_response_event(event, event_tx, inbound).await?;
+            }
+            SwarmEvent::Behaviour(CipherStreamBehaviourEvent::DirectMessage(event)) => {
+                Self::handle_direct_message_event(event, event_tx, inbound);
             }
             SwarmEvent::Behaviour(CipherStreamBehaviourEvent::Gossipsub(event)) => {
-                Self::handle_gossipsub_event(event, event_tx).await?;
+                Self::handle_gossipsub_event(event, event_tx, gossip_filter).await?;
             }
             SwarmEvent::Behaviour(CipherStreamBehaviourEvent::Identify(event)) => {
-                Self::handle_identify_event(event).await?;
+                Self::handle_identify_event(event, event_tx, event_publisher).await?;
             }
+            #[cfg(feature = "mdns")]
             SwarmEvent::Behaviour(CipherStreamBehaviourEvent::Mdns(event)) => {
-                Self::handle_mdns_event(event, event_tx, event_publisher, connected_peers).await?;
+                Self::handle_mdns_event(event, event_tx);
             }
+            #[cfg(feature = "dht")]
             SwarmEvent::Behaviour(CipherStreamBehaviourEvent::Kademlia(event)) => {
-                Self::handle_kademlia_event(event, event_tx, event_publisher).await?;
+                Self::handle_kademlia_event(event, event_tx);
             }
             _ => {}
         }
@@ -417,15 +1733,42 @@ impl LibP2pNetworkService {
     async fn handle_request_response_event(
         event: request_response::Event<ProtocolRequest, ProtocolResponse>,
         event_tx: &mpsc::UnboundedSender<NetworkEvent>,
+        inbound: &InboundRequests,
     ) -> DomainResult<()> {
         match event {
             request_response::Event::Message { peer, message, .. } => match message {
-                request_response::Message::Request { request, .. } => {
-                    println!("📥 Received file transfer request from {}", peer);
-                    let _ = event_tx.send(NetworkEvent::FileTransferRequest {
-                        from: peer,
-                        request,
-                    });
+                request_response::Message::Request {
+                    request, channel, ..
+                } => {
+                    debug!("Received file transfer request from {}", peer);
+                    if let Some(handler) = inbound.handler.clone() {
+                        // The slot covers the bandwidth wait as well as the handler
+                        let Some(slot) = handler.try_reserve(peer) else {
+                            warn!("Refusing request from {}: too many pending requests", peer);
+                            let response = ProtocolResponse::busy(&request);
+                            let _ = inbound.response_tx.send((channel, response));
+                            return Ok(());
+                        };
+                        // Disk I/O happens off the swarm task; the response is
+                        // sent back through the task's response channel
+                        let response_tx = inbound.response_tx.clone();
+                        let download_limit = inbound.download_limit.clone();
+                        tokio::spawn(async move {
+                            let _slot = slot;
+                            if let (Some(limit), ProtocolRequest::FileChunk { data, .. }) =
+                                (&download_limit, &request)
+                            {
+                                limit.acquire(data.len()).await;
+                            }
+                            let response = handler.handle_request(peer, request).await;
+                            let _ = response_tx.send((channel, response));
+                        });
+                    } else {
+                        let _ = event_tx.send(NetworkEvent::FileTransferRequest {
+                            from: peer,
+                            request,
+                        });
+                    }
                 }
                 request_response::Message::Response { response, .. } => {
                     info!("Received file transfer response from {}", peer);
@@ -448,17 +1791,62 @@ impl LibP2pNetworkService {
         Ok(())
     }
 
+    /// Handle direct message events, acknowledging each message received
+    fn handle_direct_message_event(
+        event: request_response::Event<Vec<u8>, ()>,
+        event_tx: &mpsc::UnboundedSender<NetworkEvent>,
+        inbound: &InboundRequests,
+    ) {
+        match event {
+            request_response::Event::Message {
+                peer,
+                message:
+                    request_response::Message::Request {
+                        request, channel, ..
+                    },
+                ..
+            } => {
+                debug!(
+                    "Received {} byte direct message from {}",
+                    request.len(),
+                    peer
+                );
+                let _ = event_tx.send(NetworkEvent::DirectMessage {
+                    from: peer,
+                    data: request,
+                });
+                let _ = inbound.ack_tx.send(channel);
+            }
+            request_response::Event::InboundFailure { peer, error, .. } => {
+                warn!("Inbound direct message from {} failed: {:?}", peer, error);
+            }
+            _ => {}
+        }
+    }
+
     /// Handle gossipsub events (peer discovery and messaging)
     async fn handle_gossipsub_event(
         event: gossipsub::Event,
         event_tx: &mpsc::UnboundedSender<NetworkEvent>,
+        gossip_filter: &mut GossipFilter,
     ) -> DomainResult<()> {
         match event {
             gossipsub::Event::Message {
                 propagation_source: source,
+                message_id,
                 message,
-                ..
             } => {
+                match gossip_filter.admit(message_id, &message) {
+                    Ok(()) => {}
+                    Err(GossipDrop::Duplicate) => {
+                        debug!("Dropping duplicate gossip message from {}", source);
+                        return Ok(());
+                    }
+                    Err(GossipDrop::Rejected(reason)) => {
+                        warn!("Dropping gossip message from {}: {}", source, reason);
+                        return Ok(());
+                    }
+                }
                 let topic = message.topic.as_str().to_string();
                 let _ = event_tx.send(NetworkEvent::GossipMessage {
                     from: source,
@@ -478,10 +1866,28 @@ impl LibP2pNetworkService {
     }
 
     /// Handle identify events
-    async fn handle_identify_event(event: identify::Event) -> DomainResult<()> {
+    async fn handle_identify_event(
+        event: identify::Event,
+        event_tx: &mpsc::UnboundedSender<NetworkEvent>,
+        event_publisher: &Arc<dyn EventPublisher>,
+    ) -> DomainResult<()> {
         match event {
             identify::Event::Received { peer_id, info, .. } => {
-                debug!("Identified peer {}: {}", peer_id, info.protocol_version);
+                info!(
+                    "Identified peer {}: {} ({})",
+                    peer_id, info.agent_version, info.protocol_version
+                );
+                let domain_event = DomainEvent::PeerIdentified {
+                    peer_id: DomainPeerId::new(peer_id.to_string()),
+                    addresses: info.listen_addrs.iter().map(|a| a.to_string()).collect(),
+                    protocols: info.protocols.iter().map(|p| p.to_string()).collect(),
+                };
+                let _ = event_publisher.publish(domain_event).await;
+                let _ = event_tx.send(NetworkEvent::PeerIdentified {
+                    peer: peer_id,
+                    agent_version: info.agent_version,
+                    protocol_version: info.protocol_version,
+                });
             }
             identify::Event::Sent { peer_id, .. } => {
                 debug!("Sent identify info to {}", peer_id);
@@ -491,57 +1897,28 @@ impl LibP2pNetworkService {
         Ok(())
     }
 
-    /// Handle mDNS events
-    async fn handle_mdns_event(
-        event: mdns::Event,
-        event_tx: &mpsc::UnboundedSender<NetworkEvent>,
-        event_publisher: &Arc<dyn EventPublisher>,
-        connected_peers: &mut HashMap<PeerId, Vec<Multiaddr>>,
-    ) -> DomainResult<()> {
+    /// Report peers found on the local network. Discovery alone doesn't make
+    /// a peer connected, nor does a record expiring disconnect it.
+    #[cfg(feature = "mdns")]
+    fn handle_mdns_event(event: mdns::Event, event_tx: &mpsc::UnboundedSender<NetworkEvent>) {
         match event {
             mdns::Event::Discovered(list) => {
                 for (peer_id, _) in list {
                     info!("mDNS discovered peer: {}", peer_id);
-
-                    // Send internal event
-                    let _ = event_tx.send(NetworkEvent::PeerConnected(peer_id));
-
-                    // Publish domain event
-                    let domain_peer_id = DomainPeerId::new(peer_id.to_string());
-                    let domain_event = DomainEvent::PeerConnected {
-                        peer_id: domain_peer_id,
-                    };
-                    let _ = event_publisher.publish(domain_event).await;
+                    let _ = event_tx.send(NetworkEvent::PeerDiscovered(peer_id));
                 }
             }
             mdns::Event::Expired(list) => {
                 for (peer_id, _) in list {
                     info!("mDNS peer expired: {}", peer_id);
-
-                    // Remove peer
-                    connected_peers.remove(&peer_id);
-
-                    // Send internal event
-                    let _ = event_tx.send(NetworkEvent::PeerDisconnected(peer_id));
-
-                    // Publish domain event
-                    let domain_peer_id = DomainPeerId::new(peer_id.to_string());
-                    let domain_event = DomainEvent::PeerDisconnected {
-                        peer_id: domain_peer_id,
-                    };
-                    let _ = event_publisher.publish(domain_event).await;
                 }
             }
         }
-        Ok(())
     }
 
     /// Handle Kademlia events
-    async fn handle_kademlia_event(
-        event: kad::Event,
-        event_tx: &mpsc::UnboundedSender<NetworkEvent>,
-        event_publisher: &Arc<dyn EventPublisher>,
-    ) -> DomainResult<()> {
+    #[cfg(feature = "dht")]
+    fn handle_kademlia_event(event: kad::Event, event_tx: &mpsc::UnboundedSender<NetworkEvent>) {
         match event {
             kad::Event::OutboundQueryProgressed { result, .. } => {
                 match result {
@@ -549,8 +1926,8 @@ impl LibP2pNetworkService {
                         peers, ..
                     })) => {
                         debug!("Kademlia found {} close peers", peers.len());
-                        for peer_info in peers {
-                            let _ = event_tx.send(NetworkEvent::PeerConnected(peer_info.peer_id));
+                        for peer_info in &peers {
+                            let _ = event_tx.send(NetworkEvent::PeerDiscovered(peer_info.peer_id));
                         }
                     }
                     kad::QueryResult::Bootstrap(Ok(kad::BootstrapOk { num_remaining, .. })) => {
@@ -569,18 +1946,13 @@ impl LibP2pNetworkService {
                     _ => {} // Handle other query results as needed
                 }
             }
-            kad::Event::RoutingUpdated { peer, .. } => {
+            kad::Event::RoutingUpdated {
+                peer, is_new_peer, ..
+            } => {
                 debug!("Kademlia routing table updated for peer: {}", peer);
-
-                // Send internal event
-                let _ = event_tx.send(NetworkEvent::PeerConnected(peer));
-
-                // Publish domain event
-                let domain_peer_id = DomainPeerId::new(peer.to_string());
-                let domain_event = DomainEvent::PeerConnected {
-                    peer_id: domain_peer_id,
-                };
-                let _ = event_publisher.publish(domain_event).await;
+                if is_new_peer {
+                    let _ = event_tx.send(NetworkEvent::PeerDiscovered(peer));
+                }
             }
             kad::Event::InboundRequest {
                 request:
@@ -595,7 +1967,6 @@ impl LibP2pNetworkService {
             }
             _ => {} // Handle other Kademlia events as needed
         }
-        Ok(())
     }
 
     /// Connect to a specific peer
@@ -606,15 +1977,172 @@ impl LibP2pNetworkService {
         Ok(())
     }
 
-    /// Send a file transfer request
-    pub async fn send_file_request(
+    /// Dial an address and wait for the connection, up to the configured timeout
+    pub async fn connect_and_wait(&self, addr: Multiaddr) -> DomainResult<PeerId> {
+        let (reply, response) = oneshot::channel();
+        self.command_tx
+            .send(NetworkCommand::Dial {
+                addr: addr.clone(),
+                reply,
+            })
+            .map_err(|e| format!("Failed to send dial command: {}", e))?;
+
+        match tokio::time::timeout(self.connection_timeout, response).await {
+            Ok(Ok(result)) => result,
+            Ok(Err(_)) => Err("Network service stopped before the dial completed".into()),
+            Err(_) => Err(format!("Timed out connecting to {}", addr).into()),
+        }
+    }
+
+    /// Addresses the swarm is currently listening on
+    pub async fn listen_addresses(&self) -> DomainResult<Vec<Multiaddr>> {
+        let (reply, response) = oneshot::channel();
+        self.command_tx
+            .send(NetworkCommand::GetListenAddresses(reply))
+            .map_err(|e| format!("Failed to send listen address query: {}", e))?;
+        response
+            .await
+            .map_err(|_| "Network service stopped before replying".into())
+    }
+
+    /// Peers currently in the Kademlia routing table
+    pub async fn routing_table_peers(&self) -> DomainResult<Vec<PeerId>> {
+        let (reply, response) = oneshot::channel();
+        self.command_tx
+            .send(NetworkCommand::GetRoutingTablePeers(reply))
+            .map_err(|e| format!("Failed to send routing table query: {}", e))?;
+        response
+            .await
+            .map_err(|_| "Network service stopped before replying".into())
+    }
+
+    /// Number of open connections to each connected peer. A peer reachable
+    /// over several transports or addresses may hold more than one.
+    pub async fn connection_counts(&self) -> DomainResult<HashMap<PeerId, usize>> {
+        let (reply, response) = oneshot::channel();
+        self.command_tx
+            .send(NetworkCommand::GetConnectionCounts(reply))
+            .map_err(|e| format!("Failed to send connection count query: {}", e))?;
+        response
+            .await
+            .map_err(|_| "Network service stopped before replying".into())
+    }
+
+    /// Listeners, transports, relay reservations and reachability in one
+    /// snapshot
+    pub async fn status(&self) -> DomainResult<NetworkStatus> {
+        let (reply, response) = oneshot::channel();
+        self.command_tx
+            .send(NetworkCommand::GetStatus(reply))
+            .map_err(|e| format!("Failed to send status query: {}", e))?;
+        response
+            .await
+            .map_err(|_| "Network service stopped before replying".into())
+    }
+
+    /// Close every connection to `peer_id`
+    pub async fn disconnect_peer(&self, peer_id: PeerId) -> DomainResult<()> {
+        self.command_tx
+            .send(NetworkCommand::DisconnectPeer(peer_id))
+            .map_err(|e| format!("Failed to send disconnect command: {}", e))?;
+        Ok(())
+    }
+
+    /// Refuse connections to `peer_id` until `until`, or until unbanned if
+    /// `None`, closing any that are open. Bans made here last as long as the
+    /// service; store them in a [`PeerRepository`](crate::core::traits::PeerRepository)
+    /// to keep them across restarts.
+    pub async fn ban_peer(&self, peer_id: PeerId, until: Option<SystemTime>) -> DomainResult<()> {
+        let until = until.map(|until| {
+            Instant::now() + until.duration_since(SystemTime::now()).unwrap_or_default()
+        });
+        self.command_tx
+            .send(NetworkCommand::BanPeer { peer_id, until })
+            .map_err(|e| format!("Failed to send ban command: {}", e))?;
+        Ok(())
+    }
+
+    /// Accept connections from `peer_id` again
+    pub async fn unban_peer(&self, peer_id: PeerId) -> DomainResult<()> {
+        self.command_tx
+            .send(NetworkCommand::UnbanPeer(peer_id))
+            .map_err(|e| format!("Failed to send unban command: {}", e))?;
+        Ok(())
+    }
+
+    /// Send a file transfer request
+    pub async fn send_file_request(
+        &self,
+        peer_id: PeerId,
+        request: ProtocolRequest,
+    ) -> DomainResult<()> {
+        self.command_tx
+            .send(NetworkCommand::SendFileRequest { peer_id, request })
+            .map_err(|e| format!("Failed to send file request command: {}", e))?;
+        Ok(())
+    }
+
+    /// Send a file transfer request and wait for the peer's response
+    pub async fn request(
+        &self,
+        peer_id: PeerId,
+        request: ProtocolRequest,
+    ) -> DomainResult<ProtocolResponse> {
+        let chunk_bytes = match &request {
+            ProtocolRequest::FileChunk { data, .. } => Some(data.len() as u64),
+            _ => None,
+        };
+        if let (Some(limit), Some(bytes)) = (&self.upload_limit, chunk_bytes) {
+            limit.acquire(bytes as usize).await;
+        }
+        let started = std::time::Instant::now();
+
+        let (reply, response) = oneshot::channel();
+        self.command_tx
+            .send(NetworkCommand::Request {
+                peer_id,
+                request,
+                reply,
+            })
+            .map_err(|e| format!("Failed to send request command: {}", e))?;
+        let response = response
+            .await
+            .map_err(|_| "Network service stopped before the response arrived")??;
+
+        // Acknowledged chunks give a running measure of transfer throughput
+        if let Some(bytes) = chunk_bytes {
+            self.record_transfer_throughput(peer_id, bytes, started.elapsed())
+                .await;
+        }
+        Ok(response)
+    }
+
+    /// Record data moved to or from `peer` for its quality score
+    pub async fn record_transfer_throughput(&self, peer: PeerId, bytes: u64, elapsed: Duration) {
+        self.scores.write().await.record_throughput(
+            peer,
+            bytes,
+            elapsed,
+            std::time::Instant::now(),
+        );
+    }
+
+    /// Up to `n` known peers ordered from most to least reliable
+    pub async fn best_peers(&self, n: usize) -> Vec<PeerId> {
+        self.scores
+            .read()
+            .await
+            .best_peers(n, std::time::Instant::now())
+    }
+
+    /// Answer inbound file transfer requests with `handler`
+    pub async fn serve_file_transfers(
         &self,
-        peer_id: PeerId,
-        request: ProtocolRequest,
+        handler: Arc<FileTransferHandler>,
     ) -> DomainResult<()> {
         self.command_tx
-            .send(NetworkCommand::SendFileRequest { peer_id, request })
-            .map_err(|e| format!("Failed to send file request command: {}", e))?;
+            .send(NetworkCommand::SetFileHandler(handler))
+            .map_err(|e| format!("Failed to send file handler command: {}", e))?;
         Ok(())
     }
 
@@ -626,15 +2154,74 @@ impl LibP2pNetworkService {
         Ok(())
     }
 
-    /// Publish a message to a gossipsub topic
-    pub async fn publish_message(&self, topic: &str, data: Vec<u8>) -> DomainResult<()> {
+    /// Publish a message to a gossipsub topic, returning the number of
+    /// subscribed peers it was sent to.
+    ///
+    /// Fails with [`NetworkError::NoGossipPeers`] when no connected peer is
+    /// subscribed to `topic`.
+    pub async fn publish_message(&self, topic: &str, data: Vec<u8>) -> DomainResult<usize> {
+        let (reply, response) = oneshot::channel();
         self.command_tx
-            .send(NetworkCommand::PublishMessage {
+            .send(NetworkCommand::Publish {
                 topic: topic.to_string(),
                 data,
+                reply,
             })
             .map_err(|e| format!("Failed to send publish command: {}", e))?;
-        Ok(())
+
+        match response
+            .await
+            .map_err(|_| "Network service stopped before publishing")?
+        {
+            Ok(peers) => {
+                info!("Published message to topic {} ({} peers)", topic, peers);
+                Ok(peers)
+            }
+            Err(gossipsub::PublishError::InsufficientPeers) => Err(NetworkError::NoGossipPeers {
+                topic: topic.to_string(),
+            }
+            .into()),
+            Err(e) => Err(format!("Failed to publish message: {}", e).into()),
+        }
+    }
+
+    /// Send `data` to `peer_id` alone and wait for it to acknowledge receipt
+    pub async fn send_direct_message(&self, peer_id: PeerId, data: Vec<u8>) -> DomainResult<()> {
+        let (reply, response) = oneshot::channel();
+        self.command_tx
+            .send(NetworkCommand::SendDirectMessage {
+                peer_id,
+                data,
+                reply,
+            })
+            .map_err(|e| format!("Failed to send direct message command: {}", e))?;
+        response
+            .await
+            .map_err(|_| "Network service stopped before the message was acknowledged")?
+    }
+
+    /// Publish to a gossipsub topic, waiting up to `wait` for a subscribed
+    /// peer to appear
+    pub async fn publish_when_subscribed(
+        &self,
+        topic: &str,
+        data: Vec<u8>,
+        wait: Duration,
+    ) -> DomainResult<usize> {
+        let deadline = Instant::now() + wait;
+        loop {
+            match self.publish_message(topic, data.clone()).await {
+                Err(e)
+                    if matches!(
+                        e.downcast_ref::<NetworkError>(),
+                        Some(NetworkError::NoGossipPeers { .. })
+                    ) && Instant::now() < deadline =>
+                {
+                    tokio::time::sleep(PUBLISH_RETRY_INTERVAL).await;
+                }
+                result => return result,
+            }
+        }
     }
 
     /// Get connected peers (simplified - would need event-based tracking in real implementation)
@@ -675,7 +2262,8 @@ impl LibP2pNetworkService {
         Ok(())
     }
 
-    /// Start mDNS discovery (automatically enabled)
+    /// Resume mDNS discovery after [`stop_mdns_discovery`](Self::stop_mdns_discovery);
+    /// it is on from startup
     pub async fn start_mdns_discovery(&self) -> DomainResult<()> {
         self.command_tx
             .send(NetworkCommand::StartMdnsDiscovery)
@@ -683,6 +2271,20 @@ impl LibP2pNetworkService {
         Ok(())
     }
 
+    /// Stop discovering peers and advertising this node over mDNS, e.g. on
+    /// an untrusted LAN
+    pub async fn stop_mdns_discovery(&self) -> DomainResult<()> {
+        self.command_tx
+            .send(NetworkCommand::StopMdnsDiscovery)
+            .map_err(|e| format!("Failed to send mDNS stop command: {}", e))?;
+        Ok(())
+    }
+
+    /// Wait for the next network event, or `None` once the swarm task stops
+    pub async fn next_event(&self) -> Option<NetworkEvent> {
+        self.event_rx.lock().await.recv().await
+    }
+
     /// Collect network events for a fixed duration and return them.
     /// This is useful for short-lived discovery flows from the CLI.
     pub async fn collect_events_for(&self, duration: Duration) -> Vec<NetworkEvent> {
@@ -715,31 +2317,79 @@ impl NetworkService for LibP2pNetworkService {
         self.start(port).await
     }
 
+    async fn connect(&self, addr: Multiaddr) -> DomainResult<DomainPeerId> {
+        self.connect_and_wait(addr).await.map(DomainPeerId::from)
+    }
+
     async fn send_message(
         &self,
         peer_id: &crate::core::domain::PeerId,
         message: Vec<u8>,
     ) -> DomainResult<()> {
-        let _libp2p_peer_id: PeerId = peer_id
+        let peer_id: PeerId = peer_id
             .id
             .parse()
             .map_err(|e| format!("Invalid peer ID: {}", e))?;
-
-        // For now, we'll use gossipsub for general messaging
-        self.publish_message("cipherstream-messages", message).await
+        self.send_direct_message(peer_id, message).await
     }
 
     async fn broadcast_message(&self, message: Vec<u8>) -> DomainResult<()> {
         self.publish_message("cipherstream-broadcast", message)
             .await
+            .map(|_| ())
+    }
+
+    async fn broadcast_to_topic(&self, topic: &str, data: Vec<u8>) -> DomainResult<usize> {
+        self.publish_message(topic, data).await
+    }
+
+    async fn shutdown(&self) -> DomainResult<()> {
+        let (reply, response) = oneshot::channel();
+        if self
+            .command_tx
+            .send(NetworkCommand::Shutdown(reply))
+            .is_err()
+        {
+            // Already stopped
+            return Ok(());
+        }
+        let _ = response.await;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl TransferTransport for LibP2pNetworkService {
+    async fn send_request(
+        &self,
+        peer: PeerId,
+        request: ProtocolRequest,
+    ) -> DomainResult<ProtocolResponse> {
+        self.request(peer, request).await
     }
 }
 
+/// A message delivered to a [`SimpleNetworkService`] topic subscriber, as
+/// `(sender peer id, data)`
+pub type TopicMessage = (String, Vec<u8>);
+
+/// A subscriber's peer id and where to deliver its messages
+type TopicSubscriber = (String, mpsc::UnboundedSender<TopicMessage>);
+
+/// Topic subscriptions shared between [`SimpleNetworkService`] instances.
+/// Instances built on the same registry deliver published messages to each
+/// other in-process, standing in for a gossipsub mesh.
+#[derive(Clone, Default)]
+pub struct TopicRegistry {
+    topics: Arc<RwLock<HashMap<String, Vec<TopicSubscriber>>>>,
+}
+
 // Simple implementation of NetworkService for testing/fallback
 pub struct SimpleNetworkService {
     local_peer_id: String,
     connected_peers: Arc<RwLock<HashMap<String, Vec<String>>>>,
     event_publisher: Option<Arc<dyn EventPublisher>>,
+    topics: TopicRegistry,
 }
 
 impl SimpleNetworkService {
@@ -748,6 +2398,7 @@ impl SimpleNetworkService {
             local_peer_id: format!("peer-{}", uuid::Uuid::new_v4()),
             connected_peers: Arc::new(RwLock::new(HashMap::new())),
             event_publisher: None,
+            topics: TopicRegistry::default(),
         }
     }
 
@@ -756,6 +2407,58 @@ impl SimpleNetworkService {
         self
     }
 
+    /// Share topic subscriptions with other instances built on `topics`
+    pub fn with_topic_registry(mut self, topics: TopicRegistry) -> Self {
+        self.topics = topics;
+        self
+    }
+
+    /// Subscribe to a topic, returning the messages other instances publish
+    /// to it
+    pub async fn subscribe_topic(
+        &self,
+        topic: &str,
+    ) -> DomainResult<mpsc::UnboundedReceiver<TopicMessage>> {
+        let (tx, rx) = mpsc::unbounded_channel();
+        self.topics
+            .topics
+            .write()
+            .await
+            .entry(topic.to_string())
+            .or_default()
+            .push((self.local_peer_id.clone(), tx));
+        Ok(rx)
+    }
+
+    /// Publish a message to every other instance subscribed to `topic`,
+    /// returning how many received it.
+    ///
+    /// Like gossipsub, our own subscriptions are skipped, and the publish
+    /// fails with [`NetworkError::NoGossipPeers`] when nobody else listens.
+    pub async fn publish_message(&self, topic: &str, data: Vec<u8>) -> DomainResult<usize> {
+        let mut topics = self.topics.topics.write().await;
+        let subscribers = topics.entry(topic.to_string()).or_default();
+        // Drop subscribers whose receiver is gone
+        subscribers.retain(|(_, tx)| !tx.is_closed());
+
+        let mut delivered = 0;
+        for (peer_id, tx) in subscribers.iter() {
+            if *peer_id != self.local_peer_id
+                && tx.send((self.local_peer_id.clone(), data.clone())).is_ok()
+            {
+                delivered += 1;
+            }
+        }
+        if delivered == 0 {
+            return Err(NetworkError::NoGossipPeers {
+                topic: topic.to_string(),
+            }
+            .into());
+        }
+        debug!("Published message to topic {} ({} peers)", topic, delivered);
+        Ok(delivered)
+    }
+
     /// Get connected peers
     pub async fn get_connected_peers(&self) -> Vec<String> {
         let peers = self.connected_peers.read().await;
@@ -806,6 +2509,26 @@ impl NetworkService for SimpleNetworkService {
         Ok(())
     }
 
+    async fn connect(&self, addr: Multiaddr) -> DomainResult<DomainPeerId> {
+        let peer_id = peer_id_from_addr(&addr)
+            .map(DomainPeerId::from)
+            .ok_or_else(|| format!("Address {} has no /p2p/ peer id", addr))?;
+
+        {
+            let mut peers = self.connected_peers.write().await;
+            peers.insert(peer_id.as_str().to_string(), vec![addr.to_string()]);
+        }
+
+        if let Some(ref publisher) = self.event_publisher {
+            let domain_event = DomainEvent::PeerConnected {
+                peer_id: peer_id.clone(),
+            };
+            let _ = publisher.publish(domain_event).await;
+        }
+
+        Ok(peer_id)
+    }
+
     async fn send_message(
         &self,
         peer_id: &crate::core::domain::PeerId,
@@ -819,6 +2542,18 @@ impl NetworkService for SimpleNetworkService {
         debug!("Broadcasting message");
         Ok(())
     }
+
+    async fn broadcast_to_topic(&self, topic: &str, data: Vec<u8>) -> DomainResult<usize> {
+        self.publish_message(topic, data).await
+    }
+}
+
+/// Extract the `/p2p/` peer id component from a multiaddr, if present
+pub(crate) fn peer_id_from_addr(addr: &Multiaddr) -> Option<PeerId> {
+    addr.iter().find_map(|p| match p {
+        libp2p::multiaddr::Protocol::P2p(peer_id) => Some(peer_id),
+        _ => None,
+    })
 }
 
 /// Message ID function for gossipsub
@@ -834,6 +2569,212 @@ fn message_id_fn(message: &gossipsub::Message) -> gossipsub::MessageId {
 mod tests {
     use super::*;
     use crate::infrastructure::events::InMemoryEventPublisher;
+    use crate::testing::loopback_addr;
+
+    #[test]
+    fn test_failed_bootstrap_is_retried_with_backoff() {
+        let now = Instant::now();
+        let mut bootstrap = BootstrapRetry::new(Duration::from_secs(1), 3);
+        assert_eq!(bootstrap.next_attempt(), None);
+
+        bootstrap.connectivity_changed(now);
+        assert_eq!(bootstrap.next_attempt(), Some(now));
+
+        bootstrap.record_attempt(false, now);
+        assert_eq!(bootstrap.next_attempt(), Some(now + Duration::from_secs(1)));
+
+        bootstrap.record_attempt(true, now);
+        assert_eq!(bootstrap.next_attempt(), None);
+        bootstrap.record_failure(now);
+        assert_eq!(bootstrap.next_attempt(), Some(now + Duration::from_secs(2)));
+
+        bootstrap.record_attempt(false, now);
+        assert_eq!(
+            bootstrap.next_attempt(),
+            None,
+            "gives up after max attempts"
+        );
+
+        bootstrap.connectivity_changed(now);
+        assert_eq!(bootstrap.next_attempt(), Some(now), "connectivity resets");
+
+        bootstrap.record_attempt(true, now);
+        bootstrap.record_success();
+        bootstrap.connectivity_changed(now);
+        assert_eq!(bootstrap.next_attempt(), None);
+    }
+
+    #[test]
+    fn test_network_event_dto_serializes_every_variant() {
+        let peer = PeerId::random();
+        let events = [
+            (NetworkEvent::PeerConnected(peer), "peer_connected"),
+            (NetworkEvent::PeerDisconnected(peer), "peer_disconnected"),
+            (NetworkEvent::PeerDiscovered(peer), "peer_discovered"),
+            (
+                NetworkEvent::PeerIdentified {
+                    peer,
+                    agent_version: "cipherstream/0.1.0".to_string(),
+                    protocol_version: "/cipherstream/1.0.0".to_string(),
+                },
+                "peer_identified",
+            ),
+            (
+                NetworkEvent::FileTransferRequest {
+                    from: peer,
+                    request: ProtocolRequest::FileChunk {
+                        transfer_id: "t".to_string(),
+                        chunk_index: 0,
+                        total_chunks: 1,
+                        offset: 0,
+                        data: vec![0; 3],
+                        is_last: true,
+                        proof: Vec::new(),
+                    },
+                },
+                "file_transfer_request",
+            ),
+            (
+                NetworkEvent::FileTransferResponse {
+                    from: peer,
+                    response: ProtocolResponse::TransferComplete {
+                        transfer_id: "t".to_string(),
+                        success: true,
+                        error: None,
+                    },
+                },
+                "file_transfer_response",
+            ),
+            (
+                NetworkEvent::GossipMessage {
+                    from: peer,
+                    topic: "news".to_string(),
+                    data: b"hello".to_vec(),
+                },
+                "gossip_message",
+            ),
+            (
+                NetworkEvent::DirectMessage {
+                    from: peer,
+                    data: b"hi".to_vec(),
+                },
+                "direct_message",
+            ),
+            (
+                NetworkEvent::TransferProgress {
+                    from: peer,
+                    transfer_id: "t".to_string(),
+                    progress: TransferProgress::new(10, 1),
+                },
+                "transfer_progress",
+            ),
+        ];
+
+        for (event, kind) in &events {
+            let json = serde_json::to_value(NetworkEventDto::from(event)).unwrap();
+            assert_eq!(json["type"], *kind);
+            assert_eq!(json["peer"], peer.to_string());
+        }
+
+        let gossip = serde_json::to_value(NetworkEventDto::from(&events[6].0)).unwrap();
+        assert_eq!(gossip["topic"], "news");
+        assert_eq!(gossip["bytes"], 5);
+        let connected = serde_json::to_value(NetworkEventDto::from(&events[0].0)).unwrap();
+        assert!(connected.get("topic").is_none());
+        assert!(connected.get("bytes").is_none());
+    }
+
+    #[test]
+    fn test_dht_refresh_runs_repeatedly_while_connected() {
+        let start = Instant::now();
+        let interval = Duration::from_millis(10);
+        let mut refresh = DhtRefresh::new(interval, start);
+
+        let mut runs = 0;
+        for _ in 0..3 {
+            let due = refresh.next_refresh().expect("refresh scheduled");
+            if refresh.tick(due, true) {
+                runs += 1;
+            }
+        }
+        assert_eq!(runs, 3);
+        assert_eq!(refresh.next_refresh(), Some(start + interval * 4));
+
+        let due = refresh.next_refresh().unwrap();
+        assert!(!refresh.tick(due, false), "skipped while disconnected");
+        assert!(refresh.next_refresh().is_some());
+
+        assert_eq!(DhtRefresh::new(Duration::ZERO, start).next_refresh(), None);
+    }
+
+    /// Feed `messages` through the gossip handler and count what is delivered
+    async fn deliver(filter: &mut GossipFilter, messages: &[(&str, Vec<u8>)]) -> usize {
+        let (event_tx, mut event_rx) = mpsc::unbounded_channel();
+        for (topic, data) in messages {
+            let message = gossipsub::Message {
+                source: None,
+                data: data.clone(),
+                sequence_number: None,
+                topic: gossipsub::IdentTopic::new(*topic).hash(),
+            };
+            let event = gossipsub::Event::Message {
+                propagation_source: PeerId::random(),
+                message_id: message_id_fn(&message),
+                message,
+            };
+            LibP2pNetworkService::handle_gossipsub_event(event, &event_tx, filter)
+                .await
+                .unwrap();
+        }
+        drop(event_tx);
+
+        let mut delivered = 0;
+        while let Some(event) = event_rx.recv().await {
+            assert!(matches!(event, NetworkEvent::GossipMessage { .. }));
+            delivered += 1;
+        }
+        delivered
+    }
+
+    #[tokio::test]
+    async fn test_duplicate_gossip_messages_are_dropped() {
+        let mut filter = GossipFilter::new(&AppConfig::default());
+        let update = ("catalog", b"catalog update".to_vec());
+        assert_eq!(deliver(&mut filter, &[update.clone(), update]).await, 1);
+    }
+
+    #[tokio::test]
+    async fn test_oversized_gossip_message_is_dropped() {
+        let config = AppConfig {
+            max_gossip_message_bytes: 8,
+            ..AppConfig::default()
+        };
+        let mut filter = GossipFilter::new(&config);
+        let messages = [("catalog", vec![0; 9]), ("catalog", vec![1; 8])];
+        assert_eq!(deliver(&mut filter, &messages).await, 1);
+    }
+
+    #[tokio::test]
+    async fn test_gossip_on_disallowed_topic_is_dropped() {
+        let config = AppConfig {
+            allowed_topics: Some(vec!["catalog".to_string()]),
+            ..AppConfig::default()
+        };
+        let mut filter = GossipFilter::new(&config);
+        let messages = [("chatter", b"hi".to_vec()), ("catalog", b"hi".to_vec())];
+        assert_eq!(deliver(&mut filter, &messages).await, 1);
+    }
+
+    #[test]
+    fn test_seen_messages_evicts_oldest() {
+        let mut seen = SeenMessages::new(2);
+        let id = |n: u8| gossipsub::MessageId::from(vec![n]);
+        assert!(seen.insert(id(1)));
+        assert!(seen.insert(id(2)));
+        assert!(!seen.insert(id(1)));
+        assert!(seen.insert(id(3)));
+        assert!(seen.insert(id(1)), "oldest id was evicted");
+    }
 
     #[tokio::test]
     async fn test_simple_network_service() {
@@ -855,6 +2796,36 @@ mod tests {
         assert_eq!(events.len(), 1);
     }
 
+    #[tokio::test]
+    async fn test_simple_network_services_share_topics_through_registry() {
+        let topics = TopicRegistry::default();
+        let publisher = SimpleNetworkService::new().with_topic_registry(topics.clone());
+        let subscriber = SimpleNetworkService::new().with_topic_registry(topics);
+        let mut messages = subscriber.subscribe_topic("news").await.unwrap();
+        // Our own subscription never receives what we publish
+        let mut own = publisher.subscribe_topic("news").await.unwrap();
+
+        let delivered = publisher
+            .broadcast_to_topic("news", b"hello".to_vec())
+            .await
+            .unwrap();
+        assert_eq!(delivered, 1);
+        assert_eq!(
+            messages.recv().await,
+            Some((publisher.local_peer_id().to_string(), b"hello".to_vec()))
+        );
+        assert!(own.try_recv().is_err());
+
+        let err = publisher
+            .publish_message("sports", b"score".to_vec())
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<NetworkError>(),
+            Some(NetworkError::NoGossipPeers { .. })
+        ));
+    }
+
     #[tokio::test]
     async fn test_libp2p_network_service_creation() {
         let config = Arc::new(crate::infrastructure::config::AppConfig::default());
@@ -871,6 +2842,42 @@ mod tests {
         }
     }
 
+    #[tokio::test]
+    async fn test_simple_network_service_connect_uses_address_peer_id() {
+        let service = SimpleNetworkService::new();
+        let peer = PeerId::random();
+        let addr: Multiaddr = format!("/ip4/127.0.0.1/tcp/9/p2p/{}", peer)
+            .parse()
+            .unwrap();
+
+        let connected = service.connect(addr).await.unwrap();
+        assert_eq!(connected.as_str(), peer.to_string());
+        assert!(
+            service
+                .connect("/ip4/127.0.0.1/tcp/9".parse().unwrap())
+                .await
+                .is_err()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_connect_returns_remote_peer_id() {
+        let config = Arc::new(crate::infrastructure::config::AppConfig::default());
+        let dialer =
+            LibP2pNetworkService::new(config.clone(), Arc::new(InMemoryEventPublisher::new()))
+                .await
+                .unwrap();
+        let listener = LibP2pNetworkService::new(config, Arc::new(InMemoryEventPublisher::new()))
+            .await
+            .unwrap();
+
+        listener.start_listening(0).await.unwrap();
+        let addr = loopback_addr(&listener).await;
+
+        let peer = NetworkService::connect(&dialer, addr).await.unwrap();
+        assert_eq!(peer.as_str(), listener.local_peer_id().to_string());
+    }
+
     #[tokio::test]
     async fn test_libp2p_network_service_topics() {
         let config = Arc::new(crate::infrastructure::config::AppConfig::default());
@@ -878,12 +2885,479 @@ mod tests {
 
         if let Ok(service) = LibP2pNetworkService::new(config, event_publisher).await {
             assert!(service.subscribe_topic("test-topic").await.is_ok());
-            assert!(
-                service
-                    .publish_message("test-topic", b"test message".to_vec())
-                    .await
-                    .is_ok()
-            );
         }
     }
+
+    #[tokio::test]
+    async fn test_connections_beyond_max_connections_are_refused() {
+        let mut config = AppConfig::default();
+        config.network.max_connections = 1;
+        let listener =
+            LibP2pNetworkService::new(Arc::new(config), Arc::new(InMemoryEventPublisher::new()))
+                .await
+                .unwrap();
+        listener.start_listening(0).await.unwrap();
+        let addr = loopback_addr(&listener).await;
+
+        let mut dialers = Vec::new();
+        for _ in 0..3 {
+            let dialer = LibP2pNetworkService::new(
+                Arc::new(AppConfig::default()),
+                Arc::new(InMemoryEventPublisher::new()),
+            )
+            .await
+            .unwrap();
+            // The handshake completes before the listener applies its limit,
+            // so refused dials still report success and are then closed
+            let _ = dialer.connect_and_wait(addr.clone()).await;
+            dialers.push(dialer);
+        }
+
+        let mut dropped = Vec::new();
+        for dialer in &dialers {
+            let events = dialer.collect_events_for(Duration::from_millis(300)).await;
+            dropped.push(events.iter().any(|event| {
+                matches!(event, NetworkEvent::PeerDisconnected(peer) if *peer == listener.local_peer_id())
+            }));
+        }
+        assert_eq!(dropped, vec![false, true, true]);
+    }
+
+    #[tokio::test]
+    async fn test_connections_beyond_per_peer_cap_are_refused() {
+        let mut config = AppConfig::default();
+        config.network.max_connections_per_peer = 2;
+        let listener =
+            LibP2pNetworkService::new(Arc::new(config), Arc::new(InMemoryEventPublisher::new()))
+                .await
+                .unwrap();
+        listener.start_listening(0).await.unwrap();
+        let addr = loopback_addr(&listener).await;
+
+        // Let the dialer open more connections than the listener accepts
+        let mut config = AppConfig::default();
+        config.network.max_connections_per_peer = 10;
+        let dialer =
+            LibP2pNetworkService::new(Arc::new(config), Arc::new(InMemoryEventPublisher::new()))
+                .await
+                .unwrap();
+        for _ in 0..4 {
+            let _ = dialer.connect_and_wait(addr.clone()).await;
+        }
+
+        // Refused connections close while two stay open, so the peer is
+        // never reported as disconnected
+        let events = dialer.collect_events_for(Duration::from_millis(300)).await;
+        assert!(!events.iter().any(|event| {
+            matches!(event, NetworkEvent::PeerDisconnected(peer) if *peer == listener.local_peer_id())
+        }));
+        let counts = dialer.connection_counts().await.unwrap();
+        assert_eq!(counts.get(&listener.local_peer_id()), Some(&2));
+    }
+
+    #[tokio::test]
+    async fn test_second_connection_to_a_peer_emits_no_duplicate_events() {
+        let listener = LibP2pNetworkService::new(
+            Arc::new(AppConfig::default()),
+            Arc::new(InMemoryEventPublisher::new()),
+        )
+        .await
+        .unwrap();
+        listener.start_listening(0).await.unwrap();
+        let addr = loopback_addr(&listener).await;
+        let dialer = LibP2pNetworkService::new(
+            Arc::new(AppConfig::default()),
+            Arc::new(InMemoryEventPublisher::new()),
+        )
+        .await
+        .unwrap();
+
+        dialer.connect_and_wait(addr.clone()).await.unwrap();
+        dialer.connect_and_wait(addr).await.unwrap();
+        let listener_id = listener.local_peer_id();
+        let connected = |events: &[NetworkEvent]| {
+            events
+                .iter()
+                .filter(|event| matches!(event, NetworkEvent::PeerConnected(peer) if *peer == listener_id))
+                .count()
+        };
+
+        let events = dialer.collect_events_for(Duration::from_millis(300)).await;
+        assert_eq!(connected(&events), 1);
+        assert_eq!(
+            dialer.connection_counts().await.unwrap().get(&listener_id),
+            Some(&2)
+        );
+
+        // Losing every connection reports a single disconnect
+        listener.shutdown().await.unwrap();
+        let events = dialer.collect_events_for(Duration::from_millis(300)).await;
+        let disconnected = events
+            .iter()
+            .filter(|event| matches!(event, NetworkEvent::PeerDisconnected(peer) if *peer == listener_id))
+            .count();
+        assert_eq!(disconnected, 1);
+        assert!(dialer.connection_counts().await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_nodes_with_overlapping_port_ranges_pick_distinct_ports() {
+        let new_node = || async {
+            LibP2pNetworkService::new(
+                Arc::new(AppConfig::default()),
+                Arc::new(InMemoryEventPublisher::new()),
+            )
+            .await
+            .unwrap()
+        };
+        let (first, second) = (new_node().await, new_node().await);
+        // Away from the ephemeral range so other tests don't take these
+        let ports = 24_310..=24_339;
+
+        let a = first.start_in_range(ports.clone()).await.unwrap();
+        let b = second.start_in_range(ports.clone()).await.unwrap();
+        assert!(ports.contains(&a) && ports.contains(&b), "{} {}", a, b);
+        assert_ne!(a, b);
+
+        // Port 0 asks the OS for any free port
+        let third = new_node().await;
+        assert_ne!(third.start_in_range(0..=0).await.unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_status_reports_tcp_listener_after_start() {
+        let node = LibP2pNetworkService::new(
+            Arc::new(AppConfig::default()),
+            Arc::new(InMemoryEventPublisher::new()),
+        )
+        .await
+        .unwrap();
+        node.start_listening(0).await.unwrap();
+        let listener = loopback_addr(&node).await;
+
+        let status = node.status().await.unwrap();
+        assert_eq!(status.local_peer_id, node.local_peer_id());
+        assert!(status.listen_addresses.contains(&listener));
+        assert_eq!(status.transports, vec!["tcp".to_string()]);
+        assert!(status.relay_reservations.is_empty());
+        assert_eq!(status.connected_peers, 0);
+        assert_eq!(status.reachability, Reachability::Unknown);
+    }
+
+    #[cfg(feature = "mdns")]
+    #[tokio::test]
+    async fn test_stopped_mdns_reports_no_discoveries() {
+        let new_node = || {
+            LibP2pNetworkService::new(
+                Arc::new(AppConfig::default()),
+                Arc::new(InMemoryEventPublisher::new()),
+            )
+        };
+        let quiet = new_node().await.unwrap();
+        let neighbour = new_node().await.unwrap();
+        assert!(quiet.status().await.unwrap().mdns_enabled);
+
+        quiet.stop_mdns_discovery().await.unwrap();
+        assert!(!quiet.status().await.unwrap().mdns_enabled);
+        quiet.start_listening(0).await.unwrap();
+        neighbour.start_listening(0).await.unwrap();
+
+        // Neither side learns of the other: the quiet node neither queries
+        // nor answers
+        let (quiet_events, neighbour_events) = tokio::join!(
+            quiet.collect_events_for(Duration::from_secs(3)),
+            neighbour.collect_events_for(Duration::from_secs(3)),
+        );
+        let saw = |events: &[NetworkEvent], peer: PeerId| {
+            events
+                .iter()
+                .any(|event| matches!(event, NetworkEvent::PeerDiscovered(p) if *p == peer))
+        };
+        assert!(!saw(&quiet_events, neighbour.local_peer_id()));
+        assert!(!saw(&neighbour_events, quiet.local_peer_id()));
+
+        quiet.start_mdns_discovery().await.unwrap();
+        assert!(quiet.status().await.unwrap().mdns_enabled);
+    }
+
+    #[tokio::test]
+    async fn test_persistent_peer_is_redialed_after_disconnect() {
+        let remote = LibP2pNetworkService::new(
+            Arc::new(AppConfig::default()),
+            Arc::new(InMemoryEventPublisher::new()),
+        )
+        .await
+        .unwrap();
+        remote.start_listening(0).await.unwrap();
+        let remote_id = remote.local_peer_id();
+        let addr = loopback_addr(&remote)
+            .await
+            .with(libp2p::multiaddr::Protocol::P2p(remote_id));
+
+        let mut config = AppConfig::default();
+        config.network.persistent_peers = vec![addr.to_string()];
+        config.network.persistent_peer_retry_seconds = 1;
+        let local =
+            LibP2pNetworkService::new(Arc::new(config), Arc::new(InMemoryEventPublisher::new()))
+                .await
+                .unwrap();
+
+        let wait_for = |connected: bool| {
+            let local = &local;
+            async move {
+                tokio::time::timeout(Duration::from_secs(10), async {
+                    loop {
+                        match local.next_event().await.unwrap() {
+                            NetworkEvent::PeerConnected(peer) if connected && peer == remote_id => {
+                                return;
+                            }
+                            NetworkEvent::PeerDisconnected(peer)
+                                if !connected && peer == remote_id =>
+                            {
+                                return;
+                            }
+                            _ => {}
+                        }
+                    }
+                })
+                .await
+                .expect("persistent peer connection did not change in time")
+            }
+        };
+
+        // Dialed at start without being asked to
+        wait_for(true).await;
+        let local_id = local.local_peer_id();
+        while !remote
+            .connection_counts()
+            .await
+            .unwrap()
+            .contains_key(&local_id)
+        {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        remote.disconnect_peer(local_id).await.unwrap();
+        wait_for(false).await;
+        wait_for(true).await;
+        assert_eq!(
+            local.connection_counts().await.unwrap().get(&remote_id),
+            Some(&1)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_behaviour_constructs_with_any_discovery_features() {
+        let mut config = AppConfig::default();
+        config.network.bootstrap_peers = vec![format!(
+            "/ip4/203.0.113.7/tcp/4001/p2p/{}",
+            PeerId::random()
+        )];
+        let node =
+            LibP2pNetworkService::new(Arc::new(config), Arc::new(InMemoryEventPublisher::new()))
+                .await
+                .unwrap();
+
+        // Without the DHT there is no routing table to seed, but asking is harmless
+        let routing_table = node.routing_table_peers().await.unwrap();
+        assert_eq!(routing_table.len(), usize::from(cfg!(feature = "dht")));
+        node.find_closest_peers(PeerId::random()).await.unwrap();
+        let status = node.status().await.unwrap();
+        assert_eq!(status.connected_peers, 0);
+    }
+
+    #[cfg(feature = "dht")]
+    #[tokio::test]
+    async fn test_configured_bootstrap_peers_are_added_to_kademlia() {
+        let peers = [PeerId::random(), PeerId::random(), PeerId::random()];
+        let mut config = AppConfig::default();
+        config.network.bootstrap_peers = vec![
+            format!("/ip4/203.0.113.7/tcp/4001/p2p/{}", peers[0]),
+            format!("/ip6/2001:db8::7/tcp/4001/p2p/{}", peers[1]),
+            format!("/dnsaddr/bootstrap.example.com/p2p/{}", peers[2]),
+            "/ip4/203.0.113.8/tcp/4001".to_string(),
+        ];
+        let node =
+            LibP2pNetworkService::new(Arc::new(config), Arc::new(InMemoryEventPublisher::new()))
+                .await
+                .unwrap();
+
+        let routing_table: HashSet<PeerId> = node
+            .routing_table_peers()
+            .await
+            .unwrap()
+            .into_iter()
+            .collect();
+        assert_eq!(routing_table, peers.into_iter().collect());
+    }
+
+    #[cfg(feature = "dht")]
+    #[tokio::test]
+    async fn test_public_bootstrap_peers_are_opt_in() {
+        let public_peers: HashSet<PeerId> = PUBLIC_BOOTSTRAP_PEERS
+            .iter()
+            .filter_map(|addr| peer_id_from_addr(&addr.parse().unwrap()))
+            .collect();
+
+        for join_public_dht in [false, true] {
+            let mut config = AppConfig::default();
+            config.network.join_public_dht = join_public_dht;
+            let node = LibP2pNetworkService::new(
+                Arc::new(config),
+                Arc::new(InMemoryEventPublisher::new()),
+            )
+            .await
+            .unwrap();
+
+            let routing_table: HashSet<PeerId> = node
+                .routing_table_peers()
+                .await
+                .unwrap()
+                .into_iter()
+                .collect();
+            if join_public_dht {
+                assert_eq!(routing_table, public_peers);
+            } else {
+                assert!(routing_table.is_empty(), "{:?}", routing_table);
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_through_trait_object_stops_the_service() {
+        let services: Vec<Box<dyn NetworkService>> = vec![
+            Box::new(
+                LibP2pNetworkService::new(
+                    Arc::new(AppConfig::default()),
+                    Arc::new(InMemoryEventPublisher::new()),
+                )
+                .await
+                .unwrap(),
+            ),
+            Box::new(SimpleNetworkService::new()),
+        ];
+
+        for service in &services {
+            service.shutdown().await.unwrap();
+            // Shutting down twice is harmless
+            service.shutdown().await.unwrap();
+        }
+        assert!(services[0].start_listening(0).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_service_builds_with_custom_request_response_config() {
+        let mut config = AppConfig::default();
+        config.network.request_timeout_seconds = 90;
+        config.network.max_concurrent_streams = 8;
+        config.validate().unwrap();
+
+        let rr_config = format!("{:?}", request_response_config(&config));
+        assert!(rr_config.contains("request_timeout: 90s"), "{}", rr_config);
+        assert!(
+            rr_config.contains("max_concurrent_streams: 8"),
+            "{}",
+            rr_config
+        );
+
+        let service =
+            LibP2pNetworkService::new(Arc::new(config), Arc::new(InMemoryEventPublisher::new()))
+                .await;
+        assert!(service.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_restarted_node_rebinds_its_port_immediately() {
+        let start = || async {
+            LibP2pNetworkService::new(
+                Arc::new(AppConfig::default()),
+                Arc::new(InMemoryEventPublisher::new()),
+            )
+            .await
+            .unwrap()
+        };
+        let port = crate::testing::reserve_port().release();
+
+        let first = start().await;
+        first.start_listening(port).await.unwrap();
+        let client = start().await;
+        client
+            .connect_and_wait(loopback_addr(&first).await)
+            .await
+            .unwrap();
+        // Closing with a live connection leaves the port in TIME_WAIT
+        first.shutdown().await.unwrap();
+
+        let second = start().await;
+        second.start_listening(port).await.unwrap();
+        let addr = loopback_addr(&second).await;
+        assert!(addr.to_string().ends_with(&format!("/tcp/{}", port)));
+    }
+
+    #[tokio::test]
+    async fn test_identify_reports_configured_agent_version() {
+        let mut config = AppConfig::default();
+        config.network.agent_label = Some("fleet-a".to_string());
+        let agent_version = config.network.agent_version();
+        let labelled =
+            LibP2pNetworkService::new(Arc::new(config), Arc::new(InMemoryEventPublisher::new()))
+                .await
+                .unwrap();
+        labelled.start_listening(0).await.unwrap();
+        let observer = LibP2pNetworkService::new(
+            Arc::new(AppConfig::default()),
+            Arc::new(InMemoryEventPublisher::new()),
+        )
+        .await
+        .unwrap();
+
+        let labelled_id = observer
+            .connect_and_wait(loopback_addr(&labelled).await)
+            .await
+            .unwrap();
+
+        let mut identified = None;
+        for _ in 0..20 {
+            identified = observer
+                .collect_events_for(Duration::from_millis(100))
+                .await
+                .into_iter()
+                .find_map(|event| match event {
+                    NetworkEvent::PeerIdentified {
+                        peer,
+                        agent_version,
+                        protocol_version,
+                    } if peer == labelled_id => Some((agent_version, protocol_version)),
+                    _ => None,
+                });
+            if identified.is_some() {
+                break;
+            }
+        }
+
+        let (reported, protocol) = identified.expect("peer was never identified");
+        assert_eq!(reported, agent_version);
+        assert!(reported.starts_with(&format!("cipherstream/{}", env!("CARGO_PKG_VERSION"))));
+        assert!(reported.ends_with("(fleet-a)"));
+        assert_eq!(protocol, "/cipherstream/1.0.0");
+    }
+
+    #[tokio::test]
+    async fn test_publish_without_subscribed_peers_reports_no_gossip_peers() {
+        let service = LibP2pNetworkService::new(
+            Arc::new(AppConfig::default()),
+            Arc::new(InMemoryEventPublisher::new()),
+        )
+        .await
+        .unwrap();
+        service.subscribe_topic("lonely-topic").await.unwrap();
+
+        let err = service
+            .publish_message("lonely-topic", b"anyone?".to_vec())
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<NetworkError>(),
+            Some(NetworkError::NoGossipPeers { topic }) if topic == "lonely-topic"
+        ));
+    }
 }
@@ -1,7 +1,8 @@
 use crate::core::{domain::*, traits::*};
 use async_trait::async_trait;
-use std::collections::HashMap;
+use std::collections::{BTreeSet, HashMap};
 use std::sync::Arc;
+use std::time::SystemTime;
 use tokio::sync::RwLock;
 
 /// In-memory repository for files (could be replaced with database implementation)
@@ -61,12 +62,14 @@ impl FileRepository for InMemoryFileRepository {
 /// In-memory repository for transfers
 pub struct InMemoryTransferRepository {
     transfers: Arc<RwLock<HashMap<TransferId, Transfer>>>,
+    resume_states: Arc<RwLock<HashMap<TransferId, ResumeState>>>,
 }
 
 impl InMemoryTransferRepository {
     pub fn new() -> Self {
         Self {
             transfers: Arc::new(RwLock::new(HashMap::new())),
+            resume_states: Arc::new(RwLock::new(HashMap::new())),
         }
     }
 }
@@ -110,6 +113,16 @@ impl TransferRepository for InMemoryTransferRepository {
         Ok(matching_transfers)
     }
 
+    async fn find_transfers_by_file(&self, file_id: &FileId) -> DomainResult<Vec<Transfer>> {
+        let transfers = self.transfers.read().await;
+        let matching_transfers: Vec<Transfer> = transfers
+            .values()
+            .filter(|transfer| transfer.file.id == *file_id)
+            .cloned()
+            .collect();
+        Ok(matching_transfers)
+    }
+
     async fn list_active_transfers(&self) -> DomainResult<Vec<Transfer>> {
         let transfers = self.transfers.read().await;
         let active_transfers: Vec<Transfer> = transfers
@@ -117,7 +130,10 @@ impl TransferRepository for InMemoryTransferRepository {
             .filter(|transfer| {
                 matches!(
                     transfer.status,
-                    TransferStatus::InProgress | TransferStatus::Pending
+                    TransferStatus::InProgress
+                        | TransferStatus::Verifying
+                        | TransferStatus::Pending
+                        | TransferStatus::Paused
                 )
             })
             .cloned()
@@ -148,17 +164,42 @@ impl TransferRepository for InMemoryTransferRepository {
         }
         Ok(())
     }
+
+    async fn record_received_chunk(
+        &self,
+        id: &TransferId,
+        partial_path: &str,
+        chunk_index: u64,
+    ) -> DomainResult<()> {
+        let mut states = self.resume_states.write().await;
+        let state = states.entry(id.clone()).or_default();
+        state.partial_path = partial_path.to_string();
+        state.received_chunks.insert(chunk_index);
+        Ok(())
+    }
+
+    async fn find_resume_state(&self, id: &TransferId) -> DomainResult<Option<ResumeState>> {
+        let states = self.resume_states.read().await;
+        Ok(states.get(id).cloned())
+    }
+
+    async fn clear_resume_state(&self, id: &TransferId) -> DomainResult<()> {
+        self.resume_states.write().await.remove(id);
+        Ok(())
+    }
 }
 
 /// In-memory repository for peers
 pub struct InMemoryPeerRepository {
     peers: Arc<RwLock<HashMap<PeerId, Peer>>>,
+    bans: Arc<RwLock<HashMap<PeerId, PeerBan>>>,
 }
 
 impl InMemoryPeerRepository {
     pub fn new() -> Self {
         Self {
             peers: Arc::new(RwLock::new(HashMap::new())),
+            bans: Arc::new(RwLock::new(HashMap::new())),
         }
     }
 }
@@ -208,6 +249,37 @@ impl PeerRepository for InMemoryPeerRepository {
         }
         Ok(())
     }
+
+    async fn find_peers_seen_before(&self, cutoff: SystemTime) -> DomainResult<Vec<Peer>> {
+        let peers = self.peers.read().await;
+        Ok(peers
+            .values()
+            .filter(|peer| peer.last_seen < cutoff)
+            .cloned()
+            .collect())
+    }
+
+    async fn delete_peer(&self, id: &PeerId) -> DomainResult<()> {
+        let mut peers = self.peers.write().await;
+        peers.remove(id);
+        Ok(())
+    }
+
+    async fn save_ban(&self, ban: &PeerBan) -> DomainResult<()> {
+        let mut bans = self.bans.write().await;
+        bans.insert(ban.peer.clone(), ban.clone());
+        Ok(())
+    }
+
+    async fn remove_ban(&self, id: &PeerId) -> DomainResult<bool> {
+        let mut bans = self.bans.write().await;
+        Ok(bans.remove(id).is_some())
+    }
+
+    async fn list_bans(&self) -> DomainResult<Vec<PeerBan>> {
+        let bans = self.bans.read().await;
+        Ok(bans.values().cloned().collect())
+    }
 }
 
 /// Builder for creating repository instances
@@ -259,22 +331,38 @@ pub struct SledStores {
     _db: sled::Db,
     files: sled::Tree,
     transfers: sled::Tree,
+    /// Partial file each resumable transfer is written to
+    resume_paths: sled::Tree,
+    /// One empty entry per received chunk, keyed by [`resume_chunk_key`], so
+    /// recording a chunk never rewrites the others
+    resume_chunks: sled::Tree,
     peers: sled::Tree,
+    bans: sled::Tree,
 }
 
 impl SledStores {
     fn open() -> Result<Self, Box<dyn std::error::Error>> {
         let path = std::env::var("CIPHERSTREAM_DB_PATH")
             .unwrap_or_else(|_| ".cipherstream_db".to_string());
+        Self::open_at(path)
+    }
+
+    fn open_at(path: impl AsRef<std::path::Path>) -> Result<Self, Box<dyn std::error::Error>> {
         let db = sled::open(path)?;
         let files = db.open_tree("files")?;
         let transfers = db.open_tree("transfers")?;
+        let resume_paths = db.open_tree("resume_paths")?;
+        let resume_chunks = db.open_tree("resume_chunks")?;
         let peers = db.open_tree("peers")?;
+        let bans = db.open_tree("bans")?;
         Ok(Self {
             _db: db,
             files,
             transfers,
+            resume_paths,
+            resume_chunks,
             peers,
+            bans,
         })
     }
 }
@@ -356,6 +444,13 @@ impl SledTransferRepository {
             store: SledStores::open()?,
         })
     }
+
+    /// Open the database at `path` instead of `CIPHERSTREAM_DB_PATH`
+    pub fn open(path: impl AsRef<std::path::Path>) -> Result<Self, Box<dyn std::error::Error>> {
+        Ok(Self {
+            store: SledStores::open_at(path)?,
+        })
+    }
 }
 
 #[async_trait]
@@ -405,6 +500,21 @@ impl TransferRepository for SledTransferRepository {
         Ok(entries)
     }
 
+    async fn find_transfers_by_file(&self, file_id: &FileId) -> DomainResult<Vec<Transfer>> {
+        let file_id = file_id.clone();
+        let t = self.store.transfers.clone();
+        let entries: Vec<Transfer> = tokio::task::spawn_blocking(move || {
+            t.iter()
+                .values()
+                .filter_map(|res| res.ok())
+                .filter_map(|v| serde_json::from_slice::<Transfer>(&v).ok())
+                .filter(|tr| tr.file.id == file_id)
+                .collect()
+        })
+        .await?;
+        Ok(entries)
+    }
+
     async fn list_active_transfers(&self) -> DomainResult<Vec<Transfer>> {
         let t = self.store.transfers.clone();
         let entries: Vec<Transfer> = tokio::task::spawn_blocking(move || {
@@ -415,7 +525,10 @@ impl TransferRepository for SledTransferRepository {
                 .filter(|tr| {
                     matches!(
                         tr.status,
-                        TransferStatus::InProgress | TransferStatus::Pending
+                        TransferStatus::InProgress
+                            | TransferStatus::Verifying
+                            | TransferStatus::Pending
+                            | TransferStatus::Paused
                     )
                 })
                 .collect()
@@ -447,6 +560,83 @@ impl TransferRepository for SledTransferRepository {
         }
         Ok(())
     }
+
+    async fn record_received_chunk(
+        &self,
+        id: &TransferId,
+        partial_path: &str,
+        chunk_index: u64,
+    ) -> DomainResult<()> {
+        let path_key = id.as_str().as_bytes().to_vec();
+        let chunk_key = resume_chunk_key(id, chunk_index);
+        let partial_path = partial_path.as_bytes().to_vec();
+        let paths = self.store.resume_paths.clone();
+        let chunks = self.store.resume_chunks.clone();
+        tokio::task::spawn_blocking(move || -> sled::Result<()> {
+            paths.insert(path_key, partial_path)?;
+            chunks.insert(chunk_key, &[])?;
+            Ok(())
+        })
+        .await??;
+        Ok(())
+    }
+
+    async fn find_resume_state(&self, id: &TransferId) -> DomainResult<Option<ResumeState>> {
+        let path_key = id.as_str().as_bytes().to_vec();
+        let prefix = resume_chunk_prefix(id);
+        let paths = self.store.resume_paths.clone();
+        let chunks = self.store.resume_chunks.clone();
+        tokio::task::spawn_blocking(move || -> DomainResult<Option<ResumeState>> {
+            let Some(partial_path) = paths.get(path_key)? else {
+                return Ok(None);
+            };
+            let mut received_chunks = BTreeSet::new();
+            for key in chunks.scan_prefix(&prefix).keys() {
+                let index = key?[prefix.len()..]
+                    .try_into()
+                    .map(u64::from_be_bytes)
+                    .map_err(|_| "Malformed resume chunk key")?;
+                received_chunks.insert(index);
+            }
+            Ok(Some(ResumeState {
+                partial_path: String::from_utf8(partial_path.to_vec())?,
+                received_chunks,
+            }))
+        })
+        .await?
+    }
+
+    async fn clear_resume_state(&self, id: &TransferId) -> DomainResult<()> {
+        let path_key = id.as_str().as_bytes().to_vec();
+        let prefix = resume_chunk_prefix(id);
+        let paths = self.store.resume_paths.clone();
+        let chunks = self.store.resume_chunks.clone();
+        tokio::task::spawn_blocking(move || -> sled::Result<()> {
+            paths.remove(path_key)?;
+            for key in chunks.scan_prefix(&prefix).keys() {
+                chunks.remove(key?)?;
+            }
+            Ok(())
+        })
+        .await??;
+        Ok(())
+    }
+}
+
+/// Start of every [`resume_chunk_key`] of transfer `id`; the separator keeps
+/// one id's chunks apart from those of ids it is a prefix of
+fn resume_chunk_prefix(id: &TransferId) -> Vec<u8> {
+    let mut prefix = id.as_str().as_bytes().to_vec();
+    prefix.push(0);
+    prefix
+}
+
+/// Key recording that chunk `index` of transfer `id` has been received.
+/// Big-endian indices keep a transfer's chunks in order.
+fn resume_chunk_key(id: &TransferId, index: u64) -> Vec<u8> {
+    let mut key = resume_chunk_prefix(id);
+    key.extend_from_slice(&index.to_be_bytes());
+    key
 }
 
 pub struct SledPeerRepository {
@@ -459,6 +649,13 @@ impl SledPeerRepository {
             store: SledStores::open()?,
         })
     }
+
+    /// Open the database at `path` instead of `CIPHERSTREAM_DB_PATH`
+    pub fn open(path: impl AsRef<std::path::Path>) -> Result<Self, Box<dyn std::error::Error>> {
+        Ok(Self {
+            store: SledStores::open_at(path)?,
+        })
+    }
 }
 
 #[async_trait]
@@ -516,4 +713,171 @@ impl PeerRepository for SledPeerRepository {
         }
         Ok(())
     }
+
+    async fn find_peers_seen_before(&self, cutoff: SystemTime) -> DomainResult<Vec<Peer>> {
+        Ok(self
+            .list_all_peers()
+            .await?
+            .into_iter()
+            .filter(|peer| peer.last_seen < cutoff)
+            .collect())
+    }
+
+    async fn delete_peer(&self, id: &PeerId) -> DomainResult<()> {
+        let key = id.as_str().as_bytes().to_vec();
+        let p = self.store.peers.clone();
+        tokio::task::spawn_blocking(move || p.remove(key)).await??;
+        Ok(())
+    }
+
+    async fn save_ban(&self, ban: &PeerBan) -> DomainResult<()> {
+        let key = ban.peer.as_str().as_bytes().to_vec();
+        let value = serde_json::to_vec(ban)?;
+        let b = self.store.bans.clone();
+        tokio::task::spawn_blocking(move || b.insert(key, value)).await??;
+        Ok(())
+    }
+
+    async fn remove_ban(&self, id: &PeerId) -> DomainResult<bool> {
+        let key = id.as_str().as_bytes().to_vec();
+        let b = self.store.bans.clone();
+        let res = tokio::task::spawn_blocking(move || b.remove(key)).await??;
+        Ok(res.is_some())
+    }
+
+    async fn list_bans(&self) -> DomainResult<Vec<PeerBan>> {
+        let b = self.store.bans.clone();
+        let entries: Vec<PeerBan> = tokio::task::spawn_blocking(move || {
+            b.iter()
+                .values()
+                .filter_map(|res| res.ok())
+                .filter_map(|v| serde_json::from_slice::<PeerBan>(&v).ok())
+                .collect()
+        })
+        .await?;
+        Ok(entries)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn transfer() -> Transfer {
+        Transfer {
+            id: TransferId::new(),
+            file: File {
+                id: FileId::new(),
+                name: "movie.mkv".to_string(),
+                size: 8 * 1024,
+                hash: "abc".to_string(),
+                path: "/tmp/movie.mkv".to_string(),
+                created_at: SystemTime::now(),
+                modified_at: None,
+            },
+            sender: PeerId::new("alice".to_string()),
+            receiver: PeerId::new("bob".to_string()),
+            status: TransferStatus::InProgress,
+            progress: TransferProgress::new(8 * 1024, 8),
+            started_at: SystemTime::now(),
+            completed_at: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_transfers_are_found_by_file_in_both_backends() {
+        let dir = tempfile::tempdir().unwrap();
+        let sled = SledTransferRepository::open(dir.path().join("db")).unwrap();
+        let memory = InMemoryTransferRepository::new();
+        let repos: [&dyn TransferRepository; 2] = [&memory, &sled];
+
+        let shared = transfer();
+        let mut to_carol = transfer();
+        to_carol.file = shared.file.clone();
+        to_carol.receiver = PeerId::new("carol".to_string());
+        let unrelated = transfer();
+
+        for repo in repos {
+            for t in [&shared, &to_carol, &unrelated] {
+                repo.save_transfer(t).await.unwrap();
+            }
+            let mut found: Vec<_> = repo
+                .find_transfers_by_file(&shared.file.id)
+                .await
+                .unwrap()
+                .into_iter()
+                .map(|t| t.receiver.as_str().to_string())
+                .collect();
+            found.sort();
+            assert_eq!(found, ["bob", "carol"]);
+            assert!(
+                repo.find_transfers_by_file(&FileId::new())
+                    .await
+                    .unwrap()
+                    .is_empty()
+            );
+        }
+    }
+
+    /// Open the database at `path` again, giving sled's background threads a
+    /// moment to release the lock held by an instance just dropped
+    fn reopen_transfers(path: &std::path::Path) -> SledTransferRepository {
+        for _ in 0..50 {
+            if let Ok(repo) = SledTransferRepository::open(path) {
+                return repo;
+            }
+            std::thread::sleep(std::time::Duration::from_millis(20));
+        }
+        SledTransferRepository::open(path).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_sled_resume_state_survives_reopen() {
+        let dir = tempfile::tempdir().unwrap();
+        let db_path = dir.path().join("db");
+        let transfer = transfer();
+
+        {
+            let repo = SledTransferRepository::open(&db_path).unwrap();
+            repo.save_transfer(&transfer).await.unwrap();
+            for index in [0, 1, 2, 5] {
+                repo.record_received_chunk(&transfer.id, "/downloads/movie.mkv", index)
+                    .await
+                    .unwrap();
+            }
+        }
+
+        let repo = reopen_transfers(&db_path);
+        let state = repo.find_resume_state(&transfer.id).await.unwrap().unwrap();
+        assert_eq!(state.partial_path, "/downloads/movie.mkv");
+        assert_eq!(state.received_chunks, BTreeSet::from([0, 1, 2, 5]));
+        assert_eq!(state.missing_chunks(8), vec![3, 4, 6, 7]);
+
+        repo.clear_resume_state(&transfer.id).await.unwrap();
+        assert_eq!(repo.find_resume_state(&transfer.id).await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn test_sled_resume_state_is_kept_per_transfer() {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = SledTransferRepository::open(dir.path().join("db")).unwrap();
+        // One id is a prefix of the other
+        let short = TransferId::from_string("abc".to_string());
+        let long = TransferId::from_string("abcd".to_string());
+        repo.record_received_chunk(&short, "/downloads/a", 3)
+            .await
+            .unwrap();
+        repo.record_received_chunk(&long, "/downloads/b", 9)
+            .await
+            .unwrap();
+
+        let state = repo.find_resume_state(&short).await.unwrap().unwrap();
+        assert_eq!(state.received_chunks, BTreeSet::from([3]));
+        repo.clear_resume_state(&short).await.unwrap();
+        assert_eq!(repo.find_resume_state(&short).await.unwrap(), None);
+
+        let state = repo.find_resume_state(&long).await.unwrap().unwrap();
+        assert_eq!(state.partial_path, "/downloads/b");
+        assert_eq!(state.received_chunks, BTreeSet::from([9]));
+    }
 }
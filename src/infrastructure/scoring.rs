@@ -0,0 +1,175 @@
+use libp2p::PeerId;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+/// Weight given to each new sample in the moving averages
+const SMOOTHING: f64 = 0.3;
+/// Score of a peer nothing is known about; decayed scores drift back here
+const NEUTRAL_SCORE: f64 = 0.5;
+/// Round-trip time at which the latency component is halved
+const REFERENCE_RTT_MS: f64 = 100.0;
+/// Throughput at which the throughput component is halved
+const REFERENCE_THROUGHPUT: f64 = 1024.0 * 1024.0;
+
+/// Connection quality observed for a single peer
+#[derive(Debug, Clone)]
+pub struct PeerScore {
+    rtt_ms: Option<f64>,
+    failure_rate: f64,
+    throughput: Option<f64>,
+    updated_at: Instant,
+}
+
+impl PeerScore {
+    fn new(now: Instant) -> Self {
+        Self {
+            rtt_ms: None,
+            failure_rate: 0.0,
+            throughput: None,
+            updated_at: now,
+        }
+    }
+
+    /// Smoothed round-trip time, if any ping has completed
+    pub fn rtt(&self) -> Option<Duration> {
+        self.rtt_ms.map(|ms| Duration::from_secs_f64(ms / 1000.0))
+    }
+
+    /// Smoothed share of recent requests that failed, from 0 to 1
+    pub fn failure_rate(&self) -> f64 {
+        self.failure_rate
+    }
+
+    /// Smoothed transfer throughput in bytes per second
+    pub fn throughput(&self) -> Option<f64> {
+        self.throughput
+    }
+
+    /// Quality score between 0 and 1, decayed toward neutral with age
+    pub fn score(&self, now: Instant, half_life: Duration) -> f64 {
+        let latency = self
+            .rtt_ms
+            .map_or(NEUTRAL_SCORE, |ms| 1.0 / (1.0 + ms / REFERENCE_RTT_MS));
+        let throughput = self
+            .throughput
+            .map_or(NEUTRAL_SCORE, |bps| bps / (bps + REFERENCE_THROUGHPUT));
+        let raw = (1.0 - self.failure_rate) * (latency + throughput) / 2.0;
+
+        let age = now.saturating_duration_since(self.updated_at);
+        let weight = 0.5f64.powf(age.as_secs_f64() / half_life.as_secs_f64());
+        NEUTRAL_SCORE + (raw - NEUTRAL_SCORE) * weight
+    }
+}
+
+fn smooth(previous: Option<f64>, sample: f64) -> f64 {
+    previous.map_or(sample, |prev| prev + SMOOTHING * (sample - prev))
+}
+
+/// Per-peer connection quality, fed by pings, request outcomes and
+/// completed transfers
+#[derive(Debug)]
+pub struct PeerScores {
+    half_life: Duration,
+    scores: HashMap<PeerId, PeerScore>,
+}
+
+impl PeerScores {
+    /// Observations lose half their influence every `half_life`
+    pub fn new(half_life: Duration) -> Self {
+        Self {
+            half_life,
+            scores: HashMap::new(),
+        }
+    }
+
+    fn entry(&mut self, peer: PeerId, now: Instant) -> &mut PeerScore {
+        let score = self
+            .scores
+            .entry(peer)
+            .or_insert_with(|| PeerScore::new(now));
+        score.updated_at = now;
+        score
+    }
+
+    pub fn record_rtt(&mut self, peer: PeerId, rtt: Duration, now: Instant) {
+        let score = self.entry(peer, now);
+        score.rtt_ms = Some(smooth(score.rtt_ms, rtt.as_secs_f64() * 1000.0));
+    }
+
+    pub fn record_success(&mut self, peer: PeerId, now: Instant) {
+        let score = self.entry(peer, now);
+        score.failure_rate = smooth(Some(score.failure_rate), 0.0);
+    }
+
+    pub fn record_failure(&mut self, peer: PeerId, now: Instant) {
+        let score = self.entry(peer, now);
+        score.failure_rate = smooth(Some(score.failure_rate), 1.0);
+    }
+
+    pub fn record_throughput(&mut self, peer: PeerId, bytes: u64, elapsed: Duration, now: Instant) {
+        if elapsed.is_zero() {
+            return;
+        }
+        let score = self.entry(peer, now);
+        let bps = bytes as f64 / elapsed.as_secs_f64();
+        score.throughput = Some(smooth(score.throughput, bps));
+    }
+
+    pub fn get(&self, peer: &PeerId) -> Option<&PeerScore> {
+        self.scores.get(peer)
+    }
+
+    /// Up to `n` peers ordered from most to least reliable
+    pub fn best_peers(&self, n: usize, now: Instant) -> Vec<PeerId> {
+        let mut ranked: Vec<(PeerId, f64)> = self
+            .scores
+            .iter()
+            .map(|(peer, score)| (*peer, score.score(now, self.half_life)))
+            .collect();
+        ranked.sort_by(|a, b| b.1.total_cmp(&a.1));
+        ranked.into_iter().take(n).map(|(peer, _)| peer).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_best_peers_orders_by_quality() {
+        let now = Instant::now();
+        let mut scores = PeerScores::new(Duration::from_secs(600));
+        let (fast, slow, flaky) = (PeerId::random(), PeerId::random(), PeerId::random());
+
+        for _ in 0..5 {
+            scores.record_rtt(fast, Duration::from_millis(10), now);
+            scores.record_rtt(slow, Duration::from_millis(400), now);
+            scores.record_rtt(flaky, Duration::from_millis(10), now);
+            scores.record_success(fast, now);
+            scores.record_success(slow, now);
+            scores.record_failure(flaky, now);
+        }
+        scores.record_throughput(fast, 8 * 1024 * 1024, Duration::from_secs(1), now);
+        scores.record_throughput(slow, 256 * 1024, Duration::from_secs(1), now);
+
+        assert_eq!(scores.best_peers(3, now), vec![fast, slow, flaky]);
+        assert_eq!(scores.best_peers(1, now), vec![fast]);
+    }
+
+    #[test]
+    fn test_scores_decay_toward_neutral() {
+        let now = Instant::now();
+        let half_life = Duration::from_secs(60);
+        let mut scores = PeerScores::new(half_life);
+        let peer = PeerId::random();
+        for _ in 0..5 {
+            scores.record_failure(peer, now);
+        }
+
+        let score = scores.get(&peer).unwrap();
+        let fresh = score.score(now, half_life);
+        let later = score.score(now + half_life * 10, half_life);
+        assert!(fresh < 0.2);
+        assert!((later - NEUTRAL_SCORE).abs() < 0.01);
+    }
+}
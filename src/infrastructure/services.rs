@@ -1,29 +1,26 @@
-// Infrastructure services - placeholder for now
+// Infrastructure services
 
+use crate::core::crypto;
 use crate::core::traits::*;
+use crate::infrastructure::network::LibP2pNetworkService;
 use async_trait::async_trait;
 use libp2p::{PeerId as LibP2PPeerId, identity};
-use ring::{
-    aead::{self, AES_256_GCM, UnboundKey},
-    digest,
-    rand::{SecureRandom, SystemRandom},
-    signature::{self, Ed25519KeyPair, KeyPair},
-};
 use std::collections::HashMap;
 use std::path::Path;
 use std::sync::Arc;
-use tokio::fs::File;
-use tokio::io::AsyncReadExt;
 use tokio::sync::RwLock;
 
-/// Network service for P2P operations
+/// Network service for P2P operations, backed by a live libp2p swarm. Also
+/// remembers the addresses of peers it has connected to.
 pub struct NetworkServiceImpl {
+    network: Arc<LibP2pNetworkService>,
     discovered_peers: Arc<RwLock<HashMap<crate::core::domain::PeerId, Vec<String>>>>,
 }
 
 impl NetworkServiceImpl {
-    pub fn new() -> Self {
+    pub fn new(network: Arc<LibP2pNetworkService>) -> Self {
         Self {
+            network,
             discovered_peers: Arc::new(RwLock::new(HashMap::new())),
         }
     }
@@ -61,38 +58,45 @@ impl NetworkServiceImpl {
     }
 }
 
-impl Default for NetworkServiceImpl {
-    fn default() -> Self {
-        Self::new()
-    }
-}
-
 #[async_trait]
 impl crate::core::traits::NetworkService for NetworkServiceImpl {
-    async fn start_listening(&self, _port: u16) -> DomainResult<()> {
-        // This would integrate with the legacy network module functionality
-        // For now, return Ok as a placeholder
-        Ok(())
+    async fn start_listening(&self, port: u16) -> DomainResult<()> {
+        self.network.start_listening(port).await
+    }
+
+    async fn connect(&self, addr: libp2p::Multiaddr) -> DomainResult<crate::core::domain::PeerId> {
+        let peer_id = self.network.connect(addr.clone()).await?;
+        let mut peers = self.discovered_peers.write().await;
+        let addresses = peers.entry(peer_id.clone()).or_default();
+        if !addresses.contains(&addr.to_string()) {
+            addresses.push(addr.to_string());
+        }
+        Ok(peer_id)
     }
 
     async fn send_message(
         &self,
-        _peer_id: &crate::core::domain::PeerId,
-        _message: Vec<u8>,
+        peer_id: &crate::core::domain::PeerId,
+        message: Vec<u8>,
     ) -> DomainResult<()> {
-        // This would integrate with the legacy network module functionality
-        // For now, return Ok as a placeholder
-        Ok(())
+        self.network.send_message(peer_id, message).await
     }
 
-    async fn broadcast_message(&self, _message: Vec<u8>) -> DomainResult<()> {
-        // This would integrate with the legacy network module functionality
-        // For now, return Ok as a placeholder
-        Ok(())
+    async fn broadcast_message(&self, message: Vec<u8>) -> DomainResult<()> {
+        self.network.broadcast_message(message).await
+    }
+
+    async fn broadcast_to_topic(&self, topic: &str, data: Vec<u8>) -> DomainResult<usize> {
+        self.network.broadcast_to_topic(topic, data).await
+    }
+
+    async fn shutdown(&self) -> DomainResult<()> {
+        self.network.shutdown().await
     }
 }
 
-/// Cryptographic service for encryption, decryption, and signing operations
+/// Cryptographic service for encryption, decryption, and signing operations.
+/// Thin `DomainResult` wrappers over [`crate::core::crypto`].
 pub struct CryptoService;
 
 impl CryptoService {
@@ -102,78 +106,27 @@ impl CryptoService {
 
     /// Generate a random AES-256 key
     pub fn generate_key() -> DomainResult<Vec<u8>> {
-        let mut key = vec![0u8; 32]; // 256 bits
-        let rng = SystemRandom::new();
-        rng.fill(&mut key).map_err(|_| "Failed to generate key")?;
-        Ok(key)
+        Ok(crypto::generate_key()?)
     }
 
     /// Encrypt data with AES-256-GCM
     pub fn encrypt(data: &[u8], key: &[u8]) -> DomainResult<Vec<u8>> {
-        // Generate a random nonce
-        let mut nonce_bytes = [0u8; 12];
-        let rng = SystemRandom::new();
-        rng.fill(&mut nonce_bytes)
-            .map_err(|_| "Encryption failed")?;
-
-        let unbound_key = UnboundKey::new(&AES_256_GCM, key).map_err(|_| "Invalid key")?;
-        let aead_key = aead::LessSafeKey::new(unbound_key);
-        let nonce = aead::Nonce::assume_unique_for_key(nonce_bytes);
-
-        let mut in_out = data.to_vec();
-        aead_key
-            .seal_in_place_append_tag(nonce, aead::Aad::empty(), &mut in_out)
-            .map_err(|_| "Encryption failed")?;
-
-        // Prepend nonce to encrypted data
-        let mut result = nonce_bytes.to_vec();
-        result.extend_from_slice(&in_out);
-
-        Ok(result)
+        Ok(crypto::encrypt(data, key)?)
     }
 
     /// Decrypt data encrypted with AES-256-GCM
     pub fn decrypt(encrypted: &[u8], key: &[u8]) -> DomainResult<Vec<u8>> {
-        // Expect nonce (12) + tag (16)
-        if encrypted.len() < 12 + 16 {
-            return Err("Invalid encrypted data".into());
-        }
-        let (nonce_bytes, ciphertext_and_tag) = encrypted.split_at(12);
-
-        let unbound = aead::UnboundKey::new(&aead::AES_256_GCM, key).map_err(|_| "Invalid key")?;
-        let aead_key = aead::LessSafeKey::new(unbound);
-
-        let mut nonce_arr = [0u8; 12];
-        nonce_arr.copy_from_slice(nonce_bytes);
-        let nonce = aead::Nonce::assume_unique_for_key(nonce_arr);
-
-        let mut buffer = ciphertext_and_tag.to_vec();
-        let decrypted = aead_key
-            .open_in_place(nonce, aead::Aad::empty(), &mut buffer)
-            .map_err(|_| "Decryption failed")?;
-        Ok(decrypted.to_vec())
+        Ok(crypto::decrypt(encrypted, key)?)
     }
 
     /// Generate an Ed25519 signing keypair
     pub fn generate_signing_keypair() -> DomainResult<(Vec<u8>, Vec<u8>)> {
-        let rng = SystemRandom::new();
-        let pkcs8_bytes =
-            Ed25519KeyPair::generate_pkcs8(&rng).map_err(|_| "Failed to generate keypair")?;
-        let key_pair =
-            Ed25519KeyPair::from_pkcs8(pkcs8_bytes.as_ref()).map_err(|_| "Invalid key")?;
-
-        let private_key = pkcs8_bytes.as_ref().to_vec();
-        let public_key = key_pair.public_key().as_ref().to_vec();
-
-        Ok((private_key, public_key))
+        Ok(crypto::generate_signing_keypair()?)
     }
 
     /// Sign a message using an Ed25519 private key
     pub fn sign_message(message: &[u8], private_key: &[u8]) -> DomainResult<Vec<u8>> {
-        let key_pair =
-            Ed25519KeyPair::from_pkcs8(private_key).map_err(|_| "Invalid private key")?;
-        let signature = key_pair.sign(message);
-        Ok(signature.as_ref().to_vec())
+        Ok(crypto::sign_message(message, private_key)?)
     }
 
     /// Verify a signature using an Ed25519 public key
@@ -182,31 +135,12 @@ impl CryptoService {
         signature: &[u8],
         public_key: &[u8],
     ) -> DomainResult<bool> {
-        let peer_public_key = signature::UnparsedPublicKey::new(&signature::ED25519, public_key);
-
-        match peer_public_key.verify(message, signature) {
-            Ok(_) => Ok(true),
-            Err(_) => Ok(false),
-        }
+        Ok(crypto::verify_signature(message, signature, public_key)?)
     }
 
     /// Compute SHA-256 hash for a file
     pub async fn compute_file_hash<P: AsRef<Path>>(path: P) -> DomainResult<String> {
-        let mut file = File::open(path).await.map_err(|_| "Failed to open file")?;
-        let mut context = digest::Context::new(&digest::SHA256);
-        let mut buffer = [0u8; 8192];
-        loop {
-            let count = file
-                .read(&mut buffer)
-                .await
-                .map_err(|_| "Failed to read file")?;
-            if count == 0 {
-                break;
-            }
-            context.update(&buffer[..count]);
-        }
-        let hash = context.finish();
-        Ok(hex::encode(hash.as_ref()))
+        Ok(crypto::compute_file_hash(path).await?)
     }
 }
 
@@ -226,20 +160,10 @@ impl UtilityService {
 
     /// Calculate SHA-256 hash of a file
     pub async fn sha256_file<P: AsRef<Path>>(path: P) -> std::io::Result<String> {
-        let mut file = File::open(path).await?;
-        let mut context = ring::digest::Context::new(&ring::digest::SHA256);
-        let mut buffer = [0u8; 1024 * 64];
-
-        loop {
-            let count = file.read(&mut buffer).await?;
-            if count == 0 {
-                break;
-            }
-            context.update(&buffer[..count]);
-        }
-
-        let digest = context.finish();
-        Ok(hex::encode(digest.as_ref()))
+        crypto::compute_file_hash(path).await.map_err(|e| match e {
+            crypto::CryptoError::Io(err) => err,
+            other => std::io::Error::other(other),
+        })
     }
 
     /// Generate a random unique ID for transfers and other operations
@@ -273,11 +197,107 @@ impl UtilityService {
         }
     }
 
-    /// Get the filename from a path
+    /// Parse a port range written `lo-hi`, or a single port. `0` stands for
+    /// an ephemeral port picked by the OS.
+    pub fn parse_port_range(input: &str) -> Result<std::ops::RangeInclusive<u16>, String> {
+        let input = input.trim();
+        let parse = |port: &str| {
+            port.trim()
+                .parse::<u16>()
+                .map_err(|e| format!("Invalid port {:?} in {:?}: {}", port, input, e))
+        };
+        let (lo, hi) = match input.split_once('-') {
+            Some((lo, hi)) => (parse(lo)?, parse(hi)?),
+            None => {
+                let port = parse(input)?;
+                (port, port)
+            }
+        };
+        if lo > hi {
+            return Err(format!("Port range {:?} ends before it starts", input));
+        }
+        if lo == 0 && hi != 0 {
+            return Err(format!("Port 0 can't be part of a range in {:?}", input));
+        }
+        Ok(lo..=hi)
+    }
+
+    /// Parse a human-readable size such as `1024`, `1.5MB` or `2GiB` into
+    /// bytes. `KB`/`MB`/`GB`/`TB` are decimal (powers of 1000) and
+    /// `KiB`/`MiB`/`GiB`/`TiB` binary (powers of 1024); units are
+    /// case-insensitive and a bare number is a byte count.
+    pub fn parse_size(input: &str) -> Result<u64, String> {
+        let input = input.trim();
+        let split = input
+            .find(|c: char| !c.is_ascii_digit() && c != '.')
+            .unwrap_or(input.len());
+        let (number, unit) = input.split_at(split);
+        let multiplier: u64 = match unit.trim().to_ascii_lowercase().as_str() {
+            "" | "b" => 1,
+            "kb" => 1000,
+            "mb" => 1000u64.pow(2),
+            "gb" => 1000u64.pow(3),
+            "tb" => 1000u64.pow(4),
+            "kib" => 1 << 10,
+            "mib" => 1 << 20,
+            "gib" => 1 << 30,
+            "tib" => 1 << 40,
+            other => return Err(format!("Unknown size unit {:?} in {:?}", other, input)),
+        };
+
+        let invalid = || format!("Invalid size {:?}", input);
+        if let Ok(whole) = number.parse::<u64>() {
+            return whole.checked_mul(multiplier).ok_or_else(invalid);
+        }
+        let value: f64 = number.parse().map_err(|_| invalid())?;
+        let bytes = (value * multiplier as f64).round();
+        if !bytes.is_finite() || bytes >= u64::MAX as f64 {
+            return Err(invalid());
+        }
+        Ok(bytes as u64)
+    }
+
+    /// Parse a point in time written as an RFC3339 timestamp such as
+    /// `2030-01-01T00:00:00Z`, or as a duration from now such as `2h` or
+    /// `7days`
+    pub fn parse_expiry(input: &str) -> Result<std::time::SystemTime, String> {
+        let input = input.trim();
+        if let Ok(at) = humantime::parse_rfc3339_weak(input) {
+            return Ok(at);
+        }
+        humantime::parse_duration(input)
+            .map(|duration| std::time::SystemTime::now() + duration)
+            .map_err(|_| {
+                format!(
+                    "Invalid expiry {:?}: expected a timestamp or a duration like 2h",
+                    input
+                )
+            })
+    }
+
+    /// Format a duration like `1h 02m 03s`, `2m 05s`, `45s` or `120ms`
+    pub fn format_duration(duration: std::time::Duration) -> String {
+        let secs = duration.as_secs();
+        let (hours, minutes, seconds) = (secs / 3600, secs / 60 % 60, secs % 60);
+        if hours > 0 {
+            format!("{}h {:02}m {:02}s", hours, minutes, seconds)
+        } else if minutes > 0 {
+            format!("{}m {:02}s", minutes, seconds)
+        } else if secs > 0 {
+            format!("{}s", seconds)
+        } else {
+            format!("{}ms", duration.as_millis())
+        }
+    }
+
+    /// Get the filename from a path, replacing invalid UTF-8. Names with
+    /// control characters are not safe to print and yield `None`.
     pub fn get_filename(path: &Path) -> Option<String> {
-        path.file_name()
-            .and_then(|os_str| os_str.to_str())
-            .map(String::from)
+        let name = path.file_name()?.to_string_lossy();
+        if name.chars().any(char::is_control) {
+            return None;
+        }
+        Some(name.into_owned())
     }
 
     /// Calculate the number of chunks for a file given a chunk size
@@ -308,3 +328,153 @@ impl Default for UtilityService {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    #[test]
+    fn test_get_filename_rejects_control_characters() {
+        assert_eq!(
+            UtilityService::get_filename(Path::new("/tmp/report.pdf")),
+            Some("report.pdf".to_string())
+        );
+        for name in ["evil\nname.txt", "nul\0.txt", "\x1b[31mred.txt"] {
+            assert_eq!(UtilityService::get_filename(Path::new(name)), None);
+        }
+    }
+
+    #[test]
+    fn test_parse_size() {
+        assert_eq!(UtilityService::parse_size("1024"), Ok(1024));
+        assert_eq!(UtilityService::parse_size("1KB"), Ok(1000));
+        assert_eq!(UtilityService::parse_size("1KiB"), Ok(1024));
+        assert_eq!(UtilityService::parse_size("1.5MB"), Ok(1_500_000));
+        assert_eq!(UtilityService::parse_size("1.5mib"), Ok(1_572_864));
+        assert_eq!(UtilityService::parse_size("2GiB"), Ok(2 << 30));
+        assert_eq!(UtilityService::parse_size(" 10 MB "), Ok(10_000_000));
+        assert_eq!(UtilityService::parse_size("512B"), Ok(512));
+
+        for invalid in ["", "MB", "abc", "1.2.3KB", "10XB", "-1KB", "99999999999TiB"] {
+            assert!(
+                UtilityService::parse_size(invalid).is_err(),
+                "{:?} should not parse",
+                invalid
+            );
+        }
+    }
+
+    #[test]
+    fn test_parse_port_range() {
+        assert_eq!(
+            UtilityService::parse_port_range("9000-9010"),
+            Ok(9000..=9010)
+        );
+        assert_eq!(UtilityService::parse_port_range(" 9000 "), Ok(9000..=9000));
+        assert_eq!(UtilityService::parse_port_range("0"), Ok(0..=0));
+
+        for invalid in ["", "9010-9000", "0-10", "9000-", "1-70000", "a-b"] {
+            assert!(
+                UtilityService::parse_port_range(invalid).is_err(),
+                "{:?} should not parse",
+                invalid
+            );
+        }
+    }
+
+    #[test]
+    fn test_parse_expiry() {
+        use std::time::{Duration, SystemTime};
+
+        assert_eq!(
+            UtilityService::parse_expiry("2030-01-01T00:00:00Z"),
+            Ok(humantime::parse_rfc3339("2030-01-01T00:00:00Z").unwrap())
+        );
+        let before = SystemTime::now();
+        let in_two_hours = UtilityService::parse_expiry(" 2h ").unwrap();
+        assert!(in_two_hours >= before + Duration::from_secs(7200));
+        assert!(in_two_hours <= SystemTime::now() + Duration::from_secs(7200));
+
+        for invalid in ["", "soon", "2030-13-01T00:00:00Z"] {
+            assert!(
+                UtilityService::parse_expiry(invalid).is_err(),
+                "{:?} should not parse",
+                invalid
+            );
+        }
+    }
+
+    #[test]
+    fn test_format_duration() {
+        use std::time::Duration;
+
+        assert_eq!(UtilityService::format_duration(Duration::ZERO), "0ms");
+        assert_eq!(
+            UtilityService::format_duration(Duration::from_millis(120)),
+            "120ms"
+        );
+        assert_eq!(
+            UtilityService::format_duration(Duration::from_millis(45_900)),
+            "45s"
+        );
+        assert_eq!(
+            UtilityService::format_duration(Duration::from_secs(125)),
+            "2m 05s"
+        );
+        assert_eq!(
+            UtilityService::format_duration(Duration::from_secs(3723)),
+            "1h 02m 03s"
+        );
+        assert_eq!(
+            UtilityService::format_duration(Duration::from_secs(30 * 3600)),
+            "30h 00m 00s"
+        );
+    }
+
+    #[test]
+    fn test_crypto_service_matches_core_crypto() {
+        let key = crypto::generate_key().unwrap();
+        let data = b"same input, same output";
+
+        // Resealing under the nonce each entry point picked must reproduce
+        // its ciphertext byte for byte
+        for encrypted in [
+            CryptoService::encrypt(data, &key).unwrap(),
+            crypto::encrypt(data, &key).unwrap(),
+        ] {
+            let nonce = encrypted[..12].try_into().unwrap();
+            assert_eq!(crypto::seal(data, &key, nonce).unwrap(), encrypted);
+            assert_eq!(CryptoService::decrypt(&encrypted, &key).unwrap(), data);
+            assert_eq!(crypto::decrypt(&encrypted, &key).unwrap(), data);
+        }
+
+        let (private_key, public_key) = CryptoService::generate_signing_keypair().unwrap();
+        assert_eq!(
+            CryptoService::sign_message(data, &private_key).unwrap(),
+            crypto::sign_message(data, &private_key).unwrap()
+        );
+        let signature = crypto::sign_message(data, &private_key).unwrap();
+        assert!(CryptoService::verify_signature(data, &signature, &public_key).unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_file_hash_entry_points_agree() {
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        file.write_all(&vec![7u8; 100_000]).unwrap();
+
+        let expected = crypto::hash::compute_data_hash(&vec![7u8; 100_000]);
+        assert_eq!(
+            crypto::compute_file_hash(file.path()).await.unwrap(),
+            expected
+        );
+        assert_eq!(
+            CryptoService::compute_file_hash(file.path()).await.unwrap(),
+            expected
+        );
+        assert_eq!(
+            UtilityService::sha256_file(file.path()).await.unwrap(),
+            expected
+        );
+    }
+}
@@ -13,6 +13,10 @@ pub mod file_transfer;
 // Protocol module for backward compatibility
 pub mod protocol;
 
+// Test helpers for this crate and downstream users
+#[cfg(feature = "testing")]
+pub mod testing;
+
 // Re-export specific items to avoid ambiguous glob re-exports
 pub use application::{ApplicationService, FileSystemService, UseCases};
 pub use core::domain::*;
@@ -1,7 +1,7 @@
 use clap::{Parser, Subcommand};
 use std::error::Error;
 use std::path::PathBuf;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use tracing::{error, info};
 
 // Added for tracing file logging
@@ -10,9 +10,20 @@ use tracing_subscriber::{EnvFilter, Layer, fmt, layer::SubscriberExt, util::Subs
 
 // Use new modular structure
 use cipherstream::{
-    application::ApplicationService,
-    core::{domain::PeerId, traits::NetworkService},
-    infrastructure::{AppConfig, CryptoService, InMemoryEventPublisher, LibP2pNetworkService},
+    application::{
+        ApplicationService, ControlRequest, ControlServer, GossipSink, Node, active_bans, ban_peer,
+        export_peers, import_peers, send_control_request,
+    },
+    core::{
+        domain::PeerId,
+        services::{HeartbeatService, PeerDomainService},
+        traits::NetworkService,
+    },
+    file_transfer::{SymlinkPolicy, WalkEntry, walk_directory},
+    infrastructure::{
+        AppConfig, CryptoService, DEFAULT_CONTROL_PORT, InMemoryEventPublisher,
+        LibP2pNetworkService, UtilityService, network::NetworkEventDto,
+    },
 };
 
 #[derive(Parser)]
@@ -33,19 +44,52 @@ enum Commands {
         #[arg(short, long, default_value_t = 8000)]
         port: u16,
 
+        /// Listen on the first free port in this range, e.g. 8000-8010, instead
+        /// of --port; 0 lets the OS pick one
+        #[arg(long, value_parser = UtilityService::parse_port_range)]
+        port_range: Option<std::ops::RangeInclusive<u16>>,
+
         /// Optional data directory for storing node data
         #[arg(long, default_value = ".cipherstream")]
         data_dir: String,
+
+        /// Re-hash received files against the sender's hash (pass false to skip)
+        #[arg(long, default_value_t = true, action = clap::ArgAction::Set)]
+        verify_after: bool,
+
+        /// Largest chunk to send or accept, e.g. 1MiB or 512KB
+        #[arg(long, default_value = "1MiB", value_parser = UtilityService::parse_size)]
+        chunk_size: u64,
+
+        /// Cap on upload bandwidth per second, e.g. 10MB
+        #[arg(long, value_parser = UtilityService::parse_size)]
+        max_upload_rate: Option<u64>,
+
+        /// Cap on download bandwidth per second, e.g. 10MB
+        #[arg(long, value_parser = UtilityService::parse_size)]
+        max_download_rate: Option<u64>,
+
+        /// Loopback port to take `transfer` commands on; 0 disables them
+        #[arg(long, default_value_t = DEFAULT_CONTROL_PORT)]
+        control_port: u16,
     },
     /// Send a file to a peer
     Send {
-        /// Path to the file to send
+        /// Path to the file or directory to send
         #[arg(short, long)]
         file: PathBuf,
 
         /// Peer ID to send to
         #[arg(short, long)]
         peer: String,
+
+        /// Refuse files larger than this, e.g. 500MB (defaults to the configured limit)
+        #[arg(long, value_parser = UtilityService::parse_size)]
+        max_file_size: Option<u64>,
+
+        /// What to do with symlinks inside a directory: skip, follow or preserve
+        #[arg(long, default_value_t = SymlinkPolicy::Skip)]
+        follow_symlinks: SymlinkPolicy,
     },
     /// Connect to a specific peer
     Connect {
@@ -53,8 +97,19 @@ enum Commands {
         #[arg(short, long)]
         peer: String,
     },
-    /// List discovered peers
-    Peers,
+    /// Cancel, pause or resume a transfer on the node running on this machine
+    Transfer {
+        /// Control port the node was started with
+        #[arg(long, global = true, default_value_t = DEFAULT_CONTROL_PORT)]
+        control_port: u16,
+        #[command(subcommand)]
+        action: TransferAction,
+    },
+    /// List discovered peers, export/import them as an address book, or ban them
+    Peers {
+        #[command(subcommand)]
+        action: Option<PeersAction>,
+    },
     /// Discover peers for a short period and print events
     Discover {
         /// Duration in seconds to listen for discovery events
@@ -63,6 +118,128 @@ enum Commands {
         /// Port to bind temporarily (helpful if no node is running)
         #[arg(short, long, default_value_t = 8000)]
         port: u16,
+        /// Gossipsub topic to subscribe to and print messages from
+        #[arg(short, long)]
+        topic: Option<String>,
+        /// Print one JSON object per event instead of text
+        #[arg(long, default_value_t = false)]
+        json: bool,
+    },
+    /// Publish a single message to a gossipsub topic and exit
+    #[command(group(clap::ArgGroup::new("payload").required(true).args(["message", "file"])))]
+    Publish {
+        /// Gossipsub topic to publish to
+        #[arg(short, long)]
+        topic: String,
+        /// Message text to publish
+        #[arg(short, long)]
+        message: Option<String>,
+        /// Read the payload from a file instead
+        #[arg(short, long)]
+        file: Option<PathBuf>,
+        /// Multiaddr of a peer to dial before publishing (repeatable)
+        #[arg(long = "peer")]
+        peers: Vec<String>,
+        /// Seconds to wait for a subscribed peer before giving up
+        #[arg(short, long, default_value_t = 10)]
+        wait: u64,
+        /// Port to bind temporarily
+        #[arg(long, default_value_t = 0)]
+        port: u16,
+    },
+    /// Join a gossipsub topic and print its messages until Ctrl-C
+    Subscribe {
+        /// Gossipsub topic to subscribe to
+        #[arg(short, long)]
+        topic: String,
+        /// Directory to write each received payload to
+        #[arg(short, long)]
+        output_dir: Option<PathBuf>,
+        /// Multiaddr of a peer to dial after starting (repeatable)
+        #[arg(long = "peer")]
+        peers: Vec<String>,
+        /// Port to listen on
+        #[arg(short, long, default_value_t = 0)]
+        port: u16,
+    },
+}
+
+#[derive(Subcommand)]
+enum TransferAction {
+    /// Stop a transfer for good
+    Cancel {
+        /// Transfer ID
+        transfer_id: String,
+    },
+    /// Stop sending a transfer until it is resumed
+    Pause {
+        /// Transfer ID
+        transfer_id: String,
+    },
+    /// Continue a paused transfer
+    Resume {
+        /// Transfer ID
+        transfer_id: String,
+    },
+}
+
+impl TransferAction {
+    /// The control request carrying out this action, and what to print once
+    /// the node has
+    fn into_request(self) -> (ControlRequest, String) {
+        match self {
+            TransferAction::Cancel { transfer_id } => (
+                ControlRequest::CancelTransfer {
+                    transfer_id: transfer_id.clone(),
+                },
+                format!("Cancelled transfer {}", transfer_id),
+            ),
+            TransferAction::Pause { transfer_id } => (
+                ControlRequest::PauseTransfer {
+                    transfer_id: transfer_id.clone(),
+                },
+                format!("Paused transfer {}", transfer_id),
+            ),
+            TransferAction::Resume { transfer_id } => (
+                ControlRequest::ResumeTransfer {
+                    transfer_id: transfer_id.clone(),
+                },
+                format!("Resumed transfer {}", transfer_id),
+            ),
+        }
+    }
+}
+
+#[derive(Subcommand)]
+enum PeersAction {
+    /// Write known peers to a JSON address book
+    Export {
+        /// File to write the address book to
+        #[arg(long)]
+        out: PathBuf,
+    },
+    /// Load peers from a JSON address book written by `peers export`
+    Import {
+        /// Address book to read
+        #[arg(long = "in")]
+        input: PathBuf,
+    },
+    /// Refuse connections from a peer, across restarts
+    Ban {
+        /// Peer ID to ban
+        peer: String,
+        /// Lift the ban at this time, e.g. 2030-01-01T00:00:00Z, or after a
+        /// duration such as 2h; bans last until unbanned otherwise
+        #[arg(long, value_parser = UtilityService::parse_expiry)]
+        until: Option<std::time::SystemTime>,
+        /// Why the peer is banned, kept with the ban
+        #[arg(long)]
+        reason: Option<String>,
+    },
+    /// Lift a ban made with `peers ban`
+    Unban {
+        /// Peer ID to unban
+        peer: String,
     },
 }
 
@@ -139,16 +316,31 @@ async fn main() -> Result<(), Box<dyn Error>> {
     let _guard = init_logging("cipherstream_node", cli.quiet)?;
 
     match cli.command {
-        Commands::Start { port, data_dir } => {
+        Commands::Start {
+            port,
+            port_range,
+            data_dir,
+            verify_after,
+            chunk_size,
+            max_upload_rate,
+            max_download_rate,
+            control_port,
+        } => {
             info!("Starting node on port {}...", port);
 
             // Create application configuration
-            let config = AppConfig {
+            let mut config = AppConfig {
                 default_port: port,
+                control_port,
                 data_directory: data_dir.clone(),
                 download_directory: format!("{}/downloads", data_dir),
+                verify_after_transfer: verify_after,
+                chunk_size: usize::try_from(chunk_size)
+                    .map_err(|_| format!("Chunk size {} is too large", chunk_size))?,
                 ..AppConfig::default()
             };
+            config.network.max_upload_bytes_per_sec = max_upload_rate;
+            config.network.max_download_bytes_per_sec = max_download_rate;
 
             // Initialize application service
             let app_service = ApplicationService::new(config.clone()).await?;
@@ -157,24 +349,102 @@ async fn main() -> Result<(), Box<dyn Error>> {
                 app_service.config().data_directory
             );
 
-            // Initialize event publisher
-            let event_publisher = std::sync::Arc::new(InMemoryEventPublisher::new());
+            // Wire the network and transfer services, answering inbound
+            // transfers once the node listens
+            let node = Node::new(&app_service)
+                .await
+                .map_err(|e| format!("Failed to build node: {}", e))?;
+            let event_publisher = node.events.clone();
+            let transfer_service = node.transfer_service.clone();
+            let network_service = node.network.clone();
+
+            // Connections don't survive a restart, whatever the stored peers say
+            PeerDomainService::new(app_service.peer_repository.clone(), event_publisher.clone())
+                .reset_connections()
+                .await
+                .map_err(|e| format!("Failed to reset peer connections: {}", e))?;
 
-            // Initialize libp2p network service
-            let network_service =
-                LibP2pNetworkService::new(std::sync::Arc::new(config), event_publisher)
+            // Take transfer commands from the CLI on this machine
+            if config.control_port != 0 {
+                let control = ControlServer::bind(config.control_port, transfer_service.clone())
                     .await
-                    .map_err(|e| format!("Failed to create network service: {}", e))?;
+                    .map_err(|e| format!("Failed to start control API: {}", e))?;
+                info!("Control API listening on port {}", config.control_port);
+                control.spawn();
+            }
+
+            // Reconcile transfers interrupted by a previous run in the background
+            let grace_period = config.resume_grace_period();
+            tokio::spawn(async move {
+                match transfer_service
+                    .recover_interrupted_transfers(grace_period)
+                    .await
+                {
+                    Ok(recovery) => info!(
+                        "Transfer recovery: {} paused until resumed, {} failed",
+                        recovery.resumable.len(),
+                        recovery.failed.len()
+                    ),
+                    Err(e) => error!("Transfer recovery failed: {}", e),
+                }
+            });
+
+            // Publish a liveness signal for external monitors
+            if let Some(interval) = config.heartbeat_interval() {
+                HeartbeatService::new(
+                    app_service.peer_repository.clone(),
+                    app_service.transfer_repository.clone(),
+                    event_publisher.clone(),
+                )
+                .spawn(interval);
+            }
 
             let peer_id = network_service.local_peer_id();
             info!("Local peer id: {}", peer_id);
 
-            // Start the network service
-            network_service
-                .start_listening(port)
+            // Seed the DHT with peers remembered from earlier runs or imports
+            let known_peers = app_service
+                .peer_repository
+                .list_all_peers()
                 .await
-                .map_err(|e| format!("Failed to start listening: {}", e))?;
+                .map_err(|e| format!("Failed to load known peers: {}", e))?;
+            for peer in known_peers {
+                let Ok(id) = peer.id.as_str().parse() else {
+                    continue;
+                };
+                for addr in peer.addresses.iter().filter_map(|a| a.parse().ok()) {
+                    network_service
+                        .add_kademlia_address(id, addr)
+                        .await
+                        .map_err(|e| format!("Failed to add known peer: {}", e))?;
+                }
+            }
+
+            // Keep refusing peers banned in earlier runs
+            let bans = active_bans(
+                app_service.peer_repository.as_ref(),
+                std::time::SystemTime::now(),
+            )
+            .await
+            .map_err(|e| format!("Failed to load peer bans: {}", e))?;
+            for ban in bans {
+                let Ok(id) = ban.peer.as_str().parse() else {
+                    continue;
+                };
+                network_service
+                    .ban_peer(id, ban.until)
+                    .await
+                    .map_err(|e| format!("Failed to ban peer: {}", e))?;
+            }
+
+            // Start the network service
+            let port = match port_range {
+                Some(ports) => network_service.start_in_range(ports).await,
+                None => network_service.start_listening(port).await.map(|_| port),
+            }
+            .map_err(|e| format!("Failed to start listening: {}", e))?;
             info!("Node started on port {}", port);
+            println!("Listening on port {}", port);
 
             // Keep the process running
             loop {
@@ -182,11 +452,48 @@ async fn main() -> Result<(), Box<dyn Error>> {
                 info!("Node is running...");
             }
         }
-        Commands::Send { file, peer } => {
+        Commands::Send {
+            file,
+            peer,
+            max_file_size,
+            follow_symlinks,
+        } => {
             if !file.exists() {
                 error!("File does not exist: {:?}", file);
                 return Err("File not found".into());
             }
+            if file.is_dir() {
+                let entries = walk_directory(&file, follow_symlinks)
+                    .await
+                    .map_err(|e| format!("Failed to walk {}: {}", file.display(), e))?;
+                println!(
+                    "{} entries to send from {} (symlinks: {}):",
+                    entries.len(),
+                    file.display(),
+                    follow_symlinks
+                );
+                for entry in &entries {
+                    match entry {
+                        WalkEntry::File { relative, .. } => println!("  {}", relative.display()),
+                        WalkEntry::Symlink { relative, target } => {
+                            println!("  {} -> {}", relative.display(), target.display())
+                        }
+                    }
+                }
+                return Ok(());
+            }
+            let max_file_size =
+                max_file_size.unwrap_or_else(|| AppConfig::default().max_file_size());
+            let size = std::fs::metadata(&file)?.len();
+            if size > max_file_size {
+                return Err(format!(
+                    "{} is {}, over the {} limit for sending",
+                    file.display(),
+                    UtilityService::format_size(size),
+                    UtilityService::format_size(max_file_size)
+                )
+                .into());
+            }
 
             // Parse peer ID using new modular structure
             let peer_id = PeerId::from_string(peer);
@@ -205,6 +512,22 @@ async fn main() -> Result<(), Box<dyn Error>> {
                 "File transfer command prepared (implementation pending with new architecture)."
             );
         }
+        Commands::Transfer {
+            control_port,
+            action,
+        } => {
+            let (request, done) = action.into_request();
+            let response = send_control_request(control_port, &request)
+                .await
+                .map_err(|e| e.to_string())?;
+            if !response.ok {
+                let error = response
+                    .error
+                    .unwrap_or_else(|| "no reason given".to_string());
+                return Err(error.into());
+            }
+            println!("{}", done);
+        }
         Commands::Connect { peer } => {
             // Parse peer ID using new structure
             let peer_id = PeerId::from_string(peer);
@@ -212,7 +535,66 @@ async fn main() -> Result<(), Box<dyn Error>> {
             info!("Connecting to peer: {}", peer_id.as_str());
             println!("Connection functionality will be implemented with new modular architecture.");
         }
-        Commands::Peers => {
+        Commands::Peers {
+            action: Some(PeersAction::Export { out }),
+        } => {
+            let app_service = ApplicationService::new(AppConfig::default()).await?;
+            let count = export_peers(app_service.peer_repository.as_ref(), &out)
+                .await
+                .map_err(|e| format!("Failed to export peers: {}", e))?;
+            println!("Exported {} peers to {}", count, out.display());
+        }
+        Commands::Peers {
+            action: Some(PeersAction::Import { input }),
+        } => {
+            let app_service = ApplicationService::new(AppConfig::default()).await?;
+            let peers = import_peers(app_service.peer_repository.as_ref(), &input)
+                .await
+                .map_err(|e| format!("Failed to import peers: {}", e))?;
+            println!(
+                "Imported {} peers from {}; they are added to the DHT when the node starts",
+                peers.len(),
+                input.display()
+            );
+        }
+        Commands::Peers {
+            action:
+                Some(PeersAction::Ban {
+                    peer,
+                    until,
+                    reason,
+                }),
+        } => {
+            let app_service = ApplicationService::new(AppConfig::default()).await?;
+            let ban = ban_peer(app_service.peer_repository.as_ref(), &peer, reason, until)
+                .await
+                .map_err(|e| format!("Failed to ban peer: {}", e))?;
+            match ban.until {
+                Some(until) => println!(
+                    "Banned {} until {}",
+                    ban.peer.as_str(),
+                    humantime::format_rfc3339_seconds(until)
+                ),
+                None => println!("Banned {}", ban.peer.as_str()),
+            }
+            println!("The ban takes effect the next time the node starts");
+        }
+        Commands::Peers {
+            action: Some(PeersAction::Unban { peer }),
+        } => {
+            let app_service = ApplicationService::new(AppConfig::default()).await?;
+            let removed = app_service
+                .peer_repository
+                .remove_ban(&PeerId::from_string(peer.clone()))
+                .await
+                .map_err(|e| format!("Failed to unban peer: {}", e))?;
+            if removed {
+                println!("Unbanned {}", peer);
+            } else {
+                println!("{} was not banned", peer);
+            }
+        }
+        Commands::Peers { action: None } => {
             info!("Listing peers...");
 
             // In the new architecture, peer discovery would be done through the running network service
@@ -226,6 +608,8 @@ async fn main() -> Result<(), Box<dyn Error>> {
         Commands::Discover {
             duration_secs,
             port,
+            topic,
+            json,
         } => {
             info!(
                 "Discovering peers for {} seconds on port {}...",
@@ -248,40 +632,155 @@ async fn main() -> Result<(), Box<dyn Error>> {
                 .await
                 .map_err(|e| format!("Failed to start listening: {}", e))?;
 
+            if let Some(topic) = &topic {
+                network_service
+                    .subscribe_topic(topic)
+                    .await
+                    .map_err(|e| format!("Failed to subscribe to {}: {}", topic, e))?;
+                info!("Listening for gossip on topic {}", topic);
+            }
+
             let events = network_service
                 .collect_events_for(Duration::from_secs(duration_secs))
                 .await;
 
-            println!("Discovered {} events:", events.len());
+            if !json {
+                println!("Discovered {} events:", events.len());
+            }
             for ev in events {
-                match ev {
-                    cipherstream::infrastructure::network::NetworkEvent::PeerConnected(pid) => {
-                        println!("Peer connected: {}", pid);
-                    }
-                    cipherstream::infrastructure::network::NetworkEvent::PeerDisconnected(pid) => {
-                        println!("Peer disconnected: {}", pid);
-                    }
-                    cipherstream::infrastructure::network::NetworkEvent::GossipMessage {
-                        from,
-                        topic,
-                        ..
-                    } => {
-                        println!("Gossip message from {} on topic {}", from, topic);
-                    }
-                    cipherstream::infrastructure::network::NetworkEvent::FileTransferRequest {
-                        from,
-                        ..
-                    } => {
-                        println!("File transfer request from {}", from);
-                    }
-                    cipherstream::infrastructure::network::NetworkEvent::FileTransferResponse {
-                        from,
-                        ..
-                    } => {
-                        println!("File transfer response from {}", from);
+                if json {
+                    println!("{}", serde_json::to_string(&NetworkEventDto::from(&ev))?);
+                } else {
+                    println!("{}", ev);
+                }
+            }
+        }
+        Commands::Publish {
+            topic,
+            message,
+            file,
+            peers,
+            wait,
+            port,
+        } => {
+            let payload = match (message, file) {
+                (Some(message), _) => message.into_bytes(),
+                (None, Some(file)) => std::fs::read(&file)
+                    .map_err(|e| format!("Failed to read {}: {}", file.display(), e))?,
+                (None, None) => unreachable!("clap requires a message or file"),
+            };
+
+            let event_publisher = std::sync::Arc::new(InMemoryEventPublisher::new());
+            let network_service = LibP2pNetworkService::new(
+                std::sync::Arc::new(AppConfig::default()),
+                event_publisher,
+            )
+            .await
+            .map_err(|e| format!("Failed to create network service: {}", e))?;
+            network_service
+                .start_listening(port)
+                .await
+                .map_err(|e| format!("Failed to start listening: {}", e))?;
+
+            for peer in peers {
+                let addr = peer
+                    .parse()
+                    .map_err(|e| format!("Invalid peer address {}: {}", peer, e))?;
+                let peer_id = network_service
+                    .connect_and_wait(addr)
+                    .await
+                    .map_err(|e| format!("Failed to connect to {}: {}", peer, e))?;
+                info!("Connected to {}", peer_id);
+            }
+
+            let len = payload.len();
+            network_service
+                .publish_when_subscribed(&topic, payload, Duration::from_secs(wait))
+                .await
+                .map_err(|e| {
+                    format!(
+                        "{}. Start a subscriber with `discover --topic {}` or pass --peer",
+                        e, topic
+                    )
+                })?;
+
+            // Give the swarm a moment to flush the message before exiting
+            tokio::time::sleep(Duration::from_millis(500)).await;
+            println!("Published {} bytes to topic {}", len, topic);
+        }
+        Commands::Subscribe {
+            topic,
+            output_dir,
+            peers,
+            port,
+        } => {
+            let event_publisher = std::sync::Arc::new(InMemoryEventPublisher::new());
+            let network_service = LibP2pNetworkService::new(
+                std::sync::Arc::new(AppConfig::default()),
+                event_publisher,
+            )
+            .await
+            .map_err(|e| format!("Failed to create network service: {}", e))?;
+            network_service
+                .start_listening(port)
+                .await
+                .map_err(|e| format!("Failed to start listening: {}", e))?;
+            network_service
+                .subscribe_topic(&topic)
+                .await
+                .map_err(|e| format!("Failed to subscribe to {}: {}", topic, e))?;
+            info!("Local peer id: {}", network_service.local_peer_id());
+
+            for peer in peers {
+                let addr = peer
+                    .parse()
+                    .map_err(|e| format!("Invalid peer address {}: {}", peer, e))?;
+                network_service
+                    .connect_and_wait(addr)
+                    .await
+                    .map_err(|e| format!("Failed to connect to {}: {}", peer, e))?;
+            }
+
+            let mut sink = GossipSink::new(topic.clone());
+            if let Some(dir) = output_dir {
+                sink = sink.with_output_dir(dir);
+            }
+            println!("Subscribed to {}; press Ctrl-C to stop", topic);
+            let subscribed_at = Instant::now();
+
+            loop {
+                tokio::select! {
+                    _ = tokio::signal::ctrl_c() => break,
+                    event = network_service.next_event() => {
+                        let Some(event) = event else { break };
+                        let Some(delivery) = sink
+                            .accept(event)
+                            .await
+                            .map_err(|e| format!("Failed to handle message: {}", e))?
+                        else {
+                            continue;
+                        };
+                        match delivery.saved_to {
+                            Some(path) => println!(
+                                "Message from {} saved to {}",
+                                delivery.from,
+                                path.display()
+                            ),
+                            None => println!(
+                                "Message from {}: {}",
+                                delivery.from,
+                                String::from_utf8_lossy(&delivery.data)
+                            ),
+                        }
                     }
                 }
             }
+            println!(
+                "Received {} messages on {} in {}",
+                sink.received(),
+                topic,
+                UtilityService::format_duration(subscribed_at.elapsed())
+            );
         }
     }
 
@@ -8,6 +8,7 @@ pub const PROTOCOL_ID: &str = "/cipherstream/file-transfer/1.0.0";
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::file_transfer::Compression;
     use crate::file_transfer::types::{ProtocolRequest, ProtocolResponse};
 
     #[test]
@@ -22,6 +23,11 @@ mod tests {
             filename: "test.txt".to_string(),
             filesize: 1024,
             transfer_id: "abc123".to_string(),
+            sha256: None,
+            merkle_root: None,
+            chunk_size: None,
+            adaptive_chunks: false,
+            compression: Compression::None,
         };
 
         // Basic sanity check that the request is constructed properly
@@ -30,6 +36,7 @@ mod tests {
                 filename,
                 filesize,
                 transfer_id,
+                ..
             } => {
                 assert_eq!(filename, "test.txt");
                 assert_eq!(filesize, 1024);
@@ -45,6 +52,8 @@ mod tests {
             accepted: true,
             reason: None,
             transfer_id: Some("abc123".to_string()),
+            accepted_chunk_size: None,
+            compression: Compression::None,
         };
 
         // Basic sanity check that the response is constructed properly
@@ -53,10 +62,14 @@ mod tests {
                 accepted,
                 reason,
                 transfer_id,
+                accepted_chunk_size,
+                compression,
             } => {
                 assert!(accepted);
                 assert_eq!(reason, None);
                 assert_eq!(transfer_id, Some("abc123".to_string()));
+                assert_eq!(accepted_chunk_size, None);
+                assert_eq!(compression, Compression::None);
             }
             _ => panic!("Wrong variant"),
         }
@@ -0,0 +1,191 @@
+use crate::core::domain::{DomainEvent, PeerId};
+use crate::core::traits::{DomainResult, EventPublisher, NetworkService};
+use crate::infrastructure::events::InMemoryEventPublisher;
+use crate::infrastructure::network::{LibP2pNetworkService, peer_id_from_addr};
+use async_trait::async_trait;
+use libp2p::Multiaddr;
+use std::net::{Ipv4Addr, TcpListener};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Mutex;
+
+/// A local TCP port held open until the caller is ready to use it
+#[derive(Debug)]
+pub struct ReservedPort {
+    listener: TcpListener,
+    port: u16,
+}
+
+impl ReservedPort {
+    /// The reserved port number
+    pub fn port(&self) -> u16 {
+        self.port
+    }
+
+    /// Release the port so it can be bound, returning its number.
+    ///
+    /// Call this immediately before binding to keep the window in which
+    /// another process could grab the port as small as possible.
+    pub fn release(self) -> u16 {
+        drop(self.listener);
+        self.port
+    }
+}
+
+/// Reserve a free local TCP port chosen by the OS
+pub fn reserve_port() -> ReservedPort {
+    let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).expect("Failed to bind port 0");
+    let port = listener
+        .local_addr()
+        .expect("Bound listener has no local address")
+        .port();
+    ReservedPort { listener, port }
+}
+
+/// Wait for a started node to report its loopback listen address
+pub async fn loopback_addr(node: &LibP2pNetworkService) -> Multiaddr {
+    for _ in 0..250 {
+        if let Some(addr) = node
+            .listen_addresses()
+            .await
+            .expect("Network service stopped")
+            .into_iter()
+            .find(|a| a.to_string().starts_with("/ip4/127.0.0.1"))
+        {
+            return addr;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    panic!("Node never reported a loopback listen address");
+}
+
+/// `NetworkService` test double that records outgoing traffic instead of
+/// touching the network
+pub struct MockNetworkService {
+    sent: Mutex<Vec<(PeerId, Vec<u8>)>>,
+    broadcasts: Mutex<Vec<Vec<u8>>>,
+    topic_broadcasts: Mutex<Vec<(String, Vec<u8>)>>,
+    event_publisher: Arc<dyn EventPublisher>,
+}
+
+impl MockNetworkService {
+    pub fn new() -> Self {
+        Self {
+            sent: Mutex::new(Vec::new()),
+            broadcasts: Mutex::new(Vec::new()),
+            topic_broadcasts: Mutex::new(Vec::new()),
+            event_publisher: Arc::new(InMemoryEventPublisher::new()),
+        }
+    }
+
+    /// Publish injected and connection events to `event_publisher`
+    pub fn with_event_publisher(mut self, event_publisher: Arc<dyn EventPublisher>) -> Self {
+        self.event_publisher = event_publisher;
+        self
+    }
+
+    /// Messages passed to `send_message`, in order
+    pub async fn sent_messages(&self) -> Vec<(PeerId, Vec<u8>)> {
+        self.sent.lock().await.clone()
+    }
+
+    /// Messages passed to `broadcast_message`, in order
+    pub async fn broadcasts(&self) -> Vec<Vec<u8>> {
+        self.broadcasts.lock().await.clone()
+    }
+
+    /// Messages passed to `broadcast_to_topic` with their topics, in order
+    pub async fn topic_broadcasts(&self) -> Vec<(String, Vec<u8>)> {
+        self.topic_broadcasts.lock().await.clone()
+    }
+
+    /// Deliver `event` as if it had arrived from the network
+    pub async fn inject_event(&self, event: DomainEvent) -> DomainResult<()> {
+        self.event_publisher.publish(event).await
+    }
+}
+
+impl Default for MockNetworkService {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl NetworkService for MockNetworkService {
+    async fn start_listening(&self, _port: u16) -> DomainResult<()> {
+        Ok(())
+    }
+
+    async fn connect(&self, addr: Multiaddr) -> DomainResult<PeerId> {
+        let peer_id = peer_id_from_addr(&addr)
+            .map(PeerId::from)
+            .ok_or_else(|| format!("Address {} has no /p2p/ peer id", addr))?;
+        self.event_publisher
+            .publish(DomainEvent::PeerConnected {
+                peer_id: peer_id.clone(),
+            })
+            .await?;
+        Ok(peer_id)
+    }
+
+    async fn send_message(&self, peer_id: &PeerId, message: Vec<u8>) -> DomainResult<()> {
+        self.sent.lock().await.push((peer_id.clone(), message));
+        Ok(())
+    }
+
+    async fn broadcast_message(&self, message: Vec<u8>) -> DomainResult<()> {
+        self.broadcasts.lock().await.push(message);
+        Ok(())
+    }
+
+    async fn broadcast_to_topic(&self, topic: &str, data: Vec<u8>) -> DomainResult<usize> {
+        self.topic_broadcasts
+            .lock()
+            .await
+            .push((topic.to_string(), data));
+        Ok(0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reservations_differ() {
+        let first = reserve_port();
+        let second = reserve_port();
+        assert_ne!(first.port(), second.port());
+        assert_ne!(first.release(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_mock_network_service_records_traffic() {
+        let events = Arc::new(InMemoryEventPublisher::new());
+        let network = MockNetworkService::new().with_event_publisher(events.clone());
+        let peer = PeerId::new("peer-a".to_string());
+
+        network
+            .send_message(&peer, b"hello".to_vec())
+            .await
+            .unwrap();
+        network.broadcast_message(b"to all".to_vec()).await.unwrap();
+        network
+            .inject_event(DomainEvent::PeerDisconnected {
+                peer_id: peer.clone(),
+            })
+            .await
+            .unwrap();
+
+        assert_eq!(
+            network.sent_messages().await,
+            vec![(peer.clone(), b"hello".to_vec())]
+        );
+        assert_eq!(network.broadcasts().await, vec![b"to all".to_vec()]);
+        assert!(matches!(
+            events.get_events().await.as_slice(),
+            [DomainEvent::PeerDisconnected { peer_id }] if *peer_id == peer
+        ));
+    }
+}
@@ -1,6 +1,9 @@
 use async_std::task;
-use cipherstream::file_transfer::request_handler::{FileTransferCodec, FileTransferProtocol};
-use cipherstream::file_transfer::types::{ProtocolRequest, ProtocolResponse};
+use cipherstream::file_transfer::Compression;
+use cipherstream::file_transfer::request_handler::{
+    FileTransferCodec, FileTransferProtocol, WIRE_VERSION, WireFormatError,
+};
+use cipherstream::file_transfer::types::{ProtocolRequest, ProtocolResponse, RejectReason};
 use futures::io::Cursor;
 use libp2p::request_response::Codec;
 
@@ -22,6 +25,11 @@ fn test_codec_read_write_request() {
         filename: "test.txt".to_string(),
         filesize: 1024,
         transfer_id: "test-id-1".to_string(),
+        sha256: Some("ab".repeat(32)),
+        merkle_root: Some("cd".repeat(32)),
+        chunk_size: Some(1024 * 1024),
+        adaptive_chunks: false,
+        compression: Compression::None,
     };
 
     // Use a buffer to simulate the IO
@@ -54,16 +62,30 @@ fn test_codec_read_write_request() {
                 filename: f1,
                 filesize: s1,
                 transfer_id: t1,
+                sha256: h1,
+                merkle_root: m1,
+                chunk_size: c1,
+                adaptive_chunks: d1,
+                compression: Compression::None,
             },
             ProtocolRequest::HandshakeRequest {
                 filename: f2,
                 filesize: s2,
                 transfer_id: t2,
+                sha256: h2,
+                merkle_root: m2,
+                chunk_size: c2,
+                adaptive_chunks: d2,
+                compression: Compression::None,
             },
         ) => {
             assert_eq!(f1, f2);
             assert_eq!(s1, s2);
             assert_eq!(t1, t2);
+            assert_eq!(h1, h2);
+            assert_eq!(m1, m2);
+            assert_eq!(c1, c2);
+            assert_eq!(d1, d2);
         }
         _ => panic!("Decoded to wrong variant"),
     }
@@ -79,6 +101,8 @@ fn test_codec_read_write_response() {
         accepted: true,
         reason: None,
         transfer_id: Some("test-id-1".to_string()),
+        accepted_chunk_size: Some(256 * 1024),
+        compression: Compression::None,
     };
 
     // Use a buffer to simulate the IO
@@ -111,16 +135,21 @@ fn test_codec_read_write_response() {
                 accepted: a1,
                 reason: r1,
                 transfer_id: t1,
+                accepted_chunk_size: c1,
+                compression: Compression::None,
             },
             ProtocolResponse::HandshakeResponse {
                 accepted: a2,
                 reason: r2,
                 transfer_id: t2,
+                accepted_chunk_size: c2,
+                compression: Compression::None,
             },
         ) => {
             assert_eq!(a1, a2);
             assert_eq!(r1, r2);
             assert_eq!(t1, t2);
+            assert_eq!(c1, c2);
         }
         _ => panic!("Decoded to wrong variant"),
     }
@@ -137,8 +166,10 @@ fn test_codec_file_chunk_roundtrip() {
         transfer_id: "chunk-test-id".to_string(),
         chunk_index: 42,
         total_chunks: 100,
+        offset: 378,
         data: chunk_data.clone(),
         is_last: false,
+        proof: vec!["ef".repeat(32)],
     };
 
     // Use a buffer to simulate the IO
@@ -170,14 +201,18 @@ fn test_codec_file_chunk_roundtrip() {
             transfer_id,
             chunk_index,
             total_chunks,
+            offset,
             data,
             is_last,
+            proof,
         } => {
             assert_eq!(transfer_id, "chunk-test-id");
             assert_eq!(chunk_index, 42);
             assert_eq!(total_chunks, 100);
+            assert_eq!(offset, 378);
             assert_eq!(data, chunk_data);
             assert!(!is_last);
+            assert_eq!(proof, vec!["ef".repeat(32)]);
         }
         _ => panic!("Decoded to wrong variant"),
     }
@@ -196,8 +231,10 @@ fn test_codec_large_data_handling() {
         transfer_id: "large-data-test".to_string(),
         chunk_index: 1,
         total_chunks: 10,
+        offset: 1024 * 1024,
         data: large_data.clone(),
         is_last: false,
+        proof: Vec::new(),
     };
 
     // Use a buffer to simulate the IO
@@ -229,3 +266,172 @@ fn test_codec_large_data_handling() {
         _ => panic!("Decoded to wrong variant"),
     }
 }
+
+#[test]
+fn test_codec_reject_reason_roundtrip() {
+    let protocol = FileTransferProtocol::new();
+    let mut codec = FileTransferCodec;
+
+    let reasons = [
+        RejectReason::TooLarge,
+        RejectReason::DisallowedExtension,
+        RejectReason::RateLimited,
+        RejectReason::InsufficientSpace,
+        RejectReason::InvalidFilename,
+        RejectReason::Other("Disk on fire".to_string()),
+        RejectReason::DisallowedContentType("application/x-executable".to_string()),
+        RejectReason::ChunkSizeUnsupported {
+            min: 1024,
+            max: 1 << 20,
+        },
+    ];
+
+    for reason in reasons {
+        let responses = [
+            ProtocolResponse::HandshakeResponse {
+                accepted: false,
+                reason: Some(reason.clone()),
+                transfer_id: Some("reject-test".to_string()),
+                accepted_chunk_size: None,
+                compression: Compression::None,
+            },
+            ProtocolResponse::TransferComplete {
+                transfer_id: "reject-test".to_string(),
+                success: false,
+                error: Some(reason.clone()),
+            },
+        ];
+
+        for response in responses {
+            let mut buffer = Vec::new();
+            let mut cursor = Cursor::new(&mut buffer);
+            task::block_on(async {
+                codec
+                    .write_response(&protocol, &mut cursor, response.clone())
+                    .await
+            })
+            .unwrap();
+
+            let mut read_cursor = Cursor::new(&buffer);
+            let decoded =
+                task::block_on(async { codec.read_response(&protocol, &mut read_cursor).await })
+                    .unwrap();
+
+            match (decoded, response) {
+                (
+                    ProtocolResponse::HandshakeResponse { reason: r1, .. },
+                    ProtocolResponse::HandshakeResponse { reason: r2, .. },
+                ) => assert_eq!(r1, r2),
+                (
+                    ProtocolResponse::TransferComplete { error: e1, .. },
+                    ProtocolResponse::TransferComplete { error: e2, .. },
+                ) => assert_eq!(e1, e2),
+                _ => panic!("Decoded to wrong variant"),
+            }
+        }
+    }
+}
+
+#[test]
+fn test_codec_ping_pong_roundtrip() {
+    let protocol = FileTransferProtocol::new();
+    let mut codec = FileTransferCodec;
+
+    let mut buffer = Vec::new();
+    task::block_on(async {
+        codec
+            .write_request(
+                &protocol,
+                &mut Cursor::new(&mut buffer),
+                ProtocolRequest::Ping {
+                    nonce: u64::MAX - 7,
+                },
+            )
+            .await
+    })
+    .unwrap();
+    let request = task::block_on(async {
+        codec
+            .read_request(&protocol, &mut Cursor::new(&buffer))
+            .await
+    })
+    .unwrap();
+    assert_eq!(
+        request,
+        ProtocolRequest::Ping {
+            nonce: u64::MAX - 7
+        }
+    );
+
+    let mut buffer = Vec::new();
+    task::block_on(async {
+        codec
+            .write_response(
+                &protocol,
+                &mut Cursor::new(&mut buffer),
+                ProtocolResponse::Pong { nonce: 42 },
+            )
+            .await
+    })
+    .unwrap();
+    let response = task::block_on(async {
+        codec
+            .read_response(&protocol, &mut Cursor::new(&buffer))
+            .await
+    })
+    .unwrap();
+    assert_eq!(response, ProtocolResponse::Pong { nonce: 42 });
+}
+
+#[test]
+fn test_codec_rejects_frames_with_a_foreign_header() {
+    let protocol = FileTransferProtocol::new();
+    let mut codec = FileTransferCodec;
+
+    let mut frame = Vec::new();
+    task::block_on(async {
+        codec
+            .write_request(
+                &protocol,
+                &mut Cursor::new(&mut frame),
+                ProtocolRequest::Ping { nonce: 7 },
+            )
+            .await
+    })
+    .unwrap();
+    assert_eq!(&frame[..3], &[b'C', b'S', WIRE_VERSION]);
+
+    let read = |frame: Vec<u8>| {
+        let mut codec = FileTransferCodec;
+        task::block_on(async {
+            codec
+                .read_request(&protocol, &mut Cursor::new(&frame))
+                .await
+        })
+        .unwrap_err()
+    };
+    let wire_error = |error: &std::io::Error| {
+        assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+        error
+            .get_ref()
+            .and_then(|inner| inner.downcast_ref::<WireFormatError>())
+            .cloned()
+    };
+
+    let mut newer = frame.clone();
+    newer[2] = WIRE_VERSION + 1;
+    assert_eq!(
+        wire_error(&read(newer)),
+        Some(WireFormatError::UnsupportedVersion {
+            found: WIRE_VERSION + 1,
+            expected: WIRE_VERSION,
+        })
+    );
+
+    // A frame from a build without the header starts with its length
+    let legacy = frame[3..].to_vec();
+    assert!(matches!(
+        wire_error(&read(legacy)),
+        Some(WireFormatError::BadMagic { .. })
+    ));
+}
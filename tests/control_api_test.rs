@@ -0,0 +1,266 @@
+use cipherstream::application::{
+    ControlRequest, ControlResponse, ControlServer, FileSystemService, send_control_request,
+};
+use cipherstream::core::domain::*;
+use cipherstream::core::services::TransferDomainService;
+use cipherstream::core::traits::*;
+use cipherstream::infrastructure::{
+    AppConfig, InMemoryEventPublisher, InMemoryFileRepository, InMemoryPeerRepository,
+    InMemoryTransferRepository,
+};
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+
+/// Transfer control that records what it was told to do with live sends
+#[derive(Default)]
+struct RecordingControl {
+    calls: Mutex<Vec<(&'static str, TransferId)>>,
+}
+
+#[async_trait::async_trait]
+impl TransferControl for RecordingControl {
+    async fn pause(&self, transfer_id: &TransferId) -> bool {
+        self.calls
+            .lock()
+            .unwrap()
+            .push(("pause", transfer_id.clone()));
+        true
+    }
+
+    async fn resume(&self, transfer_id: &TransferId) -> bool {
+        self.calls
+            .lock()
+            .unwrap()
+            .push(("resume", transfer_id.clone()));
+        true
+    }
+
+    async fn cancel(&self, transfer_id: &TransferId) -> bool {
+        self.calls
+            .lock()
+            .unwrap()
+            .push(("cancel", transfer_id.clone()));
+        true
+    }
+}
+
+struct Node {
+    transfer_repo: Arc<InMemoryTransferRepository>,
+    control: Arc<RecordingControl>,
+    port: u16,
+}
+
+/// A control API on a free port in front of in-memory repositories
+async fn node() -> Node {
+    let transfer_repo = Arc::new(InMemoryTransferRepository::new());
+    let control = Arc::new(RecordingControl::default());
+    let service = TransferDomainService::new(
+        Arc::new(InMemoryFileRepository::new()),
+        transfer_repo.clone(),
+        Arc::new(InMemoryPeerRepository::new()),
+        Arc::new(FileSystemService::new(Arc::new(AppConfig::default()))),
+        Arc::new(InMemoryEventPublisher::new()),
+    )
+    .with_transfer_control(control.clone());
+    let server = ControlServer::bind(0, Arc::new(service)).await.unwrap();
+    let port = server.local_addr().unwrap().port();
+    server.spawn();
+    Node {
+        transfer_repo,
+        control,
+        port,
+    }
+}
+
+async fn saved_transfer(node: &Node, status: TransferStatus) -> Transfer {
+    let transfer = Transfer {
+        id: TransferId::new(),
+        file: File {
+            id: FileId::new(),
+            name: "report.pdf".to_string(),
+            size: 2048,
+            hash: "abc".to_string(),
+            path: "/tmp/report.pdf".to_string(),
+            created_at: SystemTime::now(),
+            modified_at: None,
+        },
+        sender: PeerId::new("local".to_string()),
+        receiver: PeerId::new("remote".to_string()),
+        status,
+        progress: TransferProgress::new(2048, 2),
+        started_at: SystemTime::now(),
+        completed_at: None,
+    };
+    node.transfer_repo.save_transfer(&transfer).await.unwrap();
+    transfer
+}
+
+async fn status(node: &Node, transfer: &Transfer) -> TransferStatus {
+    node.transfer_repo
+        .find_transfer_by_id(&transfer.id)
+        .await
+        .unwrap()
+        .unwrap()
+        .status
+}
+
+#[tokio::test]
+async fn test_control_api_pauses_resumes_and_cancels_a_transfer() {
+    let node = node().await;
+    let transfer = saved_transfer(&node, TransferStatus::InProgress).await;
+    let transfer_id = transfer.id.as_str().to_string();
+    let ok = ControlResponse {
+        ok: true,
+        error: None,
+    };
+
+    let pause = ControlRequest::PauseTransfer {
+        transfer_id: transfer_id.clone(),
+    };
+    assert_eq!(send_control_request(node.port, &pause).await.unwrap(), ok);
+    assert!(matches!(
+        status(&node, &transfer).await,
+        TransferStatus::Paused
+    ));
+
+    let resume = ControlRequest::ResumeTransfer {
+        transfer_id: transfer_id.clone(),
+    };
+    assert_eq!(send_control_request(node.port, &resume).await.unwrap(), ok);
+    assert!(matches!(
+        status(&node, &transfer).await,
+        TransferStatus::InProgress
+    ));
+    // The live send followed both
+    assert_eq!(
+        *node.control.calls.lock().unwrap(),
+        vec![
+            ("pause", transfer.id.clone()),
+            ("resume", transfer.id.clone())
+        ]
+    );
+
+    let cancel = ControlRequest::CancelTransfer { transfer_id };
+    assert_eq!(send_control_request(node.port, &cancel).await.unwrap(), ok);
+    assert!(matches!(
+        status(&node, &transfer).await,
+        TransferStatus::Cancelled
+    ));
+    assert_eq!(
+        node.control.calls.lock().unwrap().last(),
+        Some(&("cancel", transfer.id.clone()))
+    );
+}
+
+#[tokio::test]
+async fn test_control_api_explains_unknown_and_finished_transfers() {
+    let node = node().await;
+    let completed = saved_transfer(&node, TransferStatus::Completed).await;
+    let completed_id = completed.id.as_str().to_string();
+
+    let unknown = send_control_request(
+        node.port,
+        &ControlRequest::PauseTransfer {
+            transfer_id: "no-such-transfer".to_string(),
+        },
+    )
+    .await
+    .unwrap();
+    assert!(!unknown.ok);
+    assert_eq!(
+        unknown.error.as_deref(),
+        Some("Transfer no-such-transfer not found")
+    );
+
+    for request in [
+        ControlRequest::CancelTransfer {
+            transfer_id: completed_id.clone(),
+        },
+        ControlRequest::PauseTransfer {
+            transfer_id: completed_id.clone(),
+        },
+        ControlRequest::ResumeTransfer {
+            transfer_id: completed_id.clone(),
+        },
+    ] {
+        let response = send_control_request(node.port, &request).await.unwrap();
+        assert!(!response.ok);
+        let error = response.error.unwrap();
+        assert!(error.contains("already finished"), "{}", error);
+    }
+    assert!(matches!(
+        status(&node, &completed).await,
+        TransferStatus::Completed
+    ));
+
+    // Resuming a transfer that isn't paused says why it can't
+    let running = saved_transfer(&node, TransferStatus::InProgress).await;
+    let response = send_control_request(
+        node.port,
+        &ControlRequest::ResumeTransfer {
+            transfer_id: running.id.as_str().to_string(),
+        },
+    )
+    .await
+    .unwrap();
+    assert!(response.error.unwrap().starts_with("Cannot resume"));
+    assert!(node.control.calls.lock().unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn test_control_api_answers_malformed_requests_and_keeps_serving() {
+    let node = node().await;
+    let stream = tokio::net::TcpStream::connect(("127.0.0.1", node.port))
+        .await
+        .unwrap();
+    let (reader, mut writer) = stream.into_split();
+    let mut lines = BufReader::new(reader).lines();
+
+    writer
+        .write_all(b"{\"command\":\"explode\"}\n")
+        .await
+        .unwrap();
+    let response: ControlResponse =
+        serde_json::from_str(&lines.next_line().await.unwrap().unwrap()).unwrap();
+    assert!(!response.ok);
+    assert!(
+        response
+            .error
+            .unwrap()
+            .starts_with("Invalid control request")
+    );
+
+    // The same connection still takes well-formed requests
+    writer
+        .write_all(b"{\"command\":\"cancel_transfer\",\"transfer_id\":\"gone\"}\n")
+        .await
+        .unwrap();
+    let response: ControlResponse =
+        serde_json::from_str(&lines.next_line().await.unwrap().unwrap()).unwrap();
+    assert_eq!(response.error.as_deref(), Some("Transfer gone not found"));
+}
+
+#[tokio::test]
+async fn test_control_request_fails_clearly_without_a_node() {
+    // Bind and release a port so nothing is listening on it
+    let port = std::net::TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port();
+    let err = send_control_request(
+        port,
+        &ControlRequest::PauseTransfer {
+            transfer_id: "any".to_string(),
+        },
+    )
+    .await
+    .unwrap_err();
+    assert!(
+        err.to_string()
+            .starts_with(&format!("No node is taking commands on port {}", port)),
+        "{}",
+        err
+    );
+}
@@ -0,0 +1,97 @@
+use cipherstream::core::domain::PeerId as DomainPeerId;
+use cipherstream::core::traits::NetworkService;
+use cipherstream::infrastructure::network::NetworkEvent;
+use cipherstream::infrastructure::{
+    AppConfig, InMemoryEventPublisher, LibP2pNetworkService, NetworkServiceImpl,
+};
+use cipherstream::testing::loopback_addr;
+use std::sync::Arc;
+use std::time::Duration;
+
+async fn start_node() -> LibP2pNetworkService {
+    let node = LibP2pNetworkService::new(
+        Arc::new(AppConfig::default()),
+        Arc::new(InMemoryEventPublisher::new()),
+    )
+    .await
+    .unwrap();
+    node.start_listening(0).await.unwrap();
+    node
+}
+
+fn direct_messages(events: &[NetworkEvent]) -> Vec<(libp2p::PeerId, Vec<u8>)> {
+    events
+        .iter()
+        .filter_map(|event| match event {
+            NetworkEvent::DirectMessage { from, data } => Some((*from, data.clone())),
+            _ => None,
+        })
+        .collect()
+}
+
+#[tokio::test]
+async fn test_send_message_reaches_only_the_target_peer() {
+    let sender = start_node().await;
+    let target = start_node().await;
+    let bystander = start_node().await;
+    let target_id = sender
+        .connect_and_wait(loopback_addr(&target).await)
+        .await
+        .unwrap();
+    sender
+        .connect_and_wait(loopback_addr(&bystander).await)
+        .await
+        .unwrap();
+
+    sender
+        .send_message(&DomainPeerId::from(target_id), b"just for you".to_vec())
+        .await
+        .unwrap();
+
+    let received = target.collect_events_for(Duration::from_millis(300)).await;
+    assert_eq!(
+        direct_messages(&received),
+        vec![(sender.local_peer_id(), b"just for you".to_vec())]
+    );
+    let overheard = bystander
+        .collect_events_for(Duration::from_millis(300))
+        .await;
+    assert!(direct_messages(&overheard).is_empty());
+}
+
+#[tokio::test]
+async fn test_send_message_to_unreachable_peer_fails() {
+    let sender = start_node().await;
+    let stranger = libp2p::PeerId::random();
+
+    let result = sender
+        .send_message(&DomainPeerId::from(stranger), b"hello?".to_vec())
+        .await;
+    assert!(result.is_err());
+}
+
+#[tokio::test]
+async fn test_network_service_impl_delivers_through_the_swarm() {
+    let service = NetworkServiceImpl::new(Arc::new(start_node().await));
+    let target = start_node().await;
+    let target_addr = loopback_addr(&target).await;
+
+    let target_id = service.connect(target_addr.clone()).await.unwrap();
+    assert_eq!(
+        service.get_peer_addresses(&target_id).await,
+        Some(vec![target_addr.to_string()])
+    );
+    service
+        .send_message(&target_id, b"through the wrapper".to_vec())
+        .await
+        .unwrap();
+
+    let received = target.collect_events_for(Duration::from_millis(300)).await;
+    assert_eq!(
+        direct_messages(&received)
+            .into_iter()
+            .map(|(_, data)| data)
+            .collect::<Vec<_>>(),
+        vec![b"through the wrapper".to_vec()]
+    );
+}
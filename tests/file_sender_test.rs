@@ -0,0 +1,1037 @@
+use async_trait::async_trait;
+use cipherstream::core::crypto::merkle::compute_chunk_merkle;
+use cipherstream::core::traits::DomainResult;
+use cipherstream::file_transfer::{
+    AdaptiveChunking, Compression, CompressorRegistry, FileSender, FileTransferHandler,
+    ProtocolRequest, ProtocolResponse, RejectReason, SendOutcome, TransferTransport,
+};
+use libp2p::PeerId;
+use std::collections::BTreeSet;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Transport that hands requests straight to a local handler, optionally
+/// delaying each chunk to simulate a slow link
+struct LoopbackTransport {
+    handler: Arc<FileTransferHandler>,
+    local_peer: PeerId,
+    chunk_delay: Duration,
+}
+
+#[async_trait]
+impl TransferTransport for LoopbackTransport {
+    async fn send_request(
+        &self,
+        _peer: PeerId,
+        request: ProtocolRequest,
+    ) -> DomainResult<ProtocolResponse> {
+        if matches!(request, ProtocolRequest::FileChunk { .. }) {
+            tokio::time::sleep(self.chunk_delay).await;
+        }
+        Ok(self.handler.handle_request(self.local_peer, request).await)
+    }
+}
+
+fn loopback(
+    download_dir: &std::path::Path,
+    chunk_size: usize,
+    chunk_delay: Duration,
+) -> (Arc<FileTransferHandler>, FileSender) {
+    let handler = Arc::new(FileTransferHandler::new(download_dir, chunk_size));
+    let transport = Arc::new(LoopbackTransport {
+        handler: handler.clone(),
+        local_peer: PeerId::random(),
+        chunk_delay,
+    });
+    (handler, FileSender::new(transport, chunk_size))
+}
+
+#[tokio::test]
+async fn test_send_file_round_trips_through_handler() {
+    let src_dir = tempfile::tempdir().unwrap();
+    let dst_dir = tempfile::tempdir().unwrap();
+    let content: Vec<u8> = (0..10_000u32).map(|i| (i % 251) as u8).collect();
+    let path = src_dir.path().join("data.bin");
+    std::fs::write(&path, &content).unwrap();
+
+    let (_handler, sender) = loopback(dst_dir.path(), 1024, Duration::ZERO);
+    let outcome = sender
+        .send_file(PeerId::random(), &path, "round-trip")
+        .await
+        .unwrap();
+
+    assert_eq!(outcome, SendOutcome::Completed { chunks_sent: 10 });
+    assert_eq!(
+        std::fs::read(dst_dir.path().join("data.bin")).unwrap(),
+        content
+    );
+}
+
+#[tokio::test]
+async fn test_cancel_stops_chunk_loop_promptly() {
+    let src_dir = tempfile::tempdir().unwrap();
+    let dst_dir = tempfile::tempdir().unwrap();
+    let path = src_dir.path().join("large.bin");
+    std::fs::write(&path, vec![7u8; 64 * 1024]).unwrap();
+
+    // 64 chunks at 50ms each would take over three seconds to finish
+    let (handler, sender) = loopback(dst_dir.path(), 1024, Duration::from_millis(50));
+    let sender = Arc::new(sender);
+    let peer = PeerId::random();
+
+    let send = {
+        let sender = sender.clone();
+        let path = path.clone();
+        tokio::spawn(async move { sender.send_file(peer, &path, "cancel-me").await })
+    };
+
+    tokio::time::sleep(Duration::from_millis(175)).await;
+    let cancelled_at = Instant::now();
+    sender.cancel_transfer(peer, "cancel-me").await.unwrap();
+
+    let outcome = send.await.unwrap().unwrap();
+    assert!(cancelled_at.elapsed() < Duration::from_millis(100));
+    match outcome {
+        SendOutcome::Cancelled { chunks_sent } => assert!(chunks_sent < 64),
+        other => panic!("Expected cancellation, got {:?}", other),
+    }
+    assert!(handler.is_cancelled("cancel-me").await);
+    assert_eq!(handler.active_transfers().await, 0);
+}
+
+#[tokio::test]
+async fn test_saturated_uploads_do_not_block_downloads() {
+    let src_dir = tempfile::tempdir().unwrap();
+    let remote_dir = tempfile::tempdir().unwrap();
+    let local_dir = tempfile::tempdir().unwrap();
+    let path = src_dir.path().join("slow.bin");
+    std::fs::write(&path, vec![1u8; 8 * 1024]).unwrap();
+
+    let (_remote, sender) = loopback(remote_dir.path(), 1024, Duration::from_millis(50));
+    let sender = Arc::new(sender.with_max_uploads(1));
+    let local = FileTransferHandler::new(local_dir.path(), 1024).with_max_downloads(2);
+
+    let upload = {
+        let sender = sender.clone();
+        let path = path.clone();
+        tokio::spawn(async move { sender.send_file(PeerId::random(), &path, "up-1").await })
+    };
+    tokio::time::sleep(Duration::from_millis(20)).await;
+
+    // The second upload waits for the first instead of failing
+    let second = {
+        let sender = sender.clone();
+        let path = path.clone();
+        tokio::spawn(async move { sender.send_file(PeerId::random(), &path, "up-2").await })
+    };
+
+    let mut accepted = 0;
+    for i in 0..3 {
+        let response = local
+            .handle_request(
+                PeerId::random(),
+                ProtocolRequest::HandshakeRequest {
+                    filename: format!("incoming-{}.bin", i),
+                    filesize: 10,
+                    transfer_id: format!("down-{}", i),
+                    sha256: None,
+                    merkle_root: None,
+                    chunk_size: None,
+                    adaptive_chunks: false,
+                    compression: Compression::None,
+                },
+            )
+            .await;
+        if matches!(
+            response,
+            ProtocolResponse::HandshakeResponse { accepted: true, .. }
+        ) {
+            accepted += 1;
+        }
+    }
+    assert_eq!(accepted, 2);
+
+    assert!(!second.is_finished());
+    let first_done = {
+        let outcome = upload.await.unwrap().unwrap();
+        assert!(matches!(outcome, SendOutcome::Completed { .. }));
+        Instant::now()
+    };
+    assert!(matches!(
+        second.await.unwrap().unwrap(),
+        SendOutcome::Completed { .. }
+    ));
+    // Eight 50ms chunks that could only start once the first upload ended
+    assert!(first_done.elapsed() >= Duration::from_millis(300));
+}
+
+#[tokio::test]
+async fn test_upload_waiting_for_a_slot_can_be_cancelled() {
+    let src_dir = tempfile::tempdir().unwrap();
+    let dst_dir = tempfile::tempdir().unwrap();
+    let path = src_dir.path().join("slow.bin");
+    std::fs::write(&path, vec![1u8; 8 * 1024]).unwrap();
+
+    let (_handler, sender) = loopback(dst_dir.path(), 1024, Duration::from_millis(50));
+    let sender = Arc::new(sender.with_max_uploads(1));
+    let peer = PeerId::random();
+
+    let first = {
+        let sender = sender.clone();
+        let path = path.clone();
+        tokio::spawn(async move { sender.send_file(peer, &path, "running").await })
+    };
+    tokio::time::sleep(Duration::from_millis(20)).await;
+    let waiting = {
+        let sender = sender.clone();
+        let path = path.clone();
+        tokio::spawn(async move { sender.send_file(PeerId::random(), &path, "waiting").await })
+    };
+    tokio::time::sleep(Duration::from_millis(20)).await;
+
+    sender
+        .cancel_transfer(PeerId::random(), "waiting")
+        .await
+        .unwrap();
+    assert_eq!(
+        waiting.await.unwrap().unwrap(),
+        SendOutcome::Cancelled { chunks_sent: 0 }
+    );
+    assert!(!first.is_finished());
+    assert!(matches!(
+        first.await.unwrap().unwrap(),
+        SendOutcome::Completed { .. }
+    ));
+}
+
+#[tokio::test]
+async fn test_sends_queued_for_one_peer_leave_upload_slots_to_others() {
+    let src_dir = tempfile::tempdir().unwrap();
+    let dst_dir = tempfile::tempdir().unwrap();
+    let (_handler, sender) = loopback(dst_dir.path(), 1024, Duration::from_millis(50));
+    let sender = Arc::new(sender.with_max_uploads(2).with_sends_per_peer(1));
+    let busy = PeerId::random();
+
+    let spawn_send = |peer: PeerId, transfer_id: &'static str, chunks: usize| {
+        let sender = sender.clone();
+        let path = src_dir.path().join(format!("{}.bin", transfer_id));
+        std::fs::write(&path, vec![1u8; chunks * 1024]).unwrap();
+        tokio::spawn(async move { sender.send_file(peer, &path, transfer_id).await })
+    };
+    // Twice as long as the send to the other peer
+    let first = spawn_send(busy, "busy-1", 16);
+    tokio::time::sleep(Duration::from_millis(20)).await;
+    let queued = spawn_send(busy, "busy-2", 8);
+    tokio::time::sleep(Duration::from_millis(20)).await;
+
+    // The send queued behind "busy-1" must not hold the second upload slot
+    let other = spawn_send(PeerId::random(), "other", 8);
+    assert!(matches!(
+        other.await.unwrap().unwrap(),
+        SendOutcome::Completed { .. }
+    ));
+    assert!(!first.is_finished());
+    assert!(!queued.is_finished());
+
+    for send in [first, queued] {
+        assert!(matches!(
+            send.await.unwrap().unwrap(),
+            SendOutcome::Completed { .. }
+        ));
+    }
+}
+
+/// Loopback transport that records the size of every chunk it carries
+struct RecordingTransport {
+    inner: LoopbackTransport,
+    chunk_sizes: Mutex<Vec<usize>>,
+    chunk_capacities: Mutex<Vec<usize>>,
+}
+
+#[async_trait]
+impl TransferTransport for RecordingTransport {
+    async fn send_request(
+        &self,
+        peer: PeerId,
+        request: ProtocolRequest,
+    ) -> DomainResult<ProtocolResponse> {
+        if let ProtocolRequest::FileChunk { data, .. } = &request {
+            self.chunk_sizes.lock().unwrap().push(data.len());
+            self.chunk_capacities.lock().unwrap().push(data.capacity());
+        }
+        self.inner.send_request(peer, request).await
+    }
+}
+
+#[tokio::test]
+async fn test_adaptive_chunk_size_grows_on_high_latency_link() {
+    let src_dir = tempfile::tempdir().unwrap();
+    let dst_dir = tempfile::tempdir().unwrap();
+    let content: Vec<u8> = (0..24 * 1024u32).map(|i| (i % 249) as u8).collect();
+    let path = src_dir.path().join("adaptive.bin");
+    std::fs::write(&path, &content).unwrap();
+
+    // Every chunk takes 30ms regardless of size, so larger chunks amortize
+    // the latency and acks keep arriving within target
+    let transport = Arc::new(RecordingTransport {
+        inner: LoopbackTransport {
+            handler: Arc::new(FileTransferHandler::new(dst_dir.path(), 4096)),
+            local_peer: PeerId::random(),
+            chunk_delay: Duration::from_millis(30),
+        },
+        chunk_sizes: Mutex::new(Vec::new()),
+        chunk_capacities: Mutex::new(Vec::new()),
+    });
+    let sender = FileSender::new(transport.clone(), 512).with_adaptive_chunking(AdaptiveChunking {
+        min_chunk_size: 512,
+        max_chunk_size: 4096,
+        target_latency: Duration::from_millis(500),
+    });
+
+    let outcome = sender
+        .send_file(PeerId::random(), &path, "adaptive")
+        .await
+        .unwrap();
+
+    let sizes = transport.chunk_sizes.lock().unwrap().clone();
+    assert_eq!(
+        outcome,
+        SendOutcome::Completed {
+            chunks_sent: sizes.len() as u64
+        }
+    );
+    assert_eq!(sizes[0], 512);
+    assert_eq!(sizes.iter().max(), Some(&4096));
+    // The final chunk only carries what is left of the file
+    let (_last, full) = sizes.split_last().unwrap();
+    assert!(full.windows(2).all(|w| w[0] <= w[1]), "{:?}", sizes);
+    assert!(sizes.len() < content.len() / 512);
+    assert_eq!(
+        std::fs::read(dst_dir.path().join("adaptive.bin")).unwrap(),
+        content
+    );
+}
+
+#[tokio::test]
+async fn test_receiver_clamps_oversized_chunk_proposal() {
+    let src_dir = tempfile::tempdir().unwrap();
+    let dst_dir = tempfile::tempdir().unwrap();
+    let content: Vec<u8> = (0..10_000u32).map(|i| (i % 241) as u8).collect();
+    let path = src_dir.path().join("clamped.bin");
+    std::fs::write(&path, &content).unwrap();
+
+    let handler = Arc::new(FileTransferHandler::new(dst_dir.path(), 1024));
+    let response = handler
+        .handle_request(
+            PeerId::random(),
+            ProtocolRequest::HandshakeRequest {
+                filename: "probe.bin".to_string(),
+                filesize: 10_000,
+                transfer_id: "probe".to_string(),
+                sha256: None,
+                merkle_root: None,
+                chunk_size: Some(4096),
+                adaptive_chunks: false,
+                compression: Compression::None,
+            },
+        )
+        .await;
+    assert!(matches!(
+        response,
+        ProtocolResponse::HandshakeResponse {
+            accepted: true,
+            accepted_chunk_size: Some(1024),
+            compression: Compression::None,
+            ..
+        }
+    ));
+
+    // The sender proposes 4096 byte chunks and falls back to the receiver's 1024
+    let transport = Arc::new(RecordingTransport {
+        inner: LoopbackTransport {
+            handler,
+            local_peer: PeerId::random(),
+            chunk_delay: Duration::ZERO,
+        },
+        chunk_sizes: Mutex::new(Vec::new()),
+        chunk_capacities: Mutex::new(Vec::new()),
+    });
+    let sender = FileSender::new(transport.clone(), 4096);
+    let outcome = sender
+        .send_file(PeerId::random(), &path, "clamped")
+        .await
+        .unwrap();
+
+    assert_eq!(outcome, SendOutcome::Completed { chunks_sent: 10 });
+    let sizes = transport.chunk_sizes.lock().unwrap().clone();
+    assert!(sizes.iter().all(|&size| size <= 1024), "{:?}", sizes);
+    assert_eq!(
+        std::fs::read(dst_dir.path().join("clamped.bin")).unwrap(),
+        content
+    );
+}
+
+#[tokio::test]
+async fn test_resumed_send_fills_in_only_the_missing_chunks() {
+    let src_dir = tempfile::tempdir().unwrap();
+    let dst_dir = tempfile::tempdir().unwrap();
+    let content: Vec<u8> = (0..10_000u32).map(|i| (i % 239) as u8).collect();
+    let path = src_dir.path().join("partial.bin");
+    std::fs::write(&path, &content).unwrap();
+
+    // The receiver already holds chunks 0-2 and 5 of an interrupted send
+    let handler = Arc::new(FileTransferHandler::new(dst_dir.path(), 1024));
+    let sender_id = PeerId::random();
+    handler
+        .handle_request(
+            sender_id,
+            ProtocolRequest::HandshakeRequest {
+                filename: "partial.bin".to_string(),
+                filesize: content.len() as u64,
+                transfer_id: "resume".to_string(),
+                sha256: None,
+                merkle_root: None,
+                chunk_size: Some(1024),
+                adaptive_chunks: false,
+                compression: Compression::None,
+            },
+        )
+        .await;
+    for index in [0u64, 1, 2, 5] {
+        let offset = index * 1024;
+        let request = ProtocolRequest::FileChunk {
+            transfer_id: "resume".to_string(),
+            chunk_index: index,
+            total_chunks: 10,
+            offset,
+            data: content[offset as usize..offset as usize + 1024].to_vec(),
+            is_last: false,
+            proof: Vec::new(),
+        };
+        handler.handle_request(sender_id, request).await;
+    }
+
+    let transport = Arc::new(RecordingTransport {
+        inner: LoopbackTransport {
+            handler: handler.clone(),
+            local_peer: sender_id,
+            chunk_delay: Duration::ZERO,
+        },
+        chunk_sizes: Mutex::new(Vec::new()),
+        chunk_capacities: Mutex::new(Vec::new()),
+    });
+    // The sender would propose larger chunks; the resumed send keeps the
+    // 1024 bytes the receiver agreed to
+    let sender = FileSender::new(transport.clone(), 4096);
+    let outcome = sender
+        .send_file_from(
+            PeerId::random(),
+            &path,
+            "resume",
+            1024,
+            3,
+            &BTreeSet::from([5]),
+        )
+        .await
+        .unwrap();
+
+    // Chunks 3, 4 and 6-9, the last of which is short
+    assert_eq!(outcome, SendOutcome::Completed { chunks_sent: 6 });
+    assert_eq!(
+        *transport.chunk_sizes.lock().unwrap(),
+        vec![1024, 1024, 1024, 1024, 1024, 784]
+    );
+    assert_eq!(handler.active_transfers().await, 0);
+    assert_eq!(
+        std::fs::read(dst_dir.path().join("partial.bin")).unwrap(),
+        content
+    );
+}
+
+/// Transport that rejects every handshake with a fixed reason and counts attempts
+struct RejectingTransport {
+    reason: RejectReason,
+    handshakes: AtomicUsize,
+}
+
+#[async_trait]
+impl TransferTransport for RejectingTransport {
+    async fn send_request(
+        &self,
+        _peer: PeerId,
+        request: ProtocolRequest,
+    ) -> DomainResult<ProtocolResponse> {
+        match request {
+            ProtocolRequest::HandshakeRequest { transfer_id, .. } => {
+                self.handshakes.fetch_add(1, Ordering::SeqCst);
+                Ok(ProtocolResponse::HandshakeResponse {
+                    accepted: false,
+                    reason: Some(self.reason.clone()),
+                    transfer_id: Some(transfer_id),
+                    accepted_chunk_size: None,
+                    compression: Compression::None,
+                })
+            }
+            other => Err(format!("Unexpected request: {:?}", other).into()),
+        }
+    }
+}
+
+async fn handshake_attempts(reason: RejectReason) -> usize {
+    let src_dir = tempfile::tempdir().unwrap();
+    let path = src_dir.path().join("file.txt");
+    std::fs::write(&path, b"hello").unwrap();
+
+    let transport = Arc::new(RejectingTransport {
+        reason,
+        handshakes: AtomicUsize::new(0),
+    });
+    let sender = FileSender::new(transport.clone(), 1024)
+        .with_handshake_retries(3, Duration::from_millis(5));
+
+    let result = sender.send_file(PeerId::random(), &path, "rejected").await;
+    assert!(result.is_err());
+    transport.handshakes.load(Ordering::SeqCst)
+}
+
+#[tokio::test]
+async fn test_handshake_retried_only_when_rate_limited() {
+    assert_eq!(handshake_attempts(RejectReason::RateLimited).await, 4);
+    assert_eq!(
+        handshake_attempts(RejectReason::DisallowedExtension).await,
+        1
+    );
+    assert_eq!(
+        handshake_attempts(RejectReason::Other("nope".to_string())).await,
+        1
+    );
+}
+
+#[tokio::test]
+async fn test_oversized_file_is_refused_before_contacting_peer() {
+    let src_dir = tempfile::tempdir().unwrap();
+    let path = src_dir.path().join("big.bin");
+    std::fs::write(&path, vec![0u8; 2048]).unwrap();
+
+    let transport = Arc::new(RejectingTransport {
+        reason: RejectReason::TooLarge,
+        handshakes: AtomicUsize::new(0),
+    });
+    let sender = FileSender::new(transport.clone(), 1024).with_max_file_size(2047);
+
+    let err = sender
+        .send_file(PeerId::random(), &path, "too-big")
+        .await
+        .unwrap_err();
+    assert!(err.to_string().contains("2047 byte limit"), "{}", err);
+    assert_eq!(transport.handshakes.load(Ordering::SeqCst), 0);
+
+    let at_limit = FileSender::new(transport.clone(), 1024).with_max_file_size(2048);
+    assert!(
+        at_limit
+            .send_file(PeerId::random(), &path, "at-limit")
+            .await
+            .is_err()
+    );
+    assert_eq!(transport.handshakes.load(Ordering::SeqCst), 1);
+}
+
+#[tokio::test]
+async fn test_small_file_with_huge_chunk_size_allocates_only_what_it_reads() {
+    const CHUNK_SIZE: usize = 64 * 1024 * 1024;
+    let src_dir = tempfile::tempdir().unwrap();
+    let dst_dir = tempfile::tempdir().unwrap();
+    let content: Vec<u8> = (0..10_000u32).map(|i| (i % 241) as u8).collect();
+    let path = src_dir.path().join("small.bin");
+    std::fs::write(&path, &content).unwrap();
+
+    let transport = Arc::new(RecordingTransport {
+        inner: LoopbackTransport {
+            handler: Arc::new(FileTransferHandler::new(dst_dir.path(), CHUNK_SIZE)),
+            local_peer: PeerId::random(),
+            chunk_delay: Duration::ZERO,
+        },
+        chunk_sizes: Mutex::new(Vec::new()),
+        chunk_capacities: Mutex::new(Vec::new()),
+    });
+    let sender = FileSender::new(transport.clone(), CHUNK_SIZE).with_read_buffer_size(1024);
+
+    let outcome = sender
+        .send_file(PeerId::random(), &path, "small")
+        .await
+        .unwrap();
+
+    assert_eq!(outcome, SendOutcome::Completed { chunks_sent: 1 });
+    // The chunk is sized to the file rather than the 64 MiB wire chunk
+    let capacities = transport.chunk_capacities.lock().unwrap().clone();
+    assert_eq!(capacities, vec![content.len()]);
+    assert_eq!(
+        std::fs::read(dst_dir.path().join("small.bin")).unwrap(),
+        content
+    );
+}
+
+/// Transport whose peer never answers
+struct SilentTransport;
+
+#[async_trait]
+impl TransferTransport for SilentTransport {
+    async fn send_request(
+        &self,
+        _peer: PeerId,
+        _request: ProtocolRequest,
+    ) -> DomainResult<ProtocolResponse> {
+        std::future::pending().await
+    }
+}
+
+#[tokio::test]
+async fn test_ping_confirms_handler_is_alive_and_fails_fast_otherwise() {
+    let dst_dir = tempfile::tempdir().unwrap();
+    let (_handler, sender) = loopback(dst_dir.path(), 1024, Duration::ZERO);
+    assert!(
+        sender
+            .ping(PeerId::random(), Duration::from_secs(1))
+            .await
+            .is_ok()
+    );
+
+    let silent = FileSender::new(Arc::new(SilentTransport), 1024);
+    let started = Instant::now();
+    let result = silent
+        .ping(PeerId::random(), Duration::from_millis(100))
+        .await;
+    assert!(result.is_err());
+    assert!(started.elapsed() < Duration::from_secs(1));
+}
+
+/// Loopback transport that records which transfer each chunk belongs to
+struct OrderingTransport {
+    inner: LoopbackTransport,
+    chunks: Mutex<Vec<(PeerId, String)>>,
+}
+
+#[async_trait]
+impl TransferTransport for OrderingTransport {
+    async fn send_request(
+        &self,
+        peer: PeerId,
+        request: ProtocolRequest,
+    ) -> DomainResult<ProtocolResponse> {
+        if let ProtocolRequest::FileChunk { transfer_id, .. } = &request {
+            self.chunks
+                .lock()
+                .unwrap()
+                .push((peer, transfer_id.clone()));
+        }
+        self.inner.send_request(peer, request).await
+    }
+}
+
+#[tokio::test]
+async fn test_sends_to_one_peer_run_one_at_a_time() {
+    let src_dir = tempfile::tempdir().unwrap();
+    let dst_dir = tempfile::tempdir().unwrap();
+
+    let transport = Arc::new(OrderingTransport {
+        inner: LoopbackTransport {
+            handler: Arc::new(FileTransferHandler::new(dst_dir.path(), 1024)),
+            local_peer: PeerId::random(),
+            chunk_delay: Duration::from_millis(10),
+        },
+        chunks: Mutex::new(Vec::new()),
+    });
+    let sender = Arc::new(FileSender::new(transport.clone(), 1024));
+    let (queued_peer, other_peer) = (PeerId::random(), PeerId::random());
+
+    let sends: Vec<_> = ["q-1", "q-2", "q-3", "other"]
+        .into_iter()
+        .map(|id| {
+            let sender = sender.clone();
+            // Distinct names so concurrent transfers don't share a download
+            let path = src_dir.path().join(format!("{}.bin", id));
+            std::fs::write(&path, vec![3u8; 4 * 1024]).unwrap();
+            let peer = if id == "other" {
+                other_peer
+            } else {
+                queued_peer
+            };
+            tokio::spawn(async move { sender.send_file(peer, &path, id).await })
+        })
+        .collect();
+    for send in sends {
+        assert!(matches!(
+            send.await.unwrap().unwrap(),
+            SendOutcome::Completed { chunks_sent: 4 }
+        ));
+    }
+
+    let chunks = transport.chunks.lock().unwrap().clone();
+    let order = |peer: PeerId| -> Vec<String> {
+        chunks
+            .iter()
+            .filter(|(p, _)| *p == peer)
+            .map(|(_, id)| id.clone())
+            .collect()
+    };
+    // Each transfer to the queued peer finishes before the next starts
+    let mut queued = order(queued_peer);
+    queued.dedup();
+    assert_eq!(queued.len(), 3, "{:?}", order(queued_peer));
+
+    // The other peer's transfer ran alongside rather than after them
+    let first_other = chunks.iter().position(|(p, _)| *p == other_peer).unwrap();
+    let last_queued = chunks.iter().rposition(|(p, _)| *p == queued_peer).unwrap();
+    assert!(first_other < last_queued);
+}
+
+#[tokio::test]
+async fn test_paused_send_stops_until_resumed() {
+    let src_dir = tempfile::tempdir().unwrap();
+    let dst_dir = tempfile::tempdir().unwrap();
+    let content: Vec<u8> = (0..16 * 1024u32).map(|i| (i % 233) as u8).collect();
+    let path = src_dir.path().join("pausable.bin");
+    std::fs::write(&path, &content).unwrap();
+
+    let transport = Arc::new(OrderingTransport {
+        inner: LoopbackTransport {
+            handler: Arc::new(FileTransferHandler::new(dst_dir.path(), 1024)),
+            local_peer: PeerId::random(),
+            chunk_delay: Duration::from_millis(20),
+        },
+        chunks: Mutex::new(Vec::new()),
+    });
+    let sender = Arc::new(FileSender::new(transport.clone(), 1024));
+    let send = {
+        let sender = sender.clone();
+        let path = path.clone();
+        tokio::spawn(async move { sender.send_file(PeerId::random(), &path, "pausable").await })
+    };
+    let chunks_sent = || transport.chunks.lock().unwrap().len();
+
+    tokio::time::sleep(Duration::from_millis(90)).await;
+    assert!(sender.pause_transfer("pausable").await);
+    let at_pause = chunks_sent();
+    tokio::time::sleep(Duration::from_millis(150)).await;
+    assert_eq!(chunks_sent(), at_pause);
+    assert!(!send.is_finished());
+
+    assert!(sender.resume_transfer("pausable").await);
+    assert_eq!(
+        send.await.unwrap().unwrap(),
+        SendOutcome::Completed { chunks_sent: 16 }
+    );
+    assert_eq!(
+        std::fs::read(dst_dir.path().join("pausable.bin")).unwrap(),
+        content
+    );
+    assert!(!sender.pause_transfer("pausable").await);
+}
+
+#[tokio::test]
+async fn test_concurrent_sends_under_one_id_are_refused() {
+    let src_dir = tempfile::tempdir().unwrap();
+    let dst_dir = tempfile::tempdir().unwrap();
+    let first = src_dir.path().join("first.bin");
+    let second = src_dir.path().join("second.bin");
+    std::fs::write(&first, vec![1u8; 8 * 1024]).unwrap();
+    std::fs::write(&second, vec![2u8; 8 * 1024]).unwrap();
+
+    let (_handler, sender) = loopback(dst_dir.path(), 1024, Duration::from_millis(20));
+    let sender = Arc::new(sender);
+    let first_send = {
+        let sender = sender.clone();
+        tokio::spawn(async move { sender.send_file(PeerId::random(), &first, "shared").await })
+    };
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    // Another peer, same id: pausing "shared" must not reach two sends
+    let err = sender
+        .send_file(PeerId::random(), &second, "shared")
+        .await
+        .unwrap_err();
+    assert_eq!(err.to_string(), "Transfer shared is already being sent");
+
+    // The first send still answers to the id, and finishes
+    assert!(sender.pause_transfer("shared").await);
+    assert!(sender.resume_transfer("shared").await);
+    assert_eq!(
+        first_send.await.unwrap().unwrap(),
+        SendOutcome::Completed { chunks_sent: 8 }
+    );
+    assert!(!dst_dir.path().join("second.bin").exists());
+
+    // Once it ended the id is free again
+    assert_eq!(
+        sender
+            .send_file(
+                PeerId::random(),
+                &src_dir.path().join("second.bin"),
+                "shared"
+            )
+            .await
+            .unwrap(),
+        SendOutcome::Completed { chunks_sent: 8 }
+    );
+}
+
+/// Transport that flips a byte of one chunk the first time it is sent,
+/// recording the index of every chunk that goes out
+struct CorruptingTransport {
+    inner: LoopbackTransport,
+    corrupt_chunk: u64,
+    corrupted: AtomicUsize,
+    sent: Mutex<Vec<u64>>,
+}
+
+#[async_trait]
+impl TransferTransport for CorruptingTransport {
+    async fn send_request(
+        &self,
+        peer: PeerId,
+        mut request: ProtocolRequest,
+    ) -> DomainResult<ProtocolResponse> {
+        if let ProtocolRequest::FileChunk {
+            chunk_index, data, ..
+        } = &mut request
+        {
+            self.sent.lock().unwrap().push(*chunk_index);
+            if *chunk_index == self.corrupt_chunk
+                && self.corrupted.fetch_add(1, Ordering::SeqCst) == 0
+            {
+                data[0] ^= 0xff;
+            }
+        }
+        self.inner.send_request(peer, request).await
+    }
+}
+
+#[tokio::test]
+async fn test_rejected_chunk_is_resent_alone() {
+    let src_dir = tempfile::tempdir().unwrap();
+    let dst_dir = tempfile::tempdir().unwrap();
+    let content: Vec<u8> = (0..4096u32).map(|i| (i % 239) as u8).collect();
+    let path = src_dir.path().join("nacked.bin");
+    std::fs::write(&path, &content).unwrap();
+
+    let handler = Arc::new(FileTransferHandler::new(dst_dir.path(), 1024));
+    let transport = Arc::new(CorruptingTransport {
+        inner: LoopbackTransport {
+            handler,
+            local_peer: PeerId::random(),
+            chunk_delay: Duration::ZERO,
+        },
+        corrupt_chunk: 1,
+        corrupted: AtomicUsize::new(0),
+        sent: Mutex::new(Vec::new()),
+    });
+    let sender = FileSender::new(transport.clone(), 1024).with_chunk_retries(1);
+
+    // The receiver's Merkle check rejects the damaged chunk
+    let outcome = sender
+        .send_file(PeerId::random(), &path, "nacked")
+        .await
+        .unwrap();
+    assert_eq!(outcome, SendOutcome::Completed { chunks_sent: 4 });
+    assert_eq!(*transport.sent.lock().unwrap(), vec![0, 1, 1, 2, 3]);
+    assert_eq!(
+        std::fs::read(dst_dir.path().join("nacked.bin")).unwrap(),
+        content
+    );
+}
+
+/// Transport whose receiver refuses one chunk for a reason resending
+/// can't fix, recording the index of every chunk that goes out
+struct RefusingTransport {
+    inner: LoopbackTransport,
+    refuse_chunk: u64,
+    sent: Mutex<Vec<u64>>,
+}
+
+#[async_trait]
+impl TransferTransport for RefusingTransport {
+    async fn send_request(
+        &self,
+        peer: PeerId,
+        request: ProtocolRequest,
+    ) -> DomainResult<ProtocolResponse> {
+        if let ProtocolRequest::FileChunk {
+            transfer_id,
+            chunk_index,
+            ..
+        } = &request
+        {
+            self.sent.lock().unwrap().push(*chunk_index);
+            if *chunk_index == self.refuse_chunk {
+                return Ok(ProtocolResponse::ChunkResponse {
+                    transfer_id: transfer_id.clone(),
+                    chunk_index: *chunk_index,
+                    success: false,
+                    error: Some("Unknown transfer".to_string()),
+                    retryable: false,
+                });
+            }
+        }
+        self.inner.send_request(peer, request).await
+    }
+}
+
+#[tokio::test]
+async fn test_chunk_refused_for_good_is_not_resent() {
+    let src_dir = tempfile::tempdir().unwrap();
+    let dst_dir = tempfile::tempdir().unwrap();
+    let path = src_dir.path().join("refused.bin");
+    std::fs::write(&path, vec![5u8; 4096]).unwrap();
+
+    let transport = Arc::new(RefusingTransport {
+        inner: LoopbackTransport {
+            handler: Arc::new(FileTransferHandler::new(dst_dir.path(), 1024)),
+            local_peer: PeerId::random(),
+            chunk_delay: Duration::ZERO,
+        },
+        refuse_chunk: 1,
+        sent: Mutex::new(Vec::new()),
+    });
+    let sender = FileSender::new(transport.clone(), 1024).with_chunk_retries(3);
+
+    let error = sender
+        .send_file(PeerId::random(), &path, "refused")
+        .await
+        .unwrap_err();
+    assert!(error.to_string().contains("Unknown transfer"), "{}", error);
+    assert_eq!(*transport.sent.lock().unwrap(), vec![0, 1]);
+}
+
+/// Transport that records the chunk size and Merkle root of each handshake
+struct HandshakeRecorder {
+    inner: LoopbackTransport,
+    handshakes: Mutex<Vec<(Option<u64>, Option<String>)>>,
+}
+
+#[async_trait]
+impl TransferTransport for HandshakeRecorder {
+    async fn send_request(
+        &self,
+        peer: PeerId,
+        request: ProtocolRequest,
+    ) -> DomainResult<ProtocolResponse> {
+        if let ProtocolRequest::HandshakeRequest {
+            chunk_size,
+            merkle_root,
+            ..
+        } = &request
+        {
+            self.handshakes
+                .lock()
+                .unwrap()
+                .push((*chunk_size, merkle_root.clone()));
+        }
+        self.inner.send_request(peer, request).await
+    }
+}
+
+#[tokio::test]
+async fn test_chunks_are_still_proven_after_the_receiver_limits_their_size() {
+    let src_dir = tempfile::tempdir().unwrap();
+    let dst_dir = tempfile::tempdir().unwrap();
+    let content: Vec<u8> = (0..4096u32).map(|i| (i % 233) as u8).collect();
+    let path = src_dir.path().join("limited.bin");
+    std::fs::write(&path, &content).unwrap();
+
+    let transport = Arc::new(HandshakeRecorder {
+        inner: LoopbackTransport {
+            handler: Arc::new(FileTransferHandler::new(dst_dir.path(), 1024)),
+            local_peer: PeerId::random(),
+            chunk_delay: Duration::ZERO,
+        },
+        handshakes: Mutex::new(Vec::new()),
+    });
+    let sender = FileSender::new(transport.clone(), 4096);
+    let outcome = sender
+        .send_file(PeerId::random(), &path, "limited")
+        .await
+        .unwrap();
+    assert_eq!(outcome, SendOutcome::Completed { chunks_sent: 4 });
+
+    // The receiver refuses the 4096 byte proposal, so the sender rebuilds
+    // its Merkle tree for 1024 byte chunks and offers the transfer again
+    let mut expected = Vec::new();
+    for chunk_size in [4096, 1024] {
+        let tree = compute_chunk_merkle(&path, chunk_size).await.unwrap();
+        expected.push((Some(chunk_size as u64), Some(tree.root())));
+    }
+    assert_eq!(*transport.handshakes.lock().unwrap(), expected);
+    assert_eq!(
+        std::fs::read(dst_dir.path().join("limited.bin")).unwrap(),
+        content
+    );
+}
+
+/// Send `content` proposing `compression` to a handler with `compressors`,
+/// returning the size of each chunk on the wire and what was received
+async fn send_compressed(
+    content: &[u8],
+    compression: Compression,
+    compressors: CompressorRegistry,
+) -> (Vec<usize>, Vec<u8>) {
+    let src_dir = tempfile::tempdir().unwrap();
+    let dst_dir = tempfile::tempdir().unwrap();
+    let path = src_dir.path().join("compressible.txt");
+    std::fs::write(&path, content).unwrap();
+
+    let handler = FileTransferHandler::new(dst_dir.path(), 1024).with_compressors(compressors);
+    let transport = Arc::new(RecordingTransport {
+        inner: LoopbackTransport {
+            handler: Arc::new(handler),
+            local_peer: PeerId::random(),
+            chunk_delay: Duration::ZERO,
+        },
+        chunk_sizes: Mutex::new(Vec::new()),
+        chunk_capacities: Mutex::new(Vec::new()),
+    });
+    let outcome = FileSender::new(transport.clone(), 1024)
+        .with_compression(compression)
+        .send_file(PeerId::random(), &path, "compressed")
+        .await
+        .unwrap();
+    assert_eq!(outcome, SendOutcome::Completed { chunks_sent: 8 });
+
+    let received = std::fs::read(dst_dir.path().join("compressible.txt")).unwrap();
+    let chunk_sizes = transport.chunk_sizes.lock().unwrap().clone();
+    (chunk_sizes, received)
+}
+
+#[tokio::test]
+async fn test_transfer_compresses_chunks_with_each_negotiated_algorithm() {
+    let content = b"cipherstream compresses chunk data ".repeat(235);
+    let content = &content[..8 * 1024];
+
+    for algorithm in CompressorRegistry::default().algorithms() {
+        let (chunk_sizes, received) =
+            send_compressed(content, algorithm, CompressorRegistry::default()).await;
+        // Merkle proofs and offsets checked out against the decompressed data
+        assert_eq!(received, content, "{}", algorithm);
+        if algorithm == Compression::None {
+            assert_eq!(chunk_sizes, vec![1024; 8]);
+        } else {
+            assert!(
+                chunk_sizes.iter().all(|&size| size < 1024),
+                "{}: {:?}",
+                algorithm,
+                chunk_sizes
+            );
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_receiver_without_the_proposed_algorithm_takes_plain_chunks() {
+    let content = b"plain chunks ".repeat(700);
+    let content = &content[..8 * 1024];
+
+    let (chunk_sizes, received) =
+        send_compressed(content, Compression::Zstd, CompressorRegistry::empty()).await;
+    assert_eq!(received, content);
+    assert_eq!(chunk_sizes, vec![1024; 8]);
+}
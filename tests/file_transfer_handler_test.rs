@@ -0,0 +1,1120 @@
+use cipherstream::core::crypto::hash::compute_data_hash;
+use cipherstream::core::crypto::merkle::MerkleTree;
+use cipherstream::core::domain::{DomainEvent, TransferId};
+use cipherstream::core::traits::TransferRepository;
+use cipherstream::file_transfer::{
+    ApprovalDecision, Compression, DownloadLayout, FileTransferHandler, HandshakeInfo,
+    ProtocolRequest, ProtocolResponse, RejectReason,
+};
+use cipherstream::infrastructure::{InMemoryEventPublisher, InMemoryTransferRepository};
+use libp2p::PeerId;
+use std::sync::Arc;
+use std::time::Duration;
+
+fn handshake(transfer_id: &str, filename: &str, filesize: u64) -> ProtocolRequest {
+    ProtocolRequest::HandshakeRequest {
+        filename: filename.to_string(),
+        filesize,
+        transfer_id: transfer_id.to_string(),
+        sha256: None,
+        merkle_root: None,
+        chunk_size: None,
+        adaptive_chunks: false,
+        compression: Compression::None,
+    }
+}
+
+fn chunk(transfer_id: &str, chunk_index: u64, data: &[u8], is_last: bool) -> ProtocolRequest {
+    ProtocolRequest::FileChunk {
+        transfer_id: transfer_id.to_string(),
+        chunk_index,
+        total_chunks: 2,
+        offset: chunk_index * 4,
+        data: data.to_vec(),
+        is_last,
+        proof: Vec::new(),
+    }
+}
+
+/// Send "abcdefgh" as two chunks, announcing `sha256` in the handshake
+async fn send_with_hash(
+    handler: &FileTransferHandler,
+    peer: PeerId,
+    transfer_id: &str,
+    sha256: String,
+) -> ProtocolResponse {
+    let request = ProtocolRequest::HandshakeRequest {
+        filename: format!("{}.txt", transfer_id),
+        filesize: 8,
+        transfer_id: transfer_id.to_string(),
+        sha256: Some(sha256),
+        merkle_root: None,
+        chunk_size: None,
+        adaptive_chunks: false,
+        compression: Compression::None,
+    };
+    handler.handle_request(peer, request).await;
+    handler
+        .handle_request(peer, chunk(transfer_id, 0, b"abcd", false))
+        .await;
+    handler
+        .handle_request(peer, chunk(transfer_id, 1, b"efgh", true))
+        .await
+}
+
+#[tokio::test]
+async fn test_handler_rejects_chunks_after_cancel() {
+    let dir = tempfile::tempdir().unwrap();
+    let handler = FileTransferHandler::new(dir.path(), 4);
+    let peer = PeerId::random();
+
+    handler
+        .handle_request(peer, handshake("t1", "notes.txt", 8))
+        .await;
+    let first = handler
+        .handle_request(peer, chunk("t1", 0, b"abcd", false))
+        .await;
+    assert!(matches!(
+        first,
+        ProtocolResponse::ChunkResponse { success: true, .. }
+    ));
+
+    handler
+        .handle_request(
+            peer,
+            ProtocolRequest::CancelTransfer {
+                transfer_id: "t1".to_string(),
+            },
+        )
+        .await;
+
+    let late = handler
+        .handle_request(peer, chunk("t1", 1, b"efgh", true))
+        .await;
+    assert!(matches!(
+        late,
+        ProtocolResponse::ChunkResponse { success: false, .. }
+    ));
+    assert!(!dir.path().join("notes.txt").exists());
+}
+
+#[tokio::test]
+async fn test_handler_ignores_cancel_from_other_peer() {
+    let dir = tempfile::tempdir().unwrap();
+    let handler = FileTransferHandler::new(dir.path(), 4);
+    let owner = PeerId::random();
+
+    handler
+        .handle_request(owner, handshake("t2", "notes.txt", 8))
+        .await;
+    handler
+        .handle_request(
+            PeerId::random(),
+            ProtocolRequest::CancelTransfer {
+                transfer_id: "t2".to_string(),
+            },
+        )
+        .await;
+
+    assert!(!handler.is_cancelled("t2").await);
+    assert_eq!(handler.active_transfers().await, 1);
+}
+
+#[tokio::test]
+async fn test_handler_takes_a_cancelled_id_again_after_a_new_handshake() {
+    let dir = tempfile::tempdir().unwrap();
+    let handler = FileTransferHandler::new(dir.path(), 4);
+    let peer = PeerId::random();
+
+    handler
+        .handle_request(peer, handshake("again", "first.txt", 8))
+        .await;
+    handler
+        .handle_request(
+            peer,
+            ProtocolRequest::CancelTransfer {
+                transfer_id: "again".to_string(),
+            },
+        )
+        .await;
+    assert!(handler.is_cancelled("again").await);
+
+    handler
+        .handle_request(peer, handshake("again", "second.txt", 8))
+        .await;
+    assert!(!handler.is_cancelled("again").await);
+    handler
+        .handle_request(peer, chunk("again", 0, b"abcd", false))
+        .await;
+    let done = handler
+        .handle_request(peer, chunk("again", 1, b"efgh", true))
+        .await;
+    assert!(matches!(
+        done,
+        ProtocolResponse::TransferComplete { success: true, .. }
+    ));
+}
+
+#[tokio::test]
+async fn test_handler_publishes_lifecycle_events() {
+    let dir = tempfile::tempdir().unwrap();
+    let events = Arc::new(InMemoryEventPublisher::new());
+    let local = PeerId::random();
+    let handler =
+        FileTransferHandler::new(dir.path(), 4).with_event_publisher(events.clone(), local);
+    let sender = PeerId::random();
+
+    handler
+        .handle_request(sender, handshake("t3", "notes.txt", 8))
+        .await;
+    handler
+        .handle_request(sender, chunk("t3", 0, b"abcd", false))
+        .await;
+    let done = handler
+        .handle_request(sender, chunk("t3", 1, b"efgh", true))
+        .await;
+    assert!(matches!(
+        done,
+        ProtocolResponse::TransferComplete { success: true, .. }
+    ));
+
+    let events = events.get_events().await;
+    assert_eq!(events.len(), 4, "{:?}", events);
+    match &events[0] {
+        DomainEvent::TransferStarted { transfer } => {
+            assert_eq!(transfer.id.as_str(), "t3");
+            assert_eq!(transfer.file.name, "notes.txt");
+            assert_eq!(transfer.sender.as_str(), sender.to_string());
+            assert_eq!(transfer.receiver.as_str(), local.to_string());
+        }
+        other => panic!("Expected TransferStarted, got {:?}", other),
+    }
+    let percentages: Vec<f32> = events[1..3]
+        .iter()
+        .map(|event| match event {
+            DomainEvent::TransferProgress { progress, .. } => progress.percentage(),
+            other => panic!("Expected TransferProgress, got {:?}", other),
+        })
+        .collect();
+    assert_eq!(percentages, vec![50.0, 100.0]);
+    // No hash was announced, so there is nothing to verify
+    assert!(matches!(
+        &events[3],
+        DomainEvent::TransferCompleted { transfer_id } if transfer_id.as_str() == "t3"
+    ));
+}
+
+#[tokio::test]
+async fn test_handler_verifies_completed_file_against_sender_hash() {
+    let dir = tempfile::tempdir().unwrap();
+    let events = Arc::new(InMemoryEventPublisher::new());
+    let handler = FileTransferHandler::new(dir.path(), 4)
+        .with_event_publisher(events.clone(), PeerId::random());
+    let peer = PeerId::random();
+
+    let intact = send_with_hash(&handler, peer, "intact", compute_data_hash(b"abcdefgh")).await;
+    assert!(matches!(
+        intact,
+        ProtocolResponse::TransferComplete { success: true, .. }
+    ));
+    // Verified before being reported complete
+    let published = events.get_events().await;
+    assert!(matches!(
+        &published[published.len() - 2..],
+        [
+            DomainEvent::TransferVerifying { transfer_id: verifying },
+            DomainEvent::TransferCompleted { transfer_id: completed },
+        ] if verifying.as_str() == "intact" && completed.as_str() == "intact"
+    ));
+
+    // Chunks that don't match what the sender hashed, as if altered in transit
+    let tampered = send_with_hash(&handler, peer, "tampered", compute_data_hash(b"abcdefgX")).await;
+    assert!(matches!(
+        tampered,
+        ProtocolResponse::TransferComplete { success: false, .. }
+    ));
+    assert!(events.get_events().await.iter().any(|event| matches!(
+        event,
+        DomainEvent::TransferFailed { transfer_id, reason }
+            if transfer_id.as_str() == "tampered" && reason.contains("hash")
+    )));
+}
+
+#[tokio::test]
+async fn test_handler_skips_verification_when_disabled() {
+    let dir = tempfile::tempdir().unwrap();
+    let events = Arc::new(InMemoryEventPublisher::new());
+    let handler = FileTransferHandler::new(dir.path(), 4)
+        .with_verify_after(false)
+        .with_event_publisher(events.clone(), PeerId::random());
+
+    let response = send_with_hash(
+        &handler,
+        PeerId::random(),
+        "unchecked",
+        compute_data_hash(b"something else"),
+    )
+    .await;
+    assert!(matches!(
+        response,
+        ProtocolResponse::TransferComplete { success: true, .. }
+    ));
+    assert!(
+        !events
+            .get_events()
+            .await
+            .iter()
+            .any(|event| matches!(event, DomainEvent::TransferVerifying { .. }))
+    );
+}
+
+#[tokio::test]
+async fn test_handler_rejects_absurd_total_chunks() {
+    let dir = tempfile::tempdir().unwrap();
+    let handler = FileTransferHandler::new(dir.path(), 4).with_max_total_chunks(1024);
+    let peer = PeerId::random();
+
+    handler
+        .handle_request(peer, handshake("t4", "notes.txt", 8))
+        .await;
+    let response = handler
+        .handle_request(
+            peer,
+            ProtocolRequest::FileChunk {
+                transfer_id: "t4".to_string(),
+                chunk_index: 0,
+                total_chunks: u64::MAX,
+                offset: 0,
+                data: b"abcd".to_vec(),
+                is_last: false,
+                proof: Vec::new(),
+            },
+        )
+        .await;
+
+    assert!(matches!(
+        response,
+        ProtocolResponse::ChunkResponse { success: false, .. }
+    ));
+    assert_eq!(handler.progress("t4").await.unwrap().chunks_transferred, 0);
+}
+
+#[tokio::test]
+async fn test_handler_checks_each_chunk_against_merkle_root() {
+    let dir = tempfile::tempdir().unwrap();
+    let handler = FileTransferHandler::new(dir.path(), 4);
+    let peer = PeerId::random();
+    let tree = MerkleTree::from_chunks([&b"abcd"[..], &b"efgh"[..]]);
+
+    let request = ProtocolRequest::HandshakeRequest {
+        filename: "merkle.txt".to_string(),
+        filesize: 8,
+        transfer_id: "m1".to_string(),
+        sha256: None,
+        merkle_root: Some(tree.root()),
+        chunk_size: None,
+        adaptive_chunks: false,
+        compression: Compression::None,
+    };
+    handler.handle_request(peer, request).await;
+
+    let proven = |index: u64, data: &[u8], is_last: bool| {
+        let mut request = chunk("m1", index, data, is_last);
+        if let ProtocolRequest::FileChunk { proof, .. } = &mut request {
+            *proof = tree.proof(index);
+        }
+        request
+    };
+    let first = handler
+        .handle_request(peer, proven(0, b"abcd", false))
+        .await;
+    assert!(matches!(
+        first,
+        ProtocolResponse::ChunkResponse { success: true, .. }
+    ));
+
+    let forged = handler.handle_request(peer, proven(1, b"efgX", true)).await;
+    match forged {
+        ProtocolResponse::ChunkResponse {
+            success: false,
+            error,
+            retryable,
+            ..
+        } => {
+            assert!(error.unwrap().contains("Merkle"));
+            // Damaged on the way, so worth sending again
+            assert!(retryable);
+        }
+        other => panic!("forged chunk accepted: {:?}", other),
+    }
+
+    let last = handler.handle_request(peer, proven(1, b"efgh", true)).await;
+    assert!(matches!(
+        last,
+        ProtocolResponse::TransferComplete { success: true, .. }
+    ));
+    assert_eq!(
+        std::fs::read(dir.path().join("merkle.txt")).unwrap(),
+        b"abcdefgh"
+    );
+}
+
+#[tokio::test]
+async fn test_merkle_proofs_verify_chunks_delivered_out_of_order() {
+    let dir = tempfile::tempdir().unwrap();
+    let handler = FileTransferHandler::new(dir.path(), 4);
+    let peer = PeerId::random();
+    let parts: [&[u8]; 3] = [b"abcd", b"efgh", b"ij"];
+    let tree = MerkleTree::from_chunks(parts);
+
+    let request = ProtocolRequest::HandshakeRequest {
+        filename: "shuffled.txt".to_string(),
+        filesize: 10,
+        transfer_id: "m2".to_string(),
+        sha256: None,
+        merkle_root: Some(tree.root()),
+        chunk_size: None,
+        adaptive_chunks: false,
+        compression: Compression::None,
+    };
+    handler.handle_request(peer, request).await;
+
+    let proven = |index: u64, data: &[u8]| ProtocolRequest::FileChunk {
+        transfer_id: "m2".to_string(),
+        chunk_index: index,
+        total_chunks: 3,
+        offset: index * 4,
+        data: data.to_vec(),
+        is_last: index == 2,
+        proof: tree.proof(index),
+    };
+    let accepted = |response: &ProtocolResponse| {
+        matches!(
+            response,
+            ProtocolResponse::ChunkResponse { success: true, .. }
+                | ProtocolResponse::TransferComplete { success: true, .. }
+        )
+    };
+
+    assert!(accepted(
+        &handler.handle_request(peer, proven(1, b"efgh")).await
+    ));
+    // A proof for one chunk doesn't vouch for another chunk's data
+    assert!(!accepted(
+        &handler.handle_request(peer, proven(0, b"efgh")).await
+    ));
+    assert!(!accepted(
+        &handler.handle_request(peer, proven(0, b"abcX")).await
+    ));
+    assert!(accepted(
+        &handler.handle_request(peer, proven(0, b"abcd")).await
+    ));
+    assert!(accepted(
+        &handler.handle_request(peer, proven(2, b"ij")).await
+    ));
+
+    assert_eq!(
+        std::fs::read(dir.path().join("shuffled.txt")).unwrap(),
+        b"abcdefghij"
+    );
+}
+
+#[tokio::test]
+async fn test_handler_answers_ping_with_same_nonce() {
+    let dir = tempfile::tempdir().unwrap();
+    let handler = FileTransferHandler::new(dir.path(), 4);
+
+    for nonce in [0, 7, u64::MAX] {
+        let response = handler
+            .handle_request(PeerId::random(), ProtocolRequest::Ping { nonce })
+            .await;
+        assert_eq!(response, ProtocolResponse::Pong { nonce });
+    }
+}
+
+#[tokio::test]
+async fn test_download_dir_change_applies_to_new_transfers_only() {
+    let old_dir = tempfile::tempdir().unwrap();
+    let new_root = tempfile::tempdir().unwrap();
+    let new_dir = new_root.path().join("downloads");
+    let handler = FileTransferHandler::new(old_dir.path(), 4);
+    let peer = PeerId::random();
+
+    handler
+        .handle_request(peer, handshake("in-flight", "first.txt", 8))
+        .await;
+    handler
+        .handle_request(peer, chunk("in-flight", 0, b"abcd", false))
+        .await;
+
+    handler.set_download_dir(&new_dir).await.unwrap();
+    assert_eq!(handler.download_dir(), new_dir);
+
+    handler
+        .handle_request(peer, chunk("in-flight", 1, b"efgh", true))
+        .await;
+    handler
+        .handle_request(peer, handshake("fresh", "second.txt", 8))
+        .await;
+    handler
+        .handle_request(peer, chunk("fresh", 0, b"ijkl", false))
+        .await;
+    handler
+        .handle_request(peer, chunk("fresh", 1, b"mnop", true))
+        .await;
+
+    assert_eq!(
+        std::fs::read(old_dir.path().join("first.txt")).unwrap(),
+        b"abcdefgh"
+    );
+    assert_eq!(
+        std::fs::read(new_dir.join("second.txt")).unwrap(),
+        b"ijklmnop"
+    );
+    assert!(!new_dir.join("first.txt").exists());
+    assert!(!old_dir.path().join("second.txt").exists());
+}
+
+#[tokio::test]
+async fn test_set_download_dir_rejects_a_file() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = dir.path().join("not-a-dir");
+    std::fs::write(&file, b"x").unwrap();
+    let handler = FileTransferHandler::new(dir.path(), 4);
+
+    assert!(handler.set_download_dir(&file).await.is_err());
+    assert_eq!(handler.download_dir(), dir.path());
+}
+
+#[tokio::test]
+async fn test_handler_rejects_filenames_with_control_characters() {
+    let dir = tempfile::tempdir().unwrap();
+    let handler = FileTransferHandler::new(dir.path(), 4);
+
+    for name in [
+        "line\nbreak.txt",
+        "nul\0byte.txt",
+        "\x1b[2J\x1b[31mclear.txt",
+        "bell\x07.txt",
+        "invoice\u{202E}fdp.exe",
+        "../../\x1b]0;title\x07",
+    ] {
+        let response = handler
+            .handle_request(PeerId::random(), handshake("t", name, 4))
+            .await;
+        assert_eq!(
+            response,
+            ProtocolResponse::HandshakeResponse {
+                accepted: false,
+                reason: Some(RejectReason::InvalidFilename),
+                transfer_id: Some("t".to_string()),
+                accepted_chunk_size: None,
+                compression: Compression::None,
+            },
+            "{:?} should be rejected",
+            name
+        );
+    }
+    assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 0);
+}
+
+#[tokio::test]
+async fn test_handler_trims_and_strips_directories_from_filenames() {
+    let dir = tempfile::tempdir().unwrap();
+    let handler = FileTransferHandler::new(dir.path(), 4);
+    let peer = PeerId::random();
+
+    handler
+        .handle_request(peer, handshake("t", "../nested/ report.txt ", 4))
+        .await;
+    handler
+        .handle_request(peer, chunk("t", 0, b"abcd", true))
+        .await;
+
+    assert_eq!(
+        std::fs::read(dir.path().join("report.txt")).unwrap(),
+        b"abcd"
+    );
+}
+
+#[tokio::test]
+async fn test_handler_samples_throughput_of_active_transfers() {
+    let dir = tempfile::tempdir().unwrap();
+    let handler = FileTransferHandler::new(dir.path(), 4)
+        .with_throughput_history(std::time::Duration::from_secs(3600), 10);
+    let peer = PeerId::random();
+
+    handler
+        .handle_request(peer, handshake("t", "a.txt", 8))
+        .await;
+    assert!(handler.throughput_samples("t").await.is_empty());
+
+    handler
+        .handle_request(peer, chunk("t", 0, b"abcd", false))
+        .await;
+    let samples = handler.throughput_samples("t").await;
+    assert_eq!(samples.len(), 1);
+    assert_eq!(samples[0].1, 4);
+
+    handler
+        .handle_request(peer, chunk("t", 1, b"efgh", true))
+        .await;
+    assert!(handler.throughput_samples("t").await.is_empty());
+}
+
+/// Check that the dry run and the real handler both refuse `request` with `reason`
+async fn assert_rejected(
+    handler: &FileTransferHandler,
+    peer: PeerId,
+    request: ProtocolRequest,
+    reason: RejectReason,
+) {
+    assert_eq!(
+        handler.evaluate_handshake(peer, &request).await,
+        ApprovalDecision::Reject(reason.clone())
+    );
+    assert!(matches!(
+        handler.handle_request(peer, request).await,
+        ProtocolResponse::HandshakeResponse {
+            accepted: false,
+            reason: Some(r),
+            ..
+        } if r == reason
+    ));
+}
+
+#[tokio::test]
+async fn test_evaluate_handshake_is_a_dry_run() {
+    let dir = tempfile::tempdir().unwrap();
+    let handler = FileTransferHandler::new(dir.path(), 4).with_max_downloads(1);
+    let mut request = handshake("t", "../probe.txt", 8);
+    if let ProtocolRequest::HandshakeRequest { chunk_size, .. } = &mut request {
+        *chunk_size = Some(64);
+    }
+
+    for _ in 0..2 {
+        assert_eq!(
+            handler.evaluate_handshake(PeerId::random(), &request).await,
+            ApprovalDecision::Accept {
+                filename: "probe.txt".to_string(),
+                chunk_size: 4,
+                compression: Compression::None,
+            }
+        );
+    }
+    assert_eq!(handler.active_transfers().await, 0);
+    assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 0);
+}
+
+#[tokio::test]
+async fn test_handshake_rejected_for_invalid_filename() {
+    let dir = tempfile::tempdir().unwrap();
+    let handler = FileTransferHandler::new(dir.path(), 4);
+    let request = handshake("t", "../", 4);
+    assert_rejected(
+        &handler,
+        PeerId::random(),
+        request,
+        RejectReason::InvalidFilename,
+    )
+    .await;
+}
+
+#[tokio::test]
+async fn test_handshake_rejected_when_too_large() {
+    let dir = tempfile::tempdir().unwrap();
+    let handler = FileTransferHandler::new(dir.path(), 4).with_max_file_size(100);
+    let peer = PeerId::random();
+
+    assert_rejected(
+        &handler,
+        peer,
+        handshake("big", "big.txt", 101),
+        RejectReason::TooLarge,
+    )
+    .await;
+    assert!(matches!(
+        handler
+            .evaluate_handshake(peer, &handshake("ok", "ok.txt", 100))
+            .await,
+        ApprovalDecision::Accept { .. }
+    ));
+}
+
+#[tokio::test]
+async fn test_handshake_rejected_for_disallowed_extension() {
+    let dir = tempfile::tempdir().unwrap();
+    let handler = FileTransferHandler::new(dir.path(), 4).with_allowed_extensions([".txt", "PDF"]);
+    let peer = PeerId::random();
+
+    for name in ["tool.exe", "archive.tar.gz", "README"] {
+        assert_rejected(
+            &handler,
+            peer,
+            handshake("t", name, 4),
+            RejectReason::DisallowedExtension,
+        )
+        .await;
+    }
+    for name in ["notes.TXT", "paper.pdf"] {
+        assert!(matches!(
+            handler
+                .evaluate_handshake(peer, &handshake("t", name, 4))
+                .await,
+            ApprovalDecision::Accept { .. }
+        ));
+    }
+}
+
+#[tokio::test]
+async fn test_handshake_rejected_when_downloads_are_full() {
+    let dir = tempfile::tempdir().unwrap();
+    let handler = FileTransferHandler::new(dir.path(), 4).with_max_downloads(1);
+
+    handler
+        .handle_request(PeerId::random(), handshake("first", "first.txt", 4))
+        .await;
+    assert_rejected(
+        &handler,
+        PeerId::random(),
+        handshake("second", "second.txt", 4),
+        RejectReason::RateLimited,
+    )
+    .await;
+}
+
+#[tokio::test]
+async fn test_handshake_rejected_when_peer_exceeds_rate_limit() {
+    let dir = tempfile::tempdir().unwrap();
+    let handler = FileTransferHandler::new(dir.path(), 4)
+        .with_handshake_rate_limit(1, Duration::from_secs(60));
+    let peer = PeerId::random();
+
+    handler
+        .handle_request(peer, handshake("first", "first.txt", 4))
+        .await;
+    handler
+        .handle_request(peer, chunk("first", 0, b"abcd", true))
+        .await;
+    assert_eq!(handler.active_transfers().await, 0);
+
+    // Finishing the first transfer doesn't free up the peer's allowance
+    assert_rejected(
+        &handler,
+        peer,
+        handshake("second", "second.txt", 4),
+        RejectReason::RateLimited,
+    )
+    .await;
+    assert!(matches!(
+        handler
+            .evaluate_handshake(PeerId::random(), &handshake("other", "other.txt", 4))
+            .await,
+        ApprovalDecision::Accept { .. }
+    ));
+}
+
+#[tokio::test]
+async fn test_handshake_rejected_when_disk_budget_is_spent() {
+    let dir = tempfile::tempdir().unwrap();
+    let handler = FileTransferHandler::new(dir.path(), 4).with_disk_budget(10);
+    let peer = PeerId::random();
+
+    handler
+        .handle_request(peer, handshake("first", "first.txt", 6))
+        .await;
+    assert_rejected(
+        &handler,
+        peer,
+        handshake("second", "second.txt", 5),
+        RejectReason::InsufficientSpace,
+    )
+    .await;
+    assert!(matches!(
+        handler
+            .evaluate_handshake(peer, &handshake("third", "third.txt", 4))
+            .await,
+        ApprovalDecision::Accept { .. }
+    ));
+}
+
+#[tokio::test]
+async fn test_handshake_rejects_chunk_sizes_outside_the_receivers_range() {
+    let dir = tempfile::tempdir().unwrap();
+    let handler = FileTransferHandler::new(dir.path(), 4096).with_min_chunk_size(512);
+    let peer = PeerId::random();
+    let proposing = |transfer_id: &str, chunk_size: u64, merkle_root: Option<String>| {
+        ProtocolRequest::HandshakeRequest {
+            filename: format!("{}.bin", transfer_id),
+            filesize: 10_000,
+            transfer_id: transfer_id.to_string(),
+            sha256: None,
+            merkle_root,
+            chunk_size: Some(chunk_size),
+            adaptive_chunks: false,
+            compression: Compression::None,
+        }
+    };
+    let unsupported = RejectReason::ChunkSizeUnsupported {
+        min: 512,
+        max: 4096,
+    };
+
+    for chunk_size in [0, 511] {
+        let request = proposing("small", chunk_size, None);
+        assert_rejected(&handler, peer, request, unsupported.clone()).await;
+    }
+    assert_eq!(handler.active_transfers().await, 0);
+
+    // Without a root a larger proposal is just limited, but a root's proofs
+    // would not fit the smaller chunks
+    assert_eq!(
+        handler
+            .evaluate_handshake(peer, &proposing("big", 8192, None))
+            .await,
+        ApprovalDecision::Accept {
+            filename: "big.bin".to_string(),
+            chunk_size: 4096,
+            compression: Compression::None,
+        }
+    );
+    let request = proposing("big", 8192, Some("cd".repeat(32)));
+    assert_rejected(&handler, peer, request, unsupported).await;
+}
+
+#[tokio::test]
+async fn test_handler_aborts_file_whose_sniffed_type_is_blocked() {
+    let dir = tempfile::tempdir().unwrap();
+    let handler = FileTransferHandler::new(dir.path(), 16)
+        .with_allowed_extensions(["txt"])
+        .with_content_types::<&str>(&[], &["application/x-executable"]);
+    let peer = PeerId::random();
+
+    // An ELF binary renamed to look like text
+    let elf = b"\x7fELF\x02\x01\x01\x00 not really notes";
+    handler
+        .handle_request(peer, handshake("t", "notes.txt", elf.len() as u64))
+        .await;
+    let response = handler
+        .handle_request(
+            peer,
+            ProtocolRequest::FileChunk {
+                transfer_id: "t".to_string(),
+                chunk_index: 0,
+                total_chunks: 2,
+                offset: 0,
+                data: elf[..16].to_vec(),
+                is_last: false,
+                proof: Vec::new(),
+            },
+        )
+        .await;
+
+    assert_eq!(
+        response,
+        ProtocolResponse::TransferComplete {
+            transfer_id: "t".to_string(),
+            success: false,
+            error: Some(RejectReason::DisallowedContentType(
+                "application/x-executable".to_string()
+            )),
+        }
+    );
+    assert_eq!(handler.active_transfers().await, 0);
+    assert!(!dir.path().join("notes.txt").exists());
+
+    // Plain text under the same policy goes through
+    handler
+        .handle_request(peer, handshake("ok", "ok.txt", 4))
+        .await;
+    assert!(matches!(
+        handler
+            .handle_request(peer, chunk("ok", 0, b"text", true))
+            .await,
+        ProtocolResponse::TransferComplete { success: true, .. }
+    ));
+}
+
+#[tokio::test]
+async fn test_peer_layout_separates_files_by_sender() {
+    let dir = tempfile::tempdir().unwrap();
+    let handler =
+        FileTransferHandler::new(dir.path(), 4).with_download_layout(DownloadLayout::Peer);
+    let (alice, bob) = (PeerId::random(), PeerId::random());
+
+    // Same file name from both peers
+    for (peer, id, data) in [(alice, "from-alice", b"aaaa"), (bob, "from-bob", b"bbbb")] {
+        handler
+            .handle_request(peer, handshake(id, "report.txt", 4))
+            .await;
+        handler.handle_request(peer, chunk(id, 0, data, true)).await;
+    }
+
+    let received =
+        |peer: PeerId| std::fs::read(dir.path().join(peer.to_string()).join("report.txt"));
+    assert_eq!(received(alice).unwrap(), b"aaaa");
+    assert_eq!(received(bob).unwrap(), b"bbbb");
+    assert!(!dir.path().join("report.txt").exists());
+}
+
+#[tokio::test]
+async fn test_repeated_handshake_keeps_a_single_transfer() {
+    let dir = tempfile::tempdir().unwrap();
+    let handler = FileTransferHandler::new(dir.path(), 4).with_max_downloads(1);
+    let peer = PeerId::random();
+
+    let first = handler
+        .handle_request(peer, handshake("retried", "notes.txt", 8))
+        .await;
+    handler
+        .handle_request(peer, chunk("retried", 0, b"abcd", false))
+        .await;
+    // A network retry of the same handshake, even with no slot left
+    let second = handler
+        .handle_request(peer, handshake("retried", "notes.txt", 8))
+        .await;
+    assert_eq!(first, second);
+    assert_eq!(handler.active_transfers().await, 1);
+
+    // The chunk written before the retry survives
+    let done = handler
+        .handle_request(peer, chunk("retried", 1, b"efgh", true))
+        .await;
+    assert!(matches!(
+        done,
+        ProtocolResponse::TransferComplete { success: true, .. }
+    ));
+    assert_eq!(
+        std::fs::read(dir.path().join("notes.txt")).unwrap(),
+        b"abcdefgh"
+    );
+
+    // Someone else reusing a live transfer id is refused
+    handler
+        .handle_request(peer, handshake("taken", "a.txt", 8))
+        .await;
+    let clash = handler
+        .handle_request(PeerId::random(), handshake("taken", "a.txt", 8))
+        .await;
+    assert!(matches!(
+        clash,
+        ProtocolResponse::HandshakeResponse {
+            accepted: false,
+            ..
+        }
+    ));
+}
+
+#[tokio::test]
+async fn test_handler_rejects_chunks_off_the_agreed_size() {
+    let dir = tempfile::tempdir().unwrap();
+    let handler = FileTransferHandler::new(dir.path(), 4);
+    let peer = PeerId::random();
+    handler
+        .handle_request(peer, handshake("short", "notes.txt", 10))
+        .await;
+    let send = |chunk_index: u64, data: &[u8], is_last: bool| {
+        let request = ProtocolRequest::FileChunk {
+            transfer_id: "short".to_string(),
+            chunk_index,
+            total_chunks: 3,
+            offset: chunk_index * 4,
+            data: data.to_vec(),
+            is_last,
+            proof: Vec::new(),
+        };
+        handler.handle_request(peer, request)
+    };
+
+    // Only the final chunk may be shorter than the agreed 4 bytes
+    assert!(matches!(
+        send(0, b"abc", false).await,
+        ProtocolResponse::ChunkResponse { success: false, .. }
+    ));
+    for (index, data) in [(0, b"abcd"), (1, b"efgh")] {
+        assert!(matches!(
+            send(index, data, false).await,
+            ProtocolResponse::ChunkResponse { success: true, .. }
+        ));
+    }
+
+    // ...and it must carry exactly what remains of the file
+    assert!(matches!(
+        send(2, b"i", true).await,
+        ProtocolResponse::ChunkResponse { success: false, .. }
+    ));
+    assert!(matches!(
+        send(2, b"ij", true).await,
+        ProtocolResponse::TransferComplete { success: true, .. }
+    ));
+    assert_eq!(
+        std::fs::read(dir.path().join("notes.txt")).unwrap(),
+        b"abcdefghij"
+    );
+}
+
+#[tokio::test]
+async fn test_handler_refuses_chunk_whose_end_overflows() {
+    let dir = tempfile::tempdir().unwrap();
+    let handler = FileTransferHandler::new(dir.path(), 4);
+    let peer = PeerId::random();
+    let request = ProtocolRequest::HandshakeRequest {
+        filename: "adaptive.bin".to_string(),
+        filesize: 8,
+        transfer_id: "overflow".to_string(),
+        sha256: None,
+        merkle_root: None,
+        chunk_size: Some(4),
+        adaptive_chunks: true,
+        compression: Compression::None,
+    };
+    handler.handle_request(peer, request).await;
+
+    for is_last in [false, true] {
+        let request = ProtocolRequest::FileChunk {
+            transfer_id: "overflow".to_string(),
+            chunk_index: 0,
+            total_chunks: 2,
+            offset: u64::MAX - 1,
+            data: b"abcd".to_vec(),
+            is_last,
+            proof: Vec::new(),
+        };
+        assert!(matches!(
+            handler.handle_request(peer, request).await,
+            ProtocolResponse::ChunkResponse { success: false, .. }
+        ));
+    }
+}
+
+#[tokio::test]
+async fn test_handler_counts_a_resent_chunk_once() {
+    let dir = tempfile::tempdir().unwrap();
+    let handler = FileTransferHandler::new(dir.path(), 4);
+    let peer = PeerId::random();
+    handler
+        .handle_request(peer, handshake("resent", "resent.txt", 8))
+        .await;
+
+    for _ in 0..2 {
+        let response = handler
+            .handle_request(peer, chunk("resent", 0, b"abcd", false))
+            .await;
+        assert!(matches!(
+            response,
+            ProtocolResponse::ChunkResponse { success: true, .. }
+        ));
+    }
+    let progress = handler.progress("resent").await.unwrap();
+    assert_eq!(progress.bytes_transferred, 4);
+    assert_eq!(progress.chunks_transferred, 1);
+
+    let done = handler
+        .handle_request(peer, chunk("resent", 1, b"efgh", true))
+        .await;
+    assert!(matches!(
+        done,
+        ProtocolResponse::TransferComplete { success: true, .. }
+    ));
+    assert_eq!(
+        std::fs::read(dir.path().join("resent.txt")).unwrap(),
+        b"abcdefgh"
+    );
+}
+
+#[tokio::test]
+async fn test_handler_records_received_chunks_until_transfer_ends() {
+    let dir = tempfile::tempdir().unwrap();
+    let repo = Arc::new(InMemoryTransferRepository::new());
+    let handler = FileTransferHandler::new(dir.path(), 4).with_transfer_repository(repo.clone());
+    let peer = PeerId::random();
+    let id = TransferId::from_string("logged".to_string());
+    handler
+        .handle_request(peer, handshake("logged", "logged.txt", 8))
+        .await;
+
+    handler
+        .handle_request(peer, chunk("logged", 0, b"abcd", false))
+        .await;
+    let state = repo.find_resume_state(&id).await.unwrap().unwrap();
+    assert_eq!(
+        state.partial_path,
+        dir.path().join("logged.txt").to_string_lossy()
+    );
+    assert_eq!(state.missing_chunks(2), vec![1]);
+
+    handler
+        .handle_request(peer, chunk("logged", 1, b"efgh", true))
+        .await;
+    assert_eq!(repo.find_resume_state(&id).await.unwrap(), None);
+}
+
+#[test]
+fn test_handshake_info_maps_every_request_field() {
+    let peer = PeerId::random();
+    let request = ProtocolRequest::HandshakeRequest {
+        filename: "report.pdf".to_string(),
+        filesize: 4096,
+        transfer_id: "info".to_string(),
+        sha256: Some("ab".repeat(32)),
+        merkle_root: Some("cd".repeat(32)),
+        chunk_size: Some(512),
+        adaptive_chunks: true,
+        compression: Compression::None,
+    };
+
+    let info = HandshakeInfo::from_request(peer, request).unwrap();
+    assert_eq!(
+        info,
+        HandshakeInfo {
+            peer,
+            transfer_id: "info".to_string(),
+            filename: "report.pdf".to_string(),
+            filesize: 4096,
+            sha256: Some("ab".repeat(32)),
+            merkle_root: Some("cd".repeat(32)),
+            chunk_size: Some(512),
+            adaptive_chunks: true,
+            compression: Compression::None,
+        }
+    );
+    assert_eq!(
+        info.to_string(),
+        format!("transfer info of \"report.pdf\" (4096 bytes) from {}", peer)
+    );
+    assert!(HandshakeInfo::from_request(peer, ProtocolRequest::Ping { nonce: 1 }).is_none());
+}
+
+#[test]
+fn test_pending_requests_are_capped_per_peer_and_globally() {
+    let dir = tempfile::tempdir().unwrap();
+    let handler = FileTransferHandler::new(dir.path(), 4).with_max_pending_requests(3, 2);
+    let (greedy, other, late) = (PeerId::random(), PeerId::random(), PeerId::random());
+
+    let first = handler.try_reserve(greedy).unwrap();
+    let _second = handler.try_reserve(greedy).unwrap();
+    assert!(handler.try_reserve(greedy).is_none());
+
+    // Other peers still get in, up to the node-wide cap
+    let _third = handler.try_reserve(other).unwrap();
+    assert!(handler.try_reserve(late).is_none());
+
+    // Answering a request frees its slot
+    drop(first);
+    assert!(handler.try_reserve(late).is_some());
+
+    // Refused requests get a retryable answer
+    let busy = ProtocolResponse::busy(&handshake("t", "a.txt", 4));
+    assert!(matches!(
+        busy,
+        ProtocolResponse::HandshakeResponse {
+            accepted: false,
+            reason: Some(RejectReason::RateLimited),
+            ..
+        }
+    ));
+    assert!(matches!(
+        ProtocolResponse::busy(&chunk("t", 0, b"abcd", false)),
+        ProtocolResponse::ChunkResponse { success: false, .. }
+    ));
+}
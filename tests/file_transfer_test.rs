@@ -47,7 +47,14 @@ async fn test_file_transfer_setup() -> anyhow::Result<()> {
         .map_err(|e| anyhow::anyhow!("Failed to create ApplicationService: {}", e))?;
 
     // Initialize network service
-    let _network_service = cipherstream::infrastructure::NetworkServiceImpl::new();
+    let network = cipherstream::infrastructure::LibP2pNetworkService::new(
+        std::sync::Arc::new(cipherstream::AppConfig::default()),
+        std::sync::Arc::new(cipherstream::infrastructure::InMemoryEventPublisher::new()),
+    )
+    .await
+    .map_err(|e| anyhow::anyhow!("Failed to create network service: {}", e))?;
+    let _network_service =
+        cipherstream::infrastructure::NetworkServiceImpl::new(std::sync::Arc::new(network));
 
     println!("Successfully initialized new modular architecture components");
 
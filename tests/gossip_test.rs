@@ -0,0 +1,188 @@
+use cipherstream::application::GossipSink;
+use cipherstream::core::traits::NetworkService;
+use cipherstream::infrastructure::network::NetworkEvent;
+use cipherstream::infrastructure::{
+    AppConfig, InMemoryEventPublisher, LibP2pNetworkService, NetworkError,
+};
+use cipherstream::testing::loopback_addr;
+use std::sync::Arc;
+use std::time::Duration;
+
+async fn start_node() -> LibP2pNetworkService {
+    let node = LibP2pNetworkService::new(
+        Arc::new(AppConfig::default()),
+        Arc::new(InMemoryEventPublisher::new()),
+    )
+    .await
+    .unwrap();
+    node.start_listening(0).await.unwrap();
+    node
+}
+
+/// Two connected nodes, both subscribed to `topic`
+async fn connected_pair(topic: &str) -> (LibP2pNetworkService, LibP2pNetworkService) {
+    let publisher = start_node().await;
+    let listener = start_node().await;
+    listener.subscribe_topic(topic).await.unwrap();
+    publisher.subscribe_topic(topic).await.unwrap();
+    publisher
+        .connect_and_wait(loopback_addr(&listener).await)
+        .await
+        .unwrap();
+    (publisher, listener)
+}
+
+fn gossip_payloads(events: &[NetworkEvent], topic: &str) -> Vec<Vec<u8>> {
+    events
+        .iter()
+        .filter_map(|event| match event {
+            NetworkEvent::GossipMessage { topic: t, data, .. } if t == topic => Some(data.clone()),
+            _ => None,
+        })
+        .collect()
+}
+
+#[tokio::test]
+async fn test_discover_observes_gossip_on_topic() {
+    let topic = "catalog-announcements";
+    let (publisher, discoverer) = connected_pair(topic).await;
+
+    // The subscription exchange races the first publish, so keep publishing
+    // until the discovering node reports the message
+    let mut received = Vec::new();
+    for _ in 0..50 {
+        if let Err(e) = publisher
+            .publish_message(topic, b"new file available".to_vec())
+            .await
+        {
+            assert!(matches!(
+                e.downcast_ref::<NetworkError>(),
+                Some(NetworkError::NoGossipPeers { .. })
+            ));
+        }
+        let events = discoverer
+            .collect_events_for(Duration::from_millis(100))
+            .await;
+        received = gossip_payloads(&events, topic);
+        if !received.is_empty() {
+            break;
+        }
+    }
+
+    assert_eq!(received, vec![b"new file available".to_vec()]);
+}
+
+#[tokio::test]
+async fn test_publish_delivers_to_discovering_peer() {
+    let topic = "publish-test";
+    let subscriber = start_node().await;
+    subscriber.subscribe_topic(topic).await.unwrap();
+    let publisher = start_node().await;
+    publisher
+        .connect_and_wait(loopback_addr(&subscriber).await)
+        .await
+        .unwrap();
+
+    publisher
+        .publish_when_subscribed(topic, b"hello subscribers".to_vec(), Duration::from_secs(5))
+        .await
+        .unwrap();
+
+    let mut received = Vec::new();
+    for _ in 0..20 {
+        let events = subscriber
+            .collect_events_for(Duration::from_millis(100))
+            .await;
+        received.extend(gossip_payloads(&events, topic));
+        if !received.is_empty() {
+            break;
+        }
+    }
+    assert_eq!(received, vec![b"hello subscribers".to_vec()]);
+}
+
+#[tokio::test]
+async fn test_publish_without_subscribers_reports_clear_error() {
+    let publisher = start_node().await;
+    let err = publisher
+        .publish_when_subscribed("nobody-listens", b"hi".to_vec(), Duration::from_millis(300))
+        .await
+        .unwrap_err();
+    assert!(err.to_string().contains("No peers are subscribed"));
+}
+
+#[tokio::test]
+async fn test_broadcast_to_topic_counts_subscribed_peers() {
+    let topic = "broadcast-count";
+    let (publisher, _listener) = connected_pair(topic).await;
+
+    // Retry until the subscription exchange has reached the publisher
+    let mut forwarded = None;
+    for _ in 0..50 {
+        match publisher.broadcast_to_topic(topic, b"ping".to_vec()).await {
+            Ok(peers) => {
+                forwarded = Some(peers);
+                break;
+            }
+            Err(e) => {
+                assert!(matches!(
+                    e.downcast_ref::<NetworkError>(),
+                    Some(NetworkError::NoGossipPeers { .. })
+                ));
+                tokio::time::sleep(Duration::from_millis(100)).await;
+            }
+        }
+    }
+    assert_eq!(forwarded, Some(1));
+}
+
+#[tokio::test]
+async fn test_broadcast_to_topic_without_subscribers_fails() {
+    let publisher = start_node().await;
+    let err = publisher
+        .broadcast_to_topic("nobody-listens", b"hi".to_vec())
+        .await
+        .unwrap_err();
+    assert!(matches!(
+        err.downcast_ref::<NetworkError>(),
+        Some(NetworkError::NoGossipPeers { .. })
+    ));
+}
+
+#[tokio::test]
+async fn test_subscribe_sink_receives_and_saves_messages() {
+    let topic = "sink-test";
+    let out_dir = tempfile::tempdir().unwrap();
+    let subscriber = start_node().await;
+    subscriber.subscribe_topic(topic).await.unwrap();
+    let publisher = start_node().await;
+    publisher
+        .connect_and_wait(loopback_addr(&subscriber).await)
+        .await
+        .unwrap();
+
+    publisher
+        .publish_when_subscribed(topic, b"first".to_vec(), Duration::from_secs(5))
+        .await
+        .unwrap();
+    publisher
+        .publish_when_subscribed(topic, b"second".to_vec(), Duration::from_secs(5))
+        .await
+        .unwrap();
+
+    let mut sink = GossipSink::new(topic).with_output_dir(out_dir.path());
+    let mut saved = Vec::new();
+    while saved.len() < 2 {
+        let event = tokio::time::timeout(Duration::from_secs(5), subscriber.next_event())
+            .await
+            .expect("timed out waiting for gossip")
+            .unwrap();
+        if let Some(delivery) = sink.accept(event).await.unwrap() {
+            assert_eq!(delivery.from, publisher.local_peer_id());
+            saved.push(std::fs::read(delivery.saved_to.unwrap()).unwrap());
+        }
+    }
+
+    assert_eq!(saved, vec![b"first".to_vec(), b"second".to_vec()]);
+    assert_eq!(sink.received(), 2);
+}
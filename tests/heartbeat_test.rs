@@ -0,0 +1,85 @@
+use cipherstream::core::domain::*;
+use cipherstream::core::services::HeartbeatService;
+use cipherstream::core::traits::*;
+use cipherstream::infrastructure::{
+    InMemoryEventPublisher, InMemoryPeerRepository, InMemoryTransferRepository,
+};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+async fn save_peer(repo: &InMemoryPeerRepository, id: &str, is_connected: bool) {
+    repo.save_peer(&Peer {
+        id: PeerId::new(id.to_string()),
+        addresses: vec![],
+        last_seen: SystemTime::now(),
+        is_connected,
+        protocols: Vec::new(),
+    })
+    .await
+    .unwrap();
+}
+
+fn transfer(status: TransferStatus) -> Transfer {
+    Transfer {
+        id: TransferId::new(),
+        file: File {
+            id: FileId::new(),
+            name: "report.pdf".to_string(),
+            size: 2048,
+            hash: "abc".to_string(),
+            path: "/tmp/report.pdf".to_string(),
+            created_at: SystemTime::now(),
+            modified_at: None,
+        },
+        sender: PeerId::new("alice".to_string()),
+        receiver: PeerId::new("bob".to_string()),
+        status,
+        progress: TransferProgress::new(2048, 2),
+        started_at: SystemTime::now(),
+        completed_at: None,
+    }
+}
+
+#[tokio::test]
+async fn test_heartbeats_are_published_on_interval() {
+    let peer_repo = Arc::new(InMemoryPeerRepository::new());
+    let transfer_repo = Arc::new(InMemoryTransferRepository::new());
+    let events = Arc::new(InMemoryEventPublisher::new());
+
+    save_peer(&peer_repo, "online", true).await;
+    save_peer(&peer_repo, "offline", false).await;
+    for status in [TransferStatus::InProgress, TransferStatus::Completed] {
+        transfer_repo
+            .save_transfer(&transfer(status))
+            .await
+            .unwrap();
+    }
+
+    let task = HeartbeatService::new(peer_repo, transfer_repo, events.clone())
+        .spawn(Duration::from_millis(20));
+    tokio::time::sleep(Duration::from_millis(110)).await;
+    task.abort();
+
+    let beats: Vec<_> = events
+        .get_events()
+        .await
+        .into_iter()
+        .filter_map(|event| match event {
+            DomainEvent::Heartbeat {
+                uptime,
+                connected_peers,
+                active_transfers,
+            } => Some((uptime, connected_peers, active_transfers)),
+            _ => None,
+        })
+        .collect();
+
+    assert!(beats.len() >= 3, "only {} heartbeats", beats.len());
+    assert!(
+        beats
+            .iter()
+            .all(|&(_, peers, active)| (peers, active) == (1, 1))
+    );
+    assert!(beats.windows(2).all(|w| w[0].0 < w[1].0));
+    assert!(beats.last().unwrap().0 < Duration::from_secs(1));
+}
@@ -0,0 +1,174 @@
+use cipherstream::core::traits::NetworkService;
+use cipherstream::file_transfer::{FileSender, FileTransferHandler, SendOutcome};
+use cipherstream::infrastructure::network::NetworkEvent;
+use cipherstream::infrastructure::{AppConfig, InMemoryEventPublisher, LibP2pNetworkService};
+use cipherstream::testing::loopback_addr;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+const CHUNK_SIZE: usize = 4096;
+
+async fn start_node() -> LibP2pNetworkService {
+    start_node_with(AppConfig::default()).await
+}
+
+async fn start_node_with(config: AppConfig) -> LibP2pNetworkService {
+    let node = LibP2pNetworkService::new(Arc::new(config), Arc::new(InMemoryEventPublisher::new()))
+        .await
+        .unwrap();
+    node.start_listening(0).await.unwrap();
+    node
+}
+
+#[tokio::test]
+async fn test_file_round_trips_between_in_process_nodes() {
+    let src_dir = tempfile::tempdir().unwrap();
+    let dst_dir = tempfile::tempdir().unwrap();
+    let content: Vec<u8> = (0..20_000u32).map(|i| (i % 253) as u8).collect();
+    let path = src_dir.path().join("payload.bin");
+    std::fs::write(&path, &content).unwrap();
+
+    let receiver = start_node().await;
+    receiver
+        .serve_file_transfers(Arc::new(FileTransferHandler::new(
+            dst_dir.path(),
+            CHUNK_SIZE,
+        )))
+        .await
+        .unwrap();
+    let sender = Arc::new(start_node().await);
+
+    let receiver_id = sender
+        .connect_and_wait(loopback_addr(&receiver).await)
+        .await
+        .unwrap();
+    assert_eq!(receiver_id, receiver.local_peer_id());
+
+    let outcome = FileSender::new(sender.clone(), CHUNK_SIZE)
+        .send_file(receiver_id, &path, "in-process")
+        .await
+        .unwrap();
+
+    assert_eq!(outcome, SendOutcome::Completed { chunks_sent: 5 });
+    assert_eq!(
+        std::fs::read(dst_dir.path().join("payload.bin")).unwrap(),
+        content
+    );
+}
+
+#[tokio::test]
+async fn test_received_chunks_show_up_as_progress_events() {
+    let src_dir = tempfile::tempdir().unwrap();
+    let dst_dir = tempfile::tempdir().unwrap();
+    let path = src_dir.path().join("progress.bin");
+    std::fs::write(&path, vec![7u8; 3 * CHUNK_SIZE]).unwrap();
+
+    let receiver = start_node().await;
+    receiver
+        .serve_file_transfers(Arc::new(FileTransferHandler::new(
+            dst_dir.path(),
+            CHUNK_SIZE,
+        )))
+        .await
+        .unwrap();
+    let sender = Arc::new(start_node().await);
+    let receiver_id = sender
+        .connect_and_wait(loopback_addr(&receiver).await)
+        .await
+        .unwrap();
+
+    FileSender::new(sender.clone(), CHUNK_SIZE)
+        .send_file(receiver_id, &path, "progress")
+        .await
+        .unwrap();
+
+    let events = receiver
+        .collect_events_for(Duration::from_millis(200))
+        .await;
+    let progress: Vec<_> = events
+        .into_iter()
+        .filter_map(|event| match event {
+            NetworkEvent::TransferProgress {
+                from,
+                transfer_id,
+                progress,
+            } => {
+                assert_eq!(from, sender.local_peer_id());
+                assert_eq!(transfer_id, "progress");
+                Some(progress.bytes_transferred)
+            }
+            _ => None,
+        })
+        .collect();
+    assert_eq!(
+        progress,
+        vec![
+            CHUNK_SIZE as u64,
+            2 * CHUNK_SIZE as u64,
+            3 * CHUNK_SIZE as u64
+        ]
+    );
+}
+
+#[tokio::test]
+async fn test_global_upload_cap_is_shared_by_concurrent_transfers() {
+    const CAP: u64 = 64 * 1024;
+    const FILE_SIZE: usize = 64 * 1024;
+
+    let src_dir = tempfile::tempdir().unwrap();
+    let dst_dir = tempfile::tempdir().unwrap();
+    let paths: Vec<_> = ["first.bin", "second.bin"]
+        .iter()
+        .map(|name| {
+            let path = src_dir.path().join(name);
+            std::fs::write(&path, vec![9u8; FILE_SIZE]).unwrap();
+            path
+        })
+        .collect();
+
+    let receiver = start_node().await;
+    receiver
+        .serve_file_transfers(Arc::new(FileTransferHandler::new(
+            dst_dir.path(),
+            CHUNK_SIZE,
+        )))
+        .await
+        .unwrap();
+    let mut config = AppConfig::default();
+    config.network.max_upload_bytes_per_sec = Some(CAP);
+    let sender = Arc::new(start_node_with(config).await);
+    let receiver_id = sender
+        .connect_and_wait(loopback_addr(&receiver).await)
+        .await
+        .unwrap();
+
+    let file_sender = Arc::new(FileSender::new(sender.clone(), CHUNK_SIZE));
+    let started = Instant::now();
+    let sends: Vec<_> = paths
+        .iter()
+        .enumerate()
+        .map(|(i, path)| {
+            let file_sender = file_sender.clone();
+            let path = path.clone();
+            tokio::spawn(async move {
+                file_sender
+                    .send_file(receiver_id, &path, &format!("capped-{}", i))
+                    .await
+            })
+        })
+        .collect();
+    for send in sends {
+        assert!(matches!(
+            send.await.unwrap().unwrap(),
+            SendOutcome::Completed { .. }
+        ));
+    }
+    let elapsed = started.elapsed();
+
+    // The bucket starts with one second of burst, so two files' worth of
+    // data needs at least another second at the capped rate
+    let total = (2 * FILE_SIZE) as f64;
+    let allowed = CAP as f64 * (elapsed.as_secs_f64() + 1.0);
+    assert!(total <= allowed, "sent {} bytes in {:?}", total, elapsed);
+    assert!(elapsed >= Duration::from_millis(900), "{:?}", elapsed);
+}
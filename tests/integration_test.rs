@@ -2,13 +2,11 @@ use std::fs;
 use std::io::{BufRead, BufReader};
 use std::path::Path;
 use std::process::{Child, Command, Stdio};
-use std::sync::atomic::{AtomicU16, Ordering};
 use std::sync::{Arc, Mutex};
 use std::thread::sleep;
 use std::time::Duration;
 
-// Use a static counter to generate unique port numbers
-static PORT_COUNTER: AtomicU16 = AtomicU16::new(10000);
+use cipherstream::testing::reserve_port;
 
 // Struct to hold node info for cleanup
 struct TestNode {
@@ -30,9 +28,6 @@ impl Drop for TestNode {
         let _ = self.process.kill();
         let _ = self.process.wait();
 
-        // Sleep a moment to ensure ports are released
-        sleep(Duration::from_millis(500));
-
         // Clean up data directory
         let _ = fs::remove_dir_all(&self.data_dir);
     }
@@ -160,15 +155,13 @@ async fn test_peer_discovery_and_transfer() {
     // Ensure we keep references to the nodes until the end of the test
     drop(node1);
     drop(node2);
-
-    // Sleep after dropping nodes to ensure ports are released
-    sleep(Duration::from_secs(1));
 }
 
 // Start a node with a random port allocation
 fn start_node(data_dir: &str) -> TestNode {
-    // Get a unique port for this test
-    let port = PORT_COUNTER.fetch_add(1, Ordering::SeqCst);
+    // Hold an OS-assigned port until just before the node binds it
+    let reservation = reserve_port();
+    let port = reservation.port();
 
     println!("Starting node on port {} with data dir {}", port, data_dir);
 
@@ -190,6 +183,7 @@ fn start_node(data_dir: &str) -> TestNode {
     .stdout(Stdio::piped())
     .stderr(Stdio::piped());
 
+    reservation.release();
     let mut process = cmd.spawn().expect("Failed to start node");
 
     // Create a thread to read the node's output to extract peer ID
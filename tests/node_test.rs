@@ -0,0 +1,323 @@
+use cipherstream::application::{
+    ApplicationService, ControlRequest, ControlResponse, ControlServer, Node, send_control_request,
+};
+use cipherstream::core::domain::*;
+use cipherstream::core::traits::NetworkService;
+use cipherstream::file_transfer::{
+    Compression, FileSender, FileTransferHandler, ProtocolRequest, SendOutcome,
+};
+use cipherstream::infrastructure::{AppConfig, InMemoryEventPublisher, LibP2pNetworkService};
+use cipherstream::testing::loopback_addr;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+const CHUNK_SIZE: usize = 4096;
+
+/// A node built the way `start` builds it, listening on a free port and
+/// uploading at most `max_upload_bytes_per_sec`
+async fn start_node(
+    data_dir: &tempfile::TempDir,
+    max_upload_bytes_per_sec: Option<u64>,
+) -> (ApplicationService, Node) {
+    let data_directory = data_dir.path().to_string_lossy().into_owned();
+    let mut config = AppConfig {
+        download_directory: format!("{}/downloads", data_directory),
+        data_directory,
+        chunk_size: CHUNK_SIZE,
+        min_chunk_size: CHUNK_SIZE,
+        ..AppConfig::default()
+    };
+    config.network.max_upload_bytes_per_sec = max_upload_bytes_per_sec;
+    let app = ApplicationService::new(config).await.unwrap();
+    let node = Node::new(&app).await.unwrap();
+    node.network.start_listening(0).await.unwrap();
+    (app, node)
+}
+
+/// A plain network node to talk to the node under test
+async fn start_peer() -> Arc<LibP2pNetworkService> {
+    let peer = LibP2pNetworkService::new(
+        Arc::new(AppConfig::default()),
+        Arc::new(InMemoryEventPublisher::new()),
+    )
+    .await
+    .unwrap();
+    peer.start_listening(0).await.unwrap();
+    Arc::new(peer)
+}
+
+/// A plain network node taking files into `download_dir`
+async fn start_receiver(
+    download_dir: &tempfile::TempDir,
+) -> (Arc<LibP2pNetworkService>, Arc<FileTransferHandler>) {
+    let receiver = start_peer().await;
+    let handler = Arc::new(FileTransferHandler::new(download_dir.path(), CHUNK_SIZE));
+    receiver
+        .serve_file_transfers(handler.clone())
+        .await
+        .unwrap();
+    (receiver, handler)
+}
+
+/// Record an outgoing transfer of `size` bytes to `receiver` as in progress
+async fn record_send(app: &ApplicationService, id: &str, receiver: libp2p::PeerId, size: u64) {
+    let transfer = Transfer {
+        id: TransferId::from_string(id.to_string()),
+        file: File {
+            id: FileId::new(),
+            name: format!("{}.txt", id),
+            size,
+            hash: String::new(),
+            path: String::new(),
+            created_at: SystemTime::now(),
+            modified_at: None,
+        },
+        sender: PeerId::new("local".to_string()),
+        receiver: receiver.into(),
+        status: TransferStatus::InProgress,
+        progress: TransferProgress::new(size, size.div_ceil(CHUNK_SIZE as u64)),
+        started_at: SystemTime::now(),
+        completed_at: None,
+    };
+    app.transfer_repository
+        .save_transfer(&transfer)
+        .await
+        .unwrap();
+}
+
+async fn status(app: &ApplicationService, id: &str) -> TransferStatus {
+    app.transfer_repository
+        .find_transfer_by_id(&TransferId::from_string(id.to_string()))
+        .await
+        .unwrap()
+        .unwrap()
+        .status
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_node_publishes_inbound_transfer_events_on_its_bus() {
+    let data_dir = tempfile::tempdir().unwrap();
+    let src_dir = tempfile::tempdir().unwrap();
+    let path = src_dir.path().join("notes.txt");
+    std::fs::write(&path, vec![b'n'; 3 * CHUNK_SIZE]).unwrap();
+
+    let (_app, node) = start_node(&data_dir, None).await;
+    let sender = start_peer().await;
+    let node_id = sender
+        .connect_and_wait(loopback_addr(&node.network).await)
+        .await
+        .unwrap();
+
+    let outcome = FileSender::new(sender.clone(), CHUNK_SIZE)
+        .send_file(node_id, &path, "node-events")
+        .await
+        .unwrap();
+    assert_eq!(outcome, SendOutcome::Completed { chunks_sent: 3 });
+
+    let events = node.events.get_events().await;
+    assert!(events.iter().any(|e| matches!(
+        e,
+        DomainEvent::TransferStarted { transfer } if transfer.id.as_str() == "node-events"
+    )));
+    assert!(
+        events
+            .iter()
+            .filter(|e| matches!(e, DomainEvent::TransferProgress { .. }))
+            .count()
+            >= 3
+    );
+    assert!(events.iter().any(|e| matches!(
+        e,
+        DomainEvent::TransferCompleted { transfer_id } if transfer_id.as_str() == "node-events"
+    )));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_node_publishes_a_failure_when_the_sender_cancels() {
+    let data_dir = tempfile::tempdir().unwrap();
+    let (_app, node) = start_node(&data_dir, None).await;
+    let sender = start_peer().await;
+    let node_id = sender
+        .connect_and_wait(loopback_addr(&node.network).await)
+        .await
+        .unwrap();
+
+    sender
+        .request(
+            node_id,
+            ProtocolRequest::HandshakeRequest {
+                filename: "dropped.txt".to_string(),
+                filesize: 2 * CHUNK_SIZE as u64,
+                transfer_id: "node-cancel".to_string(),
+                sha256: None,
+                merkle_root: None,
+                chunk_size: Some(CHUNK_SIZE as u64),
+                adaptive_chunks: false,
+                compression: Compression::None,
+            },
+        )
+        .await
+        .unwrap();
+    sender
+        .request(
+            node_id,
+            ProtocolRequest::CancelTransfer {
+                transfer_id: "node-cancel".to_string(),
+            },
+        )
+        .await
+        .unwrap();
+
+    let events = node.events.get_events().await;
+    assert!(events.iter().any(|e| matches!(
+        e,
+        DomainEvent::TransferFailed { transfer_id, .. } if transfer_id.as_str() == "node-cancel"
+    )));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_control_api_steers_a_live_send_from_the_node() {
+    let data_dir = tempfile::tempdir().unwrap();
+    let src_dir = tempfile::tempdir().unwrap();
+    let dst_dir = tempfile::tempdir().unwrap();
+    let path = src_dir.path().join("live-send.txt");
+    std::fs::write(&path, vec![b'l'; 16 * CHUNK_SIZE]).unwrap();
+
+    // 16 KiB a second makes the send take about four seconds
+    let (app, node) = start_node(&data_dir, Some(4 * CHUNK_SIZE as u64)).await;
+    let (receiver, handler) = start_receiver(&dst_dir).await;
+    let receiver_id = node
+        .network
+        .connect_and_wait(loopback_addr(&receiver).await)
+        .await
+        .unwrap();
+    record_send(&app, "live-send", receiver_id, 16 * CHUNK_SIZE as u64).await;
+
+    let control = ControlServer::bind(0, node.transfer_service.clone())
+        .await
+        .unwrap();
+    let port = control.local_addr().unwrap().port();
+    control.spawn();
+    let command = |request: ControlRequest| async move {
+        send_control_request(port, &request).await.unwrap()
+    };
+    let ok = ControlResponse {
+        ok: true,
+        error: None,
+    };
+
+    let send = {
+        let sender = node.sender.clone();
+        tokio::spawn(async move { sender.send_file(receiver_id, &path, "live-send").await })
+    };
+    tokio::time::sleep(Duration::from_millis(500)).await;
+
+    let pause = ControlRequest::PauseTransfer {
+        transfer_id: "live-send".to_string(),
+    };
+    assert_eq!(command(pause).await, ok);
+    // Let a chunk already on its way land, then nothing more arrives
+    tokio::time::sleep(Duration::from_millis(400)).await;
+    let at_pause = handler.progress("live-send").await.unwrap();
+    tokio::time::sleep(Duration::from_millis(600)).await;
+    assert_eq!(
+        handler
+            .progress("live-send")
+            .await
+            .unwrap()
+            .chunks_transferred,
+        at_pause.chunks_transferred
+    );
+
+    let resume = ControlRequest::ResumeTransfer {
+        transfer_id: "live-send".to_string(),
+    };
+    assert_eq!(command(resume).await, ok);
+    tokio::time::sleep(Duration::from_millis(500)).await;
+    let cancel = ControlRequest::CancelTransfer {
+        transfer_id: "live-send".to_string(),
+    };
+    assert_eq!(command(cancel).await, ok);
+
+    let outcome = tokio::time::timeout(Duration::from_secs(2), send)
+        .await
+        .unwrap()
+        .unwrap()
+        .unwrap();
+    assert!(
+        matches!(outcome, SendOutcome::Cancelled { chunks_sent } if chunks_sent < 16),
+        "{:?}",
+        outcome
+    );
+    assert!(matches!(
+        status(&app, "live-send").await,
+        TransferStatus::Cancelled
+    ));
+    // The receiver was told and dropped its partial file
+    assert!(handler.is_cancelled("live-send").await);
+    assert_eq!(handler.active_transfers().await, 0);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_receiver_disconnect_pauses_the_node_live_send() {
+    let data_dir = tempfile::tempdir().unwrap();
+    let src_dir = tempfile::tempdir().unwrap();
+    let dst_dir = tempfile::tempdir().unwrap();
+    let path = src_dir.path().join("dropped-peer.txt");
+    std::fs::write(&path, vec![b'd'; 16 * CHUNK_SIZE]).unwrap();
+
+    let (app, node) = start_node(&data_dir, Some(4 * CHUNK_SIZE as u64)).await;
+    let (receiver, handler) = start_receiver(&dst_dir).await;
+    let receiver_id = node
+        .network
+        .connect_and_wait(loopback_addr(&receiver).await)
+        .await
+        .unwrap();
+    record_send(&app, "dropped-peer", receiver_id, 16 * CHUNK_SIZE as u64).await;
+
+    let send = {
+        let sender = node.sender.clone();
+        tokio::spawn(async move { sender.send_file(receiver_id, &path, "dropped-peer").await })
+    };
+    tokio::time::sleep(Duration::from_millis(500)).await;
+    receiver
+        .disconnect_peer(node.network.local_peer_id())
+        .await
+        .unwrap();
+
+    // The disconnect reached the transfer service, which paused the send
+    let deadline = tokio::time::Instant::now() + Duration::from_secs(2);
+    while !matches!(status(&app, "dropped-peer").await, TransferStatus::Paused) {
+        assert!(
+            tokio::time::Instant::now() < deadline,
+            "transfer not paused"
+        );
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    let at_pause = handler.progress("dropped-peer").await.unwrap();
+    tokio::time::sleep(Duration::from_millis(600)).await;
+    assert!(!send.is_finished());
+    assert_eq!(
+        handler
+            .progress("dropped-peer")
+            .await
+            .unwrap()
+            .chunks_transferred,
+        at_pause.chunks_transferred
+    );
+
+    node.transfer_service
+        .cancel_transfer(&TransferId::from_string("dropped-peer".to_string()))
+        .await
+        .unwrap();
+    let outcome = tokio::time::timeout(Duration::from_secs(2), send)
+        .await
+        .unwrap()
+        .unwrap()
+        .unwrap();
+    assert!(
+        matches!(outcome, SendOutcome::Cancelled { .. }),
+        "{:?}",
+        outcome
+    );
+}
@@ -0,0 +1,124 @@
+use cipherstream::application::{active_bans, ban_peer};
+use cipherstream::core::traits::{NetworkService, PeerRepository};
+use cipherstream::infrastructure::{
+    AppConfig, InMemoryEventPublisher, LibP2pNetworkService, SledPeerRepository,
+};
+use cipherstream::testing::loopback_addr;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+async fn start_node() -> LibP2pNetworkService {
+    let node = LibP2pNetworkService::new(
+        Arc::new(AppConfig::default()),
+        Arc::new(InMemoryEventPublisher::new()),
+    )
+    .await
+    .unwrap();
+    node.start_listening(0).await.unwrap();
+    node
+}
+
+/// Start a node that enforces the bans stored in `repo`, as `start` does
+async fn restart_node(repo: &dyn PeerRepository) -> LibP2pNetworkService {
+    let node = start_node().await;
+    for ban in active_bans(repo, SystemTime::now()).await.unwrap() {
+        node.ban_peer(ban.peer.as_str().parse().unwrap(), ban.until)
+            .await
+            .unwrap();
+    }
+    node
+}
+
+/// Open the ban database at `path` again, giving sled's background threads a
+/// moment to release the lock held by an instance just dropped
+async fn reopen(path: &std::path::Path) -> SledPeerRepository {
+    for _ in 0..50 {
+        if let Ok(repo) = SledPeerRepository::open(path) {
+            return repo;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    SledPeerRepository::open(path).unwrap()
+}
+
+/// Whether `dialer` ends up connected to `target` after dialing it
+async fn stays_connected(dialer: &LibP2pNetworkService, target: &LibP2pNetworkService) -> bool {
+    let _ = dialer.connect_and_wait(loopback_addr(target).await).await;
+    tokio::time::sleep(Duration::from_millis(300)).await;
+    let seen_by_target = target
+        .connection_counts()
+        .await
+        .unwrap()
+        .contains_key(&dialer.local_peer_id());
+    let seen_by_dialer = dialer
+        .connection_counts()
+        .await
+        .unwrap()
+        .contains_key(&target.local_peer_id());
+    seen_by_target && seen_by_dialer
+}
+
+#[tokio::test]
+async fn test_banned_peer_is_refused_after_a_restart() {
+    let dir = tempfile::tempdir().unwrap();
+    let db_path = dir.path().join("db");
+    let banned = start_node().await;
+
+    {
+        let repo = SledPeerRepository::open(&db_path).unwrap();
+        let receiver = restart_node(&repo).await;
+        assert!(stays_connected(&banned, &receiver).await);
+        ban_peer(
+            &repo,
+            &banned.local_peer_id().to_string(),
+            Some("flooding".to_string()),
+            None,
+        )
+        .await
+        .unwrap();
+        receiver.shutdown().await.unwrap();
+    }
+
+    let repo = reopen(&db_path).await;
+    let bans = repo.list_bans().await.unwrap();
+    assert_eq!(bans.len(), 1);
+    assert_eq!(bans[0].reason.as_deref(), Some("flooding"));
+
+    let receiver = restart_node(&repo).await;
+    assert!(!stays_connected(&banned, &receiver).await);
+
+    repo.remove_ban(&bans[0].peer).await.unwrap();
+    let receiver = restart_node(&repo).await;
+    assert!(stays_connected(&banned, &receiver).await);
+}
+
+#[tokio::test]
+async fn test_ban_lifts_when_it_expires() {
+    let dir = tempfile::tempdir().unwrap();
+    let repo = SledPeerRepository::open(dir.path().join("db")).unwrap();
+    let banned = start_node().await;
+    let until = SystemTime::now() + Duration::from_secs(2);
+    ban_peer(
+        &repo,
+        &banned.local_peer_id().to_string(),
+        None,
+        Some(until),
+    )
+    .await
+    .unwrap();
+
+    let receiver = restart_node(&repo).await;
+    assert!(!stays_connected(&banned, &receiver).await);
+
+    tokio::time::sleep(until.duration_since(SystemTime::now()).unwrap_or_default()).await;
+    assert!(stays_connected(&banned, &receiver).await);
+
+    // Once expired the ban is dropped instead of being loaded again
+    assert!(
+        active_bans(&repo, SystemTime::now())
+            .await
+            .unwrap()
+            .is_empty()
+    );
+    assert!(repo.list_bans().await.unwrap().is_empty());
+}
@@ -0,0 +1,141 @@
+use cipherstream::core::domain::*;
+use cipherstream::core::services::{PeerDomainService, PeerIdentifyHandler};
+use cipherstream::core::traits::*;
+use cipherstream::infrastructure::{
+    AppConfig, InMemoryEventPublisher, InMemoryPeerRepository, LibP2pNetworkService,
+};
+use cipherstream::protocol::PROTOCOL_ID;
+use cipherstream::testing::loopback_addr;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+async fn save_peer(repo: &InMemoryPeerRepository, id: &str, age: Duration, is_connected: bool) {
+    repo.save_peer(&Peer {
+        id: PeerId::new(id.to_string()),
+        addresses: vec![],
+        last_seen: SystemTime::now() - age,
+        is_connected,
+        protocols: Vec::new(),
+    })
+    .await
+    .unwrap();
+}
+
+#[tokio::test]
+async fn test_prune_stale_peers_removes_only_stale_peers() {
+    let repo = Arc::new(InMemoryPeerRepository::new());
+    let events = Arc::new(InMemoryEventPublisher::new());
+    let service = PeerDomainService::new(repo.clone(), events.clone());
+
+    let hour = Duration::from_secs(3600);
+    save_peer(&repo, "fresh", Duration::from_secs(60), true).await;
+    save_peer(&repo, "stale-connected", hour * 3, true).await;
+    save_peer(&repo, "stale-offline", hour * 5, false).await;
+
+    let pruned = service.prune_stale_peers(hour).await.unwrap();
+    assert_eq!(pruned, 2);
+
+    let remaining: Vec<String> = repo
+        .list_all_peers()
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|p| p.id.id)
+        .collect();
+    assert_eq!(remaining, vec!["fresh".to_string()]);
+
+    let events = events.get_events().await;
+    assert_eq!(events.len(), 1);
+    assert!(matches!(
+        &events[0],
+        DomainEvent::PeerDisconnected { peer_id } if peer_id.as_str() == "stale-connected"
+    ));
+}
+
+#[tokio::test]
+async fn test_reset_connections_marks_stored_peers_offline() {
+    let repo = Arc::new(InMemoryPeerRepository::new());
+    let events = Arc::new(InMemoryEventPublisher::new());
+    let service = PeerDomainService::new(repo.clone(), events.clone());
+    save_peer(&repo, "left-connected", Duration::ZERO, true).await;
+    save_peer(&repo, "offline", Duration::ZERO, false).await;
+
+    assert_eq!(service.reset_connections().await.unwrap(), 1);
+    assert!(repo.list_connected_peers().await.unwrap().is_empty());
+    assert_eq!(repo.list_all_peers().await.unwrap().len(), 2);
+    assert!(events.get_events().await.is_empty());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_identify_protocols_are_recorded_on_connected_peer() {
+    let repo = Arc::new(InMemoryPeerRepository::new());
+    let events = Arc::new(InMemoryEventPublisher::new());
+    events
+        .subscribe(Box::new(PeerIdentifyHandler::new(repo.clone())))
+        .unwrap();
+
+    let config = Arc::new(AppConfig::default());
+    let local = LibP2pNetworkService::new(config.clone(), events)
+        .await
+        .unwrap();
+    let remote = LibP2pNetworkService::new(config, Arc::new(InMemoryEventPublisher::new()))
+        .await
+        .unwrap();
+    remote.start_listening(0).await.unwrap();
+    let remote_id = local
+        .connect_and_wait(loopback_addr(&remote).await)
+        .await
+        .unwrap();
+
+    let id = PeerId::new(remote_id.to_string());
+    let mut recorded = None;
+    for _ in 0..100 {
+        recorded = repo
+            .find_peer_by_id(&id)
+            .await
+            .unwrap()
+            .filter(|peer| !peer.protocols.is_empty());
+        if recorded.is_some() {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+
+    let peer = recorded.expect("identify never reached the peer record");
+    assert!(peer.is_connected);
+    assert!(peer.protocols.iter().any(|p| p == PROTOCOL_ID));
+    assert!(peer.protocols.iter().any(|p| p == "/ipfs/id/1.0.0"));
+    assert!(!peer.addresses.is_empty());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_disconnect_marks_identified_peer_offline() {
+    let repo = Arc::new(InMemoryPeerRepository::new());
+    let events = Arc::new(InMemoryEventPublisher::new());
+    events
+        .subscribe(Box::new(PeerIdentifyHandler::new(repo.clone())))
+        .unwrap();
+    let peer_id = PeerId::new("remote".to_string());
+
+    events
+        .publish(DomainEvent::PeerIdentified {
+            peer_id: peer_id.clone(),
+            addresses: vec!["/ip4/127.0.0.1/tcp/4001".to_string()],
+            protocols: vec![PROTOCOL_ID.to_string()],
+        })
+        .await
+        .unwrap();
+    assert_eq!(repo.list_connected_peers().await.unwrap().len(), 1);
+
+    events
+        .publish(DomainEvent::PeerDisconnected {
+            peer_id: peer_id.clone(),
+        })
+        .await
+        .unwrap();
+    let peer = repo.find_peer_by_id(&peer_id).await.unwrap().unwrap();
+    assert!(!peer.is_connected);
+    assert!(repo.list_connected_peers().await.unwrap().is_empty());
+    // What identify reported is kept for when the peer comes back
+    assert_eq!(peer.protocols, vec![PROTOCOL_ID.to_string()]);
+}
@@ -1,5 +1,6 @@
 use bincode::config;
-use cipherstream::file_transfer::types::{ProtocolRequest, ProtocolResponse};
+use cipherstream::file_transfer::Compression;
+use cipherstream::file_transfer::types::{ProtocolRequest, ProtocolResponse, RejectReason};
 
 #[test]
 fn test_protocol_request_serialization() {
@@ -8,6 +9,11 @@ fn test_protocol_request_serialization() {
         filename: "test.txt".to_string(),
         filesize: 1024,
         transfer_id: "abc123".to_string(),
+        sha256: None,
+        merkle_root: None,
+        chunk_size: None,
+        adaptive_chunks: false,
+        compression: Compression::None,
     };
 
     // Serialize
@@ -23,6 +29,7 @@ fn test_protocol_request_serialization() {
             filename,
             filesize,
             transfer_id,
+            ..
         } => {
             assert_eq!(filename, "test.txt");
             assert_eq!(filesize, 1024);
@@ -36,8 +43,10 @@ fn test_protocol_request_serialization() {
         transfer_id: "abc123".to_string(),
         chunk_index: 1,
         total_chunks: 10,
+        offset: 5,
         data: vec![1, 2, 3, 4, 5],
         is_last: false,
+        proof: Vec::new(),
     };
 
     // Serialize
@@ -53,14 +62,18 @@ fn test_protocol_request_serialization() {
             transfer_id,
             chunk_index,
             total_chunks,
+            offset,
             data,
             is_last,
+            proof,
         } => {
             assert_eq!(transfer_id, "abc123");
             assert_eq!(chunk_index, 1);
             assert_eq!(total_chunks, 10);
+            assert_eq!(offset, 5);
             assert_eq!(data, vec![1, 2, 3, 4, 5]);
             assert!(!is_last);
+            assert!(proof.is_empty());
         }
         _ => panic!("Wrong variant decoded"),
     }
@@ -89,6 +102,8 @@ fn test_protocol_response_serialization() {
         accepted: true,
         reason: None,
         transfer_id: Some("test-id-1".to_string()),
+        accepted_chunk_size: None,
+        compression: Compression::Zstd,
     };
 
     let config = config::standard();
@@ -101,10 +116,14 @@ fn test_protocol_response_serialization() {
             accepted,
             reason,
             transfer_id,
+            accepted_chunk_size,
+            compression,
         } => {
             assert!(accepted);
             assert_eq!(reason, None);
             assert_eq!(transfer_id, Some("test-id-1".to_string()));
+            assert_eq!(accepted_chunk_size, None);
+            assert_eq!(compression, Compression::Zstd);
         }
         _ => panic!("Decoded to wrong variant"),
     }
@@ -112,8 +131,10 @@ fn test_protocol_response_serialization() {
     // Test HandshakeResponse - rejected
     let response_rejected = ProtocolResponse::HandshakeResponse {
         accepted: false,
-        reason: Some("File already exists".to_string()),
+        reason: Some(RejectReason::Other("File already exists".to_string())),
         transfer_id: None,
+        accepted_chunk_size: None,
+        compression: Compression::None,
     };
 
     let encoded: Vec<u8> = bincode::encode_to_vec(&response_rejected, config).unwrap();
@@ -125,10 +146,16 @@ fn test_protocol_response_serialization() {
             accepted,
             reason,
             transfer_id,
+            accepted_chunk_size,
+            ..
         } => {
             assert!(!accepted);
-            assert_eq!(reason, Some("File already exists".to_string()));
+            assert_eq!(
+                reason,
+                Some(RejectReason::Other("File already exists".to_string()))
+            );
             assert_eq!(transfer_id, None);
+            assert_eq!(accepted_chunk_size, None);
         }
         _ => panic!("Decoded to wrong variant"),
     }
@@ -139,6 +166,7 @@ fn test_protocol_response_serialization() {
         chunk_index: 3,
         success: true,
         error: None,
+        retryable: false,
     };
 
     let encoded: Vec<u8> = bincode::encode_to_vec(&chunk_response, config).unwrap();
@@ -151,11 +179,13 @@ fn test_protocol_response_serialization() {
             chunk_index,
             success,
             error,
+            retryable,
         } => {
             assert_eq!(transfer_id, "test-id-2");
             assert_eq!(chunk_index, 3);
             assert!(success);
             assert_eq!(error, None);
+            assert!(!retryable);
         }
         _ => panic!("Decoded to wrong variant"),
     }
@@ -188,7 +218,7 @@ fn test_protocol_response_serialization() {
     let failed = ProtocolResponse::TransferComplete {
         transfer_id: "test-id-4".to_string(),
         success: false,
-        error: Some("Connection lost".to_string()),
+        error: Some(RejectReason::Other("Connection lost".to_string())),
     };
 
     let encoded: Vec<u8> = bincode::encode_to_vec(&failed, config).unwrap();
@@ -203,7 +233,10 @@ fn test_protocol_response_serialization() {
         } => {
             assert_eq!(transfer_id, "test-id-4");
             assert!(!success);
-            assert_eq!(error, Some("Connection lost".to_string()));
+            assert_eq!(
+                error,
+                Some(RejectReason::Other("Connection lost".to_string()))
+            );
         }
         _ => panic!("Decoded to wrong variant"),
     }
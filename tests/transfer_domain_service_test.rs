@@ -0,0 +1,699 @@
+use cipherstream::application::FileSystemService;
+use cipherstream::core::domain::*;
+use cipherstream::core::services::{PeerDisconnectHandler, TransferDomainService};
+use cipherstream::core::traits::*;
+use cipherstream::infrastructure::{
+    AppConfig, InMemoryEventPublisher, InMemoryFileRepository, InMemoryPeerRepository,
+    InMemoryTransferRepository, SledTransferRepository,
+};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, SystemTime};
+
+struct Fixture {
+    transfer_repo: Arc<InMemoryTransferRepository>,
+    peer_repo: Arc<InMemoryPeerRepository>,
+    events: Arc<InMemoryEventPublisher>,
+    service: TransferDomainService,
+}
+
+fn fixture() -> Fixture {
+    let transfer_repo = Arc::new(InMemoryTransferRepository::new());
+    let peer_repo = Arc::new(InMemoryPeerRepository::new());
+    let events = Arc::new(InMemoryEventPublisher::new());
+    let service = TransferDomainService::new(
+        Arc::new(InMemoryFileRepository::new()),
+        transfer_repo.clone(),
+        peer_repo.clone(),
+        Arc::new(FileSystemService::new(Arc::new(AppConfig::default()))),
+        events.clone(),
+    );
+    Fixture {
+        transfer_repo,
+        peer_repo,
+        events,
+        service,
+    }
+}
+
+fn transfer_between(sender: &str, receiver: &str, status: TransferStatus) -> Transfer {
+    Transfer {
+        id: TransferId::new(),
+        file: File {
+            id: FileId::new(),
+            name: "report.pdf".to_string(),
+            size: 2048,
+            hash: "abc".to_string(),
+            path: "/tmp/report.pdf".to_string(),
+            created_at: SystemTime::now(),
+            modified_at: None,
+        },
+        sender: PeerId::new(sender.to_string()),
+        receiver: PeerId::new(receiver.to_string()),
+        status,
+        progress: TransferProgress::new(2048, 2),
+        started_at: SystemTime::now(),
+        completed_at: None,
+    }
+}
+
+async fn save_peer(repo: &InMemoryPeerRepository, id: &str, is_connected: bool) {
+    repo.save_peer(&Peer {
+        id: PeerId::new(id.to_string()),
+        addresses: vec![],
+        last_seen: SystemTime::now(),
+        is_connected,
+        protocols: Vec::new(),
+    })
+    .await
+    .unwrap();
+}
+
+#[tokio::test]
+async fn test_recovery_fails_transfer_when_peer_stays_offline() {
+    let f = fixture();
+    let transfer = transfer_between("local", "offline-peer", TransferStatus::InProgress);
+    f.transfer_repo.save_transfer(&transfer).await.unwrap();
+    save_peer(&f.peer_repo, "offline-peer", false).await;
+
+    let recovery = f
+        .service
+        .recover_interrupted_transfers(Duration::from_millis(50))
+        .await
+        .unwrap();
+
+    assert!(recovery.resumable.is_empty());
+    assert_eq!(recovery.failed, vec![transfer.id.clone()]);
+
+    let stored = f
+        .transfer_repo
+        .find_transfer_by_id(&transfer.id)
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(matches!(stored.status, TransferStatus::Failed { .. }));
+    assert!(
+        f.events
+            .get_events()
+            .await
+            .iter()
+            .any(|e| matches!(e, DomainEvent::TransferFailed { transfer_id, .. } if *transfer_id == transfer.id))
+    );
+}
+
+#[tokio::test]
+async fn test_recovery_leaves_transfer_with_connected_peer_paused() {
+    let f = fixture();
+    let transfer = transfer_between("local", "online-peer", TransferStatus::InProgress);
+    f.transfer_repo.save_transfer(&transfer).await.unwrap();
+    save_peer(&f.peer_repo, "online-peer", true).await;
+
+    let recovery = f
+        .service
+        .recover_interrupted_transfers(Duration::from_secs(5))
+        .await
+        .unwrap();
+
+    assert_eq!(recovery.resumable, vec![transfer.id.clone()]);
+    assert!(recovery.failed.is_empty());
+
+    let stored = f
+        .transfer_repo
+        .find_transfer_by_id(&transfer.id)
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(matches!(stored.status, TransferStatus::Paused));
+    assert!(
+        !f.events
+            .get_events()
+            .await
+            .iter()
+            .any(|e| matches!(e, DomainEvent::TransferResumed { .. }))
+    );
+}
+
+#[tokio::test]
+async fn test_recovery_ignores_pending_transfers() {
+    let f = fixture();
+    let transfer = transfer_between("local", "offline-peer", TransferStatus::Pending);
+    f.transfer_repo.save_transfer(&transfer).await.unwrap();
+
+    let recovery = f
+        .service
+        .recover_interrupted_transfers(Duration::from_millis(10))
+        .await
+        .unwrap();
+
+    assert!(recovery.resumable.is_empty());
+    assert!(recovery.failed.is_empty());
+}
+
+#[tokio::test]
+async fn test_list_sent_and_received_delegate_to_repository() {
+    let f = fixture();
+    let outgoing = transfer_between("alice", "bob", TransferStatus::InProgress);
+    let incoming = transfer_between("bob", "alice", TransferStatus::Completed);
+    let unrelated = transfer_between("carol", "dave", TransferStatus::Pending);
+    for t in [&outgoing, &incoming, &unrelated] {
+        f.transfer_repo.save_transfer(t).await.unwrap();
+    }
+
+    let alice = PeerId::new("alice".to_string());
+    let sent = f.service.list_sent(&alice).await.unwrap();
+    assert_eq!(sent.len(), 1);
+    assert_eq!(sent[0].id, outgoing.id);
+
+    let received = f.service.list_received(&alice).await.unwrap();
+    assert_eq!(received.len(), 1);
+    assert_eq!(received[0].id, incoming.id);
+
+    let all = f.service.list_transfers_for_peer(&alice).await.unwrap();
+    assert_eq!(all.len(), 2);
+}
+
+#[tokio::test]
+async fn test_list_active_excludes_terminal_transfers() {
+    let f = fixture();
+    let active = transfer_between("alice", "bob", TransferStatus::InProgress);
+    let done = transfer_between("alice", "bob", TransferStatus::Completed);
+    let cancelled = transfer_between("alice", "bob", TransferStatus::Cancelled);
+    for t in [&active, &done, &cancelled] {
+        f.transfer_repo.save_transfer(t).await.unwrap();
+    }
+
+    let listed = f.service.list_active().await.unwrap();
+    assert_eq!(listed.len(), 1);
+    assert_eq!(listed[0].id, active.id);
+}
+
+#[tokio::test]
+async fn test_recovery_returns_resume_state_for_resumable_transfer() {
+    let f = fixture();
+    let dir = tempfile::tempdir().unwrap();
+    let partial = dir.path().join("report.pdf.partial");
+    std::fs::write(&partial, [0u8; 1024]).unwrap();
+    let partial = partial.to_str().unwrap();
+    let transfer = transfer_between("local", "online-peer", TransferStatus::InProgress);
+    f.transfer_repo.save_transfer(&transfer).await.unwrap();
+    save_peer(&f.peer_repo, "online-peer", true).await;
+    f.service
+        .record_received_chunk(&transfer.id, partial, 0)
+        .await
+        .unwrap();
+
+    let recovery = f
+        .service
+        .recover_interrupted_transfers(Duration::from_secs(5))
+        .await
+        .unwrap();
+
+    let state = &recovery.resume_states[&transfer.id];
+    assert_eq!(state.partial_path, partial);
+    assert_eq!(state.missing_chunks(2), vec![1]);
+}
+
+#[tokio::test]
+async fn test_recovery_fails_transfer_whose_partial_file_is_gone() {
+    let f = fixture();
+    let dir = tempfile::tempdir().unwrap();
+    let partial = dir.path().join("report.pdf.partial");
+    let transfer = transfer_between("local", "online-peer", TransferStatus::InProgress);
+    f.transfer_repo.save_transfer(&transfer).await.unwrap();
+    save_peer(&f.peer_repo, "online-peer", true).await;
+    f.service
+        .record_received_chunk(&transfer.id, partial.to_str().unwrap(), 0)
+        .await
+        .unwrap();
+
+    let recovery = f
+        .service
+        .recover_interrupted_transfers(Duration::from_secs(5))
+        .await
+        .unwrap();
+
+    assert!(recovery.resumable.is_empty());
+    assert_eq!(recovery.failed, vec![transfer.id.clone()]);
+    let stored = f
+        .transfer_repo
+        .find_transfer_by_id(&transfer.id)
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(
+        matches!(stored.status, TransferStatus::Failed { reason } if reason.contains("is missing"))
+    );
+}
+
+#[tokio::test]
+async fn test_recovery_fails_transfer_with_chunks_beyond_its_end() {
+    let f = fixture();
+    let dir = tempfile::tempdir().unwrap();
+    let partial = dir.path().join("report.pdf.partial");
+    std::fs::write(&partial, [0u8; 2048]).unwrap();
+    let transfer = transfer_between("local", "online-peer", TransferStatus::Paused);
+    f.transfer_repo.save_transfer(&transfer).await.unwrap();
+    save_peer(&f.peer_repo, "online-peer", true).await;
+    f.service
+        .record_received_chunk(&transfer.id, partial.to_str().unwrap(), 7)
+        .await
+        .unwrap();
+
+    let recovery = f
+        .service
+        .recover_interrupted_transfers(Duration::from_secs(5))
+        .await
+        .unwrap();
+
+    assert_eq!(recovery.failed, vec![transfer.id.clone()]);
+}
+
+#[cfg(any(unix, windows))]
+#[tokio::test]
+async fn test_initiated_transfer_records_file_modification_time() {
+    let f = fixture();
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("report.pdf");
+    std::fs::write(&path, b"quarterly numbers").unwrap();
+    save_peer(&f.peer_repo, "receiver", true).await;
+
+    let transfer = f
+        .service
+        .initiate_transfer(
+            path.to_str().unwrap(),
+            PeerId::new("local".to_string()),
+            PeerId::new("receiver".to_string()),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(transfer.file.size, 17);
+    assert_eq!(
+        transfer.file.modified_at,
+        Some(std::fs::metadata(&path).unwrap().modified().unwrap())
+    );
+}
+
+#[tokio::test]
+async fn test_deterministic_ids_repeat_for_the_same_file_and_peers() {
+    let f = fixture();
+    let service = f.service.with_deterministic_ids(true);
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("report.pdf");
+    std::fs::write(&path, b"quarterly numbers").unwrap();
+    let path = path.to_str().unwrap();
+    save_peer(&f.peer_repo, "receiver", true).await;
+    save_peer(&f.peer_repo, "other", true).await;
+    let local = PeerId::new("local".to_string());
+
+    let first = service
+        .initiate_transfer(path, local.clone(), PeerId::new("receiver".to_string()))
+        .await
+        .unwrap();
+    let retried = service
+        .initiate_transfer(path, local.clone(), PeerId::new("receiver".to_string()))
+        .await
+        .unwrap();
+    let elsewhere = service
+        .initiate_transfer(path, local, PeerId::new("other".to_string()))
+        .await
+        .unwrap();
+
+    assert_eq!(first.id, retried.id);
+    assert_ne!(first.id, elsewhere.id);
+    assert_eq!(
+        f.transfer_repo.list_active_transfers().await.unwrap().len(),
+        2
+    );
+}
+
+/// Transfer control that records which transfers it was told to pause
+#[derive(Default)]
+struct RecordingControl {
+    paused: std::sync::Mutex<Vec<TransferId>>,
+}
+
+#[async_trait::async_trait]
+impl TransferControl for RecordingControl {
+    async fn pause(&self, transfer_id: &TransferId) -> bool {
+        self.paused.lock().unwrap().push(transfer_id.clone());
+        true
+    }
+
+    async fn resume(&self, _transfer_id: &TransferId) -> bool {
+        true
+    }
+
+    async fn cancel(&self, _transfer_id: &TransferId) -> bool {
+        true
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_receiver_disconnect_pauses_in_progress_transfer() {
+    let f = fixture();
+    let transfer = transfer_between("local", "receiver", TransferStatus::InProgress);
+    let untouched = transfer_between("local", "other", TransferStatus::InProgress);
+    f.transfer_repo.save_transfer(&transfer).await.unwrap();
+    f.transfer_repo.save_transfer(&untouched).await.unwrap();
+    let control = Arc::new(RecordingControl::default());
+    let service = f.service.with_transfer_control(control.clone());
+    f.events
+        .subscribe(Box::new(PeerDisconnectHandler::new(Arc::new(service))))
+        .unwrap();
+
+    f.events
+        .publish(DomainEvent::PeerDisconnected {
+            peer_id: PeerId::new("receiver".to_string()),
+        })
+        .await
+        .unwrap();
+
+    let find = |id| f.transfer_repo.find_transfer_by_id(id);
+    let paused = find(&transfer.id).await.unwrap().unwrap();
+    let other = find(&untouched.id).await.unwrap().unwrap();
+    assert!(matches!(paused.status, TransferStatus::Paused));
+    assert!(matches!(other.status, TransferStatus::InProgress));
+    assert!(f.events.get_events().await.iter().any(|event| matches!(
+        event,
+        DomainEvent::TransferPaused { transfer_id, .. } if *transfer_id == transfer.id
+    )));
+    // The send itself stops too, not just the stored status
+    assert_eq!(*control.paused.lock().unwrap(), vec![transfer.id.clone()]);
+}
+
+#[tokio::test]
+async fn test_failed_transfer_can_be_retried() {
+    let f = fixture();
+    let mut failed = transfer_between(
+        "alice",
+        "bob",
+        TransferStatus::Failed {
+            reason: "Peer went away".to_string(),
+        },
+    );
+    failed.progress.update(1024, 1);
+    failed.completed_at = Some(SystemTime::now());
+    f.transfer_repo.save_transfer(&failed).await.unwrap();
+
+    let retried = f.service.retry_transfer(&failed.id).await.unwrap();
+
+    assert_eq!(retried.id, failed.id);
+    assert!(matches!(retried.status, TransferStatus::Pending));
+    assert!(retried.is_active());
+    assert_eq!(retried.progress.bytes_transferred, 0);
+    assert_eq!(retried.completed_at, None);
+    let active = f.service.list_active().await.unwrap();
+    assert_eq!(active.len(), 1);
+    assert_eq!(active[0].id, failed.id);
+    assert!(f.events.get_events().await.iter().any(
+        |e| matches!(e, DomainEvent::TransferStarted { transfer } if transfer.id == failed.id)
+    ));
+}
+
+#[tokio::test]
+async fn test_completed_transfer_cannot_be_retried() {
+    let f = fixture();
+    let done = transfer_between("alice", "bob", TransferStatus::Completed);
+    f.transfer_repo.save_transfer(&done).await.unwrap();
+
+    assert!(f.service.retry_transfer(&done.id).await.is_err());
+    let stored = f
+        .transfer_repo
+        .find_transfer_by_id(&done.id)
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(matches!(stored.status, TransferStatus::Completed));
+}
+
+/// Sled-backed transfer repository that counts transfer writes
+struct CountingTransferRepository {
+    inner: SledTransferRepository,
+    saves: AtomicUsize,
+}
+
+#[async_trait::async_trait]
+impl TransferRepository for CountingTransferRepository {
+    async fn save_transfer(&self, transfer: &Transfer) -> DomainResult<()> {
+        self.saves.fetch_add(1, Ordering::SeqCst);
+        self.inner.save_transfer(transfer).await
+    }
+    async fn find_transfer_by_id(&self, id: &TransferId) -> DomainResult<Option<Transfer>> {
+        self.inner.find_transfer_by_id(id).await
+    }
+    async fn find_transfers_by_sender(&self, sender: &PeerId) -> DomainResult<Vec<Transfer>> {
+        self.inner.find_transfers_by_sender(sender).await
+    }
+    async fn find_transfers_by_receiver(&self, receiver: &PeerId) -> DomainResult<Vec<Transfer>> {
+        self.inner.find_transfers_by_receiver(receiver).await
+    }
+    async fn find_transfers_by_file(&self, file_id: &FileId) -> DomainResult<Vec<Transfer>> {
+        self.inner.find_transfers_by_file(file_id).await
+    }
+    async fn list_active_transfers(&self) -> DomainResult<Vec<Transfer>> {
+        self.inner.list_active_transfers().await
+    }
+    async fn update_transfer_status(
+        &self,
+        id: &TransferId,
+        status: TransferStatus,
+    ) -> DomainResult<()> {
+        self.inner.update_transfer_status(id, status).await
+    }
+    async fn update_transfer_progress(
+        &self,
+        id: &TransferId,
+        progress: TransferProgress,
+    ) -> DomainResult<()> {
+        self.inner.update_transfer_progress(id, progress).await
+    }
+    async fn record_received_chunk(
+        &self,
+        id: &TransferId,
+        partial_path: &str,
+        chunk_index: u64,
+    ) -> DomainResult<()> {
+        self.inner
+            .record_received_chunk(id, partial_path, chunk_index)
+            .await
+    }
+    async fn find_resume_state(&self, id: &TransferId) -> DomainResult<Option<ResumeState>> {
+        self.inner.find_resume_state(id).await
+    }
+    async fn clear_resume_state(&self, id: &TransferId) -> DomainResult<()> {
+        self.inner.clear_resume_state(id).await
+    }
+}
+
+#[tokio::test]
+async fn test_rapid_progress_updates_are_debounced_but_completion_is_persisted() {
+    let dir = tempfile::tempdir().unwrap();
+    let repo = Arc::new(CountingTransferRepository {
+        inner: SledTransferRepository::open(dir.path().join("db")).unwrap(),
+        saves: AtomicUsize::new(0),
+    });
+    let service = TransferDomainService::new(
+        Arc::new(InMemoryFileRepository::new()),
+        repo.clone(),
+        Arc::new(InMemoryPeerRepository::new()),
+        Arc::new(FileSystemService::new(Arc::new(AppConfig::default()))),
+        Arc::new(InMemoryEventPublisher::new()),
+    )
+    .with_progress_persist_interval(Duration::from_secs(3600))
+    .with_verify_after(false);
+
+    let mut transfer = transfer_between("a", "b", TransferStatus::InProgress);
+    transfer.progress = TransferProgress::new(1_000_000, 1000);
+    repo.save_transfer(&transfer).await.unwrap();
+    repo.saves.store(0, Ordering::SeqCst);
+
+    for chunk in 1..1000 {
+        service
+            .update_progress(&transfer.id, chunk * 1000, chunk)
+            .await
+            .unwrap();
+    }
+    // The first update plus one per 5% step
+    let in_flight_saves = repo.saves.load(Ordering::SeqCst);
+    assert!(in_flight_saves <= 21, "{} saves", in_flight_saves);
+
+    let stored = repo
+        .find_transfer_by_id(&transfer.id)
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(matches!(stored.status, TransferStatus::InProgress));
+    assert!(stored.progress.percentage() >= 94.0);
+
+    service
+        .update_progress(&transfer.id, 1_000_000, 1000)
+        .await
+        .unwrap();
+    assert_eq!(repo.saves.load(Ordering::SeqCst), in_flight_saves + 1);
+    let stored = repo
+        .find_transfer_by_id(&transfer.id)
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(matches!(stored.status, TransferStatus::Completed));
+    assert!(stored.progress.is_complete());
+    assert!(stored.completed_at.is_some());
+}
+
+/// A transfer of a real file, `hash` standing in for the sender's hash
+fn transfer_of(path: &std::path::Path, hash: &str) -> Transfer {
+    let mut transfer = transfer_between("a", "b", TransferStatus::InProgress);
+    transfer.file.path = path.to_string_lossy().into_owned();
+    transfer.file.hash = hash.to_string();
+    transfer
+}
+
+#[tokio::test]
+async fn test_complete_transfer_passes_through_verifying() {
+    let f = fixture();
+    let file = tempfile::NamedTempFile::new().unwrap();
+    std::fs::write(file.path(), vec![3u8; 2048]).unwrap();
+    let hash = FileSystemService::new(Arc::new(AppConfig::default()))
+        .calculate_file_hash(&file.path().to_string_lossy())
+        .await
+        .unwrap();
+    let transfer = transfer_of(file.path(), &hash);
+    f.transfer_repo.save_transfer(&transfer).await.unwrap();
+
+    f.service
+        .update_progress(&transfer.id, 2048, 2)
+        .await
+        .unwrap();
+    let stored = f
+        .transfer_repo
+        .find_transfer_by_id(&transfer.id)
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(matches!(stored.status, TransferStatus::Completed));
+    assert!(!stored.is_active());
+
+    let events = f.events.get_events().await;
+    let verifying = events
+        .iter()
+        .position(|e| matches!(e, DomainEvent::TransferVerifying { .. }))
+        .unwrap();
+    let completed = events
+        .iter()
+        .position(|e| matches!(e, DomainEvent::TransferCompleted { .. }))
+        .unwrap();
+    assert!(verifying < completed);
+}
+
+#[tokio::test]
+async fn test_complete_transfer_with_wrong_hash_fails_verification() {
+    let f = fixture();
+    let file = tempfile::NamedTempFile::new().unwrap();
+    std::fs::write(file.path(), vec![3u8; 2048]).unwrap();
+    let transfer = transfer_of(file.path(), "abc");
+    f.transfer_repo.save_transfer(&transfer).await.unwrap();
+
+    f.service
+        .update_progress(&transfer.id, 2048, 2)
+        .await
+        .unwrap();
+    let stored = f
+        .transfer_repo
+        .find_transfer_by_id(&transfer.id)
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(matches!(
+        stored.status,
+        TransferStatus::Failed { ref reason } if reason == "File hash does not match sender's hash"
+    ));
+    // Only a verifying transfer can be settled
+    assert!(
+        f.service
+            .finish_verification(&transfer.id, Ok(()))
+            .await
+            .is_err()
+    );
+
+    let events = f.events.get_events().await;
+    let verifying = events
+        .iter()
+        .position(|e| matches!(e, DomainEvent::TransferVerifying { .. }))
+        .unwrap();
+    let failed = events
+        .iter()
+        .position(|e| matches!(e, DomainEvent::TransferFailed { .. }))
+        .unwrap();
+    assert!(verifying < failed);
+}
+
+#[tokio::test]
+async fn test_complete_transfer_without_hash_skips_verifying() {
+    let f = fixture();
+    let transfer = transfer_of(std::path::Path::new("/nonexistent/report.pdf"), "");
+    f.transfer_repo.save_transfer(&transfer).await.unwrap();
+
+    f.service
+        .update_progress(&transfer.id, 2048, 2)
+        .await
+        .unwrap();
+    let stored = f
+        .transfer_repo
+        .find_transfer_by_id(&transfer.id)
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(matches!(stored.status, TransferStatus::Completed));
+    let events = f.events.get_events().await;
+    assert!(
+        !events
+            .iter()
+            .any(|e| matches!(e, DomainEvent::TransferVerifying { .. }))
+    );
+}
+
+#[tokio::test]
+async fn test_get_progress_reports_updates_not_yet_persisted() {
+    let f = fixture();
+    let service = TransferDomainService::new(
+        Arc::new(InMemoryFileRepository::new()),
+        f.transfer_repo.clone(),
+        f.peer_repo.clone(),
+        Arc::new(FileSystemService::new(Arc::new(AppConfig::default()))),
+        f.events.clone(),
+    )
+    .with_progress_persist_interval(Duration::from_secs(3600));
+    let mut transfer = transfer_between("a", "b", TransferStatus::InProgress);
+    transfer.progress = TransferProgress::new(100_000, 100);
+    f.transfer_repo.save_transfer(&transfer).await.unwrap();
+
+    service
+        .update_progress(&transfer.id, 1000, 1)
+        .await
+        .unwrap();
+    service
+        .update_progress(&transfer.id, 2000, 2)
+        .await
+        .unwrap();
+
+    // Only the first update reached the repository
+    let stored = f
+        .transfer_repo
+        .find_transfer_by_id(&transfer.id)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(stored.progress.bytes_transferred, 1000);
+    let live = service.get_progress(&transfer.id).await.unwrap().unwrap();
+    assert_eq!(live.bytes_transferred, 2000);
+    assert_eq!(live.chunks_transferred, 2);
+
+    assert!(
+        service
+            .get_progress(&TransferId::new())
+            .await
+            .unwrap()
+            .is_none()
+    );
+}
@@ -0,0 +1,389 @@
+use cipherstream::application::{FileSystemService, UseCases};
+use cipherstream::core::domain::*;
+use cipherstream::core::services::{
+    ChecksumMismatch, FileDomainService, PeerDomainService, TransferControlError,
+    TransferDomainService,
+};
+use cipherstream::core::traits::*;
+use cipherstream::file_transfer::{Compression, FileTransferHandler, ProtocolRequest};
+use cipherstream::infrastructure::{
+    AppConfig, InMemoryEventPublisher, InMemoryFileRepository, InMemoryPeerRepository,
+    InMemoryTransferRepository,
+};
+use std::sync::Arc;
+use std::time::SystemTime;
+
+struct Fixture {
+    file_repo: Arc<InMemoryFileRepository>,
+    files: Arc<FileDomainService>,
+    transfer_repo: Arc<InMemoryTransferRepository>,
+    handler: Arc<FileTransferHandler>,
+    use_cases: UseCases,
+    _download_dir: tempfile::TempDir,
+}
+
+fn fixture() -> Fixture {
+    let file_repo = Arc::new(InMemoryFileRepository::new());
+    let transfer_repo = Arc::new(InMemoryTransferRepository::new());
+    let peer_repo = Arc::new(InMemoryPeerRepository::new());
+    let events = Arc::new(InMemoryEventPublisher::new());
+    let file_service = Arc::new(FileSystemService::new(Arc::new(AppConfig::default())));
+
+    let transfer_service = Arc::new(TransferDomainService::new(
+        file_repo.clone(),
+        transfer_repo.clone(),
+        peer_repo.clone(),
+        file_service.clone(),
+        events.clone(),
+    ));
+    let peer_service = Arc::new(PeerDomainService::new(peer_repo, events));
+    let file_domain_service = Arc::new(FileDomainService::new(file_repo.clone(), file_service));
+
+    let download_dir = tempfile::tempdir().unwrap();
+    let handler = Arc::new(FileTransferHandler::new(download_dir.path(), 1024));
+
+    Fixture {
+        file_repo,
+        files: file_domain_service.clone(),
+        transfer_repo,
+        handler: handler.clone(),
+        use_cases: UseCases::new(transfer_service, peer_service, file_domain_service.clone())
+            .with_transfer_handler(handler),
+        _download_dir: download_dir,
+    }
+}
+
+fn transfer(status: TransferStatus) -> Transfer {
+    Transfer {
+        id: TransferId::new(),
+        file: File {
+            id: FileId::new(),
+            name: "report.pdf".to_string(),
+            size: 2048,
+            hash: "abc".to_string(),
+            path: "/tmp/report.pdf".to_string(),
+            created_at: SystemTime::now(),
+            modified_at: None,
+        },
+        sender: PeerId::new("alice".to_string()),
+        receiver: PeerId::new("bob".to_string()),
+        status,
+        progress: TransferProgress::new(2048, 2),
+        started_at: SystemTime::now(),
+        completed_at: None,
+    }
+}
+
+#[tokio::test]
+async fn test_list_active_transfers_returns_only_active() {
+    let f = fixture();
+    let in_progress = transfer(TransferStatus::InProgress);
+    let paused = transfer(TransferStatus::Paused);
+    let finished = [
+        transfer(TransferStatus::Completed),
+        transfer(TransferStatus::Cancelled),
+        transfer(TransferStatus::Failed {
+            reason: "gone".to_string(),
+        }),
+    ];
+    for t in finished.iter().chain([&in_progress, &paused]) {
+        f.transfer_repo.save_transfer(t).await.unwrap();
+    }
+
+    let mut listed = f.use_cases.list_active_transfers.execute().await.unwrap();
+    listed.sort_by(|a, b| a.status.cmp(&b.status));
+
+    let ids: Vec<_> = listed.iter().map(|t| t.id.as_str()).collect();
+    assert_eq!(ids, vec![in_progress.id.as_str(), paused.id.as_str()]);
+    assert_eq!(listed[0].status, "in_progress");
+    assert_eq!(listed[1].status, "paused");
+}
+
+#[tokio::test]
+async fn test_get_transfer_merges_live_progress_for_active_transfer() {
+    let f = fixture();
+    let active = transfer(TransferStatus::InProgress);
+    f.transfer_repo.save_transfer(&active).await.unwrap();
+
+    let peer = libp2p::PeerId::random();
+    let id = active.id.as_str().to_string();
+    f.handler
+        .handle_request(
+            peer,
+            ProtocolRequest::HandshakeRequest {
+                filename: "report.pdf".to_string(),
+                filesize: 2048,
+                transfer_id: id.clone(),
+                sha256: None,
+                merkle_root: None,
+                chunk_size: None,
+                adaptive_chunks: false,
+                compression: Compression::None,
+            },
+        )
+        .await;
+    f.handler
+        .handle_request(
+            peer,
+            ProtocolRequest::FileChunk {
+                transfer_id: id.clone(),
+                chunk_index: 0,
+                total_chunks: 2,
+                offset: 0,
+                data: vec![0; 1024],
+                is_last: false,
+                proof: Vec::new(),
+            },
+        )
+        .await;
+
+    let dto = f
+        .use_cases
+        .get_transfer
+        .execute(&id)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(dto.status, "in_progress");
+    assert_eq!(dto.bytes_transferred, 1024);
+    assert_eq!(dto.chunks_transferred, 1);
+    assert_eq!(dto.progress_percentage, 50.0);
+}
+
+#[tokio::test]
+async fn test_get_transfer_uses_persisted_progress_when_finished() {
+    let f = fixture();
+    let mut completed = transfer(TransferStatus::Completed);
+    completed.progress.update(2048, 2);
+    f.transfer_repo.save_transfer(&completed).await.unwrap();
+
+    let dto = f
+        .use_cases
+        .get_transfer
+        .execute(completed.id.as_str())
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(dto.status, "completed");
+    assert_eq!(dto.bytes_transferred, 2048);
+    assert_eq!(dto.progress_percentage, 100.0);
+
+    assert!(
+        f.use_cases
+            .get_transfer
+            .execute("no-such-transfer")
+            .await
+            .unwrap()
+            .is_none()
+    );
+}
+
+#[tokio::test]
+async fn test_add_file_checks_expected_hash() {
+    let f = fixture();
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("notes.txt");
+    std::fs::write(&path, b"hello").unwrap();
+    let path = path.to_str().unwrap();
+    let hash = cipherstream::crypto::hash::compute_data_hash(b"hello");
+
+    // Hex case doesn't matter
+    let added = f
+        .use_cases
+        .add_file
+        .execute(path, Some(&hash.to_uppercase()))
+        .await
+        .unwrap();
+    assert_eq!(added.hash, hash);
+    assert!(
+        f.file_repo
+            .find_file_by_id(&added.id)
+            .await
+            .unwrap()
+            .is_some()
+    );
+
+    let err = f
+        .use_cases
+        .add_file
+        .execute(path, Some("00ff"))
+        .await
+        .unwrap_err();
+    let mismatch = err.downcast_ref::<ChecksumMismatch>().unwrap();
+    assert_eq!(mismatch.expected, "00ff");
+    assert_eq!(mismatch.actual, hash);
+    assert_eq!(f.file_repo.list_all_files().await.unwrap().len(), 1);
+}
+
+#[tokio::test]
+async fn test_add_files_in_parallel_matches_sequential_hashing() {
+    let f = fixture();
+    let dir = tempfile::tempdir().unwrap();
+    let mut paths: Vec<String> = (0..12)
+        .map(|i| {
+            let path = dir.path().join(format!("file-{}.bin", i));
+            std::fs::write(&path, vec![i as u8; 1000 + i * 517]).unwrap();
+            path.to_str().unwrap().to_string()
+        })
+        .collect();
+    paths.push(dir.path().join("missing.bin").to_str().unwrap().to_string());
+
+    let added = f.files.add_files(&paths, 3).await;
+    assert_eq!(added.len(), paths.len());
+    for (path, result) in paths.iter().zip(&added).take(12) {
+        let file = result.as_ref().unwrap();
+        assert_eq!(file.path, *path);
+        assert_eq!(
+            file.hash,
+            cipherstream::crypto::compute_file_hash(path).await.unwrap()
+        );
+    }
+    assert!(added[12].is_err());
+    assert_eq!(f.file_repo.list_all_files().await.unwrap().len(), 12);
+}
+
+#[tokio::test]
+async fn test_pause_resume_and_cancel_a_transfer() {
+    let f = fixture();
+    let active = transfer(TransferStatus::InProgress);
+    f.transfer_repo.save_transfer(&active).await.unwrap();
+    let id = active.id.as_str();
+    let status = || async {
+        f.transfer_repo
+            .find_transfer_by_id(&active.id)
+            .await
+            .unwrap()
+            .unwrap()
+            .status
+    };
+
+    f.use_cases.pause_transfer.execute(id).await.unwrap();
+    assert!(matches!(status().await, TransferStatus::Paused));
+    let err = f.use_cases.pause_transfer.execute(id).await.unwrap_err();
+    assert!(matches!(
+        err.downcast_ref::<TransferControlError>(),
+        Some(TransferControlError::NotApplicable {
+            action: "pause",
+            status: TransferStatus::Paused,
+            ..
+        })
+    ));
+
+    f.use_cases.resume_transfer.execute(id).await.unwrap();
+    assert!(matches!(status().await, TransferStatus::InProgress));
+    let err = f.use_cases.resume_transfer.execute(id).await.unwrap_err();
+    assert!(matches!(
+        err.downcast_ref::<TransferControlError>(),
+        Some(TransferControlError::NotApplicable {
+            action: "resume",
+            ..
+        })
+    ));
+
+    f.use_cases.cancel_transfer.execute(id).await.unwrap();
+    assert!(matches!(status().await, TransferStatus::Cancelled));
+}
+
+#[tokio::test]
+async fn test_cancelling_a_failed_transfer_is_refused() {
+    let f = fixture();
+    let failed = transfer(TransferStatus::Failed {
+        reason: "peer vanished".to_string(),
+    });
+    f.transfer_repo.save_transfer(&failed).await.unwrap();
+
+    let err = f
+        .use_cases
+        .cancel_transfer
+        .execute(failed.id.as_str())
+        .await
+        .unwrap_err();
+    assert!(matches!(
+        err.downcast_ref::<TransferControlError>(),
+        Some(TransferControlError::Finished {
+            status: TransferStatus::Failed { .. },
+            ..
+        })
+    ));
+}
+
+#[tokio::test]
+async fn test_cancelling_a_cancelled_transfer_is_refused() {
+    let f = fixture();
+    let cancelled = transfer(TransferStatus::InProgress);
+    f.transfer_repo.save_transfer(&cancelled).await.unwrap();
+    let uc = &f.use_cases;
+    uc.cancel_transfer
+        .execute(cancelled.id.as_str())
+        .await
+        .unwrap();
+
+    let err = uc
+        .cancel_transfer
+        .execute(cancelled.id.as_str())
+        .await
+        .unwrap_err();
+    assert!(matches!(
+        err.downcast_ref::<TransferControlError>(),
+        Some(TransferControlError::Finished {
+            status: TransferStatus::Cancelled,
+            ..
+        })
+    ));
+}
+
+#[tokio::test]
+async fn test_transfer_controls_reject_unknown_and_finished_transfers() {
+    let f = fixture();
+    let finished = [
+        transfer(TransferStatus::Completed),
+        transfer(TransferStatus::Cancelled),
+        transfer(TransferStatus::Failed {
+            reason: "gone".to_string(),
+        }),
+    ];
+    for t in &finished {
+        f.transfer_repo.save_transfer(t).await.unwrap();
+    }
+
+    let uc = &f.use_cases;
+    for t in &finished {
+        let id = t.id.as_str();
+        for err in [
+            uc.cancel_transfer.execute(id).await.unwrap_err(),
+            uc.pause_transfer.execute(id).await.unwrap_err(),
+            uc.resume_transfer.execute(id).await.unwrap_err(),
+        ] {
+            assert!(
+                matches!(
+                    err.downcast_ref::<TransferControlError>(),
+                    Some(TransferControlError::Finished { transfer_id, .. }) if transfer_id == id
+                ),
+                "{}",
+                err
+            );
+        }
+    }
+
+    // Refused transfers keep their status
+    for t in &finished {
+        let stored = f
+            .transfer_repo
+            .find_transfer_by_id(&t.id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(format!("{:?}", stored.status), format!("{:?}", t.status));
+    }
+
+    let err = uc
+        .cancel_transfer
+        .execute("no-such-transfer")
+        .await
+        .unwrap_err();
+    assert_eq!(err.to_string(), "Transfer no-such-transfer not found");
+    assert!(matches!(
+        err.downcast_ref::<TransferControlError>(),
+        Some(TransferControlError::NotFound { .. })
+    ));
+}